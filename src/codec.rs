@@ -18,6 +18,8 @@ use crate::{
 };
 use std::fmt::{Debug, Display};
 use tremor_script::Value;
+use tremor_value::prelude::*;
+pub(crate) mod avro;
 pub(crate) mod binary;
 pub(crate) mod binflux;
 pub(crate) mod csv;
@@ -25,6 +27,7 @@ pub(crate) mod influx;
 pub(crate) mod json;
 pub(crate) mod msgpack;
 pub(crate) mod null;
+pub(crate) mod protobuf;
 pub(crate) mod statsd;
 pub(crate) mod string;
 pub(crate) mod syslog;
@@ -38,6 +41,7 @@ mod prelude {
 }
 
 /// The codec trait, to encode and decode data
+#[async_trait::async_trait]
 pub trait Codec: Send + Sync {
     /// The canonical name for this codec
     fn name(&self) -> &str;
@@ -63,6 +67,21 @@ pub trait Codec: Send + Sync {
         data: &'input mut [u8],
         ingest_ns: u64,
     ) -> Result<Option<Value<'input>>>;
+    /// Resolves what decoding `data` requires and can not be done without blocking,
+    /// e.g. fetching a schema from a registry. Awaited before `data` is decoded.
+    ///
+    /// # Errors
+    ///  * if the resolution fails, `data` can not be decoded then
+    async fn resolve(&mut self, _data: &[u8]) -> Result<()> {
+        Ok(())
+    }
+    /// Splits data holding several records, e.g. newline separated metrics,
+    /// into one chunk per record, so every record is decoded into its own event.
+    ///
+    /// Returns `None` if `data` is a single record, which is the default.
+    fn split_records(&self, _data: &[u8]) -> Option<Vec<Vec<u8>>> {
+        None
+    }
     /// Encodes a Value into a binary
     ///
     /// # Errors
@@ -104,25 +123,40 @@ impl Debug for dyn Codec {
 /// # Errors
 ///  * if the codec doesn't exist
 pub fn resolve(config: &config::Codec) -> Result<Box<dyn Codec>> {
-    match config.name.as_str() {
-        "json" => Ok(Box::new(json::Json::<json::Unsorted>::default())),
-        "json-sorted" => Ok(Box::new(json::Json::<json::Sorted>::default())),
-        "msgpack" => Ok(Box::new(msgpack::MsgPack {})),
-        "influx" => Ok(Box::new(influx::Influx {})),
-        "binflux" => Ok(Box::new(binflux::BInflux {})),
-        "null" => Ok(Box::new(null::Null {})),
-        "string" => Ok(Box::new(string::String {})),
-        "statsd" => Ok(Box::new(statsd::StatsD {})),
-        "yaml" => Ok(Box::new(yaml::Yaml {})),
-        "binary" => Ok(Box::new(binary::Binary {})),
-        "syslog" => Ok(Box::new(syslog::Syslog::utcnow())),
-        "csv" => Ok(Box::new(csv::Csv {})),
-        s => Err(ErrorKind::CodecNotFound(s.into()).into()),
+    let codec: Box<dyn Codec> = match config.name.as_str() {
+        "csv" => return Ok(Box::new(csv::Csv::from_config(&config.config)?)),
+        "avro" => return Ok(Box::new(avro::Avro::from_config(&config.config)?)),
+        "protobuf" => return Ok(Box::new(protobuf::Protobuf::from_config(&config.config)?)),
+        "json" => Box::new(json::Json::<json::Unsorted>::default()),
+        "json-sorted" => Box::new(json::Json::<json::Sorted>::default()),
+        "msgpack" => Box::new(msgpack::MsgPack {}),
+        "influx" => Box::new(influx::Influx {}),
+        "binflux" => Box::new(binflux::BInflux {}),
+        "null" => Box::new(null::Null {}),
+        "string" => Box::new(string::String {}),
+        "statsd" => Box::new(statsd::StatsD {}),
+        "yaml" => Box::new(yaml::Yaml {}),
+        "binary" => Box::new(binary::Binary {}),
+        "syslog" => Box::new(syslog::Syslog::utcnow()),
+        s => return Err(ErrorKind::CodecNotFound(s.into()).into()),
+    };
+    // codecs without options must not silently ignore a given config
+    match config.config.as_ref() {
+        Some(c) if !c.is_null() && c.as_object().map_or(true, |o| !o.is_empty()) => {
+            Err(ErrorKind::InvalidConfiguration(
+                format!("{} codec", config.name),
+                "This codec does not take any configuration.".to_string(),
+            )
+            .into())
+        }
+        _ => Ok(codec),
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::config::NameWithConfig;
+    use tremor_value::literal;
 
     #[test]
     fn lookup() {
@@ -136,9 +170,26 @@ mod test {
         assert!(super::resolve(&"statsd".into()).is_ok());
         assert!(super::resolve(&"yaml".into()).is_ok());
         assert!(super::resolve(&"syslog".into()).is_ok());
+        assert!(super::resolve(&"csv".into()).is_ok());
+        // requires a registry
+        assert!(super::resolve(&"avro".into()).is_err());
+        // requires a descriptor and message
+        assert!(super::resolve(&"protobuf".into()).is_err());
         assert_eq!(
             super::resolve(&"snot".into()).err().unwrap().to_string(),
             "Codec \"snot\" not found."
         )
     }
+
+    #[test]
+    fn config() {
+        let with_config = |name: &str, config| NameWithConfig {
+            name: name.to_string(),
+            config: Some(config),
+        };
+        assert!(super::resolve(&with_config("csv", literal!({"delimiter": ";"}))).is_ok());
+        assert!(super::resolve(&with_config("csv", literal!({"snot": "badger"}))).is_err());
+        assert!(super::resolve(&with_config("json", literal!({}))).is_ok());
+        assert!(super::resolve(&with_config("json", literal!({"snot": "badger"}))).is_err());
+    }
 }
@@ -0,0 +1,1012 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The `avro` codec decodes and encodes [Apache Avro](https://avro.apache.org) data.
+//!
+//! Three framings are supported:
+//!
+//! * `confluent` (default): a `0x00` magic byte, a 4 byte big endian schema id and the datum.
+//!   Schemas are resolved from the `registry`, either a confluent compatible schema registry
+//!   (`http://...`) or a local directory containing `<id>.avsc` files.
+//!   Schemas unknown to the codec are fetched from a http registry before the data written
+//!   with them is decoded. Failed lookups are retried after 10 seconds.
+//!   Encoding requires a `schema_id`, the data is written with the configured `schema`,
+//!   or the schema `schema_id` from a local registry directory.
+//! * `single_object`: Avro single object encoding, a `0xC3 0x01` marker, the 8 byte CRC-64-AVRO
+//!   fingerprint of the writer schema and the datum. Writer schemas are looked up from the
+//!   configured `schema` and the `.avsc` files in a local `registry` directory.
+//! * `container`: Avro object container files, decoded into an array of records.
+//!
+//! If a `schema` is configured, it is used as reader schema on decode, so data written with
+//! an older or newer compatible schema is resolved against it.
+//!
+//! Logical types are mapped as follows:
+//!
+//! * `decimal` to a string, e.g. `"123.45"`
+//! * `timestamp-millis` and `timestamp-micros` to nanoseconds since epoch
+//! * `time-millis` and `time-micros` to nanoseconds since midnight
+//! * `date` to days since epoch
+//! * `uuid` to a string
+//! * `duration` to a record with `months`, `days` and `millis`
+
+use super::prelude::*;
+use crate::errors::Kind as ErrorKind;
+use apache_avro::{
+    from_avro_datum,
+    rabin::Rabin,
+    schema::{Name, RecordField, UnionSchema},
+    to_avro_datum,
+    types::Value as AvroValue,
+    Days, Decimal, Duration, Millis, Months, Reader, Schema, Writer,
+};
+use num_bigint::BigInt;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration as StdDuration, Instant},
+};
+use tremor_pipeline::{ConfigImpl, ConfigMap};
+use tremor_value::literal;
+
+const CONFLUENT_MAGIC: u8 = 0x00;
+const SINGLE_OBJECT_MAGIC: [u8; 2] = [0xC3, 0x01];
+const FINGERPRINT_LEN: usize = 8;
+const NANOS_PER_MICRO: i64 = 1_000;
+const NANOS_PER_MILLI: i64 = 1_000_000;
+/// how long a failed registry lookup is cached before it is retried
+const REGISTRY_RETRY_INTERVAL: StdDuration = StdDuration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Framing {
+    Confluent,
+    SingleObject,
+    Container,
+}
+
+impl Default for Framing {
+    fn default() -> Self {
+        Self::Confluent
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    framing: Framing,
+    /// path to an `.avsc` file, used as reader schema on decode and as writer schema on encode
+    #[serde(default = "Default::default")]
+    schema: Option<String>,
+    /// url of a confluent schema registry or path to a directory containing `<id>.avsc` files
+    #[serde(default = "Default::default")]
+    registry: Option<String>,
+    /// id of the schema to encode with, for the `confluent` framing
+    #[serde(default = "Default::default")]
+    schema_id: Option<u32>,
+}
+
+impl ConfigImpl for Config {}
+
+/// A schema together with the named types it defines, to resolve references
+#[derive(Clone, Debug)]
+struct Resolved {
+    schema: Schema,
+    names: HashMap<String, Schema>,
+}
+
+impl Resolved {
+    fn new(schema: Schema) -> Self {
+        let mut names = HashMap::new();
+        collect_names(&schema, &mut names);
+        Self { schema, names }
+    }
+
+    fn parse(raw: &str) -> Result<Self> {
+        Ok(Self::new(Schema::parse_str(raw)?))
+    }
+
+    fn load(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path).map_err(|e| {
+            invalid_config(format!("Unable to read schema {}: {e}", path.display()))
+        })?;
+        Self::parse(&raw)
+    }
+
+    fn lookup(&self, name: &Name) -> Result<&Schema> {
+        self.names
+            .get(&name.fullname(None))
+            .or_else(|| self.names.get(&name.name))
+            .ok_or_else(|| format!("Unknown avro type `{}`", name.fullname(None)).into())
+    }
+}
+
+fn collect_names(schema: &Schema, names: &mut HashMap<String, Schema>) {
+    match schema {
+        Schema::Record { name, fields, .. } => {
+            names.insert(name.fullname(None), schema.clone());
+            for field in fields {
+                collect_names(&field.schema, names);
+            }
+        }
+        Schema::Enum { name, .. } | Schema::Fixed { name, .. } => {
+            names.insert(name.fullname(None), schema.clone());
+        }
+        Schema::Array(inner) | Schema::Map(inner) => collect_names(inner, names),
+        Schema::Union(union) => {
+            for variant in union.variants() {
+                collect_names(variant, names);
+            }
+        }
+        Schema::Decimal { inner, .. } => collect_names(inner, names),
+        _ => (),
+    }
+}
+
+/// Source of writer schemas for the `confluent` framing
+#[derive(Clone, Debug)]
+enum Registry {
+    Directory(PathBuf),
+    Http(url::Url),
+}
+
+impl Registry {
+    fn new(registry: &str) -> Result<Self> {
+        if registry.starts_with("http://") || registry.starts_with("https://") {
+            Ok(Self::Http(url::Url::parse(registry)?))
+        } else {
+            Ok(Self::Directory(PathBuf::from(registry)))
+        }
+    }
+
+    fn load(dir: &Path, id: u32) -> Result<Resolved> {
+        Resolved::load(&dir.join(format!("{id}.avsc")))
+    }
+
+    async fn fetch_http(url: &url::Url, id: u32) -> Result<Resolved> {
+        let url = url.join(&format!("schemas/ids/{id}"))?;
+        let mut body = surf::get(url).recv_bytes().await?;
+        let response = tremor_value::parse_to_value(&mut body)?;
+        let schema = response
+            .get_str("schema")
+            .ok_or_else(|| Error::from(format!("Invalid registry response for {id}")))?;
+        Resolved::parse(schema)
+    }
+
+    /// all schemas in a local registry directory
+    fn all(&self) -> Result<Vec<Resolved>> {
+        match self {
+            Self::Directory(dir) => {
+                let mut schemas = Vec::new();
+                for entry in std::fs::read_dir(dir)? {
+                    let path = entry?.path();
+                    if path.extension().map_or(false, |ext| ext == "avsc") {
+                        schemas.push(Resolved::load(&path)?);
+                    }
+                }
+                Ok(schemas)
+            }
+            Self::Http(_) => Ok(Vec::new()),
+        }
+    }
+}
+
+/// The state of a writer schema lookup in the registry
+#[derive(Clone, Debug)]
+enum Lookup {
+    /// the schema is known
+    Found(Resolved),
+    /// the lookup failed and is retried after `retry_at`
+    Failed { error: String, retry_at: Instant },
+}
+
+#[derive(Clone)]
+pub struct Avro {
+    framing: Framing,
+    /// reader schema on decode, writer schema on encode
+    schema: Option<Resolved>,
+    registry: Option<Registry>,
+    schema_id: Option<u32>,
+    /// writer schemas by confluent schema id
+    by_id: HashMap<u32, Resolved>,
+    /// writer schema lookups in the registry, shared by all clones of this codec
+    lookups: Arc<RwLock<HashMap<u32, Lookup>>>,
+    /// writer schemas by single object fingerprint
+    by_fingerprint: HashMap<Vec<u8>, Resolved>,
+}
+
+fn invalid_config(msg: String) -> Error {
+    ErrorKind::InvalidConfiguration("avro codec".to_string(), msg).into()
+}
+
+impl Avro {
+    pub fn from_config(config: &ConfigMap) -> Result<Self> {
+        let config = config
+            .as_ref()
+            .map(Config::new)
+            .transpose()?
+            .unwrap_or_default();
+        let schema = config
+            .schema
+            .as_ref()
+            .map(|path| Resolved::load(Path::new(path)))
+            .transpose()?;
+        let registry = config.registry.as_deref().map(Registry::new).transpose()?;
+        let mut by_id = HashMap::new();
+        let mut by_fingerprint = HashMap::new();
+        match config.framing {
+            Framing::Confluent => {
+                let registry = registry.as_ref().ok_or_else(|| {
+                    invalid_config("The `confluent` framing requires a `registry`.".to_string())
+                })?;
+                match (config.schema_id, registry) {
+                    (Some(id), Registry::Directory(dir)) if schema.is_none() => {
+                        by_id.insert(id, Registry::load(dir, id)?);
+                    }
+                    (Some(_), Registry::Http(_)) if schema.is_none() => {
+                        return Err(invalid_config(
+                            "Encoding with a http `registry` requires the `schema` registered as `schema_id`."
+                                .to_string(),
+                        ));
+                    }
+                    _ => (),
+                }
+            }
+            Framing::SingleObject => {
+                let schemas = registry
+                    .as_ref()
+                    .map(Registry::all)
+                    .transpose()?
+                    .unwrap_or_default();
+                for resolved in schemas.into_iter().chain(schema.clone()) {
+                    let fingerprint = resolved.schema.fingerprint::<Rabin>().bytes;
+                    by_fingerprint.insert(fingerprint, resolved);
+                }
+                if by_fingerprint.is_empty() {
+                    return Err(invalid_config(
+                        "The `single_object` framing requires a `schema` or a `registry` directory."
+                            .to_string(),
+                    ));
+                }
+            }
+            Framing::Container => (),
+        }
+        Ok(Self {
+            framing: config.framing,
+            schema,
+            registry,
+            schema_id: config.schema_id,
+            by_id,
+            lookups: Arc::default(),
+            by_fingerprint,
+        })
+    }
+
+    /// makes the writer schema `id` available if it is known to this codec or one of its clones,
+    /// returns `false` if it needs to be looked up in the registry.
+    ///
+    /// Errors if the lookup of `id` failed and the retry interval did not pass yet.
+    fn cached_writer(&mut self, id: u32) -> Result<bool> {
+        if self.by_id.contains_key(&id) {
+            return Ok(true);
+        }
+        let lookups = self
+            .lookups
+            .read()
+            .map_err(|_| Error::from("Avro schema lookups poisoned"))?;
+        match lookups.get(&id) {
+            Some(Lookup::Found(resolved)) => {
+                self.by_id.insert(id, resolved.clone());
+                Ok(true)
+            }
+            Some(Lookup::Failed { error, retry_at }) if Instant::now() < *retry_at => {
+                Err(error.as_str().into())
+            }
+            Some(Lookup::Failed { .. }) | None => Ok(false),
+        }
+    }
+
+    /// records the result of looking up the writer schema `id` in the registry for all clones
+    fn record_writer(&mut self, id: u32, lookup: Result<Resolved>) -> Result<()> {
+        let (lookup, res) = match lookup {
+            Ok(resolved) => {
+                self.by_id.insert(id, resolved.clone());
+                (Lookup::Found(resolved), Ok(()))
+            }
+            Err(e) => {
+                let error = lookup_failed(id, &e);
+                (failed(error.clone()), Err(error.into()))
+            }
+        };
+        self.lookups
+            .write()
+            .map_err(|_| Error::from("Avro schema lookups poisoned"))?
+            .insert(id, lookup);
+        res
+    }
+
+    /// makes the writer schema `id` available, loading it from a registry directory if needed.
+    ///
+    /// Schemas from a http registry are fetched by `resolve` before decoding.
+    fn load_writer(&mut self, id: u32) -> Result<()> {
+        if self.cached_writer(id)? {
+            return Ok(());
+        }
+        match &self.registry {
+            Some(Registry::Directory(dir)) => {
+                let lookup = Registry::load(dir, id);
+                self.record_writer(id, lookup)
+            }
+            Some(Registry::Http(_)) => {
+                Err(format!("Avro schema {id} was not fetched from the registry").into())
+            }
+            None => Err("No avro schema registry configured".into()),
+        }
+    }
+
+    fn writer_schema(&self) -> Result<&Resolved> {
+        self.schema
+            .as_ref()
+            .ok_or_else(|| Error::from("Encoding avro requires a `schema`"))
+    }
+}
+
+/// the schema id of data in the `confluent` framing
+fn confluent_id(data: &[u8]) -> Result<u32> {
+    if data.len() < 5 || data[0] != CONFLUENT_MAGIC {
+        return Err("Invalid confluent avro framing".into());
+    }
+    let mut id = [0_u8; 4];
+    id.copy_from_slice(&data[1..5]);
+    Ok(u32::from_be_bytes(id))
+}
+
+fn lookup_failed(id: u32, e: &Error) -> String {
+    format!("Unable to fetch avro schema {id}: {e}")
+}
+
+fn failed(error: String) -> Lookup {
+    Lookup::Failed {
+        error,
+        retry_at: Instant::now() + REGISTRY_RETRY_INTERVAL,
+    }
+}
+
+/// converts `t` from a unit of `nanos_per_unit` nanoseconds to nanoseconds
+fn to_nanos(t: i64, nanos_per_unit: i64) -> Result<i64> {
+    t.checked_mul(nanos_per_unit)
+        .ok_or_else(|| format!("Avro time {t} out of range for nanoseconds").into())
+}
+
+/// decodes a single datum written with `writer`, resolved against `reader` if given
+fn decode_datum(
+    writer: &Resolved,
+    reader: Option<&Resolved>,
+    mut datum: &[u8],
+) -> Result<Value<'static>> {
+    let value = from_avro_datum(&writer.schema, &mut datum, reader.map(|r| &r.schema))?;
+    let reader = reader.unwrap_or(writer);
+    avro_to_value(value, &reader.schema, reader)
+}
+
+#[async_trait::async_trait]
+impl Codec for Avro {
+    fn name(&self) -> &str {
+        "avro"
+    }
+
+    fn mime_types(&self) -> Vec<&'static str> {
+        vec!["application/avro", "avro/binary"]
+    }
+
+    fn decode<'input>(
+        &mut self,
+        data: &'input mut [u8],
+        _ingest_ns: u64,
+    ) -> Result<Option<Value<'input>>> {
+        match self.framing {
+            Framing::Confluent => {
+                let id = confluent_id(data)?;
+                self.load_writer(id)?;
+                let writer = self
+                    .by_id
+                    .get(&id)
+                    .ok_or_else(|| Error::from(format!("Unknown avro schema id {id}")))?;
+                decode_datum(writer, self.schema.as_ref(), &data[5..]).map(Some)
+            }
+            Framing::SingleObject => {
+                let header = SINGLE_OBJECT_MAGIC.len() + FINGERPRINT_LEN;
+                if data.len() < header || data[..2] != SINGLE_OBJECT_MAGIC {
+                    return Err("Invalid avro single object encoding".into());
+                }
+                let writer = self
+                    .by_fingerprint
+                    .get(&data[2..header])
+                    .ok_or_else(|| Error::from("Unknown avro schema fingerprint"))?;
+                decode_datum(writer, self.schema.as_ref(), &data[header..]).map(Some)
+            }
+            Framing::Container => {
+                let reader = if let Some(schema) = &self.schema {
+                    Reader::with_schema(&schema.schema, &data[..])?
+                } else {
+                    Reader::new(&data[..])?
+                };
+                let resolved = self
+                    .schema
+                    .clone()
+                    .unwrap_or_else(|| Resolved::new(reader.writer_schema().clone()));
+                let values = reader
+                    .map(|value| avro_to_value(value?, &resolved.schema, &resolved))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Some(Value::from(values)))
+            }
+        }
+    }
+
+    /// fetches the writer schema of `data` from a http registry, unless it is already known
+    async fn resolve(&mut self, data: &[u8]) -> Result<()> {
+        let url = match &self.registry {
+            Some(Registry::Http(url)) if self.framing == Framing::Confluent => url.clone(),
+            _ => return Ok(()),
+        };
+        let id = confluent_id(data)?;
+        if !self.cached_writer(id)? {
+            let lookup = Registry::fetch_http(&url, id).await;
+            self.record_writer(id, lookup)?;
+        }
+        Ok(())
+    }
+
+    fn encode(&self, data: &Value) -> Result<Vec<u8>> {
+        match self.framing {
+            Framing::Confluent => {
+                let id = self.schema_id.ok_or_else(|| {
+                    Error::from("Encoding with the `confluent` framing requires a `schema_id`")
+                })?;
+                let writer = self
+                    .schema
+                    .as_ref()
+                    .or_else(|| self.by_id.get(&id))
+                    .ok_or_else(|| Error::from(format!("Unknown avro schema id {id}")))?;
+                let mut res = vec![CONFLUENT_MAGIC];
+                res.extend_from_slice(&id.to_be_bytes());
+                let value = value_to_avro(data, &writer.schema, writer)?;
+                res.append(&mut to_avro_datum(&writer.schema, value)?);
+                Ok(res)
+            }
+            Framing::SingleObject => {
+                let writer = self.writer_schema()?;
+                let mut res = SINGLE_OBJECT_MAGIC.to_vec();
+                res.append(&mut writer.schema.fingerprint::<Rabin>().bytes);
+                let value = value_to_avro(data, &writer.schema, writer)?;
+                res.append(&mut to_avro_datum(&writer.schema, value)?);
+                Ok(res)
+            }
+            Framing::Container => {
+                let writer = self.writer_schema()?;
+                let mut container = Writer::new(&writer.schema, Vec::new());
+                if let Some(values) = data.as_array() {
+                    for value in values {
+                        container.append(value_to_avro(value, &writer.schema, writer)?)?;
+                    }
+                } else {
+                    container.append(value_to_avro(data, &writer.schema, writer)?)?;
+                }
+                Ok(container.into_inner()?)
+            }
+        }
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Codec> {
+        Box::new(self.clone())
+    }
+}
+
+fn avro_to_value(value: AvroValue, schema: &Schema, names: &Resolved) -> Result<Value<'static>> {
+    if let Schema::Ref { name } = schema {
+        return avro_to_value(value, names.lookup(name)?, names);
+    }
+    Ok(match value {
+        AvroValue::Null => Value::null(),
+        AvroValue::Boolean(b) => Value::from(b),
+        AvroValue::Int(i) | AvroValue::Date(i) => Value::from(i),
+        AvroValue::Long(i) => Value::from(i),
+        AvroValue::Float(f) => Value::from(f64::from(f)),
+        AvroValue::Double(f) => Value::from(f),
+        AvroValue::Bytes(b) | AvroValue::Fixed(_, b) => Value::Bytes(b.into()),
+        AvroValue::String(s) | AvroValue::Enum(_, s) => Value::from(s),
+        AvroValue::Union(idx, value) => {
+            let variant = if let Schema::Union(union) = schema {
+                union.variants().get(idx as usize)
+            } else {
+                None
+            };
+            avro_to_value(*value, variant.unwrap_or(schema), names)?
+        }
+        AvroValue::Array(values) => {
+            let inner = if let Schema::Array(inner) = schema {
+                inner.as_ref()
+            } else {
+                schema
+            };
+            Value::from(
+                values
+                    .into_iter()
+                    .map(|v| avro_to_value(v, inner, names))
+                    .collect::<Result<Vec<_>>>()?,
+            )
+        }
+        AvroValue::Map(values) => {
+            let inner = if let Schema::Map(inner) = schema {
+                inner.as_ref()
+            } else {
+                schema
+            };
+            let mut record = Object::with_capacity(values.len());
+            for (k, v) in values {
+                record.insert(k.into(), avro_to_value(v, inner, names)?);
+            }
+            Value::from(record)
+        }
+        AvroValue::Record(values) => {
+            let fields: &[RecordField] = if let Schema::Record { fields, .. } = schema {
+                fields
+            } else {
+                &[]
+            };
+            let mut record = Object::with_capacity(values.len());
+            for (k, v) in values {
+                let field_schema = fields
+                    .iter()
+                    .find(|f| f.name == k)
+                    .map_or(&Schema::Null, |f| &f.schema);
+                record.insert(k.into(), avro_to_value(v, field_schema, names)?);
+            }
+            Value::from(record)
+        }
+        AvroValue::Decimal(d) => {
+            let scale = if let Schema::Decimal { scale, .. } = schema {
+                *scale
+            } else {
+                0
+            };
+            let unscaled = BigInt::from_signed_bytes_be(&Vec::<u8>::try_from(&d)?);
+            Value::from(format_decimal(&unscaled, scale))
+        }
+        AvroValue::TimeMillis(t) => Value::from(i64::from(t) * NANOS_PER_MILLI),
+        AvroValue::TimestampMillis(t) => Value::from(to_nanos(t, NANOS_PER_MILLI)?),
+        AvroValue::TimeMicros(t) | AvroValue::TimestampMicros(t) => {
+            Value::from(to_nanos(t, NANOS_PER_MICRO)?)
+        }
+        AvroValue::Duration(d) => literal!({
+            "months": u32::from(d.months()),
+            "days": u32::from(d.days()),
+            "millis": u32::from(d.millis()),
+        }),
+        AvroValue::Uuid(u) => Value::from(u.to_string()),
+    })
+}
+
+fn value_to_avro(value: &Value, schema: &Schema, names: &Resolved) -> Result<AvroValue> {
+    let invalid = || Error::from(format!("Invalid value {value} for avro type {schema:?}"));
+    Ok(match schema {
+        Schema::Null if value.is_null() => AvroValue::Null,
+        Schema::Null => return Err(invalid()),
+        Schema::Boolean => AvroValue::Boolean(value.as_bool().ok_or_else(invalid)?),
+        Schema::Int => AvroValue::Int(value.as_i32().ok_or_else(invalid)?),
+        Schema::Long => AvroValue::Long(value.as_i64().ok_or_else(invalid)?),
+        #[allow(clippy::cast_possible_truncation)]
+        Schema::Float => AvroValue::Float(value.cast_f64().ok_or_else(invalid)? as f32),
+        Schema::Double => AvroValue::Double(value.cast_f64().ok_or_else(invalid)?),
+        Schema::Bytes => AvroValue::Bytes(
+            value
+                .as_bytes()
+                .or_else(|| value.as_str().map(str::as_bytes))
+                .ok_or_else(invalid)?
+                .to_vec(),
+        ),
+        Schema::String => AvroValue::String(value.as_str().ok_or_else(invalid)?.to_string()),
+        Schema::Array(inner) => AvroValue::Array(
+            value
+                .as_array()
+                .ok_or_else(invalid)?
+                .iter()
+                .map(|v| value_to_avro(v, inner, names))
+                .collect::<Result<_>>()?,
+        ),
+        Schema::Map(inner) => AvroValue::Map(
+            value
+                .as_object()
+                .ok_or_else(invalid)?
+                .iter()
+                .map(|(k, v)| Ok((k.to_string(), value_to_avro(v, inner, names)?)))
+                .collect::<Result<_>>()?,
+        ),
+        Schema::Union(union) => union_to_avro(value, union, names).ok_or_else(invalid)?,
+        Schema::Record { fields, .. } => {
+            if !value.is_object() {
+                return Err(invalid());
+            }
+            let mut record = Vec::with_capacity(fields.len());
+            for field in fields {
+                let v = if let Some(v) = value.get(field.name.as_str()) {
+                    value_to_avro(v, &field.schema, names)?
+                } else if let Some(default) = &field.default {
+                    value_to_avro(&tremor_value::to_value(default)?, &field.schema, names)?
+                } else {
+                    value_to_avro(&Value::null(), &field.schema, names).map_err(|_| {
+                        Error::from(format!("Missing value for avro field `{}`", field.name))
+                    })?
+                };
+                record.push((field.name.clone(), v));
+            }
+            AvroValue::Record(record)
+        }
+        Schema::Enum { symbols, .. } => {
+            let symbol = value.as_str().ok_or_else(invalid)?;
+            let idx = symbols
+                .iter()
+                .position(|s| s == symbol)
+                .ok_or_else(invalid)?;
+            AvroValue::Enum(u32::try_from(idx)?, symbol.to_string())
+        }
+        Schema::Fixed { size, .. } => {
+            let bytes = value
+                .as_bytes()
+                .or_else(|| value.as_str().map(str::as_bytes))
+                .filter(|b| b.len() == *size)
+                .ok_or_else(invalid)?;
+            AvroValue::Fixed(*size, bytes.to_vec())
+        }
+        Schema::Decimal { scale, .. } => {
+            let unscaled = if let Some(s) = value.as_str() {
+                parse_decimal(s, *scale)
+            } else if let Some(i) = value.as_i64() {
+                parse_decimal(&i.to_string(), *scale)
+            } else if let Some(f) = value.cast_f64() {
+                parse_decimal(&format!("{f:.scale$}", scale = *scale), *scale)
+            } else {
+                None
+            };
+            AvroValue::Decimal(Decimal::from(
+                unscaled.ok_or_else(invalid)?.to_signed_bytes_be(),
+            ))
+        }
+        Schema::Uuid => {
+            AvroValue::String(value.as_str().ok_or_else(invalid)?.to_string()).resolve(schema)?
+        }
+        Schema::Date => AvroValue::Date(value.as_i32().ok_or_else(invalid)?),
+        Schema::TimeMillis => AvroValue::TimeMillis(i32::try_from(
+            value.as_i64().ok_or_else(invalid)? / NANOS_PER_MILLI,
+        )?),
+        Schema::TimeMicros => {
+            AvroValue::TimeMicros(value.as_i64().ok_or_else(invalid)? / NANOS_PER_MICRO)
+        }
+        Schema::TimestampMillis => {
+            AvroValue::TimestampMillis(value.as_i64().ok_or_else(invalid)? / NANOS_PER_MILLI)
+        }
+        Schema::TimestampMicros => {
+            AvroValue::TimestampMicros(value.as_i64().ok_or_else(invalid)? / NANOS_PER_MICRO)
+        }
+        Schema::Duration => {
+            let part = |name: &str| value.get_u32(name).ok_or_else(invalid);
+            AvroValue::Duration(Duration::new(
+                Months::new(part("months")?),
+                Days::new(part("days")?),
+                Millis::new(part("millis")?),
+            ))
+        }
+        Schema::Ref { name } => value_to_avro(value, names.lookup(name)?, names)?,
+    })
+}
+
+/// encodes `value` as the first matching variant of the union
+fn union_to_avro(value: &Value, union: &UnionSchema, names: &Resolved) -> Option<AvroValue> {
+    union
+        .variants()
+        .iter()
+        .enumerate()
+        .find_map(|(idx, variant)| {
+            let v = value_to_avro(value, variant, names).ok()?;
+            Some(AvroValue::Union(u32::try_from(idx).ok()?, Box::new(v)))
+        })
+}
+
+fn format_decimal(unscaled: &BigInt, scale: usize) -> String {
+    let digits = unscaled.magnitude().to_string();
+    let sign = if unscaled.sign() == num_bigint::Sign::Minus {
+        "-"
+    } else {
+        ""
+    };
+    if scale == 0 {
+        return format!("{sign}{digits}");
+    }
+    let digits = format!("{digits:0>width$}", width = scale + 1);
+    let (int, frac) = digits.split_at(digits.len() - scale);
+    format!("{sign}{int}.{frac}")
+}
+
+fn parse_decimal(s: &str, scale: usize) -> Option<BigInt> {
+    let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+    if frac.len() > scale || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    format!("{int}{frac:0<scale$}").parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_std::{
+        io::{ReadExt, WriteExt},
+        net::TcpListener,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tremor_value::{literal, prelude::WritableTrait};
+
+    const SCHEMA_V1: &str = r#"{
+        "type": "record",
+        "name": "Event",
+        "namespace": "tremor.test",
+        "fields": [
+            {"name": "name", "type": "string"},
+            {"name": "count", "type": "long"},
+            {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["INFO", "ERROR"]}},
+            {"name": "tags", "type": {"type": "array", "items": "string"}},
+            {"name": "labels", "type": {"type": "map", "values": "int"}},
+            {"name": "parent", "type": ["null", "string"], "default": null},
+            {"name": "amount", "type": {"type": "bytes", "logicalType": "decimal", "precision": 10, "scale": 2}},
+            {"name": "at", "type": {"type": "long", "logicalType": "timestamp-micros"}}
+        ]
+    }"#;
+
+    // v2 drops `tags` and adds `source` with a default
+    const SCHEMA_V2: &str = r#"{
+        "type": "record",
+        "name": "Event",
+        "namespace": "tremor.test",
+        "fields": [
+            {"name": "name", "type": "string"},
+            {"name": "count", "type": "long"},
+            {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["INFO", "ERROR"]}},
+            {"name": "labels", "type": {"type": "map", "values": "int"}},
+            {"name": "parent", "type": ["null", "string"], "default": null},
+            {"name": "amount", "type": {"type": "bytes", "logicalType": "decimal", "precision": 10, "scale": 2}},
+            {"name": "at", "type": {"type": "long", "logicalType": "timestamp-micros"}},
+            {"name": "source", "type": "string", "default": "unknown"}
+        ]
+    }"#;
+
+    fn event() -> Value<'static> {
+        literal!({
+            "name": "snot",
+            "count": 42,
+            "kind": "ERROR",
+            "tags": ["badger"],
+            "labels": { "a": 1 },
+            "parent": "badger",
+            "amount": "-123.45",
+            "at": 1_600_000_000_123_456_000_i64
+        })
+    }
+
+    /// a local registry with the schemas `1` (v1) and `2` (v2)
+    fn registry() -> Result<tempfile::TempDir> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("1.avsc"), SCHEMA_V1)?;
+        std::fs::write(dir.path().join("2.avsc"), SCHEMA_V2)?;
+        Ok(dir)
+    }
+
+    #[test]
+    fn confluent_roundtrip() -> Result<()> {
+        let registry = registry()?;
+        let mut codec = Avro::from_config(&Some(literal!({
+            "registry": registry.path().display().to_string(),
+            "schema_id": 1
+        })))?;
+        let mut encoded = codec.encode(&event())?;
+        assert_eq!(&[0_u8, 0, 0, 0, 1], &encoded[..5]);
+        let decoded = codec.decode(&mut encoded, 0)?;
+        assert_eq!(Some(event()), decoded);
+        Ok(())
+    }
+
+    #[test]
+    fn confluent_schema_evolution() -> Result<()> {
+        let registry = registry()?;
+        let writer = Avro::from_config(&Some(literal!({
+            "registry": registry.path().display().to_string(),
+            "schema_id": 1
+        })))?;
+        let schema = registry.path().join("2.avsc");
+        let mut reader = Avro::from_config(&Some(literal!({
+            "registry": registry.path().display().to_string(),
+            "schema": schema.display().to_string()
+        })))?;
+        let mut encoded = writer.encode(&event())?;
+        let decoded = reader.decode(&mut encoded, 0)?;
+        let mut expected = event();
+        expected.remove("tags")?;
+        expected.insert("source", "unknown")?;
+        assert_eq!(Some(expected), decoded);
+
+        // unknown schema ids are an error
+        encoded[4] = 42;
+        assert!(reader.decode(&mut encoded, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn single_object_roundtrip() -> Result<()> {
+        let registry = registry()?;
+        let schema = registry.path().join("1.avsc");
+        let mut codec = Avro::from_config(&Some(literal!({
+            "framing": "single_object",
+            "schema": schema.display().to_string()
+        })))?;
+        let mut encoded = codec.encode(&event())?;
+        assert_eq!(&SINGLE_OBJECT_MAGIC, &encoded[..2]);
+        let decoded = codec.decode(&mut encoded, 0)?;
+        assert_eq!(Some(event()), decoded);
+        Ok(())
+    }
+
+    #[test]
+    fn container_roundtrip() -> Result<()> {
+        let registry = registry()?;
+        let schema = registry.path().join("1.avsc");
+        let mut writer = Avro::from_config(&Some(literal!({
+            "framing": "container",
+            "schema": schema.display().to_string()
+        })))?;
+        let events = Value::from(vec![event(), event()]);
+        let mut encoded = writer.encode(&events)?;
+        assert_eq!(Some(events.clone()), writer.decode(&mut encoded, 0)?);
+
+        // containers embed their schema
+        let mut reader = Avro::from_config(&Some(literal!({ "framing": "container" })))?;
+        assert_eq!(Some(events), reader.decode(&mut encoded, 0)?);
+        Ok(())
+    }
+
+    #[test]
+    fn unions_and_defaults() -> Result<()> {
+        let registry = registry()?;
+        let codec = Avro::from_config(&Some(literal!({
+            "registry": registry.path().display().to_string(),
+            "schema_id": 1
+        })))?;
+        let mut event = event();
+        event.remove("parent")?;
+        let mut decoder = codec.clone();
+        let mut encoded = codec.encode(&event)?;
+        let decoded = decoder.decode(&mut encoded, 0)?;
+        event.insert("parent", ())?;
+        assert_eq!(Some(event.clone()), decoded);
+
+        event.insert("kind", "SNOT")?;
+        assert!(codec.encode(&event).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn decimals() {
+        let n = parse_decimal("-0.05", 2).unwrap_or_default();
+        assert_eq!("-0.05", format_decimal(&n, 2));
+        let n = parse_decimal("12", 3).unwrap_or_default();
+        assert_eq!("12.000", format_decimal(&n, 3));
+        assert!(parse_decimal("1.234", 2).is_none());
+    }
+
+    #[test]
+    fn timestamp_overflow() {
+        let names = Resolved::new(Schema::TimestampMillis);
+        let max = i64::MAX / NANOS_PER_MILLI;
+        assert_eq!(
+            Some(Value::from(max * NANOS_PER_MILLI)),
+            avro_to_value(AvroValue::TimestampMillis(max), &names.schema, &names).ok()
+        );
+        assert!(avro_to_value(AvroValue::TimestampMillis(max + 1), &names.schema, &names).is_err());
+        let names = Resolved::new(Schema::TimestampMicros);
+        assert!(
+            avro_to_value(AvroValue::TimestampMicros(i64::MIN), &names.schema, &names).is_err()
+        );
+    }
+
+    /// a confluent schema registry serving `SCHEMA_V1` as schema `1`
+    async fn http_registry(requests: Arc<AtomicUsize>) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        async_std::task::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                requests.fetch_add(1, Ordering::AcqRel);
+                let mut buf = vec![0_u8; 4096];
+                let n = stream.read(&mut buf).await.unwrap_or_default();
+                let (status, body) = if buf[..n].starts_with(b"GET /schemas/ids/1 ") {
+                    ("200 OK", literal!({ "schema": SCHEMA_V1 }).encode())
+                } else {
+                    (
+                        "404 Not Found",
+                        r#"{"error_code":40403,"message":"Schema not found"}"#.to_string(),
+                    )
+                };
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.ok();
+            }
+        });
+        Ok(format!("http://{addr}/"))
+    }
+
+    /// resolves the writer schema of `data` and decodes it
+    async fn decode_resolved(codec: &mut Avro, data: &[u8]) -> Result<Option<Value<'static>>> {
+        codec.resolve(data).await?;
+        codec
+            .decode(&mut data.to_vec(), 0)
+            .map(|v| v.map(Value::into_static))
+    }
+
+    #[async_std::test]
+    async fn confluent_http_registry() -> Result<()> {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let url = http_registry(requests.clone()).await?;
+        let local = registry()?;
+        let encoder = Avro::from_config(&Some(literal!({
+            "registry": local.path().display().to_string(),
+            "schema_id": 1
+        })))?;
+        let encoded = encoder.encode(&event())?;
+        let mut codec = Avro::from_config(&Some(literal!({ "registry": url })))?;
+        let mut clone = codec.boxed_clone();
+
+        // the schema is fetched before decoding
+        assert!(codec.decode(&mut encoded.clone(), 0).is_err());
+        assert_eq!(Some(event()), decode_resolved(&mut codec, &encoded).await?);
+        assert_eq!(Some(event()), decode_resolved(&mut codec, &encoded).await?);
+        assert_eq!(1, requests.load(Ordering::Acquire));
+
+        // clones share the fetched schemas
+        assert!(clone.decode(&mut encoded.clone(), 0).is_ok());
+        assert_eq!(1, requests.load(Ordering::Acquire));
+
+        // failed lookups are not retried right away
+        let mut unknown = encoded.clone();
+        unknown[4] = 3;
+        assert!(decode_resolved(&mut codec, &unknown).await.is_err());
+        assert!(decode_resolved(&mut codec, &unknown).await.is_err());
+        assert_eq!(2, requests.load(Ordering::Acquire));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(Avro::from_config(&None).is_err());
+        assert!(Avro::from_config(&Some(literal!({ "framing": "single_object" }))).is_err());
+        assert!(Avro::from_config(&Some(literal!({
+            "registry": "/does/not/exist",
+            "schema_id": 1
+        })))
+        .is_err());
+        assert!(Avro::from_config(&Some(literal!({
+            "registry": "http://localhost:8081/",
+            "schema_id": 1
+        })))
+        .is_err());
+    }
+}
@@ -11,11 +11,188 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+//! Decodes a CSV row into an array of strings, or into a record if `headers` are configured.
+//!
+//! Configuration:
+//! * `delimiter` - field delimiter, defaults to `,`
+//! * `quote` - quote character, defaults to `"`
+//! * `escape` - escape character for quotes within quoted fields, quotes are doubled if not set
+//! * `quoting` - set to `false` to treat quotes as regular characters
+//! * `headers` - `true` to read field names from the first row of each stream,
+//!   or a list of field names
+//! * `types` - coerces columns to `int`, `float`, `bool` or `string`, keyed by field name,
+//!   or by zero based column index without `headers`. Empty fields of typed columns become `null`.
+//!
+//! Rows with a different number of fields than `headers` fail to decode.
+//!
+//! Records are encoded in the order of the configured field names or of their sorted keys otherwise,
+//! no header row is written.
+
 use crate::codec::prelude::*;
+use crate::errors::Kind as ErrorKind;
 use beef::Cow;
+use halfbrown::HashMap;
+use tremor_pipeline::{ConfigImpl, ConfigMap};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Type {
+    String,
+    Int,
+    Float,
+    Bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Headers {
+    /// read from the first row
+    FirstRow(bool),
+    Names(Vec<String>),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default = "default_delimiter")]
+    delimiter: char,
+    #[serde(default = "default_quote")]
+    quote: char,
+    #[serde(default = "Default::default")]
+    escape: Option<char>,
+    #[serde(default = "default_true")]
+    quoting: bool,
+    #[serde(default = "Default::default")]
+    headers: Option<Headers>,
+    #[serde(default = "Default::default")]
+    types: HashMap<String, Type>,
+}
+
+impl ConfigImpl for Config {}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            delimiter: default_delimiter(),
+            quote: default_quote(),
+            escape: None,
+            quoting: true,
+            headers: None,
+            types: HashMap::new(),
+        }
+    }
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+fn default_quote() -> char {
+    '"'
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn invalid_config(msg: String) -> Error {
+    ErrorKind::InvalidConfiguration("csv codec".to_string(), msg).into()
+}
+
+fn single_byte(name: &str, c: char) -> Result<u8> {
+    u8::try_from(c)
+        .ok()
+        .filter(u8::is_ascii)
+        .ok_or_else(|| invalid_config(format!("`{name}` needs to be a single ASCII character.")))
+}
 
 #[derive(Clone)]
-pub struct Csv {}
+pub struct Csv {
+    delimiter: u8,
+    quote: u8,
+    escape: Option<u8>,
+    quoting: bool,
+    /// read the field names from the first row
+    headers_from_first_row: bool,
+    /// field names, rows are decoded into records once they are known
+    headers: Option<Vec<String>>,
+    types: HashMap<String, Type>,
+}
+
+impl Default for Csv {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            escape: None,
+            quoting: true,
+            headers_from_first_row: false,
+            headers: None,
+            types: HashMap::new(),
+        }
+    }
+}
+
+impl Csv {
+    pub fn from_config(config: &ConfigMap) -> Result<Self> {
+        let config = config
+            .as_ref()
+            .map(Config::new)
+            .transpose()?
+            .unwrap_or_default();
+        let (headers_from_first_row, headers) = match config.headers {
+            Some(Headers::FirstRow(from_first_row)) => (from_first_row, None),
+            Some(Headers::Names(names)) => (false, Some(names)),
+            None => (false, None),
+        };
+        Ok(Self {
+            delimiter: single_byte("delimiter", config.delimiter)?,
+            quote: single_byte("quote", config.quote)?,
+            escape: config
+                .escape
+                .map(|escape| single_byte("escape", escape))
+                .transpose()?,
+            quoting: config.quoting,
+            headers_from_first_row,
+            headers,
+            types: config.types,
+        })
+    }
+
+    fn coerce<'input>(
+        &self,
+        column: usize,
+        name: Option<&str>,
+        field: &str,
+    ) -> Result<Value<'input>> {
+        let ty = match name {
+            Some(name) => self.types.get(name),
+            None => self.types.get(column.to_string().as_str()),
+        };
+        let invalid = |ty: &str| {
+            Error::from(format!(
+                "Invalid {ty} in CSV column {}: {field:?}",
+                name.map_or_else(|| column.to_string(), ToString::to_string)
+            ))
+        };
+        Ok(match ty {
+            None | Some(Type::String) => Value::String(Cow::from(field.to_string())),
+            Some(_) if field.is_empty() => Value::const_null(),
+            Some(Type::Int) => {
+                Value::from(field.trim().parse::<i64>().map_err(|_| invalid("int"))?)
+            }
+            Some(Type::Float) => {
+                Value::from(field.trim().parse::<f64>().map_err(|_| invalid("float"))?)
+            }
+            Some(Type::Bool) => match field.trim().to_ascii_lowercase().as_str() {
+                "true" => Value::from(true),
+                "false" => Value::from(false),
+                _ => return Err(invalid("bool")),
+            },
+        })
+    }
+}
 
 impl Codec for Csv {
     fn name(&self) -> &str {
@@ -33,6 +210,11 @@ impl Codec for Csv {
     ) -> Result<Option<Value<'input>>> {
         let mut reader = csv::ReaderBuilder::new()
             .has_headers(false)
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .escape(self.escape)
+            .double_quote(self.escape.is_none())
+            .quoting(self.quoting)
             .from_reader(&*data); // the reborrow here is needed because std::io::Read is implemented only for &[u8], not &mut [u8]
 
         let record = match reader.records().next() {
@@ -41,35 +223,87 @@ impl Codec for Csv {
             None => return Ok(None),
         }?;
 
-        let mut fields = vec![];
-        for field in record.iter() {
-            fields.push(Value::String(Cow::from(field.to_string())));
+        if self.headers_from_first_row && self.headers.is_none() {
+            self.headers = Some(record.iter().map(ToString::to_string).collect());
+            return Ok(None);
         }
 
-        Ok(Some(Value::Array(fields)))
+        if let Some(headers) = &self.headers {
+            if record.len() != headers.len() {
+                return Err(format!(
+                    "CSV row has {} fields, expected {} for the headers",
+                    record.len(),
+                    headers.len()
+                )
+                .into());
+            }
+            let mut fields = Object::with_capacity(headers.len());
+            for (column, (name, field)) in headers.iter().zip(record.iter()).enumerate() {
+                fields.insert(
+                    Cow::from(name.clone()),
+                    self.coerce(column, Some(name), field)?,
+                );
+            }
+            Ok(Some(Value::from(fields)))
+        } else {
+            let mut fields = vec![];
+            for (column, field) in record.iter().enumerate() {
+                fields.push(self.coerce(column, None, field)?);
+            }
+            Ok(Some(Value::Array(fields)))
+        }
     }
 
     fn encode(&self, data: &Value) -> Result<Vec<u8>> {
-        if let Some(values) = data.as_array() {
-            let fields: Vec<String> = values.iter().map(ToString::to_string).collect();
-
-            let mut result = vec![];
-            let mut writer = csv::Writer::from_writer(&mut result);
-            writer.write_record(&fields)?;
-            writer.flush()?;
-            drop(writer);
-
-            while result.last() == Some(&b'\n') || result.last() == Some(&b'\r') {
-                result.pop();
+        let fields: Vec<String> = if let Some(values) = data.as_array() {
+            values.iter().map(ToString::to_string).collect()
+        } else if let Some(record) = data.as_object() {
+            let to_string = |v: Option<&Value>| match v {
+                Some(Value::String(s)) => s.to_string(),
+                Some(v) if !v.is_null() => v.to_string(),
+                _ => String::new(),
+            };
+            if let Some(headers) = &self.headers {
+                headers
+                    .iter()
+                    .map(|name| to_string(record.get(name.as_str())))
+                    .collect()
+            } else {
+                // sorted, so all records have the same column order
+                let mut fields: Vec<_> = record.iter().collect();
+                fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+                fields
+                    .into_iter()
+                    .map(|(_, v)| to_string(Some(v)))
+                    .collect()
             }
+        } else {
+            return Err(
+                ErrorKind::NotCSVSerializableValue(format!("{:?}", data.value_type())).into(),
+            );
+        };
+
+        let mut result = vec![];
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .escape(self.escape.unwrap_or(b'\\'))
+            .double_quote(self.escape.is_none())
+            .quote_style(if self.quoting {
+                csv::QuoteStyle::Necessary
+            } else {
+                csv::QuoteStyle::Never
+            })
+            .from_writer(&mut result);
+        writer.write_record(&fields)?;
+        writer.flush()?;
+        drop(writer);
 
-            return Ok(result);
+        while result.last() == Some(&b'\n') || result.last() == Some(&b'\r') {
+            result.pop();
         }
 
-        Err(
-            crate::errors::ErrorKind::NotCSVSerializableValue(format!("{:?}", data.value_type()))
-                .into(),
-        )
+        Ok(result)
     }
 
     fn boxed_clone(&self) -> Box<dyn Codec> {
@@ -81,9 +315,20 @@ impl Codec for Csv {
 mod tests {
     use super::*;
 
+    fn decode_all(codec: &mut Csv, rows: &[&str]) -> Result<Vec<Value<'static>>> {
+        let mut res = Vec::new();
+        for row in rows {
+            let mut data = row.as_bytes().to_vec();
+            if let Some(value) = codec.decode(&mut data, 0)? {
+                res.push(value.into_static());
+            }
+        }
+        Ok(res)
+    }
+
     #[test]
     fn test_can_decode_csv() {
-        let mut codec = Csv {};
+        let mut codec = Csv::default();
         let mut data = b"a,b,c,123".to_vec();
         let result = codec.decode(&mut data, 0);
 
@@ -92,11 +337,114 @@ mod tests {
 
     #[test]
     fn test_can_encode_csv() {
-        let codec = Csv {};
+        let codec = Csv::default();
         let data = literal!(["a", "b", "c", 123]);
 
         let result = codec.encode(&data).unwrap();
 
         assert_eq!(b"a,b,c,123".to_vec(), result);
     }
+
+    #[test]
+    fn dialect() -> Result<()> {
+        let mut codec = Csv::from_config(&Some(literal!({
+            "delimiter": ";",
+            "quote": "'",
+            "escape": "\\"
+        })))?;
+        assert_eq!(
+            vec![literal!(["a;b", "it's", "c"])],
+            decode_all(&mut codec, &["'a;b';'it\\'s';c"])?
+        );
+        assert_eq!(
+            b"'a;b';'it\\'s';c".to_vec(),
+            codec.encode(&literal!(["a;b", "it's", "c"]))?
+        );
+
+        let mut codec = Csv::from_config(&Some(literal!({ "quoting": false })))?;
+        assert_eq!(
+            vec![literal!(["\"a", "b\""])],
+            decode_all(&mut codec, &["\"a,b\""])?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn headers_and_types() -> Result<()> {
+        let mut codec = Csv::from_config(&Some(literal!({
+            "headers": true,
+            "types": {
+                "count": "int",
+                "ratio": "float",
+                "ok": "bool"
+            }
+        })))?;
+        assert_eq!(
+            vec![
+                literal!({"name": "snot", "count": 1, "ratio": 0.5, "ok": true}),
+                literal!({"name": "badger", "count": null, "ratio": 2.0, "ok": false}),
+            ],
+            decode_all(
+                &mut codec,
+                &["name,count,ratio,ok", "snot,1,0.5,true", "badger,,2,FALSE"]
+            )?
+        );
+        let mut data = b"snot,nope,1,true".to_vec();
+        assert!(codec.decode(&mut data, 0).is_err());
+        // rows need to match the headers
+        let mut data = b"snot,1,0.5,true,extra".to_vec();
+        assert!(codec.decode(&mut data, 0).is_err());
+        let mut data = b"snot,1".to_vec();
+        assert!(codec.decode(&mut data, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn named_headers() -> Result<()> {
+        let mut codec = Csv::from_config(&Some(literal!({
+            "headers": ["a", "b"],
+            "types": {"b": "int"}
+        })))?;
+        assert_eq!(
+            vec![literal!({"a": "1", "b": 2})],
+            decode_all(&mut codec, &["1,2"])?
+        );
+        assert_eq!(
+            b"1,2".to_vec(),
+            codec.encode(&literal!({"b": 2, "a": "1"}))?
+        );
+        // missing fields are empty
+        assert_eq!(b",2".to_vec(), codec.encode(&literal!({"b": 2}))?);
+        Ok(())
+    }
+
+    #[test]
+    fn encode_sorted_keys() -> Result<()> {
+        let codec = Csv::default();
+        assert_eq!(
+            b"1,2,3".to_vec(),
+            codec.encode(&literal!({"c": 3, "a": 1, "b": 2}))?
+        );
+        assert_eq!(
+            b"1,2,3".to_vec(),
+            codec.encode(&literal!({"b": 2, "c": 3, "a": 1}))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn positional_types() -> Result<()> {
+        let mut codec = Csv::from_config(&Some(literal!({
+            "types": {"1": "int"}
+        })))?;
+        assert_eq!(vec![literal!(["a", 1])], decode_all(&mut codec, &["a,1"])?);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(Csv::from_config(&Some(literal!({ "delimiter": "ä" }))).is_err());
+        assert!(Csv::from_config(&Some(literal!({ "types": {"a": "date"} }))).is_err());
+        assert!(Csv::from_config(&Some(literal!({ "snot": "badger" }))).is_err());
+    }
 }
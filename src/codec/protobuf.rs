@@ -0,0 +1,588 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The `protobuf` codec decodes and encodes protocol buffers messages
+//! described by a user supplied descriptor set.
+//!
+//! The descriptor set is generated from the `.proto` files via:
+//!
+//! ```sh
+//! protoc --include_imports --descriptor_set_out=events.desc events.proto
+//! ```
+//!
+//! Messages are mapped to records, enums to the name of their value, `bytes` to binary,
+//! `map` fields to records and repeated fields to arrays.
+//!
+//! Well known types are mapped as follows:
+//!
+//! * `google.protobuf.Timestamp` to nanoseconds since epoch
+//! * `google.protobuf.Duration` to nanoseconds
+//! * wrappers like `google.protobuf.StringValue` to their wrapped value
+//! * `google.protobuf.Any` to a record of the packed message, with its type url under `@type`.
+//!   If the packed message is not part of the descriptor set the raw bytes are kept under `value`.
+
+use super::prelude::*;
+use crate::errors::Kind as ErrorKind;
+use prost::Message;
+use prost_reflect::{
+    DescriptorPool, DynamicMessage, FieldDescriptor, Kind, MapKey, MessageDescriptor,
+    Value as PbValue,
+};
+use std::collections::HashMap;
+use tremor_pipeline::{ConfigImpl, ConfigMap};
+
+const TIMESTAMP: &str = "google.protobuf.Timestamp";
+const DURATION: &str = "google.protobuf.Duration";
+const ANY: &str = "google.protobuf.Any";
+const WRAPPERS: [&str; 9] = [
+    "google.protobuf.DoubleValue",
+    "google.protobuf.FloatValue",
+    "google.protobuf.Int64Value",
+    "google.protobuf.UInt64Value",
+    "google.protobuf.Int32Value",
+    "google.protobuf.UInt32Value",
+    "google.protobuf.BoolValue",
+    "google.protobuf.StringValue",
+    "google.protobuf.BytesValue",
+];
+const TYPE_KEY: &str = "@type";
+const TYPE_URL_PREFIX: &str = "type.googleapis.com/";
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// path to a `FileDescriptorSet` containing the message and all its dependencies
+    descriptor: String,
+    /// fully qualified name of the message to decode and encode, e.g. `my.package.Event`
+    message: String,
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Clone)]
+pub struct Protobuf {
+    message: MessageDescriptor,
+}
+
+impl Protobuf {
+    pub fn from_config(config: &ConfigMap) -> Result<Self> {
+        let config = config.as_ref().ok_or_else(|| {
+            ErrorKind::InvalidConfiguration(
+                "protobuf codec".to_string(),
+                "Missing config, `descriptor` and `message` are required.".to_string(),
+            )
+        })?;
+        let config = Config::new(config)?;
+        let descriptor = std::fs::read(&config.descriptor).map_err(|e| {
+            ErrorKind::InvalidConfiguration(
+                "protobuf codec".to_string(),
+                format!("Unable to read descriptor {}: {e}", config.descriptor),
+            )
+        })?;
+        Self::from_descriptor(&descriptor, &config.message)
+    }
+
+    fn from_descriptor(descriptor: &[u8], message: &str) -> Result<Self> {
+        let pool = DescriptorPool::decode(descriptor)?;
+        let message = pool.get_message_by_name(message).ok_or_else(|| {
+            ErrorKind::InvalidConfiguration(
+                "protobuf codec".to_string(),
+                format!("Message `{message}` not found in descriptor."),
+            )
+        })?;
+        Ok(Self { message })
+    }
+}
+
+impl Codec for Protobuf {
+    fn name(&self) -> &str {
+        "protobuf"
+    }
+
+    fn mime_types(&self) -> Vec<&'static str> {
+        vec!["application/protobuf", "application/x-protobuf"]
+    }
+
+    fn decode<'input>(
+        &mut self,
+        data: &'input mut [u8],
+        _ingest_ns: u64,
+    ) -> Result<Option<Value<'input>>> {
+        let message = DynamicMessage::decode(self.message.clone(), &data[..])?;
+        Ok(Some(message_to_value(&message)?))
+    }
+
+    fn encode(&self, data: &Value) -> Result<Vec<u8>> {
+        Ok(value_to_message(&self.message, data)?.encode_to_vec())
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Codec> {
+        Box::new(self.clone())
+    }
+}
+
+fn message_to_value(message: &DynamicMessage) -> Result<Value<'static>> {
+    let desc = message.descriptor();
+    match desc.full_name() {
+        TIMESTAMP | DURATION => {
+            let seconds = message
+                .get_field_by_name("seconds")
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default();
+            let nanos = message
+                .get_field_by_name("nanos")
+                .and_then(|v| v.as_i32())
+                .unwrap_or_default();
+            seconds
+                .checked_mul(NANOS_PER_SEC)
+                .and_then(|ns| ns.checked_add(i64::from(nanos)))
+                .map(Value::from)
+                .ok_or_else(|| {
+                    Error::from(format!("{} out of range for nanoseconds", desc.full_name()))
+                })
+        }
+        name if WRAPPERS.contains(&name) => {
+            let field = desc
+                .get_field(1)
+                .ok_or_else(|| Error::from(format!("Invalid wrapper type {name}")))?;
+            pb_to_value(&field, &message.get_field(&field))
+        }
+        ANY => any_to_value(message),
+        _ => {
+            let mut record = Object::with_capacity(desc.fields().len());
+            for field in desc.fields() {
+                if field.supports_presence() && !message.has_field(&field) {
+                    continue;
+                }
+                let value = pb_to_value(&field, &message.get_field(&field))?;
+                record.insert(field.name().to_string().into(), value);
+            }
+            Ok(Value::from(record))
+        }
+    }
+}
+
+fn any_to_value(message: &DynamicMessage) -> Result<Value<'static>> {
+    let type_url = message
+        .get_field_by_name("type_url")
+        .and_then(|v| v.as_str().map(ToString::to_string))
+        .unwrap_or_default();
+    let bytes = message
+        .get_field_by_name("value")
+        .and_then(|v| v.as_bytes().cloned())
+        .unwrap_or_default();
+    let packed = type_url
+        .rsplit('/')
+        .next()
+        .and_then(|name| message.descriptor().parent_pool().get_message_by_name(name));
+    let mut record = if let Some(packed) = packed {
+        let packed = DynamicMessage::decode(packed, bytes)?;
+        match message_to_value(&packed)? {
+            Value::Object(record) => *record,
+            // well known types that are mapped to scalars
+            value => {
+                let mut record = Object::with_capacity(2);
+                record.insert("value".into(), value);
+                record
+            }
+        }
+    } else {
+        let mut record = Object::with_capacity(2);
+        record.insert("value".into(), Value::Bytes(bytes.to_vec().into()));
+        record
+    };
+    record.insert(TYPE_KEY.into(), Value::from(type_url));
+    Ok(Value::from(record))
+}
+
+/// well known types that are mapped to scalar values
+fn is_scalar(name: &str) -> bool {
+    name == TIMESTAMP || name == DURATION || WRAPPERS.contains(&name)
+}
+
+fn pb_to_value(field: &FieldDescriptor, value: &PbValue) -> Result<Value<'static>> {
+    Ok(match value {
+        PbValue::Bool(b) => Value::from(*b),
+        PbValue::I32(i) => Value::from(*i),
+        PbValue::I64(i) => Value::from(*i),
+        PbValue::U32(u) => Value::from(*u),
+        PbValue::U64(u) => Value::from(*u),
+        PbValue::F32(f) => Value::from(f64::from(*f)),
+        PbValue::F64(f) => Value::from(*f),
+        PbValue::String(s) => Value::from(s.clone()),
+        PbValue::Bytes(b) => Value::Bytes(b.to_vec().into()),
+        PbValue::EnumNumber(n) => {
+            if let Kind::Enum(e) = field.kind() {
+                // unknown values are kept as their number
+                e.get_value(*n)
+                    .map_or_else(|| Value::from(*n), |v| Value::from(v.name().to_string()))
+            } else {
+                Value::from(*n)
+            }
+        }
+        PbValue::Message(m) => message_to_value(m)?,
+        PbValue::List(l) => Value::from(
+            l.iter()
+                .map(|v| pb_to_value(field, v))
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        PbValue::Map(m) => {
+            let value_field = if let Kind::Message(entry) = field.kind() {
+                entry.map_entry_value_field()
+            } else {
+                return Err(format!("Invalid map field {}", field.full_name()).into());
+            };
+            let mut record = Object::with_capacity(m.len());
+            for (k, v) in m {
+                let key = match k {
+                    MapKey::Bool(b) => b.to_string(),
+                    MapKey::I32(i) => i.to_string(),
+                    MapKey::I64(i) => i.to_string(),
+                    MapKey::U32(u) => u.to_string(),
+                    MapKey::U64(u) => u.to_string(),
+                    MapKey::String(s) => s.clone(),
+                };
+                record.insert(key.into(), pb_to_value(&value_field, v)?);
+            }
+            Value::from(record)
+        }
+    })
+}
+
+fn value_to_message(desc: &MessageDescriptor, data: &Value) -> Result<DynamicMessage> {
+    let mut message = DynamicMessage::new(desc.clone());
+    match desc.full_name() {
+        TIMESTAMP | DURATION => {
+            let nanos = data.as_i64().ok_or_else(|| {
+                Error::from(format!("Expected nanoseconds for {}", desc.full_name()))
+            })?;
+            message.set_field_by_name("seconds", PbValue::I64(nanos.div_euclid(NANOS_PER_SEC)));
+            message.set_field_by_name(
+                "nanos",
+                PbValue::I32(i32::try_from(nanos.rem_euclid(NANOS_PER_SEC))?),
+            );
+        }
+        name if WRAPPERS.contains(&name) => {
+            let field = desc
+                .get_field(1)
+                .ok_or_else(|| Error::from(format!("Invalid wrapper type {name}")))?;
+            message.set_field(&field, value_to_pb(&field, data)?);
+        }
+        ANY => {
+            let type_url = data
+                .get_str(TYPE_KEY)
+                .ok_or_else(|| Error::from(format!("Missing `{TYPE_KEY}` for {ANY}")))?;
+            let packed = type_url
+                .rsplit('/')
+                .next()
+                .and_then(|name| desc.parent_pool().get_message_by_name(name));
+            let bytes = if let Some(packed) = packed {
+                let value = if is_scalar(packed.full_name()) {
+                    data.get("value").unwrap_or(data)
+                } else {
+                    data
+                };
+                value_to_message(&packed, value)?.encode_to_vec()
+            } else {
+                data.get("value")
+                    .and_then(|v| v.as_bytes().or_else(|| v.as_str().map(str::as_bytes)))
+                    .map(<[u8]>::to_vec)
+                    .ok_or_else(|| {
+                        Error::from(format!("Unknown type `{type_url}` without `value`"))
+                    })?
+            };
+            let type_url = if type_url.contains('/') {
+                type_url.to_string()
+            } else {
+                format!("{TYPE_URL_PREFIX}{type_url}")
+            };
+            message.set_field_by_name("type_url", PbValue::String(type_url));
+            message.set_field_by_name("value", PbValue::Bytes(bytes.into()));
+        }
+        _ => {
+            let record = data.as_object().ok_or_else(|| {
+                Error::from(format!("Expected a record for {}", desc.full_name()))
+            })?;
+            for (name, value) in record.iter() {
+                let field = desc.get_field_by_name(name).ok_or_else(|| {
+                    Error::from(format!("Unknown field `{name}` for {}", desc.full_name()))
+                })?;
+                // null is treated as absent
+                if !value.is_null() {
+                    message.set_field(&field, value_to_pb(&field, value)?);
+                }
+            }
+        }
+    }
+    Ok(message)
+}
+
+fn value_to_pb(field: &FieldDescriptor, value: &Value) -> Result<PbValue> {
+    if field.is_map() {
+        let entry = if let Kind::Message(entry) = field.kind() {
+            entry
+        } else {
+            return Err(format!("Invalid map field {}", field.full_name()).into());
+        };
+        let key_field = entry.map_entry_key_field();
+        let value_field = entry.map_entry_value_field();
+        let record = value
+            .as_object()
+            .ok_or_else(|| invalid_value(field, "a record"))?;
+        let mut map = HashMap::with_capacity(record.len());
+        for (k, v) in record.iter() {
+            let key = match key_field.kind() {
+                Kind::Bool => MapKey::Bool(k == "true"),
+                Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => MapKey::I32(k.parse()?),
+                Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => MapKey::I64(k.parse()?),
+                Kind::Uint32 | Kind::Fixed32 => MapKey::U32(k.parse()?),
+                Kind::Uint64 | Kind::Fixed64 => MapKey::U64(k.parse()?),
+                _ => MapKey::String(k.to_string()),
+            };
+            map.insert(key, value_to_single_pb(&value_field, v)?);
+        }
+        Ok(PbValue::Map(map))
+    } else if field.is_list() {
+        let array = value
+            .as_array()
+            .ok_or_else(|| invalid_value(field, "an array"))?;
+        Ok(PbValue::List(
+            array
+                .iter()
+                .map(|v| value_to_single_pb(field, v))
+                .collect::<Result<_>>()?,
+        ))
+    } else {
+        value_to_single_pb(field, value)
+    }
+}
+
+fn value_to_single_pb(field: &FieldDescriptor, value: &Value) -> Result<PbValue> {
+    Ok(match field.kind() {
+        Kind::Double => PbValue::F64(
+            value
+                .cast_f64()
+                .ok_or_else(|| invalid_value(field, "a float"))?,
+        ),
+        #[allow(clippy::cast_possible_truncation)]
+        Kind::Float => PbValue::F32(
+            value
+                .cast_f64()
+                .ok_or_else(|| invalid_value(field, "a float"))? as f32,
+        ),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => PbValue::I32(
+            value
+                .as_i32()
+                .ok_or_else(|| invalid_value(field, "an integer"))?,
+        ),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => PbValue::I64(
+            value
+                .as_i64()
+                .ok_or_else(|| invalid_value(field, "an integer"))?,
+        ),
+        Kind::Uint32 | Kind::Fixed32 => PbValue::U32(
+            value
+                .as_u32()
+                .ok_or_else(|| invalid_value(field, "an unsigned integer"))?,
+        ),
+        Kind::Uint64 | Kind::Fixed64 => PbValue::U64(
+            value
+                .as_u64()
+                .ok_or_else(|| invalid_value(field, "an unsigned integer"))?,
+        ),
+        Kind::Bool => PbValue::Bool(
+            value
+                .as_bool()
+                .ok_or_else(|| invalid_value(field, "a boolean"))?,
+        ),
+        Kind::String => PbValue::String(
+            value
+                .as_str()
+                .ok_or_else(|| invalid_value(field, "a string"))?
+                .to_string(),
+        ),
+        Kind::Bytes => PbValue::Bytes(
+            value
+                .as_bytes()
+                .or_else(|| value.as_str().map(str::as_bytes))
+                .ok_or_else(|| invalid_value(field, "binary"))?
+                .to_vec()
+                .into(),
+        ),
+        Kind::Enum(e) => {
+            let number = if let Some(name) = value.as_str() {
+                e.get_value_by_name(name).map(|v| v.number())
+            } else {
+                value.as_i32()
+            };
+            PbValue::EnumNumber(number.ok_or_else(|| {
+                invalid_value(field, &format!("a value of enum {}", e.full_name()))
+            })?)
+        }
+        Kind::Message(m) => PbValue::Message(value_to_message(&m, value)?),
+    })
+}
+
+fn invalid_value(field: &FieldDescriptor, expected: &str) -> Error {
+    format!(
+        "Invalid value for field `{}`, expected {expected}.",
+        field.full_name()
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tremor_value::literal;
+
+    const DESCRIPTOR: &str = "tests/data/protobuf/test.desc";
+
+    fn codec() -> Result<Protobuf> {
+        let config = literal!({
+            "descriptor": DESCRIPTOR,
+            "message": "tremor.test.Event"
+        });
+        Protobuf::from_config(&Some(config))
+    }
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let mut codec = codec()?;
+        let event = literal!({
+            "name": "snot",
+            "count": -42,
+            "tags": ["badger", "snot"],
+            "labels": { "a": 1 },
+            "kind": "ERROR",
+            "payload": Value::Bytes(vec![1_u8, 2, 3].into()),
+            "inner": { "flag": true },
+            "ratio": 0.5,
+            "timestamp": 1_600_000_000_123_456_789_i64
+        });
+        let mut encoded = codec.encode(&event)?;
+        let decoded = codec.decode(&mut encoded, 0)?;
+        assert_eq!(Some(event), decoded);
+        Ok(())
+    }
+
+    #[test]
+    fn any() -> Result<()> {
+        let mut codec = codec()?;
+        let event = literal!({
+            "detail": {
+                "@type": "type.googleapis.com/tremor.test.Inner",
+                "flag": true
+            }
+        });
+        let mut encoded = codec.encode(&event)?;
+        let decoded = codec.decode(&mut encoded, 0)?;
+        assert_eq!(
+            Some(&event["detail"]),
+            decoded.as_ref().and_then(|d| d.get("detail"))
+        );
+
+        // packed messages unknown to the descriptor set are kept as binary
+        let event = literal!({
+            "detail": {
+                "@type": "type.googleapis.com/snot.Badger",
+                "value": Value::Bytes(vec![8_u8, 1].into())
+            }
+        });
+        let mut encoded = codec.encode(&event)?;
+        let decoded = codec.decode(&mut encoded, 0)?;
+        assert_eq!(
+            Some(&event["detail"]),
+            decoded.as_ref().and_then(|d| d.get("detail"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn defaults_and_absent_messages() -> Result<()> {
+        let mut codec = codec()?;
+        let mut encoded = codec.encode(&literal!({ "name": "snot" }))?;
+        let decoded = codec.decode(&mut encoded, 0)?;
+        let expected = literal!({
+            "name": "snot",
+            "count": 0,
+            "tags": [],
+            "labels": {},
+            "kind": "UNKNOWN",
+            "payload": Value::Bytes(Vec::new().into()),
+            "ratio": 0.0
+        });
+        assert_eq!(Some(expected), decoded);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_data() -> Result<()> {
+        let codec = codec()?;
+        assert!(codec.encode(&literal!({ "snot": "badger" })).is_err());
+        assert!(codec.encode(&literal!({ "kind": "SNOT" })).is_err());
+        assert!(codec.encode(&literal!({ "count": "badger" })).is_err());
+        assert!(codec.encode(&literal!([])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn timestamp_overflow() -> Result<()> {
+        let codec = codec()?;
+        let desc = match codec
+            .message
+            .get_field_by_name("timestamp")
+            .map(|f| f.kind())
+        {
+            Some(Kind::Message(desc)) => desc,
+            _ => return Err("no timestamp field".into()),
+        };
+        let timestamp = |seconds: i64, nanos: i32| {
+            let mut message = DynamicMessage::new(desc.clone());
+            message.set_field_by_name("seconds", PbValue::I64(seconds));
+            message.set_field_by_name("nanos", PbValue::I32(nanos));
+            message_to_value(&message)
+        };
+        let max_seconds = i64::MAX / NANOS_PER_SEC;
+        let max_nanos = i32::try_from(i64::MAX % NANOS_PER_SEC)?;
+        assert_eq!(Value::from(i64::MAX), timestamp(max_seconds, max_nanos)?);
+        assert!(timestamp(max_seconds, max_nanos + 1).is_err());
+        assert!(timestamp(max_seconds + 1, 0).is_err());
+        assert_eq!(
+            Value::from(i64::MIN),
+            timestamp(
+                i64::MIN / NANOS_PER_SEC,
+                i32::try_from(i64::MIN % NANOS_PER_SEC)?
+            )?
+        );
+        assert!(timestamp(i64::MIN / NANOS_PER_SEC - 1, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(Protobuf::from_config(&None).is_err());
+        assert!(Protobuf::from_config(&Some(literal!({
+            "descriptor": DESCRIPTOR,
+            "message": "tremor.test.Snot"
+        })))
+        .is_err());
+        assert!(Protobuf::from_config(&Some(literal!({
+            "descriptor": "/does/not/exist.desc",
+            "message": "tremor.test.Event"
+        })))
+        .is_err());
+    }
+}
@@ -12,6 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! The `statsd` codec, including the `DogStatsD` extensions
+//!
+//! Metrics are decoded into records with `metric`, `value` and `type`, and optionally
+//! `action` for relative gauges and `sample_rate`. `DogStatsD` adds distributions (`d`),
+//! `tags` as a record, where tags without a value are `null`, `container_id` and `timestamp`:
+//!
+//! ```text
+//! page.views:1|c|@0.5|#env:prod,canary|c:83c0a99c|T1656581400
+//! ```
+//!
+//! Service checks (`_sc|...`) decode into records with the `type` `service_check`,
+//! events (`_e{..}:...`) into records with the `type` `event`.
+//! Packets with several newline separated records are decoded into one event per record.
+
 use super::prelude::*;
 use std::{slice::SliceIndex, str};
 
@@ -31,6 +45,20 @@ impl Codec for StatsD {
         decode(data, ingest_ns).map(Some)
     }
 
+    fn split_records(&self, data: &[u8]) -> Option<Vec<Vec<u8>>> {
+        if data.contains(&b'\n') {
+            Some(
+                data.split(|b| *b == b'\n')
+                    .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+                    .filter(|line| !line.is_empty())
+                    .map(<[u8]>::to_vec)
+                    .collect(),
+            )
+        } else {
+            None
+        }
+    }
+
     fn encode(&self, data: &Value) -> Result<Vec<u8>> {
         encode(data)
     }
@@ -40,14 +68,23 @@ impl Codec for StatsD {
     }
 }
 
+const SERVICE_CHECK: &str = "service_check";
+const EVENT: &str = "event";
+
 fn encode(value: &Value) -> Result<Vec<u8>> {
+    let r = match value.get_str("type") {
+        Some(SERVICE_CHECK) => encode_service_check(value)?,
+        Some(EVENT) => encode_event(value)?,
+        _ => encode_metric(value)?,
+    };
+    Ok(r.into_bytes())
+}
+
+fn encode_metric(value: &Value) -> Result<String> {
     let mut r = String::new();
     r.push_str(value.get_str("metric").ok_or(ErrorKind::InvalidStatsD)?);
     let t = value.get_str("type").ok_or(ErrorKind::InvalidStatsD)?;
     let val = value.get("value").ok_or(ErrorKind::InvalidStatsD)?;
-    if !val.is_number() {
-        return Err(ErrorKind::InvalidStatsD.into());
-    };
     r.push(':');
     if t == "g" {
         match value.get_str("action") {
@@ -57,7 +94,12 @@ fn encode(value: &Value) -> Result<Vec<u8>> {
         }
     };
 
-    r.push_str(&val.encode());
+    match val.as_str() {
+        // sets count unique values, which do not need to be numbers
+        Some(s) if t == "s" => r.push_str(s),
+        _ if val.is_number() => r.push_str(&val.encode()),
+        _ => return Err(ErrorKind::InvalidStatsD.into()),
+    }
     r.push('|');
     r.push_str(t);
 
@@ -69,105 +111,262 @@ fn encode(value: &Value) -> Result<Vec<u8>> {
             return Err(ErrorKind::InvalidStatsD.into());
         }
     }
+    encode_tags(value, &mut r)?;
+    if let Some(container_id) = value.get("container_id") {
+        r.push_str("|c:");
+        r.push_str(container_id.as_str().ok_or(ErrorKind::InvalidStatsD)?);
+    }
+    if let Some(timestamp) = value.get("timestamp") {
+        let timestamp = timestamp.as_u64().ok_or(ErrorKind::InvalidStatsD)?;
+        r.push_str("|T");
+        r.push_str(&timestamp.to_string());
+    }
 
-    Ok(r.as_bytes().to_vec())
+    Ok(r)
 }
 
-fn decode(data: &[u8], _ingest_ns: u64) -> Result<Value> {
-    enum Sign {
-        Plus,
-        Minus,
-        None,
+fn encode_service_check(value: &Value) -> Result<String> {
+    let name = value.get_str("name").ok_or(ErrorKind::InvalidStatsD)?;
+    let status = value.get_u64("status").ok_or(ErrorKind::InvalidStatsD)?;
+    let mut r = format!("_sc|{name}|{status}");
+    if let Some(timestamp) = value.get("timestamp") {
+        let timestamp = timestamp.as_u64().ok_or(ErrorKind::InvalidStatsD)?;
+        r.push_str("|d:");
+        r.push_str(&timestamp.to_string());
     }
-    let mut d = data.iter().enumerate().peekable();
-    let mut m = Object::with_capacity(4);
-    let value_start: usize;
-    let mut is_float = false;
-    loop {
-        match d.next() {
-            Some((idx, b':')) => {
-                let raw = data.get(0..idx).ok_or_else(invalid)?;
-                let v = str::from_utf8(raw)?;
-                value_start = idx + 1;
-                m.insert("metric".into(), Value::from(v));
-                break;
-            }
-            Some(_) => (),
-            None => return Err(invalid()),
-        }
+    encode_str_field(value, "hostname", "|h:", &mut r)?;
+    encode_tags(value, &mut r)?;
+    // the message needs to be the last field
+    if let Some(message) = value.get("message") {
+        let message = message.as_str().ok_or(ErrorKind::InvalidStatsD)?;
+        r.push_str("|m:");
+        r.push_str(&escape(message));
     }
-    let sign = match d.peek() {
-        Some((_, b'+')) => Sign::Plus,
-        Some((_, b'-')) => Sign::Minus,
-        _ => Sign::None,
-    };
-    let mut value: Value;
-    loop {
-        match d.next() {
-            Some((_, b'.')) => is_float = true,
-            Some((idx, b'|')) => {
-                let s = substr(data, value_start..idx)?;
-                if is_float {
-                    let v: f64 = s.parse()?;
-                    value = Value::from(v);
-                } else {
-                    let v: i64 = s.parse()?;
-                    value = Value::from(v);
-                };
-                break;
+    Ok(r)
+}
+
+fn encode_event(value: &Value) -> Result<String> {
+    let title = escape(value.get_str("title").ok_or(ErrorKind::InvalidStatsD)?);
+    let text = escape(value.get_str("text").ok_or(ErrorKind::InvalidStatsD)?);
+    let mut r = format!("_e{{{},{}}}:{title}|{text}", title.len(), text.len());
+    if let Some(timestamp) = value.get("timestamp") {
+        let timestamp = timestamp.as_u64().ok_or(ErrorKind::InvalidStatsD)?;
+        r.push_str("|d:");
+        r.push_str(&timestamp.to_string());
+    }
+    encode_str_field(value, "hostname", "|h:", &mut r)?;
+    encode_str_field(value, "aggregation_key", "|k:", &mut r)?;
+    encode_str_field(value, "priority", "|p:", &mut r)?;
+    encode_str_field(value, "source_type_name", "|s:", &mut r)?;
+    encode_str_field(value, "alert_type", "|t:", &mut r)?;
+    encode_tags(value, &mut r)?;
+    encode_str_field(value, "container_id", "|c:", &mut r)?;
+    Ok(r)
+}
+
+fn encode_str_field(value: &Value, key: &str, prefix: &str, r: &mut String) -> Result<()> {
+    if let Some(field) = value.get(key) {
+        r.push_str(prefix);
+        r.push_str(field.as_str().ok_or(ErrorKind::InvalidStatsD)?);
+    }
+    Ok(())
+}
+
+fn encode_tags(value: &Value, r: &mut String) -> Result<()> {
+    if let Some(tags) = value.get("tags") {
+        let tags = tags.as_object().ok_or(ErrorKind::InvalidStatsD)?;
+        r.push_str("|#");
+        for (i, (key, value)) in tags.iter().enumerate() {
+            if i > 0 {
+                r.push(',');
+            }
+            r.push_str(key);
+            if let Some(value) = value.as_str() {
+                r.push(':');
+                r.push_str(value);
+            } else if !value.is_null() {
+                r.push(':');
+                r.push_str(&value.encode());
             }
-            Some(_) => (),
-            None => return Err(invalid()),
         }
     }
-    match d.next() {
-        Some((i, b'c' | b'h' | b's')) => m.insert("type".into(), substr(data, i..=i)?.into()),
-        Some((i, b'm')) => {
-            if let Some((j, b's')) = d.next() {
-                m.insert("type".into(), substr(data, i..=j)?.into())
-            } else {
-                return Err(invalid());
+    Ok(())
+}
+
+fn decode(data: &[u8], _ingest_ns: u64) -> Result<Value> {
+    let line = str::from_utf8(data)?;
+    if let Some(rest) = line.strip_prefix("_sc|") {
+        decode_service_check(rest)
+    } else if let Some(rest) = line.strip_prefix("_e{") {
+        decode_event(rest)
+    } else {
+        decode_metric(line)
+    }
+}
+
+fn decode_metric(line: &str) -> Result<Value> {
+    let mut m = Object::with_capacity(4);
+    let (metric, rest) = line.split_once(':').ok_or_else(invalid)?;
+    m.insert("metric".into(), Value::from(metric));
+    let mut sections = rest.split('|');
+    let raw_value = sections.next().ok_or_else(invalid)?;
+    let t = sections.next().ok_or_else(invalid)?;
+    let mut value = if raw_value.contains('.') {
+        raw_value
+            .parse::<f64>()
+            .map(Value::from)
+            .map_err(Error::from)
+    } else {
+        raw_value
+            .parse::<i64>()
+            .map(Value::from)
+            .map_err(Error::from)
+    };
+    match t {
+        "c" | "h" | "ms" | "d" => (),
+        // sets count unique values, which do not need to be numbers
+        "s" => {
+            if value.is_err() && !raw_value.is_empty() {
+                value = Ok(Value::from(raw_value));
             }
         }
-        Some((i, b'g')) => {
-            match sign {
-                Sign::Plus => {
-                    m.insert("action".into(), "add".into());
-                }
-                Sign::Minus => {
-                    // If it was a `-` we got to negate the number
-                    value = if let Some(v) = value.as_i64() {
-                        Value::from(-v)
+        "g" => {
+            if raw_value.starts_with('+') {
+                m.insert("action".into(), "add".into());
+            } else if raw_value.starts_with('-') {
+                // If it was a `-` we got to negate the number
+                value = value.and_then(|value| {
+                    if let Some(v) = value.as_i64() {
+                        Ok(Value::from(-v))
                     } else if let Some(v) = value.as_f64() {
-                        Value::from(-v)
+                        Ok(Value::from(-v))
                     } else {
-                        return Err(invalid());
-                    };
-                    m.insert("action".into(), "sub".into());
-                }
-                Sign::None => (),
-            };
-            m.insert("type".into(), substr(data, i..=i)?.into())
+                        Err(invalid())
+                    }
+                });
+                m.insert("action".into(), "sub".into());
+            }
         }
         _ => return Err(invalid()),
+    }
+    m.insert("type".into(), t.into());
+    m.insert("value".into(), value?);
+    for section in sections {
+        if let Some(sample_rate) = section.strip_prefix('@') {
+            let v: f64 = sample_rate.parse()?;
+            m.insert("sample_rate".into(), Value::from(v));
+        } else if let Some(tags) = section.strip_prefix('#') {
+            m.insert("tags".into(), decode_tags(tags));
+        } else if let Some(container_id) = section.strip_prefix("c:") {
+            m.insert("container_id".into(), container_id.into());
+        } else if let Some(timestamp) = section.strip_prefix('T') {
+            let v: u64 = timestamp.parse()?;
+            m.insert("timestamp".into(), Value::from(v));
+        } else {
+            return Err(invalid());
+        }
+    }
+    Ok(Value::from(m))
+}
+
+fn decode_service_check(rest: &str) -> Result<Value> {
+    let mut m = Object::with_capacity(4);
+    m.insert("type".into(), SERVICE_CHECK.into());
+    // the message is the last field and may contain `|`
+    let (rest, message) = match rest.split_once("|m:") {
+        Some((rest, message)) => (rest, Some(message)),
+        None => (rest, None),
     };
-    match d.next() {
-        Some((_, b'|')) => {
-            if let Some((sample_start, b'@')) = d.next() {
-                let s = substr(data, sample_start + 1..)?;
-                let v: f64 = s.parse()?;
-                m.insert("sample_rate".into(), Value::from(v));
+    let mut sections = rest.split('|');
+    let name = sections.next().ok_or_else(invalid)?;
+    let status: u64 = sections.next().ok_or_else(invalid)?.parse()?;
+    if name.is_empty() || status > 3 {
+        return Err(invalid());
+    }
+    m.insert("name".into(), name.into());
+    m.insert("status".into(), Value::from(status));
+    for section in sections {
+        if let Some(timestamp) = section.strip_prefix("d:") {
+            let v: u64 = timestamp.parse()?;
+            m.insert("timestamp".into(), Value::from(v));
+        } else if let Some(hostname) = section.strip_prefix("h:") {
+            m.insert("hostname".into(), hostname.into());
+        } else if let Some(tags) = section.strip_prefix('#') {
+            m.insert("tags".into(), decode_tags(tags));
+        } else {
+            return Err(invalid());
+        }
+    }
+    if let Some(message) = message {
+        m.insert("message".into(), unescape(message));
+    }
+    Ok(Value::from(m))
+}
+
+fn decode_event(rest: &str) -> Result<Value> {
+    let mut m = Object::with_capacity(4);
+    m.insert("type".into(), EVENT.into());
+    let (lengths, rest) = rest.split_once("}:").ok_or_else(invalid)?;
+    let (title_len, text_len) = lengths.split_once(',').ok_or_else(invalid)?;
+    let title_len: usize = title_len.parse()?;
+    let text_len: usize = text_len.parse()?;
+    // the lengths are in bytes
+    let title = substr(rest.as_bytes(), ..title_len)?;
+    let rest = substr(rest.as_bytes(), title_len..)?;
+    let rest = rest.strip_prefix('|').ok_or_else(invalid)?;
+    let text = substr(rest.as_bytes(), ..text_len)?;
+    let rest = substr(rest.as_bytes(), text_len..)?;
+    m.insert("title".into(), unescape(title));
+    m.insert("text".into(), unescape(text));
+    if !rest.is_empty() {
+        let rest = rest.strip_prefix('|').ok_or_else(invalid)?;
+        for section in rest.split('|') {
+            if let Some(timestamp) = section.strip_prefix("d:") {
+                let v: u64 = timestamp.parse()?;
+                m.insert("timestamp".into(), Value::from(v));
+            } else if let Some(tags) = section.strip_prefix('#') {
+                m.insert("tags".into(), decode_tags(tags));
             } else {
-                return Err(invalid());
+                let key = match section.get(..2) {
+                    Some("h:") => "hostname",
+                    Some("k:") => "aggregation_key",
+                    Some("p:") => "priority",
+                    Some("s:") => "source_type_name",
+                    Some("t:") => "alert_type",
+                    Some("c:") => "container_id",
+                    _ => return Err(invalid()),
+                };
+                m.insert(key.into(), section.get(2..).ok_or_else(invalid)?.into());
             }
         }
-        Some(_) => return Err(invalid()),
-        None => (),
-    };
-    m.insert("value".into(), value);
+    }
     Ok(Value::from(m))
 }
 
+fn decode_tags(tags: &str) -> Value {
+    let mut m = Object::with_capacity(4);
+    for tag in tags.split(',').filter(|tag| !tag.is_empty()) {
+        match tag.split_once(':') {
+            Some((key, value)) => m.insert(key.into(), value.into()),
+            None => m.insert(tag.into(), Value::null()),
+        };
+    }
+    Value::from(m)
+}
+
+/// newlines in texts are sent as `\\n`
+fn unescape(s: &str) -> Value {
+    if s.contains("\\n") {
+        Value::from(s.replace("\\n", "\n"))
+    } else {
+        Value::from(s)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\n', "\\n")
+}
+
 fn invalid() -> Error {
     Error::from(ErrorKind::InvalidStatsD)
 }
@@ -338,4 +537,109 @@ mod test {
         let m = decode(data, 0).expect("failed to decode");
         assert_eq!(&data[..], encode(&m).expect("failed to encode"));
     }
+
+    #[test]
+    fn dogstatsd_metric() {
+        let data = b"page.views:1|c|@0.5|#env:prod,canary|c:83c0a99c|T1656581400";
+        let parsed = decode(data, 0).expect("failed to decode");
+        let expected = literal!({
+            "metric": "page.views",
+            "type": "c",
+            "value": 1,
+            "sample_rate": 0.5,
+            "tags": {"env": "prod", "canary": null},
+            "container_id": "83c0a99c",
+            "timestamp": 1_656_581_400
+        });
+        assert_eq!(parsed, expected);
+        let encoded = encode(&parsed).expect("failed to encode");
+        assert_eq!(encoded.as_slice(), data);
+    }
+
+    #[test]
+    fn distribution() {
+        let data = b"request.latency:0.25|d|#service:api";
+        let parsed = decode(data, 0).expect("failed to decode");
+        let expected = literal!({
+            "metric": "request.latency",
+            "type": "d",
+            "value": 0.25,
+            "tags": {"service": "api"}
+        });
+        assert_eq!(parsed, expected);
+        let encoded = encode(&parsed).expect("failed to encode");
+        assert_eq!(encoded.as_slice(), data);
+    }
+
+    #[test]
+    fn string_set() {
+        let data = b"users.uniques:badger|s";
+        let parsed = decode(data, 0).expect("failed to decode");
+        let expected = literal!({
+            "metric": "users.uniques",
+            "type": "s",
+            "value": "badger"
+        });
+        assert_eq!(parsed, expected);
+        let encoded = encode(&parsed).expect("failed to encode");
+        assert_eq!(encoded.as_slice(), data);
+    }
+
+    #[test]
+    fn service_check() {
+        let data = b"_sc|redis.can_connect|2|d:1656581400|h:snot|#env:prod|m:Redis is down\\nagain";
+        let parsed = decode(data, 0).expect("failed to decode");
+        let expected = literal!({
+            "type": "service_check",
+            "name": "redis.can_connect",
+            "status": 2,
+            "timestamp": 1_656_581_400,
+            "hostname": "snot",
+            "tags": {"env": "prod"},
+            "message": "Redis is down\nagain"
+        });
+        assert_eq!(parsed, expected);
+        let encoded = encode(&parsed).expect("failed to encode");
+        assert_eq!(encoded.as_slice(), &data[..]);
+    }
+
+    #[test]
+    fn event() {
+        let data = "_e{6,9}:déjà|vu\\nagain|d:1656581400|h:snot|p:low|t:warning|#env:prod";
+        let parsed = decode(data.as_bytes(), 0).expect("failed to decode");
+        let expected = literal!({
+            "type": "event",
+            "title": "déjà",
+            "text": "vu\nagain",
+            "timestamp": 1_656_581_400,
+            "hostname": "snot",
+            "priority": "low",
+            "alert_type": "warning",
+            "tags": {"env": "prod"}
+        });
+        assert_eq!(parsed, expected);
+        let encoded = encode(&parsed).expect("failed to encode");
+        assert_eq!(encoded.as_slice(), data.as_bytes());
+    }
+
+    #[test]
+    fn split_records() {
+        let c = StatsD {};
+        assert_eq!(None, c.split_records(b"gorets:1|c"));
+        assert_eq!(
+            Some(vec![b"gorets:1|c".to_vec(), b"glork:320|ms".to_vec()]),
+            c.split_records(b"gorets:1|c\r\nglork:320|ms\n\n")
+        );
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert!(decode(b"gorets", 0).is_err());
+        assert!(decode(b"gorets:1", 0).is_err());
+        assert!(decode(b"gorets:1|x", 0).is_err());
+        assert!(decode(b"gorets:snot|c", 0).is_err());
+        assert!(decode(b"gorets:1|c|snot", 0).is_err());
+        assert!(decode(b"_sc|snot|7", 0).is_err());
+        assert!(decode(b"_e{10,1}:snot|b", 0).is_err());
+    }
 }
@@ -54,6 +54,25 @@ impl Default for Reconnect {
     }
 }
 
+/// Batching of events in the sink, before they are handed to the connector
+///
+/// Events are serialized into the batch, which is handed to the connector as one event
+/// with the combined payload once one of the bounds is reached. Events with different metadata
+/// or from different streams are never batched together, they close the current batch.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Batch {
+    /// maximum number of events in a batch
+    #[serde(default = "Default::default")]
+    pub(crate) max_events: Option<usize>,
+    /// maximum size of the events in a batch in bytes, serialized with the connector codec and postprocessors
+    #[serde(default = "Default::default")]
+    pub(crate) max_bytes: Option<usize>,
+    /// maximum time in milliseconds the first event of a batch waits for the batch to be handed on
+    #[serde(default = "Default::default")]
+    pub(crate) linger_ms: Option<u64>,
+}
+
 /* TODO: currently this is implemented differently in every connector
 
 /// how a connector behaves upon Pause or CB trigger events
@@ -149,6 +168,13 @@ pub(crate) struct Connector {
 
     //pub(crate) on_pause: PauseBehaviour,
     pub(crate) metrics_interval_s: Option<u64>,
+
+    /// Batching of events in the sink
+    pub(crate) batch: Option<Batch>,
+
+    /// Keep the data failing to decode in the error events of the source, the default.
+    /// This copies all data before decoding, `false` avoids the copy.
+    pub(crate) dead_letter_data: bool,
 }
 
 impl Connector {
@@ -209,6 +235,18 @@ impl Connector {
             ValueType::Object,
             connector_alias,
         )?;
+        validate_type(
+            connector_config,
+            ConnectorDefinition::BATCH,
+            ValueType::Object,
+            connector_alias,
+        )?;
+        validate_type(
+            connector_config,
+            ConnectorDefinition::DEAD_LETTER_DATA,
+            ValueType::Bool,
+            connector_alias,
+        )?;
         validate_type(
             connector_config,
             ConnectorDefinition::PREPROCESSORS,
@@ -236,6 +274,27 @@ impl Connector {
             )
         })?;
 
+        let batch: Option<Batch> = connector_config
+            .get(ConnectorDefinition::BATCH)
+            .cloned()
+            .map(tremor_value::structurize)
+            .transpose()?;
+        if let Some(Batch {
+            max_events: None,
+            max_bytes: None,
+            linger_ms: None,
+        }) = batch
+        {
+            return Err(ErrorKind::InvalidConnectorDefinition(
+                connector_alias.to_string(),
+                format!(
+                    "Expected at least one of max_events, max_bytes or linger_ms for key {}",
+                    ConnectorDefinition::BATCH
+                ),
+            )
+            .into());
+        }
+
         Ok(Self {
             connector_type,
             config,
@@ -258,6 +317,10 @@ impl Connector {
                 .get(ConnectorDefinition::CODEC)
                 .map(Codec::try_from)
                 .transpose()?,
+            batch,
+            dead_letter_data: connector_config
+                .get_bool(ConnectorDefinition::DEAD_LETTER_DATA)
+                .unwrap_or(true),
         })
     }
 }
@@ -339,4 +402,53 @@ mod tests {
         assert_eq!(String::from("Invalid Definition for connector \"flow::my_id\": Expected type I64 for key metrics_interval_s but got String"), res.err().unwrap().to_string());
         Ok(())
     }
+    #[test]
+    fn test_connector_config_batch() -> Result<()> {
+        let id = Alias::new(flow::Alias::new("flow"), "my_id");
+        let c = Connector::from_config(
+            &id,
+            "tcp_client".into(),
+            &literal!({
+                "batch": {"max_events": 100, "linger_ms": 50}
+            }),
+        )?;
+        assert_eq!(
+            Some(Batch {
+                max_events: Some(100),
+                max_bytes: None,
+                linger_ms: Some(50)
+            }),
+            c.batch
+        );
+
+        // a batch needs a bound
+        let res = Connector::from_config(&id, "tcp_client".into(), &literal!({"batch": {}}));
+        assert!(res.is_err());
+        let res = Connector::from_config(
+            &id,
+            "tcp_client".into(),
+            &literal!({"batch": {"max_events": 1, "snot": 2}}),
+        );
+        assert!(res.is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_connector_config_dead_letter_data() -> Result<()> {
+        let id = Alias::new(flow::Alias::new("flow"), "my_id");
+        let c = Connector::from_config(&id, "tcp_server".into(), &literal!({}))?;
+        assert!(c.dead_letter_data);
+        let c = Connector::from_config(
+            &id,
+            "tcp_server".into(),
+            &literal!({"dead_letter_data": false}),
+        )?;
+        assert!(!c.dead_letter_data);
+        let res = Connector::from_config(
+            &id,
+            "tcp_server".into(),
+            &literal!({"dead_letter_data": "yes"}),
+        );
+        assert!(res.is_err());
+        Ok(())
+    }
 }
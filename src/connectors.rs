@@ -1254,6 +1254,7 @@ pub(crate) fn builtin_connector_types() -> Vec<Box<dyn ConnectorBuilder + 'stati
         Box::new(impls::unix_socket::client::Builder::default()),
         Box::new(impls::http::client::Builder::default()),
         Box::new(impls::http::server::Builder::default()),
+        Box::new(impls::http::sse_client::Builder::default()),
         Box::new(impls::otel::client::Builder::default()),
         Box::new(impls::otel::server::Builder::default()),
         Box::new(impls::gbq::writer::Builder::default()),
@@ -1262,7 +1263,16 @@ pub(crate) fn builtin_connector_types() -> Vec<Box<dyn ConnectorBuilder + 'stati
         Box::new(impls::clickhouse::Builder::default()),
         Box::new(impls::gcl::writer::Builder::default()),
         Box::new(impls::gcs::streamer::Builder::default()),
+        Box::new(impls::gcs::reader::Builder::default()),
         Box::new(impls::null::Builder::default()),
+        Box::new(impls::mqtt::Builder::default()),
+        Box::new(impls::amqp::consumer::Builder::default()),
+        Box::new(impls::amqp::producer::Builder::default()),
+        Box::new(impls::postgres::Builder::default()),
+        Box::new(impls::prometheus::remote_write::Builder::default()),
+        Box::new(impls::prometheus::exporter::Builder::default()),
+        Box::new(impls::nats::Builder::default()),
+        Box::new(impls::replay::Builder::default()),
     ]
 }
 
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+/// AMQP 0-9-1 consumer and producer
+pub(crate) mod amqp;
 /// benchmarking connector
 pub(crate) mod bench;
 /// connector for checking guaranteed delivery and circuit breaker logic
@@ -45,10 +47,20 @@ pub(crate) mod kv;
 pub(crate) mod metrics;
 /// Metronome
 pub(crate) mod metronome;
+/// MQTT 3.1.1 and 5 client
+pub(crate) mod mqtt;
+/// NATS core and JetStream client
+pub(crate) mod nats;
 /// Never send any events and swallow all events it receives into the void.
 pub(crate) mod null;
 /// `OpenTelemetry`
 pub(crate) mod otel;
+/// PostgreSQL sink and change data capture source
+pub(crate) mod postgres;
+/// Prometheus remote write receiver and exporter
+pub(crate) mod prometheus;
+/// Re-inject dead letter events for decoding them again
+pub(crate) mod replay;
 /// AWS S3 connectors
 pub(crate) mod s3;
 /// std streams connector (stdout, stderr, stdin)
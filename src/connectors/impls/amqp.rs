@@ -0,0 +1,121 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! AMQP 0-9-1 consumer and producer, e.g. for `RabbitMQ`
+
+pub(crate) mod consumer;
+pub(crate) mod producer;
+
+use crate::connectors::prelude::*;
+use lapin::{
+    types::{AMQPValue, FieldTable, LongString, ShortString},
+    Connection, ConnectionProperties,
+};
+
+const AMQP_META_KEY: &str = "amqp";
+
+pub(crate) struct AmqpDefaults;
+impl Defaults for AmqpDefaults {
+    const SCHEME: &'static str = "amqp";
+    const HOST: &'static str = "localhost";
+    const PORT: u16 = 5672;
+}
+
+/// connects to the broker and notifies the connector runtime once the connection breaks
+async fn connect<C>(url: &Url<AmqpDefaults>, ctx: &C) -> Result<Connection>
+where
+    C: Context + Send + Clone + 'static,
+{
+    let connection = Connection::connect(url.as_str(), ConnectionProperties::default()).await?;
+    let ctx = ctx.clone();
+    connection.on_error(move |e| {
+        error!("{ctx} AMQP connection error: {e}");
+        let ctx = ctx.clone();
+        async_std::task::spawn(async move {
+            log_error!(
+                ctx.notifier().connection_lost().await,
+                "{ctx} Failed to notify on connection lost: {e}"
+            );
+        });
+    });
+    Ok(connection)
+}
+
+/// converts AMQP headers into a record, values without a sensible mapping are omitted
+fn headers_to_value(headers: &FieldTable) -> Value<'static> {
+    let mut res = Object::with_capacity(headers.inner().len());
+    for (k, v) in headers.inner() {
+        let value = match v {
+            AMQPValue::Boolean(b) => Value::from(*b),
+            AMQPValue::ShortShortInt(i) => Value::from(*i),
+            AMQPValue::ShortShortUInt(i) => Value::from(*i),
+            AMQPValue::ShortInt(i) => Value::from(*i),
+            AMQPValue::ShortUInt(i) => Value::from(*i),
+            AMQPValue::LongInt(i) => Value::from(*i),
+            AMQPValue::LongUInt(i) => Value::from(*i),
+            AMQPValue::LongLongInt(i) => Value::from(*i),
+            AMQPValue::Float(f) => Value::from(f64::from(*f)),
+            AMQPValue::Double(f) => Value::from(*f),
+            AMQPValue::ShortString(s) => Value::from(s.as_str().to_string()),
+            AMQPValue::LongString(s) => {
+                Value::from(String::from_utf8_lossy(s.as_bytes()).to_string())
+            }
+            AMQPValue::ByteArray(b) => Value::Bytes(b.as_slice().to_vec().into()),
+            AMQPValue::Timestamp(t) => Value::from(*t),
+            AMQPValue::FieldTable(t) => headers_to_value(t),
+            _ => continue,
+        };
+        res.insert(k.as_str().to_string().into(), value);
+    }
+    Value::from(res)
+}
+
+/// converts a record into AMQP headers
+fn value_to_headers(value: &Value) -> FieldTable {
+    let mut headers = FieldTable::default();
+    for (k, v) in value.as_object().into_iter().flatten() {
+        let v = match v {
+            Value::Static(StaticNode::Bool(b)) => AMQPValue::Boolean(*b),
+            Value::Static(StaticNode::I64(i)) => AMQPValue::LongLongInt(*i),
+            Value::Static(StaticNode::U64(u)) => i64::try_from(*u).map_or_else(
+                |_| AMQPValue::LongString(LongString::from(u.to_string())),
+                AMQPValue::LongLongInt,
+            ),
+            Value::Static(StaticNode::F64(f)) => AMQPValue::Double(*f),
+            Value::String(s) => AMQPValue::LongString(LongString::from(s.to_string())),
+            Value::Bytes(b) => AMQPValue::ByteArray(b.to_vec().into()),
+            Value::Object(_) => AMQPValue::FieldTable(value_to_headers(v)),
+            _ => continue,
+        };
+        headers.insert(ShortString::from(k.to_string()), v);
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_roundtrip() {
+        let value = literal!({
+            "string": "snot",
+            "int": -42,
+            "float": 0.5,
+            "bool": true,
+            "nested": { "badger": "snot" }
+        });
+        assert_eq!(value, headers_to_value(&value_to_headers(&value)));
+    }
+}
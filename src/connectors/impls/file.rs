@@ -14,14 +14,19 @@
 
 use std::{ffi::OsStr, path::PathBuf};
 
-use crate::connectors::prelude::*;
+use crate::connectors::{
+    prelude::*,
+    sink::ContraflowData,
+    utils::columnar::{self, ColumnarFile},
+};
 use async_compression::futures::bufread::XzDecoder;
 use async_std::{
+    channel::Sender,
     fs::{File as FSFile, OpenOptions},
     io::BufReader,
 };
 use futures::{AsyncRead, AsyncReadExt, AsyncWriteExt};
-use tremor_common::asy::file;
+use tremor_common::{asy::file, time::nanotime};
 
 const URL_SCHEME: &str = "tremor-file";
 
@@ -73,6 +78,10 @@ pub(crate) struct Config {
     /// chunk_size to read from the file
     #[serde(default = "default_buf_size")]
     pub(crate) chunk_size: usize,
+    /// write a columnar file instead of using the codec, only with mode `write` or `truncate`.
+    /// Events are acked once the row group they are in has been written.
+    #[serde(default = "Default::default")]
+    pub(crate) columnar: Option<columnar::Config>,
 }
 
 impl ConfigImpl for Config {}
@@ -102,6 +111,13 @@ impl ConnectorBuilder for Builder {
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
         let config = Config::new(config)?;
+        if config.columnar.is_some() && !matches!(config.mode, Mode::Write | Mode::Truncate) {
+            return Err(ErrorKind::InvalidConfiguration(
+                "file".to_string(),
+                "`columnar` can only be used with mode `write` or `truncate`".to_string(),
+            )
+            .into());
+        }
         Ok(Box::new(File { config }))
     }
 }
@@ -116,7 +132,7 @@ impl Connector for File {
         if self.config.mode == Mode::Read {
             Ok(None)
         } else {
-            let sink = FileSink::new(self.config.clone());
+            let sink = FileSink::new(self.config.clone(), builder.reply_tx());
             builder.spawn(sink, sink_context).map(Some)
         }
     }
@@ -135,7 +151,11 @@ impl Connector for File {
     }
 
     fn codec_requirements(&self) -> CodecReq {
-        CodecReq::Required
+        if self.config.columnar.is_some() {
+            CodecReq::Structured
+        } else {
+            CodecReq::Required
+        }
     }
 }
 
@@ -246,19 +266,108 @@ impl Source for FileSource {
 struct FileSink {
     config: Config,
     file: Option<FSFile>,
+    columnar_file: Option<ColumnarFile>,
+    reply_tx: Sender<AsyncSinkReply>,
 }
 
 impl FileSink {
-    fn new(config: Config) -> Self {
-        Self { config, file: None }
+    fn new(config: Config, reply_tx: Sender<AsyncSinkReply>) -> Self {
+        Self {
+            config,
+            file: None,
+            columnar_file: None,
+            reply_tx,
+        }
+    }
+
+    async fn write(&mut self, ctx: &SinkContext, data: &[u8]) -> Result<()> {
+        let file = self
+            .file
+            .as_mut()
+            .ok_or_else(|| Error::from("No file available."))?;
+        if let Err(e) = file.write_all(data).await {
+            error!("{} Error writing to file: {}", &ctx, &e);
+            self.file = None;
+            ctx.notifier().connection_lost().await?;
+            return Err(e.into());
+        }
+        if let Err(e) = file.flush().await {
+            error!("{} Error flushing file: {}", &ctx, &e);
+            self.file = None;
+            ctx.notifier().connection_lost().await?;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    /// writes a row group of the columnar file if one is due and acks or fails the events in it
+    async fn flush_columnar_file(&mut self, ctx: &SinkContext) -> Result<()> {
+        let (contraflow, data) = match self.columnar_file.as_mut() {
+            Some(columnar_file) if columnar_file.needs_flush() => {
+                (columnar_file.take_contraflow(), columnar_file.flush())
+            }
+            _ => return Ok(()),
+        };
+        let start = nanotime();
+        let res = match data {
+            Ok(data) => self.write(ctx, &data).await,
+            Err(e) => Err(e),
+        };
+        self.reply(ctx, contraflow, res.is_ok(), start).await;
+        res
+    }
+
+    /// completes the columnar file and acks or fails the events in it
+    async fn finish_columnar_file(&mut self, ctx: &SinkContext) -> Result<()> {
+        if let Some(mut columnar_file) = self.columnar_file.take() {
+            let start = nanotime();
+            let contraflow = columnar_file.take_contraflow();
+            let res = match columnar_file.finish() {
+                Ok(data) => self.write(ctx, &data).await,
+                Err(e) => Err(e),
+            };
+            let res = match (res, self.file.as_ref()) {
+                (Ok(()), Some(file)) => file.sync_all().await.map_err(Error::from),
+                (res, _) => res,
+            };
+            self.reply(ctx, contraflow, res.is_ok(), start).await;
+            res?;
+        }
+        Ok(())
+    }
+
+    async fn reply(
+        &self,
+        ctx: &SinkContext,
+        contraflow: Option<ContraflowData>,
+        ok: bool,
+        start: u64,
+    ) {
+        if let Some(contraflow) = contraflow {
+            let reply = if ok {
+                AsyncSinkReply::Ack(contraflow, nanotime() - start)
+            } else {
+                AsyncSinkReply::Fail(contraflow)
+            };
+            ctx.swallow_err(
+                self.reply_tx.send(reply).await,
+                "Error sending columnar file ack/fail",
+            );
+        }
     }
 }
 
 #[async_trait::async_trait]
 impl Sink for FileSink {
-    async fn connect(&mut self, _ctx: &SinkContext, attempt: &Attempt) -> Result<bool> {
+    async fn connect(&mut self, ctx: &SinkContext, attempt: &Attempt) -> Result<bool> {
         let mode = if attempt.is_first() || attempt.success() == 0 {
             &self.config.mode
+        } else if let Some(mut columnar_file) = self.columnar_file.take() {
+            // a partially written columnar file can not be continued,
+            // so we start a new one and fail the events of the old one
+            self.reply(ctx, columnar_file.take_contraflow(), false, nanotime())
+                .await;
+            &self.config.mode
         } else {
             // if we have already opened the file successfully once
             // we should not truncate it again or overwrite, but indeed append
@@ -284,33 +393,45 @@ impl Sink for FileSink {
         serializer: &mut EventSerializer,
         _start: u64,
     ) -> Result<SinkReply> {
-        let file = self
-            .file
-            .as_mut()
-            .ok_or_else(|| Error::from("No file available."))?;
+        if let Some(config) = &self.config.columnar {
+            let columnar_file = self
+                .columnar_file
+                .get_or_insert_with(|| ColumnarFile::new(config));
+            // all records of the event are written or none
+            let records: Vec<&Value> = event.value_iter().collect();
+            if let Err(e) = columnar_file.write(&records) {
+                error!("{ctx} Error writing columnar records: {e}");
+                return Ok(SinkReply::FAIL);
+            }
+            if event.transactional {
+                columnar_file.track(ContraflowData::from(&event));
+            }
+            self.flush_columnar_file(ctx).await?;
+            return Ok(SinkReply::NONE);
+        }
         let ingest_ns = event.ingest_ns;
         for value in event.value_iter() {
             let data = serializer.serialize(value, ingest_ns)?;
             for chunk in data {
-                if let Err(e) = file.write_all(&chunk).await {
-                    error!("{} Error writing to file: {}", &ctx, &e);
-                    self.file = None;
-                    ctx.notifier().connection_lost().await?;
-                    return Err(e.into());
-                }
-            }
-            if let Err(e) = file.flush().await {
-                error!("{} Error flushing file: {}", &ctx, &e);
-                self.file = None;
-                ctx.notifier().connection_lost().await?;
-                return Err(e.into());
+                self.write(ctx, &chunk).await?;
             }
         }
         Ok(SinkReply::NONE)
     }
 
+    async fn on_signal(
+        &mut self,
+        _signal: Event,
+        ctx: &SinkContext,
+        _serializer: &mut EventSerializer,
+    ) -> Result<SinkReply> {
+        // row groups of columnar files also need to be written when no events arrive
+        self.flush_columnar_file(ctx).await?;
+        Ok(SinkReply::NONE)
+    }
+
     fn auto_ack(&self) -> bool {
-        true
+        self.config.columnar.is_none()
     }
 
     fn asynchronous(&self) -> bool {
@@ -318,6 +439,7 @@ impl Sink for FileSink {
     }
 
     async fn on_stop(&mut self, ctx: &SinkContext) -> Result<()> {
+        self.finish_columnar_file(ctx).await?;
         if let Some(file) = self.file.take() {
             if let Err(e) = file.sync_all().await {
                 error!("{} Error flushing file: {}", &ctx, e);
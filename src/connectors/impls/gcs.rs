@@ -14,4 +14,5 @@
 
 mod api_client;
 mod chunked_buffer;
+pub(crate) mod reader;
 pub(crate) mod streamer;
@@ -348,6 +348,104 @@ impl<THttpClient: HttpClient, TBackoffStrategy: BackoffStrategy>
 
         Ok(())
     }
+
+    #[cfg(test)]
+    #[allow(clippy::unused_self, clippy::unnecessary_wraps)]
+    fn authorization(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    #[cfg(not(test))]
+    #[allow(clippy::unnecessary_wraps)]
+    fn authorization(&self) -> Result<Option<String>> {
+        Ok(Some(self.token.header_value()?.to_string()))
+    }
+
+    /// Lists one page of the objects in `bucket` whose names start with `prefix`
+    pub(crate) async fn list_objects(
+        &mut self,
+        url: &Url<HttpsDefaults>,
+        bucket: &str,
+        prefix: Option<&str>,
+        page_token: Option<&str>,
+    ) -> Result<ObjectList> {
+        let mut list_url = url::Url::parse(&format!(
+            "{}/b/{}/o",
+            url.to_string().trim_end_matches('/'),
+            bucket
+        ))?;
+        {
+            let mut query = list_url.query_pairs_mut();
+            if let Some(prefix) = prefix {
+                query.append_pair("prefix", prefix);
+            }
+            if let Some(page_token) = page_token {
+                query.append_pair("pageToken", page_token);
+            }
+        }
+        let authorization = self.authorization()?;
+        let mut response = retriable_request(&self.backoff_strategy, &mut self.client, || {
+            let mut request = Request::new(Method::Get, list_url.clone());
+            if let Some(authorization) = &authorization {
+                request.insert_header("Authorization", authorization.as_str());
+            }
+            Ok(request)
+        })
+        .await?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "Error listing objects in bucket {bucket}: {} {}",
+                response.status(),
+                response.body_string().await.unwrap_or_default()
+            )
+            .into());
+        }
+        let mut body = response.body_bytes().await?;
+        Ok(simd_json::from_slice(body.as_mut_slice())?)
+    }
+
+    /// Requests the data of an object, the body of the response is not read yet.
+    ///
+    /// Gzip encoded objects are served as they are stored, without decompressing them.
+    pub(crate) async fn download_object(
+        &mut self,
+        url: &Url<HttpsDefaults>,
+        file: &FileId,
+    ) -> Result<Response> {
+        let mut object_url = url::Url::parse(&format!(
+            "{}/b/{}/o",
+            url.to_string().trim_end_matches('/'),
+            file.bucket
+        ))?;
+        object_url
+            .path_segments_mut()
+            .map_err(|_| ErrorKind::GoogleCloudStorageError("Invalid storage url"))?
+            .push(&file.name);
+        object_url.query_pairs_mut().append_pair("alt", "media");
+        let authorization = self.authorization()?;
+        let mut response = retriable_request(&self.backoff_strategy, &mut self.client, || {
+            let mut request = Request::new(Method::Get, object_url.clone());
+            if let Some(authorization) = &authorization {
+                request.insert_header("Authorization", authorization.as_str());
+            }
+            request.insert_header("Accept-Encoding", "gzip");
+            Ok(request)
+        })
+        .await?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "Error downloading object {} from bucket {}: {} {}",
+                file.name,
+                file.bucket,
+                response.status(),
+                response.body_string().await.unwrap_or_default()
+            )
+            .into());
+        }
+        Ok(response)
+    }
 }
 
 #[derive(Debug)]
@@ -379,6 +477,34 @@ impl FileId {
     }
 }
 
+/// An object resource as listed by the JSON API, numbers are sent as strings
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ObjectMeta {
+    pub name: String,
+    pub bucket: String,
+    #[serde(default = "Default::default")]
+    pub size: Option<String>,
+    #[serde(default = "Default::default")]
+    pub content_type: Option<String>,
+    #[serde(default = "Default::default")]
+    pub content_encoding: Option<String>,
+    #[serde(default = "Default::default")]
+    pub generation: Option<String>,
+    #[serde(default = "Default::default")]
+    pub updated: Option<String>,
+}
+
+/// A page of listed objects
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ObjectList {
+    #[serde(default = "Default::default")]
+    pub items: Vec<ObjectMeta>,
+    #[serde(default = "Default::default")]
+    pub next_page_token: Option<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -679,6 +805,79 @@ mod tests {
         assert_eq!(response.status(), StatusCode::Ok);
     }
 
+    #[async_std::test]
+    async fn can_list_objects() -> Result<()> {
+        let client = MockHttpClient {
+            config: Default::default(),
+            handle_request: Box::new(|req| {
+                assert_eq!(req.url().path(), "/storage/v1/b/bucket/o");
+                assert_eq!(req.url().query(), Some("prefix=logs%2F&pageToken=next"));
+
+                let mut response = Response::new(StatusCode::Ok);
+                response.set_body(
+                    r#"{"kind":"storage#objects","items":[{"name":"logs/a.json","bucket":"bucket","size":"42","contentEncoding":"gzip"}]}"#,
+                );
+                Ok(response)
+            }),
+            simulate_failure: Arc::new(AtomicBool::new(true)),
+            simulate_transport_failure: Arc::new(AtomicBool::new(false)),
+        };
+        let mut api_client = DefaultApiClient {
+            sessions_per_file: HashMap::new(),
+            client,
+            backoff_strategy: ExponentialBackoffRetryStrategy {
+                max_retries: 3,
+                base_sleep_time: Duration::from_nanos(1),
+            },
+        };
+        let list = api_client
+            .list_objects(
+                &Url::parse("http://example.com/storage/v1/").unwrap(),
+                "bucket",
+                Some("logs/"),
+                Some("next"),
+            )
+            .await?;
+        assert_eq!(None, list.next_page_token);
+        assert_eq!(1, list.items.len());
+        assert_eq!("logs/a.json", list.items[0].name);
+        assert_eq!(Some("42".to_string()), list.items[0].size);
+        assert_eq!(Some("gzip".to_string()), list.items[0].content_encoding);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn can_download_object() -> Result<()> {
+        let client = MockHttpClient {
+            config: Default::default(),
+            handle_request: Box::new(|req| {
+                assert_eq!(req.url().path(), "/storage/v1/b/bucket/o/logs%2Fa.json");
+                assert_eq!(req.url().query(), Some("alt=media"));
+                let mut response = Response::new(StatusCode::Ok);
+                response.set_body("snot");
+                Ok(response)
+            }),
+            simulate_failure: Arc::new(AtomicBool::new(false)),
+            simulate_transport_failure: Arc::new(AtomicBool::new(false)),
+        };
+        let mut api_client = DefaultApiClient {
+            sessions_per_file: HashMap::new(),
+            client,
+            backoff_strategy: ExponentialBackoffRetryStrategy {
+                max_retries: 3,
+                base_sleep_time: Duration::from_nanos(1),
+            },
+        };
+        let url = Url::parse("http://example.com/storage/v1").unwrap();
+        let mut response = api_client
+            .download_object(&url, &FileId::new("bucket", "logs/a.json"))
+            .await?;
+        assert_eq!("snot", response.body_string().await?);
+
+        Ok(())
+    }
+
     #[test]
     pub fn mock_http_client_config() {
         let mut client = MockHttpClient {
@@ -0,0 +1,328 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Google Cloud Storage reader
+//!
+//! Lists the objects of a bucket, optionally filtered by `prefix` and a `glob` on their names,
+//! and reads them one after the other. Every object is its own stream, so preprocessors
+//! and the codec see each object from its beginning. Objects stored with `Content-Encoding: gzip`
+//! are decompressed before they are handed on.
+
+use crate::connectors::impls::gcs::api_client::{
+    DefaultApiClient, ExponentialBackoffRetryStrategy, FileId, ObjectList, ObjectMeta,
+};
+use crate::connectors::impls::gcs::streamer::create_client;
+use crate::connectors::prelude::*;
+use async_compression::futures::bufread::GzipDecoder;
+use async_std::channel::{bounded, Sender};
+use async_std::io::{Read, ReadExt};
+use async_std::task::{self, JoinHandle};
+use glob::Pattern;
+use http_client::h1::H1Client;
+use std::time::Duration;
+
+pub(crate) const CONNECTOR_TYPE: &str = "gcs_reader";
+const URL_SCHEME: &str = "tremor-gcs";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    #[serde(default = "default_endpoint")]
+    url: Url<HttpsDefaults>,
+    bucket: String,
+    /// only objects whose names start with this prefix are listed
+    #[serde(default = "Default::default")]
+    prefix: Option<String>,
+    /// only objects whose names match this glob pattern are read
+    #[serde(default = "Default::default")]
+    glob: Option<String>,
+    #[serde(default = "default_connect_timeout")]
+    connect_timeout: u64,
+    #[serde(default = "default_max_retries")]
+    max_retries: u32,
+    #[serde(default = "default_backoff_base_time")]
+    default_backoff_base_time: u64,
+}
+
+#[allow(clippy::unwrap_used)]
+fn default_endpoint() -> Url<HttpsDefaults> {
+    // ALLOW: this URL is hardcoded, so the only reason for parse failing would be if it was changed
+    Url::parse("https://storage.googleapis.com/storage/v1").unwrap()
+}
+
+fn default_connect_timeout() -> u64 {
+    10_000_000_000
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_backoff_base_time() -> u64 {
+    25_000_000
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Debug, Default)]
+pub(crate) struct Builder {}
+
+#[async_trait::async_trait]
+impl ConnectorBuilder for Builder {
+    fn connector_type(&self) -> ConnectorType {
+        ConnectorType::from(CONNECTOR_TYPE)
+    }
+
+    async fn build_cfg(
+        &self,
+        _alias: &Alias,
+        _config: &ConnectorConfig,
+        connector_config: &Value,
+        _kill_switch: &KillSwitch,
+    ) -> Result<Box<dyn Connector>> {
+        let config = Config::new(connector_config)?;
+        let glob = config.glob.as_deref().map(Pattern::new).transpose()?;
+
+        Ok(Box::new(GCSReaderConnector {
+            config,
+            glob,
+            tx: None,
+            handle: None,
+        }))
+    }
+}
+
+struct GCSReaderConnector {
+    config: Config,
+    glob: Option<Pattern>,
+    tx: Option<Sender<SourceReply>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+#[async_trait::async_trait]
+impl Connector for GCSReaderConnector {
+    async fn create_source(
+        &mut self,
+        source_context: SourceContext,
+        builder: SourceManagerBuilder,
+    ) -> Result<Option<SourceAddr>> {
+        let (tx, rx) = bounded(QSIZE.load(Ordering::Relaxed));
+        let source = ChannelSource::from_channel(tx.clone(), rx);
+
+        self.tx = Some(tx);
+
+        builder.spawn(source, source_context).map(Some)
+    }
+
+    async fn connect(&mut self, ctx: &ConnectorContext, _attempt: &Attempt) -> Result<bool> {
+        // cancelling the reader of a previous connection, if any
+        if let Some(handle) = self.handle.take() {
+            handle.cancel().await;
+        }
+        let tx = self.tx.clone().ok_or(ErrorKind::GoogleCloudStorageError(
+            "Source sender not initialized",
+        ))?;
+
+        let client = create_client(Duration::from_nanos(self.config.connect_timeout))?;
+        let mut api_client = DefaultApiClient::new(
+            client,
+            ExponentialBackoffRetryStrategy::new(
+                self.config.max_retries,
+                Duration::from_nanos(self.config.default_backoff_base_time),
+            ),
+        )?;
+
+        // listing the first page also checks that the bucket is accessible
+        let first_page = api_client
+            .list_objects(
+                &self.config.url,
+                &self.config.bucket,
+                self.config.prefix.as_deref(),
+                None,
+            )
+            .await?;
+
+        let reader = ObjectReader {
+            ctx: ctx.clone(),
+            api_client,
+            config: self.config.clone(),
+            glob: self.glob.clone(),
+            origin_uri: EventOriginUri {
+                scheme: URL_SCHEME.to_string(),
+                host: hostname(),
+                port: None,
+                path: vec![self.config.bucket.clone()],
+            },
+            tx,
+        };
+        let ctx = ctx.clone();
+        self.handle = Some(task::spawn(async move {
+            log_error!(
+                reader.run(first_page).await,
+                "{ctx} Error reading objects: {e}"
+            );
+        }));
+
+        Ok(true)
+    }
+
+    fn codec_requirements(&self) -> CodecReq {
+        CodecReq::Required
+    }
+
+    async fn on_stop(&mut self, _ctx: &ConnectorContext) -> Result<()> {
+        if let Some(handle) = self.handle.take() {
+            handle.cancel().await;
+        }
+        Ok(())
+    }
+}
+
+struct ObjectReader {
+    ctx: ConnectorContext,
+    api_client: DefaultApiClient<H1Client, ExponentialBackoffRetryStrategy>,
+    config: Config,
+    glob: Option<Pattern>,
+    origin_uri: EventOriginUri,
+    tx: Sender<SourceReply>,
+}
+
+impl ObjectReader {
+    /// Reads all listed objects, fetching the next page once the current one is done
+    async fn run(mut self, first_page: ObjectList) -> Result<()> {
+        let mut page = first_page;
+        let mut stream = 0;
+        loop {
+            for object in page.items {
+                if let Some(glob) = &self.glob {
+                    if !glob.matches(&object.name) {
+                        continue;
+                    }
+                }
+                debug!("{} Reading object {}...", self.ctx, object.name);
+                let reply = match self.read_object(&object, stream).await {
+                    Ok(()) => SourceReply::EndStream {
+                        origin_uri: self.origin_uri.clone(),
+                        stream,
+                        meta: None,
+                    },
+                    Err(e) => {
+                        error!("{} Error reading object {}: {e}", self.ctx, object.name);
+                        SourceReply::StreamFail(stream)
+                    }
+                };
+                self.tx.send(reply).await?;
+                stream += 1;
+            }
+            if let Some(page_token) = page.next_page_token {
+                page = self
+                    .api_client
+                    .list_objects(
+                        &self.config.url,
+                        &self.config.bucket,
+                        self.config.prefix.as_deref(),
+                        Some(&page_token),
+                    )
+                    .await?;
+            } else {
+                break;
+            }
+        }
+        debug!(
+            "{} Read all objects in bucket {}.",
+            self.ctx, self.config.bucket
+        );
+        Ok(())
+    }
+
+    async fn read_object(&mut self, object: &ObjectMeta, stream: u64) -> Result<()> {
+        let file = FileId::new(object.bucket.as_str(), object.name.as_str());
+        let mut response = self
+            .api_client
+            .download_object(&self.config.url, &file)
+            .await?;
+        let gzipped = response
+            .header("Content-Encoding")
+            .map_or(false, |encoding| encoding.last().as_str() == "gzip");
+        let body = response.take_body();
+        let mut body: Box<dyn Read + Send + Unpin> = if gzipped {
+            Box::new(GzipDecoder::new(body))
+        } else {
+            Box::new(body)
+        };
+
+        let meta = self.object_meta(object);
+        let mut buf = vec![0_u8; DEFAULT_BUF_SIZE];
+        loop {
+            let n = body.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            self.tx
+                .send(SourceReply::Data {
+                    origin_uri: self.origin_uri.clone(),
+                    data: buf[..n].to_vec(),
+                    meta: Some(meta.clone()),
+                    stream: Some(stream),
+                    port: None,
+                    codec_overwrite: None,
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn object_meta(&self, object: &ObjectMeta) -> Value<'static> {
+        let ObjectMeta {
+            name,
+            bucket,
+            size,
+            content_type,
+            content_encoding,
+            generation,
+            updated,
+        } = object.clone();
+        self.ctx.meta(literal!({
+            "bucket": bucket,
+            "name": name,
+            "size": size.and_then(|size| size.parse::<u64>().ok()),
+            "content_type": content_type,
+            "content_encoding": content_encoding,
+            "generation": generation,
+            "updated": updated
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn invalid_glob() {
+        let config = literal!({
+            "bucket": "snot",
+            "glob": "**/[badger"
+        });
+        let res = Builder::default()
+            .build_cfg(
+                &Alias::new("flow", "gcs"),
+                &ConnectorConfig::default(),
+                &config,
+                &KillSwitch::dummy(),
+            )
+            .await;
+        assert!(res.is_err());
+    }
+}
@@ -19,6 +19,7 @@ use crate::connectors::impls::gcs::api_client::{
 use crate::connectors::impls::gcs::chunked_buffer::ChunkedBuffer;
 use crate::connectors::prelude::*;
 use crate::connectors::sink::{AsyncSinkReply, ContraflowData, Sink};
+use crate::connectors::utils::columnar::{self, ColumnarFile};
 use crate::system::KillSwitch;
 use crate::{connectors, QSIZE};
 use async_std::channel::{bounded, Receiver, Sender};
@@ -46,6 +47,11 @@ pub(crate) struct Config {
     max_retries: u32,
     #[serde(default = "default_backoff_base_time")]
     default_backoff_base_time: u64,
+
+    /// write columnar files instead of using the codec,
+    /// events are acked once the file they are in has been uploaded
+    #[serde(default = "Default::default")]
+    columnar: Option<columnar::Config>,
 }
 
 #[allow(clippy::unwrap_used)]
@@ -121,6 +127,7 @@ impl Connector for GCSWriterConnector {
             current_bucket: None,
             default_bucket,
             done_until: Arc::new(AtomicUsize::new(0)),
+            columnar_file: None,
             reply_tx,
         };
 
@@ -128,11 +135,15 @@ impl Connector for GCSWriterConnector {
     }
 
     fn codec_requirements(&self) -> CodecReq {
-        CodecReq::Required
+        if self.config.columnar.is_some() {
+            CodecReq::Structured
+        } else {
+            CodecReq::Required
+        }
     }
 }
 
-fn create_client(connect_timeout: Duration) -> Result<H1Client> {
+pub(super) fn create_client(connect_timeout: Duration) -> Result<H1Client> {
     let mut client = H1Client::new();
     client.set_config(http_client::Config::new().set_timeout(Some(connect_timeout)))?;
 
@@ -201,6 +212,8 @@ struct GCSWriterSink {
     current_bucket: Option<String>,
     default_bucket: Option<Value<'static>>,
     done_until: Arc<AtomicUsize>,
+    /// the columnar file written to the current name
+    columnar_file: Option<ColumnarFile>,
     reply_tx: Sender<AsyncSinkReply>,
 }
 
@@ -237,12 +250,26 @@ impl Sink for GCSWriterSink {
             self.start_upload_if_needed(meta, name, contraflow_data.clone(), start)
                 .await?;
 
-            let serialized_data = serializer.serialize(value, event.ingest_ns)?;
-            for item in serialized_data {
-                self.buffers.write(&item);
+            if let Some(config) = &self.config.columnar {
+                let file = self
+                    .columnar_file
+                    .get_or_insert_with(|| ColumnarFile::new(config));
+                if let Err(e) = file.write(&[value]) {
+                    error!("{ctx} Error writing columnar record: {e}");
+                    return Ok(SinkReply::FAIL);
+                }
+                if file.needs_flush() {
+                    self.buffers.write(&file.flush()?);
+                }
+            } else {
+                let serialized_data = serializer.serialize(value, event.ingest_ns)?;
+                for item in serialized_data {
+                    self.buffers.write(&item);
+                }
             }
 
             if let Some(data) = self.buffers.read_current_block() {
+                let upload_contraflow = self.upload_contraflow(&contraflow_data);
                 let client_tx = self
                     .client_tx
                     .as_mut()
@@ -262,12 +289,29 @@ impl Sink for GCSWriterSink {
                     .send(HttpTaskRequest {
                         command,
                         start,
-                        contraflow_data: Some(contraflow_data.clone()),
+                        contraflow_data: upload_contraflow,
                     })
                     .await?;
             }
         }
+        // the event is acked or failed together with the file its last value went to
+        if let Some(file) = self.columnar_file.as_mut() {
+            if event.transactional {
+                file.track(contraflow_data);
+            }
+        }
+
+        Ok(SinkReply::NONE)
+    }
 
+    async fn on_signal(
+        &mut self,
+        _signal: Event,
+        _ctx: &SinkContext,
+        _serializer: &mut EventSerializer,
+    ) -> Result<SinkReply> {
+        // row groups of columnar files also need to be written when no events arrive
+        self.flush_columnar_file(nanotime()).await?;
         Ok(SinkReply::NONE)
     }
 
@@ -309,11 +353,50 @@ impl Sink for GCSWriterSink {
     }
 
     fn auto_ack(&self) -> bool {
-        true
+        self.config.columnar.is_none()
     }
 }
 
 impl GCSWriterSink {
+    /// contraflow data for intermediate requests, with columnar files only finishing the upload acks or fails
+    fn upload_contraflow(&self, contraflow_data: &ContraflowData) -> Option<ContraflowData> {
+        self.config
+            .columnar
+            .is_none()
+            .then(|| contraflow_data.clone())
+    }
+
+    /// writes a row group of the columnar file if one is due, uploading the filled chunk if any
+    async fn flush_columnar_file(&mut self, start: u64) -> Result<()> {
+        if let Some(file) = self.columnar_file.as_mut() {
+            if file.needs_flush() {
+                self.buffers.write(&file.flush()?);
+            }
+        }
+        self.buffers
+            .mark_done_until(self.done_until.load(Ordering::Acquire))?;
+        if let (Some(name), Some(bucket), Some(client_tx)) = (
+            self.current_name.as_ref(),
+            self.current_bucket.as_ref(),
+            self.client_tx.as_ref(),
+        ) {
+            if let Some(data) = self.buffers.read_current_block() {
+                let command = HttpTaskCommand::UploadData {
+                    file: FileId::new(bucket, name),
+                    data,
+                };
+                client_tx
+                    .send(HttpTaskRequest {
+                        command,
+                        start,
+                        contraflow_data: None,
+                    })
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
     async fn finish_upload_if_needed(
         &mut self,
         name: &str,
@@ -341,6 +424,26 @@ impl GCSWriterSink {
                     "not connected",
                 ))?;
 
+            let mut contraflow_data = contraflow_data;
+            if self.config.columnar.is_some() {
+                contraflow_data = None;
+            }
+            if let Some(mut file) = self.columnar_file.take() {
+                contraflow_data = file.take_contraflow();
+                match file.finish() {
+                    Ok(data) => self.buffers.write(&data),
+                    Err(e) => {
+                        // the upload is still finished, but the events in the file are failed
+                        error!("Error finishing columnar file {current_name}: {e}");
+                        if let Some(contraflow_data) = contraflow_data.take() {
+                            self.reply_tx
+                                .send(AsyncSinkReply::Fail(contraflow_data))
+                                .await?;
+                        }
+                    }
+                }
+            }
+
             let mut buffers = ChunkedBuffer::new(self.config.buffer_size);
 
             std::mem::swap(&mut self.buffers, &mut buffers);
@@ -379,6 +482,7 @@ impl GCSWriterSink {
         contraflow_data: ContraflowData,
         start: u64,
     ) -> Result<()> {
+        let contraflow_data = self.upload_contraflow(&contraflow_data);
         let client_tx = self
             .client_tx
             .as_mut()
@@ -398,7 +502,7 @@ impl GCSWriterSink {
                 .send(HttpTaskRequest {
                     command,
                     start,
-                    contraflow_data: Some(contraflow_data),
+                    contraflow_data,
                 })
                 .await?;
 
@@ -464,6 +568,8 @@ mod tests {
                     postprocessors: None,
                     reconnect: Default::default(),
                     metrics_interval_s: None,
+                    batch: None,
+                    dead_letter_data: true,
                 },
                 &raw_config,
                 &KillSwitch::dummy(),
@@ -487,12 +593,14 @@ mod tests {
                 bucket: None,
                 max_retries: 3,
                 default_backoff_base_time: 1,
+                columnar: None,
             },
             buffers: ChunkedBuffer::new(10),
             current_name: None,
             current_bucket: None,
             default_bucket: None,
             done_until: Arc::new(Default::default()),
+            columnar_file: None,
             reply_tx,
         };
 
@@ -566,12 +674,14 @@ mod tests {
                 bucket: None,
                 max_retries: 3,
                 default_backoff_base_time: 1,
+                columnar: None,
             },
             buffers: ChunkedBuffer::new(10),
             current_name: None,
             current_bucket: None,
             default_bucket: None,
             done_until: Arc::new(Default::default()),
+            columnar_file: None,
             reply_tx,
         };
 
@@ -652,12 +762,14 @@ mod tests {
                 bucket: None,
                 max_retries: 3,
                 default_backoff_base_time: 1,
+                columnar: None,
             },
             buffers: ChunkedBuffer::new(10),
             current_name: None,
             current_bucket: None,
             default_bucket: None,
             done_until: Arc::new(Default::default()),
+            columnar_file: None,
             reply_tx,
         };
 
@@ -773,12 +885,14 @@ mod tests {
                 bucket: None,
                 max_retries: 3,
                 default_backoff_base_time: 1,
+                columnar: None,
             },
             buffers: ChunkedBuffer::new(10),
             current_name: None,
             current_bucket: None,
             default_bucket: None,
             done_until: Arc::new(Default::default()),
+            columnar_file: None,
             reply_tx,
         };
 
@@ -903,6 +1017,7 @@ mod tests {
                 bucket: None,
                 max_retries: 3,
                 default_backoff_base_time: 1,
+                columnar: None,
             },
             &mut MockApiClient {
                 inject_failure: Arc::new(AtomicBool::new(false)),
@@ -943,6 +1058,7 @@ mod tests {
                 bucket: None,
                 max_retries: 3,
                 default_backoff_base_time: 1,
+                columnar: None,
             },
             MockApiClient {
                 inject_failure: Arc::new(AtomicBool::new(true)),
@@ -1010,6 +1126,7 @@ mod tests {
                 bucket: None,
                 max_retries: 3,
                 default_backoff_base_time: 1,
+                columnar: None,
             },
             MockApiClient {
                 inject_failure: Arc::new(AtomicBool::new(false)),
@@ -16,4 +16,6 @@ pub(crate) mod auth;
 pub(crate) mod client;
 pub(crate) mod meta;
 pub(crate) mod server;
+pub(crate) mod sse;
+pub(crate) mod sse_client;
 pub(crate) mod utils;
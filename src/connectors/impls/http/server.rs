@@ -14,28 +14,39 @@
 
 use crate::connectors::{
     prelude::*,
-    utils::{mime::MimeCodecMap, tls::TLSServerConfig},
+    utils::{
+        mime::MimeCodecMap,
+        tls::{client_cert_meta, ReloadingServerConfig, TLSServerConfig},
+    },
 };
 use crate::{connectors::spawn_task, errors::err_connector_def};
 use async_std::channel::unbounded;
 use async_std::{
-    channel::{bounded, Receiver, Sender},
-    task::JoinHandle,
+    channel::{bounded, Receiver, Sender, TrySendError},
+    net::{TcpListener, TcpStream},
+    task::{self, JoinHandle},
 };
 use dashmap::DashMap;
+use futures::{AsyncRead, AsyncWrite};
 use halfbrown::{Entry, HashMap};
 use http_types::headers::{self, HeaderValue, HeaderValues};
 use http_types::{mime::BYTE_STREAM, Mime, StatusCode};
 use simd_json::ValueAccess;
-use std::{str::FromStr, sync::Arc};
+use std::{
+    io,
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex, PoisonError},
+    task::Poll,
+};
 use tide::{
     listener::{Listener, ToListener},
     Response,
 };
-use tide_rustls::TlsListener;
 use tremor_common::ids::Id;
 
 use super::meta::{extract_request_meta, BodyData};
+use super::sse;
 use super::utils::{FixedBodyReader, RequestId, StreamingBodyReader};
 
 #[derive(Deserialize, Debug, Clone)]
@@ -50,6 +61,9 @@ pub(crate) struct Config {
     /// e.g. for handling `application/json` with the `binary` codec, if desired
     #[serde(default)]
     custom_codecs: HashMap<String, String>,
+    /// hold requests accepting `text/event-stream` open as Server-Sent Events streams
+    #[serde(default = "Default::default")]
+    sse: bool,
 }
 
 impl ConfigImpl for Config {}
@@ -76,7 +90,11 @@ impl ConnectorBuilder for Builder {
         _kill_switch: &KillSwitch,
     ) -> Result<Box<dyn Connector>> {
         let config = Config::new(config)?;
-        let tls_server_config = config.tls.clone();
+        let tls_server_config = config
+            .tls
+            .as_ref()
+            .map(ReloadingServerConfig::new)
+            .transpose()?;
 
         if tls_server_config.is_some() && config.url.scheme() != "https" {
             return Err(err_connector_def(id, Self::HTTPS_REQUIRED));
@@ -93,6 +111,7 @@ impl ConnectorBuilder for Builder {
             .as_ref()
             .map_or_else(|| HttpServer::DEFAULT_CODEC.to_string(), |c| c.name.clone());
         let inflight = Arc::default();
+        let sse_streams = Arc::default();
         let codec_map = MimeCodecMap::with_overwrites(&config.custom_codecs);
 
         Ok(Box::new(HttpServer {
@@ -100,6 +119,7 @@ impl ConnectorBuilder for Builder {
             origin_uri,
             tls_server_config,
             inflight,
+            sse_streams,
             configured_codec,
             codec_map,
         }))
@@ -110,8 +130,10 @@ impl ConnectorBuilder for Builder {
 pub(crate) struct HttpServer {
     config: Config,
     origin_uri: EventOriginUri,
-    tls_server_config: Option<TLSServerConfig>,
+    tls_server_config: Option<ReloadingServerConfig>,
     inflight: Arc<DashMap<RequestId, Sender<Response>>>,
+    /// open SSE streams
+    sse_streams: Arc<DashMap<RequestId, Sender<Vec<u8>>>>,
     configured_codec: String,
     codec_map: MimeCodecMap,
 }
@@ -140,6 +162,8 @@ impl Connector for HttpServer {
         let source = HttpServerSource {
             url: self.config.url.clone(),
             inflight: self.inflight.clone(),
+            sse: self.config.sse,
+            sse_streams: self.sse_streams.clone(),
             request_counter: Self::REQUEST_COUNTER_START,
             request_tx,
             request_rx,
@@ -159,6 +183,7 @@ impl Connector for HttpServer {
     ) -> Result<Option<SinkAddr>> {
         let sink = HttpServerSink::new(
             self.inflight.clone(),
+            self.sse_streams.clone(),
             self.codec_map.clone(),
             self.configured_codec.clone(),
         );
@@ -170,11 +195,13 @@ struct HttpServerSource {
     url: Url,
     origin_uri: EventOriginUri,
     inflight: Arc<DashMap<RequestId, Sender<Response>>>,
+    sse: bool,
+    sse_streams: Arc<DashMap<RequestId, Sender<Vec<u8>>>>,
     request_counter: u64,
     request_rx: Receiver<RawRequestData>,
     request_tx: Sender<RawRequestData>,
     server_task: Option<JoinHandle<()>>,
-    tls_server_config: Option<TLSServerConfig>,
+    tls_server_config: Option<ReloadingServerConfig>,
     configured_codec: String,
     codec_map: MimeCodecMap,
 }
@@ -208,6 +235,7 @@ impl Source for HttpServerSource {
         // Answer all pending requests with a 503 status?
 
         let tx = self.request_tx.clone();
+        let sse = self.sse;
 
         let ctx = ctx.clone();
         let tls_server_config = self.tls_server_config.clone();
@@ -215,25 +243,28 @@ impl Source for HttpServerSource {
         // Server task - this is the main receive loop for http server instances
         self.server_task = Some(spawn_task(ctx.clone(), async move {
             if let Some(tls_server_config) = tls_server_config {
-                let mut endpoint = tide::Server::with_state(HttpServerState::new(tx, ctx.clone()));
+                let mut endpoint =
+                    tide::Server::with_state(HttpServerState::new(tx, ctx.clone(), sse));
                 endpoint.at("/").all(handle_request);
                 endpoint.at("/*").all(handle_request);
 
-                let mut listener = TlsListener::build()
-                    .addrs(&hostport)
-                    .cert(&tls_server_config.cert)
-                    .key(&tls_server_config.key)
-                    .finish()?;
-                listener.bind(endpoint).await?;
-                if let Some(info) = listener.info().into_iter().next() {
-                    info!(
-                        "{ctx} Listening for HTTPS requests on {}",
-                        info.connection()
-                    );
+                let listener = TcpListener::bind(&hostport).await?;
+                info!(
+                    "{ctx} Listening for HTTPS requests on {}",
+                    listener.local_addr()?
+                );
+                loop {
+                    let (stream, _peer_addr) = listener.accept().await?;
+                    task::spawn(serve_tls(
+                        endpoint.clone(),
+                        tls_server_config.clone(),
+                        stream,
+                        ctx.clone(),
+                    ));
                 }
-                listener.accept().await?;
             } else {
-                let mut endpoint = tide::Server::with_state(HttpServerState::new(tx, ctx.clone()));
+                let mut endpoint =
+                    tide::Server::with_state(HttpServerState::new(tx, ctx.clone(), sse));
                 endpoint.at("/").all(handle_request);
                 endpoint.at("/*").all(handle_request);
                 let mut listener = (&hostport).to_listener()?;
@@ -255,6 +286,7 @@ impl Source for HttpServerSource {
             request_meta,
             content_type,
             response_channel,
+            sse_stream,
         } = self.request_rx.recv().await?;
 
         // assign request id, set pull_id
@@ -264,12 +296,21 @@ impl Source for HttpServerSource {
 
         // prepare meta
         debug!("{ctx} Received HTTP request with request id {request_id}");
-        let meta = ctx.meta(literal!({
+        let mut http_meta = literal!({
             "request": request_meta,
             "request_id": *pull_id
-        }));
-        // store request context so we can respond to this request
-        if self.inflight.insert(request_id, response_channel).is_some() {
+        });
+        if sse_stream.is_some() {
+            http_meta.try_insert("sse", true);
+        }
+        let meta = ctx.meta(http_meta);
+        if let Some(sse_stream) = sse_stream {
+            // the response is already sent, events for this request are streamed to it
+            if self.sse_streams.insert(request_id, sse_stream).is_some() {
+                error!("{ctx} Request id collision: {request_id}");
+            }
+        } else if self.inflight.insert(request_id, response_channel).is_some() {
+            // store request context so we can respond to this request
             error!("{ctx} Request id collision: {request_id}");
         };
         Ok(if data.is_empty() {
@@ -314,6 +355,7 @@ impl Source for HttpServerSource {
 
 struct HttpServerSink {
     inflight: Arc<DashMap<RequestId, Sender<Response>>>,
+    sse_streams: Arc<DashMap<RequestId, Sender<Vec<u8>>>>,
     codec_map: MimeCodecMap,
     configured_codec: String,
 }
@@ -321,18 +363,64 @@ struct HttpServerSink {
 impl HttpServerSink {
     const ERROR_MSG_EXTRACT_VALUE: &'static str = "Error turning Event into HTTP response";
     const ERROR_MSG_APPEND_RESPONSE: &'static str = "Error appending batched data to HTTP response";
+    const ERROR_MSG_SSE: &'static str = "Error sending event to SSE stream";
 
     fn new(
         inflight: Arc<DashMap<RequestId, Sender<Response>>>,
+        sse_streams: Arc<DashMap<RequestId, Sender<Vec<u8>>>>,
         codec_map: MimeCodecMap,
         configured_codec: String,
     ) -> Self {
         Self {
             inflight,
+            sse_streams,
             codec_map,
             configured_codec,
         }
     }
+
+    /// Sends `value` as an event to the SSE streams of `targets`.
+    ///
+    /// `$http_server.sse.event`, `id` and `retry` are sent as the respective fields,
+    /// `$http_server.sse.close` ends the streams after this event.
+    /// Streams whose clients do not keep up and fill their buffer are closed.
+    fn send_sse<'event>(
+        &self,
+        ctx: &SinkContext,
+        targets: &[RequestId],
+        value: &Value<'event>,
+        http_meta: Option<&Value<'event>>,
+        ingest_ns: u64,
+        serializer: &mut EventSerializer,
+    ) -> Result<()> {
+        let sse_meta = http_meta.get("sse");
+        let data = serializer.serialize(value, ingest_ns)?.concat();
+        let frame = sse::encode(
+            sse_meta.get_str("event"),
+            sse_meta.get_str("id"),
+            sse_meta.get_u64("retry"),
+            &data,
+        );
+        let close = sse_meta.get_bool("close").unwrap_or_default();
+        for rid in targets {
+            let stream = self.sse_streams.get(rid).map(|s| s.value().clone());
+            if let Some(stream) = stream {
+                // a closed stream means the client went away
+                let sent = match stream.try_send(frame.clone()) {
+                    Err(TrySendError::Full(_)) => {
+                        warn!("{ctx} SSE stream {rid} is not keeping up. Closing it.");
+                        false
+                    }
+                    res => res.is_ok(),
+                };
+                if !sent || close {
+                    self.sse_streams.remove(rid);
+                    stream.close();
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait()]
@@ -357,9 +445,34 @@ impl Sink for HttpServerSink {
         // - update SinkResponse for each element of the batch
         // - send response immediately in case of chunked encoding
         let mut response_map = HashMap::new();
+        let mut sse_sent = false;
         for (value, meta) in event.value_meta_iter() {
             let http_meta = ctx.extract_meta(meta);
 
+            // events for SSE streams are sent right away, the response stays open
+            if !self.sse_streams.is_empty() {
+                let rid = http_meta
+                    .get_u64("request_id")
+                    .or_else(|| min_pull_id.filter(|_| min_pull_id == max_pull_id));
+                let targets: Vec<RequestId> = match rid.map(RequestId::new) {
+                    Some(rid) if self.sse_streams.contains_key(&rid) => vec![rid],
+                    Some(_) => Vec::new(),
+                    // not a response to any request, so it goes to every stream
+                    None if min_pull_id.is_none() => {
+                        self.sse_streams.iter().map(|s| *s.key()).collect()
+                    }
+                    None => Vec::new(),
+                };
+                if !targets.is_empty() {
+                    ctx.bail_err(
+                        self.send_sse(ctx, &targets, value, http_meta, ingest_ns, serializer),
+                        Self::ERROR_MSG_SSE,
+                    )?;
+                    sse_sent = true;
+                    continue;
+                }
+            }
+
             // first try to extract request_id from event batch element metadata
             if let Some(rid) = http_meta.get_u64("request_id").map(RequestId::new) {
                 match response_map.entry(rid) {
@@ -485,6 +598,9 @@ impl Sink for HttpServerSink {
         }
 
         if response_map.is_empty() {
+            if sse_sent {
+                return Ok(SinkReply::NONE);
+            }
             error!("{ctx} No request context found for event.");
             return Ok(SinkReply::FAIL);
         }
@@ -506,6 +622,7 @@ impl Sink for HttpServerSink {
     ) -> Result<SinkReply> {
         // clean out closed channels
         self.inflight.retain(|_key, sender| !sender.is_closed());
+        self.sse_streams.retain(|_key, sender| !sender.is_closed());
         Ok(SinkReply::NONE)
     }
 
@@ -695,11 +812,12 @@ impl SinkResponse {
 struct HttpServerState {
     tx: Sender<RawRequestData>,
     ctx: SourceContext,
+    sse: bool,
 }
 
 impl HttpServerState {
-    fn new(tx: Sender<RawRequestData>, ctx: SourceContext) -> Self {
-        Self { tx, ctx }
+    fn new(tx: Sender<RawRequestData>, ctx: SourceContext, sse: bool) -> Self {
+        Self { tx, ctx, sse }
     }
 }
 
@@ -710,6 +828,8 @@ struct RawRequestData {
     request_meta: Value<'static>,
     content_type: Option<String>,
     response_channel: Sender<Response>,
+    /// body chunks of an already sent SSE response
+    sse_stream: Option<Sender<Vec<u8>>>,
 }
 
 async fn handle_request(mut req: tide::Request<HttpServerState>) -> tide::Result<tide::Response> {
@@ -726,12 +846,40 @@ async fn handle_request(mut req: tide::Request<HttpServerState>) -> tide::Result
     }
 }
 async fn _handle_request(req: &mut tide::Request<HttpServerState>) -> tide::Result<tide::Response> {
-    let request_meta = extract_request_meta(req.as_ref());
+    let mut request_meta = extract_request_meta(req.as_ref());
+    if let Some(ClientCert(client_cert)) = req.ext::<ClientCert>() {
+        request_meta.try_insert("client_cert", client_cert.clone());
+    }
     let content_type = req.content_type().map(|mime| mime.essence().to_string());
     let data = req.body_bytes().await?;
+    let accepts_sse = req.header(headers::ACCEPT).map_or(false, |accept| {
+        accept.iter().any(|v| v.as_str().contains(sse::MIME_TYPE))
+    });
 
     // Dispatch
     let (response_tx, response_rx) = bounded(1);
+    if req.state().sse && accepts_sse {
+        // the stream is closed if the client does not keep up, instead of buffering without bound
+        let (chunk_tx, chunk_rx) = bounded(crate::QSIZE.load(Ordering::Relaxed));
+        req.state()
+            .tx
+            .send(RawRequestData {
+                data,
+                request_meta,
+                content_type,
+                response_channel: response_tx,
+                sse_stream: Some(chunk_tx),
+            })
+            .await?;
+        let mut res = Response::new(StatusCode::Ok);
+        res.set_content_type(sse::MIME_TYPE);
+        res.insert_header(headers::CACHE_CONTROL, "no-cache");
+        res.set_body(tide::Body::from_reader(
+            StreamingBodyReader::new(chunk_rx),
+            None,
+        ));
+        return Ok(res);
+    }
     req.state()
         .tx
         .send(RawRequestData {
@@ -739,8 +887,91 @@ async fn _handle_request(req: &mut tide::Request<HttpServerState>) -> tide::Resu
             request_meta,
             content_type,
             response_channel: response_tx,
+            sse_stream: None,
         })
         .await?;
 
     Ok(response_rx.recv().await?)
 }
+
+/// Metadata of the certificate a client presented, attached to each request on its connection
+#[derive(Clone)]
+struct ClientCert(Value<'static>);
+
+/// Serves all requests on a single HTTPS connection.
+///
+/// The TLS config is fetched per connection so rotated certificates are picked up.
+pub(crate) async fn serve_tls<State: Clone + Send + Sync + 'static>(
+    endpoint: tide::Server<State>,
+    tls_server_config: ReloadingServerConfig,
+    stream: TcpStream,
+    ctx: impl std::fmt::Display + Send,
+) {
+    let local_addr = stream.local_addr().ok();
+    let peer_addr = stream.peer_addr().ok();
+    let tls_stream = match tls_server_config.acceptor().accept(stream).await {
+        Ok(tls_stream) => tls_stream,
+        Err(e) => {
+            warn!("{ctx} TLS handshake with {peer_addr:?} failed: {e}");
+            return;
+        }
+    };
+    let client_cert = client_cert_meta(tls_stream.get_ref().1).map(ClientCert);
+    let connection = TlsConnection(Arc::new(Mutex::new(tls_stream)));
+    let served = async_h1::accept(connection, |mut req| async {
+        req.set_local_addr(local_addr);
+        req.set_peer_addr(peer_addr);
+        // setting `https` on an `http` url never fails
+        let _ = req.url_mut().set_scheme("https");
+        if let Some(client_cert) = client_cert.clone() {
+            req.ext_mut().insert(client_cert);
+        }
+        endpoint.respond(req).await
+    })
+    .await;
+    if let Err(e) = served {
+        debug!("{ctx} Error serving HTTPS connection from {peer_addr:?}: {e}");
+    }
+}
+
+/// A TLS stream that can be shared between reading requests and writing responses, as `async_h1` needs it
+#[derive(Clone)]
+struct TlsConnection(Arc<Mutex<async_rustls::server::TlsStream<TcpStream>>>);
+
+impl TlsConnection {
+    fn poll_with<T>(
+        &self,
+        f: impl FnOnce(Pin<&mut async_rustls::server::TlsStream<TcpStream>>) -> Poll<T>,
+    ) -> Poll<T> {
+        let mut stream = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        f(Pin::new(&mut *stream))
+    }
+}
+
+impl AsyncRead for TlsConnection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_with(|stream| stream.poll_read(cx, buf))
+    }
+}
+
+impl AsyncWrite for TlsConnection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.poll_with(|stream| stream.poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_with(|stream| stream.poll_flush(cx))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_with(|stream| stream.poll_close(cx))
+    }
+}
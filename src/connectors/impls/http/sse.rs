@@ -0,0 +1,228 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Server-Sent Events framing as specified in
+//! <https://html.spec.whatwg.org/multipage/server-sent-events.html#parsing-an-event-stream>
+
+use std::time::Duration;
+
+pub(crate) const MIME_TYPE: &str = "text/event-stream";
+const DEFAULT_EVENT: &str = "message";
+
+/// A dispatched event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SseEvent {
+    /// the event type, `message` if none was given
+    pub(crate) event: String,
+    pub(crate) data: Vec<u8>,
+    /// the last event id seen on the stream, sent as `Last-Event-ID` when reconnecting
+    pub(crate) id: Option<String>,
+}
+
+/// Something of interest in an event stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Item {
+    Event(SseEvent),
+    /// the server asks for a different reconnection delay
+    Retry(Duration),
+}
+
+/// Incremental event stream parser, bytes can be fed in arbitrary chunks
+#[derive(Debug, Default)]
+pub(crate) struct Parser {
+    line: Vec<u8>,
+    /// the last chunk ended with `\r`, a following `\n` is part of the same line break
+    after_cr: bool,
+    event: Option<String>,
+    data: Vec<u8>,
+    has_data: bool,
+    last_event_id: Option<String>,
+}
+
+impl Parser {
+    /// A parser continuing a stream that last saw `last_event_id`
+    pub(crate) fn new(last_event_id: Option<String>) -> Self {
+        Self {
+            last_event_id,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn feed(&mut self, bytes: &[u8]) -> Vec<Item> {
+        let mut items = Vec::new();
+        for &b in bytes {
+            match b {
+                b'\n' if self.after_cr => self.after_cr = false,
+                b'\r' | b'\n' => {
+                    self.after_cr = b == b'\r';
+                    let line = std::mem::take(&mut self.line);
+                    if let Some(item) = self.process_line(&line) {
+                        items.push(item);
+                    }
+                }
+                _ => {
+                    self.after_cr = false;
+                    self.line.push(b);
+                }
+            }
+        }
+        items
+    }
+
+    fn process_line(&mut self, line: &[u8]) -> Option<Item> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(b":") {
+            // comment
+            return None;
+        }
+        let (field, value) = match line.iter().position(|b| *b == b':') {
+            Some(idx) => {
+                let value = &line[idx + 1..];
+                (&line[..idx], value.strip_prefix(b" ").unwrap_or(value))
+            }
+            None => (line, &[][..]),
+        };
+        match field {
+            b"event" => self.event = Some(String::from_utf8_lossy(value).to_string()),
+            b"data" => {
+                if self.has_data {
+                    self.data.push(b'\n');
+                }
+                self.data.extend_from_slice(value);
+                self.has_data = true;
+            }
+            b"id" if !value.contains(&0) => {
+                self.last_event_id = Some(String::from_utf8_lossy(value).to_string());
+            }
+            b"retry" => {
+                return std::str::from_utf8(value)
+                    .ok()
+                    .filter(|v| !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit()))
+                    .and_then(|v| v.parse().ok())
+                    .map(|ms| Item::Retry(Duration::from_millis(ms)));
+            }
+            // unknown fields are ignored
+            _ => (),
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Item> {
+        let event = self.event.take();
+        if !self.has_data {
+            return None;
+        }
+        self.has_data = false;
+        Some(Item::Event(SseEvent {
+            event: event.unwrap_or_else(|| DEFAULT_EVENT.to_string()),
+            data: std::mem::take(&mut self.data),
+            id: self.last_event_id.clone(),
+        }))
+    }
+}
+
+/// Frames `data` as a single event, every line of it is sent as a `data` field
+pub(crate) fn encode(
+    event: Option<&str>,
+    id: Option<&str>,
+    retry: Option<u64>,
+    data: &[u8],
+) -> Vec<u8> {
+    let mut res = Vec::with_capacity(data.len() + 16);
+    if let Some(event) = event {
+        res.extend_from_slice(format!("event: {event}\n").as_bytes());
+    }
+    if let Some(id) = id {
+        res.extend_from_slice(format!("id: {id}\n").as_bytes());
+    }
+    if let Some(retry) = retry {
+        res.extend_from_slice(format!("retry: {retry}\n").as_bytes());
+    }
+    // a trailing line break would be an empty `data` field
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+    for line in data.split(|b| *b == b'\n') {
+        res.extend_from_slice(b"data: ");
+        res.extend_from_slice(line.strip_suffix(b"\r").unwrap_or(line));
+        res.push(b'\n');
+    }
+    res.push(b'\n');
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event: &str, data: &str, id: Option<&str>) -> Item {
+        Item::Event(SseEvent {
+            event: event.to_string(),
+            data: data.as_bytes().to_vec(),
+            id: id.map(ToString::to_string),
+        })
+    }
+
+    #[test]
+    fn parse() {
+        let mut parser = Parser::default();
+        let items = parser.feed(
+            b": comment\n\ndata: first\ndata:second\n\nevent: update\nid: 1\ndata: {}\n\nretry: 100\nretry: nope\ndata\n\n",
+        );
+        assert_eq!(
+            vec![
+                event("message", "first\nsecond", None),
+                event("update", "{}", Some("1")),
+                Item::Retry(Duration::from_millis(100)),
+                event("message", "", Some("1")),
+            ],
+            items
+        );
+    }
+
+    #[test]
+    fn parse_chunked() {
+        let mut parser = Parser::new(Some("0".to_string()));
+        assert!(parser.feed(b"data: sn").is_empty());
+        assert!(parser.feed(b"ot\r").is_empty());
+        assert_eq!(
+            vec![event("message", "snot", Some("0"))],
+            parser.feed(b"\n\r\nid: 2\r")
+        );
+        // the id applies to the next event
+        assert_eq!(
+            vec![event("message", "badger", Some("2"))],
+            parser.feed(b"data: badger\r\r")
+        );
+        // events without data are not dispatched
+        assert!(parser.feed(b"event: empty\n\n").is_empty());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let encoded = encode(Some("update"), Some("42"), Some(1000), b"snot\nbadger\n");
+        assert_eq!(
+            "event: update\nid: 42\nretry: 1000\ndata: snot\ndata: badger\n\n",
+            String::from_utf8_lossy(&encoded)
+        );
+        let mut parser = Parser::default();
+        assert_eq!(
+            vec![
+                Item::Retry(Duration::from_millis(1000)),
+                event("update", "snot\nbadger", Some("42"))
+            ],
+            parser.feed(&encoded)
+        );
+    }
+}
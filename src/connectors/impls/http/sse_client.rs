@@ -0,0 +1,322 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Server-Sent Events client
+//!
+//! Subscribes to an event stream and emits the `data` of each event, decoded with the configured codec.
+//! The event type and id are available as `$sse_client.event` and `$sse_client.id`.
+//!
+//! When the server ends the stream the connector reconnects after the reconnection delay,
+//! which starts as `retry_ms` and can be changed by the server via `retry:` fields.
+//! The id of the last received event is sent as `Last-Event-ID` so the server can resume the stream.
+//! Failing to reach the server is handled by the configured reconnect strategy.
+//! A `204 No Content` response tells the connector to stop.
+
+use super::auth::Auth;
+use super::sse::{self, Item, Parser, SseEvent};
+use super::utils::Header;
+use crate::connectors::utils::tls::{tls_client_config, TLSClientConfig};
+use crate::{connectors::prelude::*, errors::err_connector_def};
+use async_std::{
+    channel::{bounded, Receiver, Sender},
+    io::ReadExt,
+    task::{self, JoinHandle},
+};
+use either::Either;
+use halfbrown::HashMap;
+use http_client::{h1::H1Client, HttpClient};
+use http_types::{headers, Body, Method, Request, StatusCode};
+use std::{sync::Arc, time::Duration};
+
+const CONNECTOR_TYPE: &str = "sse_client";
+const DEFAULT_CODEC: &str = "json";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// URL of the event stream
+    url: Url,
+    /// Authorization method
+    #[serde(default = "Default::default")]
+    auth: Auth,
+    /// additional HTTP headers
+    #[serde(default = "Default::default")]
+    headers: HashMap<String, Header>,
+    /// optional tls client config
+    #[serde(with = "either::serde_untagged_optional", default = "Default::default")]
+    tls: Option<Either<TLSClientConfig, bool>>,
+    /// reconnection delay until the server sends a `retry:` field
+    #[serde(default = "default_retry_ms")]
+    retry_ms: u64,
+    /// `Last-Event-ID` to send with the first request
+    #[serde(default = "Default::default")]
+    last_event_id: Option<String>,
+}
+
+impl ConfigImpl for Config {}
+
+fn default_retry_ms() -> u64 {
+    3000
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Builder {}
+
+#[async_trait::async_trait]
+impl ConnectorBuilder for Builder {
+    fn connector_type(&self) -> ConnectorType {
+        CONNECTOR_TYPE.into()
+    }
+
+    async fn build_cfg(
+        &self,
+        id: &Alias,
+        _: &ConnectorConfig,
+        config: &Value,
+        _kill_switch: &KillSwitch,
+    ) -> Result<Box<dyn Connector>> {
+        let config = Config::new(config)?;
+        let tls_client_config = match config.tls.as_ref() {
+            Some(Either::Right(true)) => {
+                Some(tls_client_config(&TLSClientConfig::default()).await?)
+            }
+            Some(Either::Left(tls_config)) => Some(tls_client_config(tls_config).await?),
+            Some(Either::Right(false)) | None => None,
+        };
+        if config.url.scheme() == "https" && tls_client_config.is_none() {
+            return Err(err_connector_def(
+                id,
+                "missing tls config with 'https' url. Set 'tls' to 'true' or provide a full tls config.",
+            ));
+        }
+        Ok(Box::new(SseClient {
+            config,
+            tls_client_config,
+        }))
+    }
+}
+
+struct SseClient {
+    config: Config,
+    tls_client_config: Option<rustls::ClientConfig>,
+}
+
+#[async_trait::async_trait]
+impl Connector for SseClient {
+    fn codec_requirements(&self) -> CodecReq {
+        CodecReq::Optional(DEFAULT_CODEC)
+    }
+
+    async fn create_source(
+        &mut self,
+        source_context: SourceContext,
+        builder: SourceManagerBuilder,
+    ) -> Result<Option<SourceAddr>> {
+        let (tx, rx) = bounded(QSIZE.load(Ordering::Relaxed));
+        let origin_uri = EventOriginUri {
+            scheme: "tremor-sse".to_string(),
+            host: self.config.url.host_or_local().to_string(),
+            port: self.config.url.port(),
+            path: self
+                .config
+                .url
+                .path_segments()
+                .map(|segments| segments.map(ToString::to_string).collect())
+                .unwrap_or_default(),
+        };
+        let source = SseSource {
+            retry: Duration::from_millis(self.config.retry_ms),
+            last_event_id: self.config.last_event_id.clone(),
+            config: self.config.clone(),
+            tls_client_config: self.tls_client_config.clone().map(Arc::new),
+            origin_uri,
+            tx,
+            rx,
+            reader: None,
+        };
+        builder.spawn(source, source_context).map(Some)
+    }
+}
+
+/// What the reader task passes on to the source
+enum Received {
+    Item(Item),
+    /// the server ended the stream
+    Closed,
+    /// the server does not want us to reconnect
+    Finished,
+}
+
+struct SseSource {
+    config: Config,
+    tls_client_config: Option<Arc<rustls::ClientConfig>>,
+    origin_uri: EventOriginUri,
+    /// reconnection delay
+    retry: Duration,
+    last_event_id: Option<String>,
+    tx: Sender<Received>,
+    rx: Receiver<Received>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl SseSource {
+    fn request(&self) -> Result<Request> {
+        let mut request = Request::new(Method::Get, self.config.url.url().clone());
+        for (name, values) in &self.config.headers {
+            match &values.0 {
+                Either::Left(values) => {
+                    for value in values {
+                        request.append_header(name.as_str(), value.as_str());
+                    }
+                }
+                Either::Right(value) => request.append_header(name.as_str(), value.as_str()),
+            }
+        }
+        if let Some(auth_header) = self.config.auth.as_header_value()? {
+            request.insert_header(headers::AUTHORIZATION, auth_header);
+        }
+        request.insert_header(headers::ACCEPT, sse::MIME_TYPE);
+        request.insert_header(headers::CACHE_CONTROL, "no-cache");
+        if let Some(last_event_id) = &self.last_event_id {
+            request.insert_header("Last-Event-ID", last_event_id.as_str());
+        }
+        Ok(request)
+    }
+}
+
+/// reads the event stream until the server closes it or the connection breaks
+async fn read_events(mut body: Body, mut parser: Parser, tx: Sender<Received>, ctx: SourceContext) {
+    let mut buf = vec![0_u8; DEFAULT_BUF_SIZE];
+    loop {
+        match body.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(n) => {
+                for item in parser.feed(&buf[..n]) {
+                    if tx.send(Received::Item(item)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(e) => {
+                warn!("{ctx} Error reading event stream: {e}");
+                break;
+            }
+        }
+    }
+    log_error!(
+        tx.send(Received::Closed).await,
+        "{ctx} Error notifying the source about the closed event stream: {e}"
+    );
+}
+
+#[async_trait::async_trait]
+impl Source for SseSource {
+    async fn connect(&mut self, ctx: &SourceContext, _attempt: &Attempt) -> Result<bool> {
+        if let Some(reader) = self.reader.take() {
+            reader.cancel().await;
+        }
+        // no request timeout, the stream is expected to stay open
+        let client_config = http_client::Config::new()
+            .set_tcp_no_delay(true)
+            .set_timeout(None)
+            .set_tls_config(self.tls_client_config.clone());
+        let client = H1Client::try_from(client_config)
+            .map_err(|e| format!("Invalid HTTP Client config: {e}."))?;
+
+        let mut response = client.send(self.request()?).await?;
+        let status = response.status();
+        if status == StatusCode::NoContent {
+            info!("{ctx} Server asked to stop receiving events.");
+            self.tx.send(Received::Finished).await?;
+            return Ok(true);
+        } else if !status.is_success() {
+            return Err(format!("Unexpected status {status} for event stream request").into());
+        }
+        match response.content_type() {
+            Some(mime) if mime.essence() == sse::MIME_TYPE => (),
+            other => {
+                return Err(format!(
+                    "Expected content type {}, got {}",
+                    sse::MIME_TYPE,
+                    other.map_or_else(|| "none".to_string(), |mime| mime.to_string())
+                )
+                .into());
+            }
+        }
+        let parser = Parser::new(self.last_event_id.clone());
+        self.reader = Some(task::spawn(read_events(
+            response.take_body(),
+            parser,
+            self.tx.clone(),
+            ctx.clone(),
+        )));
+        info!("{ctx} Receiving events from {}", self.config.url);
+        Ok(true)
+    }
+
+    async fn pull_data(&mut self, _pull_id: &mut u64, ctx: &SourceContext) -> Result<SourceReply> {
+        loop {
+            match self.rx.recv().await? {
+                Received::Item(Item::Event(SseEvent { event, data, id })) => {
+                    self.last_event_id = id.clone();
+                    return Ok(SourceReply::Data {
+                        origin_uri: self.origin_uri.clone(),
+                        data,
+                        meta: Some(ctx.meta(literal!({
+                            "event": event,
+                            "id": id,
+                        }))),
+                        stream: None,
+                        port: None,
+                        codec_overwrite: None,
+                    });
+                }
+                Received::Item(Item::Retry(retry)) => self.retry = retry,
+                Received::Closed => {
+                    info!(
+                        "{ctx} Event stream closed, reconnecting in {}ms",
+                        self.retry.as_millis()
+                    );
+                    // outside of `pull_data`, so it is not lost if pulling is cancelled
+                    let retry = self.retry;
+                    let notifier = ctx.notifier().clone();
+                    let ctx = ctx.clone();
+                    task::spawn(async move {
+                        task::sleep(retry).await;
+                        log_error!(
+                            notifier.connection_lost().await,
+                            "{ctx} Error notifying about the closed event stream: {e}"
+                        );
+                    });
+                }
+                Received::Finished => return Ok(SourceReply::Finished),
+            }
+        }
+    }
+
+    async fn on_stop(&mut self, _ctx: &SourceContext) -> Result<()> {
+        if let Some(reader) = self.reader.take() {
+            reader.cancel().await;
+        }
+        Ok(())
+    }
+
+    fn is_transactional(&self) -> bool {
+        false
+    }
+
+    fn asynchronous(&self) -> bool {
+        true
+    }
+}
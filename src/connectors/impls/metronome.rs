@@ -151,6 +151,8 @@ mod tests {
             postprocessors: None,
             reconnect: Reconnect::None,
             metrics_interval_s: Some(5),
+            batch: None,
+            dead_letter_data: true,
         };
         let kill_switch = KillSwitch::dummy();
         assert!(matches!(
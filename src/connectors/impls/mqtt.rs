@@ -0,0 +1,220 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! MQTT connector
+//!
+//! Subscribes to the configured topic filters as a source and publishes to `topic` as a sink,
+//! speaking either MQTT 3.1.1 or MQTT 5.
+//!
+//! Received messages carry their `topic`, `qos`, `retain` flag and, for MQTT 5, `user_properties`
+//! in the `$mqtt` metadata. The same metadata fields overwrite the configured defaults when publishing.
+//!
+//! With `qos` 1 or 2 the source is transactional: deliveries are only acknowledged to the broker
+//! once the event is acked. Failed events are not acknowledged and are redelivered by the broker
+//! on the next session, as `clean_session` defaults to `false`.
+//! The sink acks events once the broker acknowledged all messages for it, and fails events whose
+//! messages were not acknowledged when the connection is lost.
+//!
+//! Source and sink use separate connections, the sink with a client id suffixed with `-pub`.
+
+mod client;
+mod sink;
+mod source;
+
+use crate::connectors::prelude::*;
+use sink::MqttSink;
+use source::MqttSource;
+
+const MQTT_META_KEY: &str = "mqtt";
+const URL_SCHEME: &str = "tremor-mqtt";
+
+pub(crate) struct MqttDefaults;
+impl Defaults for MqttDefaults {
+    const SCHEME: &'static str = "mqtt";
+    const HOST: &'static str = "localhost";
+    const PORT: u16 = 1883;
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub(crate) enum Version {
+    #[serde(rename = "3.1.1")]
+    V3,
+    #[serde(rename = "5")]
+    V5,
+}
+
+impl Default for Version {
+    fn default() -> Self {
+        Self::V3
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// broker url, `mqtt://host:port` or `mqtts://host:port` for TLS
+    url: Url<MqttDefaults>,
+    /// protocol version, `3.1.1` or `5`
+    #[serde(default)]
+    version: Version,
+    /// client id, defaults to `tremor-<hostname>-<alias>`
+    #[serde(default = "Default::default")]
+    client_id: Option<String>,
+    #[serde(default = "Default::default")]
+    username: Option<String>,
+    #[serde(default = "Default::default")]
+    password: Option<String>,
+    /// topic filters the source subscribes to
+    #[serde(default = "Default::default")]
+    subscriptions: Vec<String>,
+    /// topic to publish to, overwritten by `$mqtt.topic`
+    #[serde(default = "Default::default")]
+    topic: Option<String>,
+    /// QoS for subscriptions and published messages, overwritten by `$mqtt.qos`
+    #[serde(default = "default_qos")]
+    qos: u8,
+    /// retain flag for published messages, overwritten by `$mqtt.retain`
+    #[serde(default = "default_false")]
+    retain: bool,
+    #[serde(default = "default_keep_alive_s")]
+    keep_alive_s: u64,
+    /// start a new session on connect, discarding subscriptions and undelivered messages
+    #[serde(default = "default_false")]
+    clean_session: bool,
+}
+
+impl ConfigImpl for Config {}
+
+fn default_qos() -> u8 {
+    1
+}
+
+fn default_keep_alive_s() -> u64 {
+    30
+}
+
+impl Config {
+    fn is_tls(&self) -> bool {
+        matches!(self.url.scheme(), "mqtts" | "ssl")
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Builder {}
+
+#[async_trait::async_trait]
+impl ConnectorBuilder for Builder {
+    fn connector_type(&self) -> ConnectorType {
+        "mqtt".into()
+    }
+
+    async fn build_cfg(
+        &self,
+        alias: &Alias,
+        _: &ConnectorConfig,
+        raw: &Value,
+        _kill_switch: &KillSwitch,
+    ) -> Result<Box<dyn Connector>> {
+        let config = Config::new(raw)?;
+        if config.qos > 2 {
+            return Err(err_connector_def(alias, "`qos` must be 0, 1 or 2."));
+        }
+        let client_id = config
+            .client_id
+            .clone()
+            .unwrap_or_else(|| format!("tremor-{}-{alias}", hostname()));
+        Ok(Box::new(Mqtt { config, client_id }))
+    }
+}
+
+struct Mqtt {
+    config: Config,
+    client_id: String,
+}
+
+#[async_trait::async_trait]
+impl Connector for Mqtt {
+    async fn create_source(
+        &mut self,
+        source_context: SourceContext,
+        builder: SourceManagerBuilder,
+    ) -> Result<Option<SourceAddr>> {
+        if self.config.subscriptions.is_empty() {
+            return Ok(None);
+        }
+        let source = MqttSource::new(self.config.clone(), self.client_id.clone());
+        builder.spawn(source, source_context).map(Some)
+    }
+
+    async fn create_sink(
+        &mut self,
+        sink_context: SinkContext,
+        builder: SinkManagerBuilder,
+    ) -> Result<Option<SinkAddr>> {
+        let sink = MqttSink::new(
+            self.config.clone(),
+            format!("{}-pub", self.client_id),
+            builder.reply_tx(),
+        );
+        builder.spawn(sink, sink_context).map(Some)
+    }
+
+    fn codec_requirements(&self) -> CodecReq {
+        CodecReq::Required
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Reconnect;
+
+    #[async_std::test]
+    async fn invalid_qos() -> Result<()> {
+        let raw = literal!({
+            "url": "mqtt://localhost",
+            "qos": 3
+        });
+        let alias = Alias::new("flow", "mqtt");
+        let config = ConnectorConfig {
+            connector_type: "mqtt".into(),
+            codec: None,
+            config: Some(raw.clone()),
+            preprocessors: None,
+            postprocessors: None,
+            reconnect: Reconnect::None,
+            metrics_interval_s: None,
+            batch: None,
+            dead_letter_data: true,
+        };
+        let kill_switch = KillSwitch::dummy();
+        assert!(Builder::default()
+            .build_cfg(&alias, &config, &raw, &kill_switch)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn config_defaults() -> Result<()> {
+        let config = Config::new(&literal!({ "url": "mqtts://localhost" }))?;
+        assert_eq!(Version::V3, config.version);
+        assert_eq!(1, config.qos);
+        assert_eq!(8883, config.url.port().unwrap_or(8883));
+        assert!(config.is_tls());
+        assert!(!config.clean_session);
+        assert!(Config::new(&literal!({ "url": "mqtt://localhost", "version": "4" })).is_err());
+        Ok(())
+    }
+}
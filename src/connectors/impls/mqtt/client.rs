@@ -0,0 +1,226 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Thin layer over the MQTT 3.1.1 and MQTT 5 clients of `rumqttc`,
+//! so source and sink don't need to care about the protocol version.
+
+use super::{Config, Version};
+use crate::connectors::prelude::*;
+use rumqttc::{v5, Outgoing, Transport};
+use std::time::Duration;
+
+/// A message received from the broker
+pub(super) struct Delivery {
+    pub(super) topic: String,
+    pub(super) qos: u8,
+    pub(super) retain: bool,
+    pub(super) payload: Vec<u8>,
+    pub(super) user_properties: Vec<(String, String)>,
+    /// kept for acknowledging the delivery
+    publish: Publish,
+}
+
+enum Publish {
+    V3(rumqttc::Publish),
+    V5(v5::mqttbytes::v5::Publish),
+}
+
+/// What we care about from the event loop
+pub(super) enum Notification {
+    ConnAck,
+    Publish(Delivery),
+    /// a publish was sent with the given packet id, `0` for QoS 0
+    Outgoing(u16),
+    /// the broker acknowledged the publish with the given packet id
+    Acked(u16),
+    Other,
+}
+
+pub(super) enum Client {
+    V3(rumqttc::AsyncClient),
+    V5(v5::AsyncClient),
+}
+
+pub(super) enum EventLoop {
+    V3(rumqttc::EventLoop),
+    V5(v5::EventLoop),
+}
+
+fn qos_v3(qos: u8) -> rumqttc::QoS {
+    match qos {
+        0 => rumqttc::QoS::AtMostOnce,
+        1 => rumqttc::QoS::AtLeastOnce,
+        _ => rumqttc::QoS::ExactlyOnce,
+    }
+}
+
+fn qos_v5(qos: u8) -> v5::mqttbytes::QoS {
+    match qos {
+        0 => v5::mqttbytes::QoS::AtMostOnce,
+        1 => v5::mqttbytes::QoS::AtLeastOnce,
+        _ => v5::mqttbytes::QoS::ExactlyOnce,
+    }
+}
+
+/// Creates a client and its event loop, no connection is made until the event loop is polled.
+pub(super) fn new(config: &Config, client_id: &str) -> (Client, EventLoop) {
+    let host = config.url.host_or_local().to_string();
+    let port = config.url.port_or_dflt();
+    let keep_alive = Duration::from_secs(config.keep_alive_s);
+    let cap = QSIZE.load(Ordering::Relaxed);
+    match config.version {
+        Version::V3 => {
+            let mut options = rumqttc::MqttOptions::new(client_id, host, port);
+            options
+                .set_keep_alive(keep_alive)
+                .set_clean_session(config.clean_session)
+                .set_manual_acks(true);
+            if let Some(username) = &config.username {
+                options.set_credentials(username, config.password.as_deref().unwrap_or_default());
+            }
+            if config.is_tls() {
+                options.set_transport(Transport::tls_with_default_config());
+            }
+            let (client, eventloop) = rumqttc::AsyncClient::new(options, cap);
+            (Client::V3(client), EventLoop::V3(eventloop))
+        }
+        Version::V5 => {
+            let mut options = v5::MqttOptions::new(client_id, host, port);
+            options
+                .set_keep_alive(keep_alive)
+                .set_clean_start(config.clean_session)
+                .set_manual_acks(true);
+            if let Some(username) = &config.username {
+                options.set_credentials(username, config.password.as_deref().unwrap_or_default());
+            }
+            if config.is_tls() {
+                options.set_transport(Transport::tls_with_default_config());
+            }
+            let (client, eventloop) = v5::AsyncClient::new(options, cap);
+            (Client::V5(client), EventLoop::V5(eventloop))
+        }
+    }
+}
+
+impl Client {
+    pub(super) async fn subscribe(&self, filter: &str, qos: u8) -> Result<()> {
+        match self {
+            Self::V3(client) => client.subscribe(filter, qos_v3(qos)).await?,
+            Self::V5(client) => client.subscribe(filter, qos_v5(qos)).await?,
+        }
+        Ok(())
+    }
+
+    pub(super) async fn publish(
+        &self,
+        topic: &str,
+        qos: u8,
+        retain: bool,
+        payload: Vec<u8>,
+        user_properties: Vec<(String, String)>,
+    ) -> Result<()> {
+        match self {
+            Self::V3(client) => client.publish(topic, qos_v3(qos), retain, payload).await?,
+            Self::V5(client) if user_properties.is_empty() => {
+                client.publish(topic, qos_v5(qos), retain, payload).await?;
+            }
+            Self::V5(client) => {
+                let properties = v5::mqttbytes::v5::PublishProperties {
+                    user_properties,
+                    ..v5::mqttbytes::v5::PublishProperties::default()
+                };
+                client
+                    .publish_with_properties(topic, qos_v5(qos), retain, payload, properties)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// acknowledges a QoS 1 or 2 delivery to the broker
+    pub(super) async fn ack(&self, delivery: &Delivery) -> Result<()> {
+        match (self, &delivery.publish) {
+            (Self::V3(client), Publish::V3(publish)) => client.ack(publish).await?,
+            (Self::V5(client), Publish::V5(publish)) => client.ack(publish).await?,
+            // the protocol version is fixed per connector
+            _ => (),
+        }
+        Ok(())
+    }
+
+    pub(super) async fn disconnect(&self) -> Result<()> {
+        match self {
+            Self::V3(client) => client.disconnect().await?,
+            Self::V5(client) => client.disconnect().await?,
+        }
+        Ok(())
+    }
+}
+
+impl EventLoop {
+    /// Drives the connection, needs to be called continuously for any progress to be made.
+    pub(super) async fn poll(&mut self) -> Result<Notification> {
+        Ok(match self {
+            Self::V3(eventloop) => {
+                use rumqttc::{Event, Packet};
+                match eventloop.poll().await? {
+                    Event::Incoming(Packet::ConnAck(_)) => Notification::ConnAck,
+                    Event::Incoming(Packet::Publish(publish)) => Notification::Publish(Delivery {
+                        topic: publish.topic.clone(),
+                        qos: publish.qos as u8,
+                        retain: publish.retain,
+                        payload: publish.payload.to_vec(),
+                        user_properties: Vec::new(),
+                        publish: Publish::V3(publish),
+                    }),
+                    Event::Incoming(Packet::PubAck(ack)) => Notification::Acked(ack.pkid),
+                    Event::Incoming(Packet::PubComp(comp)) => Notification::Acked(comp.pkid),
+                    Event::Outgoing(Outgoing::Publish(pkid)) => Notification::Outgoing(pkid),
+                    _ => Notification::Other,
+                }
+            }
+            Self::V5(eventloop) => {
+                use v5::{mqttbytes::v5::Packet, Event};
+                match eventloop.poll().await? {
+                    Event::Incoming(Packet::ConnAck(_)) => Notification::ConnAck,
+                    Event::Incoming(Packet::Publish(publish)) => Notification::Publish(Delivery {
+                        topic: String::from_utf8_lossy(&publish.topic).to_string(),
+                        qos: publish.qos as u8,
+                        retain: publish.retain,
+                        payload: publish.payload.to_vec(),
+                        user_properties: publish
+                            .properties
+                            .as_ref()
+                            .map(|p| p.user_properties.clone())
+                            .unwrap_or_default(),
+                        publish: Publish::V5(publish),
+                    }),
+                    Event::Incoming(Packet::PubAck(ack)) => Notification::Acked(ack.pkid),
+                    Event::Incoming(Packet::PubComp(comp)) => Notification::Acked(comp.pkid),
+                    Event::Outgoing(Outgoing::Publish(pkid)) => Notification::Outgoing(pkid),
+                    _ => Notification::Other,
+                }
+            }
+        })
+    }
+
+    /// Polls until the broker accepted the connection
+    pub(super) async fn connect(&mut self) -> Result<()> {
+        loop {
+            if let Notification::ConnAck = self.poll().await? {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,239 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{
+    client::{self, Client, EventLoop, Notification},
+    Config, MQTT_META_KEY,
+};
+use crate::connectors::prelude::*;
+use async_std::{
+    channel::{unbounded, Receiver, Sender},
+    sync::{Arc, Mutex},
+    task::JoinHandle,
+};
+use std::collections::HashMap;
+use tremor_common::time::nanotime;
+
+/// A QoS 1 or 2 publish waiting to be acknowledged by the broker.
+///
+/// Only the last publish of an event carries its contraflow data,
+/// as the broker acknowledges publishes in order.
+struct Pending {
+    cf: Option<(ContraflowData, u64)>,
+}
+
+pub(super) struct MqttSink {
+    config: Config,
+    client_id: String,
+    client: Option<Client>,
+    pending_tx: Option<Sender<Pending>>,
+    pending_rx: Option<Receiver<Pending>>,
+    /// publishes sent on the current connection, by packet id
+    in_flight: Arc<Mutex<HashMap<u16, Pending>>>,
+    reply_tx: Sender<AsyncSinkReply>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl MqttSink {
+    pub(super) fn new(config: Config, client_id: String, reply_tx: Sender<AsyncSinkReply>) -> Self {
+        Self {
+            config,
+            client_id,
+            client: None,
+            pending_tx: None,
+            pending_rx: None,
+            in_flight: Arc::default(),
+            reply_tx,
+            task: None,
+        }
+    }
+
+    /// Fails the events of all publishes the broker did not acknowledge yet and stops
+    /// the delivery task, as the connection they were sent on is dropped.
+    async fn stop_delivery(&mut self) -> Result<()> {
+        self.pending_tx = None;
+        if let Some(pending_rx) = self.pending_rx.take() {
+            fail_unacked(&self.in_flight, &pending_rx, &self.reply_tx).await?;
+        }
+        if let Some(task) = self.task.take() {
+            task.cancel().await;
+        }
+        Ok(())
+    }
+}
+
+/// Fails the events of all publishes that were not acknowledged by the broker
+async fn fail_unacked(
+    in_flight: &Mutex<HashMap<u16, Pending>>,
+    pending_rx: &Receiver<Pending>,
+    reply_tx: &Sender<AsyncSinkReply>,
+) -> Result<()> {
+    pending_rx.close();
+    let sent: Vec<_> = in_flight.lock().await.drain().map(|(_, p)| p).collect();
+    let not_sent = std::iter::from_fn(|| pending_rx.try_recv().ok());
+    for pending in sent.into_iter().chain(not_sent) {
+        if let Some((cf, _)) = pending.cf {
+            reply_tx.send(AsyncSinkReply::Fail(cf)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Drives the connection and acks events once the broker acknowledged their publishes.
+///
+/// Publishes are sent in the order they are registered as pending, so the packet id of each
+/// outgoing QoS 1 or 2 publish is matched with the next pending entry.
+async fn deliver(
+    mut eventloop: EventLoop,
+    in_flight: Arc<Mutex<HashMap<u16, Pending>>>,
+    pending_rx: Receiver<Pending>,
+    reply_tx: Sender<AsyncSinkReply>,
+) -> Result<()> {
+    let res = loop {
+        match eventloop.poll().await {
+            Ok(Notification::Outgoing(pkid)) if pkid != 0 => {
+                if let Ok(pending) = pending_rx.try_recv() {
+                    in_flight.lock().await.insert(pkid, pending);
+                }
+            }
+            Ok(Notification::Acked(pkid)) => {
+                let acked = in_flight.lock().await.remove(&pkid);
+                if let Some(Pending {
+                    cf: Some((cf, start)),
+                }) = acked
+                {
+                    reply_tx
+                        .send(AsyncSinkReply::Ack(cf, nanotime() - start))
+                        .await?;
+                }
+            }
+            Ok(_) => (),
+            Err(e) => break Err(e),
+        };
+    };
+    // the connection is gone, everything not yet acknowledged failed
+    fail_unacked(&in_flight, &pending_rx, &reply_tx).await?;
+    res
+}
+
+#[async_trait::async_trait]
+impl Sink for MqttSink {
+    async fn connect(&mut self, ctx: &SinkContext, _attempt: &Attempt) -> Result<bool> {
+        self.stop_delivery().await?;
+        let (client, mut eventloop) = client::new(&self.config, &self.client_id);
+        eventloop.connect().await?;
+        info!("{ctx} Connected to {}", self.config.url);
+        let (pending_tx, pending_rx) = unbounded();
+        self.in_flight = Arc::default();
+        self.task = Some(spawn_task(
+            ctx.clone(),
+            deliver(
+                eventloop,
+                self.in_flight.clone(),
+                pending_rx.clone(),
+                self.reply_tx.clone(),
+            ),
+        ));
+        self.client = Some(client);
+        self.pending_tx = Some(pending_tx);
+        self.pending_rx = Some(pending_rx);
+        Ok(true)
+    }
+
+    async fn on_event(
+        &mut self,
+        _input: &str,
+        event: Event,
+        ctx: &SinkContext,
+        serializer: &mut EventSerializer,
+        start: u64,
+    ) -> Result<SinkReply> {
+        let (client, pending_tx) = if let (Some(client), Some(pending_tx)) =
+            (self.client.as_ref(), self.pending_tx.as_ref())
+        {
+            (client, pending_tx)
+        } else {
+            return Err(ErrorKind::ClientNotAvailable("MQTT", "not connected").into());
+        };
+        let ingest_ns = event.ingest_ns;
+        let mut publishes = Vec::with_capacity(event.len());
+        for (value, meta) in event.value_meta_iter() {
+            let mqtt_meta = meta.get(MQTT_META_KEY);
+            let topic = mqtt_meta
+                .get_str("topic")
+                .or(self.config.topic.as_deref())
+                .ok_or_else(|| {
+                    Error::from(format!("{ctx} No `topic` configured or in `$mqtt.topic`"))
+                })?;
+            let qos = mqtt_meta
+                .get_u8("qos")
+                .filter(|qos| *qos <= 2)
+                .unwrap_or(self.config.qos);
+            let retain = mqtt_meta.get_bool("retain").unwrap_or(self.config.retain);
+            let user_properties: Vec<(String, String)> = mqtt_meta
+                .get_object("user_properties")
+                .map(|props| {
+                    props
+                        .iter()
+                        .filter_map(|(k, v)| Some((k.to_string(), v.as_str()?.to_string())))
+                        .collect()
+                })
+                .unwrap_or_default();
+            for payload in serializer.serialize(value, ingest_ns)? {
+                publishes.push((
+                    topic.to_string(),
+                    qos,
+                    retain,
+                    payload,
+                    user_properties.clone(),
+                ));
+            }
+        }
+
+        let mut cf = event
+            .transactional
+            .then(|| (ContraflowData::from(&event), start));
+        let mut awaiting = publishes.iter().filter(|(_, qos, ..)| *qos > 0).count();
+        for (topic, qos, retain, payload, user_properties) in publishes {
+            if qos > 0 {
+                awaiting -= 1;
+                let cf = if awaiting == 0 { cf.take() } else { None };
+                // register before publishing, so the delivery task finds it
+                pending_tx.send(Pending { cf }).await?;
+            }
+            client
+                .publish(&topic, qos, retain, payload, user_properties)
+                .await?;
+        }
+        // transactional events with QoS 0 publishes only are done once they are sent
+        Ok(if cf.is_some() {
+            SinkReply::ACK
+        } else {
+            SinkReply::NONE
+        })
+    }
+
+    async fn on_stop(&mut self, ctx: &SinkContext) -> Result<()> {
+        if let Some(client) = self.client.take() {
+            if let Err(e) = client.disconnect().await {
+                warn!("{ctx} Error disconnecting: {e}");
+            }
+        }
+        self.stop_delivery().await
+    }
+
+    fn auto_ack(&self) -> bool {
+        false
+    }
+}
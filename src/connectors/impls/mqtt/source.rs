@@ -0,0 +1,166 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{
+    client::{self, Client, Delivery, EventLoop, Notification},
+    Config, MQTT_META_KEY, URL_SCHEME,
+};
+use crate::connectors::prelude::*;
+use async_std::{
+    channel::{bounded, Receiver, Sender},
+    task::JoinHandle,
+};
+use std::collections::HashMap;
+
+pub(super) struct MqttSource {
+    config: Config,
+    client_id: String,
+    client: Option<Client>,
+    rx: Option<Receiver<Delivery>>,
+    task: Option<JoinHandle<()>>,
+    /// QoS 1 and 2 deliveries waiting for their event to be acked, by pull id
+    pending: HashMap<u64, Delivery>,
+    origin_uri: EventOriginUri,
+}
+
+impl MqttSource {
+    pub(super) fn new(config: Config, client_id: String) -> Self {
+        let origin_uri = EventOriginUri {
+            scheme: URL_SCHEME.to_string(),
+            host: config.url.host_or_local().to_string(),
+            port: Some(config.url.port_or_dflt()),
+            path: vec![],
+        };
+        Self {
+            config,
+            client_id,
+            client: None,
+            rx: None,
+            task: None,
+            pending: HashMap::new(),
+            origin_uri,
+        }
+    }
+}
+
+/// forwards all received messages to the source
+async fn receive(mut eventloop: EventLoop, tx: Sender<Delivery>) -> Result<()> {
+    loop {
+        if let Notification::Publish(delivery) = eventloop.poll().await? {
+            tx.send(delivery).await?;
+        }
+    }
+}
+
+fn mqtt_meta(delivery: &Delivery) -> Value<'static> {
+    let mut meta = Object::with_capacity(4);
+    meta.insert("topic".into(), Value::from(delivery.topic.clone()));
+    meta.insert("qos".into(), Value::from(delivery.qos));
+    meta.insert("retain".into(), Value::from(delivery.retain));
+    if !delivery.user_properties.is_empty() {
+        let user_properties: Object = delivery
+            .user_properties
+            .iter()
+            .map(|(k, v)| (k.clone().into(), Value::from(v.clone())))
+            .collect();
+        meta.insert("user_properties".into(), Value::from(user_properties));
+    }
+    let mut res = Object::with_capacity(1);
+    res.insert(MQTT_META_KEY.into(), Value::from(meta));
+    Value::from(res)
+}
+
+#[async_trait::async_trait]
+impl Source for MqttSource {
+    async fn connect(&mut self, ctx: &SourceContext, _attempt: &Attempt) -> Result<bool> {
+        if let Some(task) = self.task.take() {
+            task.cancel().await;
+        }
+        // deliveries of the previous session will be redelivered
+        self.pending.clear();
+
+        let (client, mut eventloop) = client::new(&self.config, &self.client_id);
+        eventloop.connect().await?;
+        for filter in &self.config.subscriptions {
+            client.subscribe(filter, self.config.qos).await?;
+        }
+        info!(
+            "{ctx} Connected to {}, subscribed to {:?}",
+            self.config.url, self.config.subscriptions
+        );
+        let (tx, rx) = bounded(QSIZE.load(Ordering::Relaxed));
+        self.task = Some(spawn_task(ctx.clone(), receive(eventloop, tx)));
+        self.client = Some(client);
+        self.rx = Some(rx);
+        Ok(true)
+    }
+
+    async fn pull_data(&mut self, pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
+        let rx = self
+            .rx
+            .as_ref()
+            .ok_or_else(|| Error::from(ErrorKind::ClientNotAvailable("MQTT", "not connected")))?;
+        let mut delivery = rx.recv().await?;
+        let meta = mqtt_meta(&delivery);
+        let data = std::mem::take(&mut delivery.payload);
+        if delivery.qos > 0 {
+            self.pending.insert(*pull_id, delivery);
+        }
+        Ok(SourceReply::Data {
+            origin_uri: self.origin_uri.clone(),
+            data,
+            meta: Some(meta),
+            stream: None,
+            port: None,
+            codec_overwrite: None,
+        })
+    }
+
+    async fn ack(&mut self, _stream_id: u64, pull_id: u64, _ctx: &SourceContext) -> Result<()> {
+        if let (Some(delivery), Some(client)) = (self.pending.remove(&pull_id), &self.client) {
+            client.ack(&delivery).await?;
+        }
+        Ok(())
+    }
+
+    async fn fail(&mut self, _stream_id: u64, pull_id: u64, ctx: &SourceContext) -> Result<()> {
+        if let Some(delivery) = self.pending.remove(&pull_id) {
+            debug!(
+                "{ctx} Not acknowledging failed delivery on {}, it will be redelivered.",
+                delivery.topic
+            );
+        }
+        Ok(())
+    }
+
+    async fn on_stop(&mut self, ctx: &SourceContext) -> Result<()> {
+        if let Some(client) = self.client.take() {
+            if let Err(e) = client.disconnect().await {
+                warn!("{ctx} Error disconnecting: {e}");
+            }
+        }
+        if let Some(task) = self.task.take() {
+            task.cancel().await;
+        }
+        Ok(())
+    }
+
+    fn is_transactional(&self) -> bool {
+        self.config.qos > 0
+    }
+
+    fn asynchronous(&self) -> bool {
+        true
+    }
+}
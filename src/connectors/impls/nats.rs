@@ -0,0 +1,277 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! NATS connector
+//!
+//! As a source it subscribes to `subscriptions`, optionally as part of `queue_group`, or consumes
+//! from a JetStream durable consumer configured in `jetstream`. JetStream messages are acked once
+//! their event is acked and nacked on fail, so they are redelivered.
+//!
+//! As a sink it publishes to `subject`, sends a request and emits the reply via the source if `request`
+//! is set, or publishes to JetStream, acking events once the stream stored them, if `jetstream_publish` is set.
+//!
+//! Received messages carry their `subject`, `reply` subject and `headers` in the `$nats` metadata.
+//! The same fields and `request` overwrite the configured defaults when publishing.
+//! Replying to a request is done by publishing to its `reply` subject.
+
+mod sink;
+mod source;
+
+use crate::connectors::prelude::*;
+use async_nats::{
+    header::{HeaderName, HeaderValue},
+    HeaderMap,
+};
+use async_std::channel::{bounded, Receiver, Sender};
+use sink::NatsSink;
+use source::NatsSource;
+use std::str::FromStr;
+
+const NATS_META_KEY: &str = "nats";
+
+pub(crate) struct NatsDefaults;
+impl Defaults for NatsDefaults {
+    const SCHEME: &'static str = "nats";
+    const HOST: &'static str = "localhost";
+    const PORT: u16 = 4222;
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct JetStream {
+    /// the stream to consume from
+    stream: String,
+    /// name of the durable consumer, it is created if it doesn't exist
+    consumer: String,
+    /// only consume messages with a matching subject
+    #[serde(default = "Default::default")]
+    filter_subject: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// server url, e.g. `nats://localhost:4222`
+    url: Url<NatsDefaults>,
+    #[serde(default = "Default::default")]
+    username: Option<String>,
+    #[serde(default = "Default::default")]
+    password: Option<String>,
+    #[serde(default = "Default::default")]
+    token: Option<String>,
+    /// subjects the source subscribes to
+    #[serde(default = "Default::default")]
+    subscriptions: Vec<String>,
+    /// subscribe as member of this queue group, so each message is only received by one member
+    #[serde(default = "Default::default")]
+    queue_group: Option<String>,
+    /// consume from a JetStream durable consumer
+    #[serde(default = "Default::default")]
+    jetstream: Option<JetStream>,
+    /// subject to publish to, overwritten by `$nats.subject`
+    #[serde(default = "Default::default")]
+    subject: Option<String>,
+    /// send requests and emit their replies, overwritten by `$nats.request`
+    #[serde(default = "Default::default")]
+    request: bool,
+    #[serde(default = "default_request_timeout_ms")]
+    request_timeout_ms: u64,
+    /// publish to JetStream and wait for the stream to acknowledge the message
+    #[serde(default = "Default::default")]
+    jetstream_publish: bool,
+}
+
+impl ConfigImpl for Config {}
+
+fn default_request_timeout_ms() -> u64 {
+    5000
+}
+
+/// A message for the source
+enum Received {
+    Core(async_nats::Message),
+    JetStream(async_nats::jetstream::Message),
+    /// a reply to a request sent by the sink, with the correlation of the request
+    Reply(async_nats::Message, Option<Value<'static>>),
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Builder {}
+
+#[async_trait::async_trait]
+impl ConnectorBuilder for Builder {
+    fn connector_type(&self) -> ConnectorType {
+        "nats".into()
+    }
+
+    async fn build_cfg(
+        &self,
+        alias: &Alias,
+        _: &ConnectorConfig,
+        raw: &Value,
+        _kill_switch: &KillSwitch,
+    ) -> Result<Box<dyn Connector>> {
+        let config = Config::new(raw)?;
+        if config.request && config.jetstream_publish {
+            return Err(err_connector_def(
+                alias,
+                "`request` and `jetstream_publish` can't be used together.",
+            ));
+        }
+        if config.username.is_some() != config.password.is_some() {
+            return Err(err_connector_def(
+                alias,
+                "`username` and `password` need to be configured together.",
+            ));
+        }
+        let (tx, rx) = bounded(QSIZE.load(Ordering::Relaxed));
+        Ok(Box::new(Nats { config, tx, rx }))
+    }
+}
+
+struct Nats {
+    config: Config,
+    /// shared by subscriptions and the sink, for replies to its requests
+    tx: Sender<Received>,
+    rx: Receiver<Received>,
+}
+
+#[async_trait::async_trait]
+impl Connector for Nats {
+    async fn create_source(
+        &mut self,
+        source_context: SourceContext,
+        builder: SourceManagerBuilder,
+    ) -> Result<Option<SourceAddr>> {
+        // always created, as `$nats.request` turns on requests per event and replies are received here
+        let source = NatsSource::new(self.config.clone(), self.tx.clone(), self.rx.clone());
+        builder.spawn(source, source_context).map(Some)
+    }
+
+    async fn create_sink(
+        &mut self,
+        sink_context: SinkContext,
+        builder: SinkManagerBuilder,
+    ) -> Result<Option<SinkAddr>> {
+        let sink = NatsSink::new(self.config.clone(), self.tx.clone(), builder.reply_tx());
+        builder.spawn(sink, sink_context).map(Some)
+    }
+
+    fn codec_requirements(&self) -> CodecReq {
+        CodecReq::Required
+    }
+}
+
+/// `async-nats` reports most errors boxed
+fn nats_error<E: std::fmt::Display>(e: E) -> Error {
+    Error::from(format!("NATS error: {e}"))
+}
+
+async fn connect(config: &Config) -> Result<async_nats::Client> {
+    let options = match (&config.username, &config.password, &config.token) {
+        (Some(username), Some(password), _) => {
+            async_nats::ConnectOptions::with_user_and_password(username.clone(), password.clone())
+        }
+        (_, _, Some(token)) => async_nats::ConnectOptions::with_token(token.clone()),
+        _ => async_nats::ConnectOptions::new(),
+    };
+    options
+        .connect(config.url.as_str())
+        .await
+        .map_err(nats_error)
+}
+
+fn headers_to_value(headers: &HeaderMap) -> Value<'static> {
+    let mut res = Object::with_capacity(headers.keys_len());
+    for name in headers.keys() {
+        let values: Vec<Value<'static>> = headers
+            .get_all(name)
+            .iter()
+            .map(|v| Value::from(String::from_utf8_lossy(v.as_bytes()).to_string()))
+            .collect();
+        res.insert(name.as_str().to_string().into(), Value::from(values));
+    }
+    Value::from(res)
+}
+
+/// converts a record of strings or arrays of strings into headers
+fn value_to_headers(value: &Value) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (k, v) in value.as_object().into_iter().flatten() {
+        let name = HeaderName::from_str(k).map_err(nats_error)?;
+        let values = v
+            .as_array()
+            .map_or_else(|| vec![v], |values| values.iter().collect());
+        for v in values {
+            if let Some(v) = v.as_str() {
+                headers.append(name.clone(), HeaderValue::from_str(v).map_err(nats_error)?);
+            }
+        }
+    }
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Reconnect;
+
+    #[test]
+    fn headers_roundtrip() -> Result<()> {
+        let value = literal!({
+            "snot": ["badger"],
+            "multi": ["1", "2"]
+        });
+        assert_eq!(value, headers_to_value(&value_to_headers(&value)?));
+        assert_eq!(
+            literal!({"single": ["value"]}),
+            headers_to_value(&value_to_headers(&literal!({"single": "value"}))?)
+        );
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn invalid_config() -> Result<()> {
+        let alias = Alias::new("flow", "nats");
+        let kill_switch = KillSwitch::dummy();
+        for raw in [
+            literal!({
+                "url": "nats://localhost",
+                "request": true,
+                "jetstream_publish": true
+            }),
+            literal!({
+                "url": "nats://localhost",
+                "username": "snot"
+            }),
+        ] {
+            let config = ConnectorConfig {
+                connector_type: "nats".into(),
+                codec: None,
+                config: Some(raw.clone()),
+                preprocessors: None,
+                postprocessors: None,
+                reconnect: Reconnect::None,
+                metrics_interval_s: None,
+                batch: None,
+                dead_letter_data: true,
+            };
+            assert!(Builder::default()
+                .build_cfg(&alias, &config, &raw, &kill_switch)
+                .await
+                .is_err());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{nats_error, value_to_headers, Config, Received, NATS_META_KEY};
+use crate::connectors::prelude::*;
+use async_nats::{jetstream, Client, HeaderMap};
+use async_std::{channel::Sender, future::timeout, task};
+use bytes::Bytes;
+use std::time::Duration;
+use tremor_common::time::nanotime;
+
+/// A message to publish
+struct Outgoing {
+    subject: String,
+    reply: Option<String>,
+    headers: HeaderMap,
+    payload: Bytes,
+    request: bool,
+    correlation: Option<Value<'static>>,
+}
+
+pub(super) struct NatsSink {
+    config: Config,
+    client: Option<Client>,
+    jetstream: Option<jetstream::Context>,
+    source_tx: Sender<Received>,
+    reply_tx: Sender<AsyncSinkReply>,
+}
+
+impl NatsSink {
+    pub(super) fn new(
+        config: Config,
+        source_tx: Sender<Received>,
+        reply_tx: Sender<AsyncSinkReply>,
+    ) -> Self {
+        Self {
+            config,
+            client: None,
+            jetstream: None,
+            source_tx,
+            reply_tx,
+        }
+    }
+}
+
+async fn publish(client: &Client, msg: Outgoing) -> Result<()> {
+    match (msg.reply, msg.headers.is_empty()) {
+        (Some(reply), true) => {
+            client
+                .publish_with_reply(msg.subject, reply, msg.payload)
+                .await
+        }
+        (Some(reply), false) => {
+            client
+                .publish_with_reply_and_headers(msg.subject, reply, msg.headers, msg.payload)
+                .await
+        }
+        (None, true) => client.publish(msg.subject, msg.payload).await,
+        (None, false) => {
+            client
+                .publish_with_headers(msg.subject, msg.headers, msg.payload)
+                .await
+        }
+    }
+    .map_err(nats_error)
+}
+
+/// Sends requests and JetStream publishes, acking the event once all of them succeeded.
+///
+/// Replies to requests are emitted via the source.
+async fn deliver(
+    client: Client,
+    jetstream: Option<jetstream::Context>,
+    messages: Vec<Outgoing>,
+    request_timeout: Duration,
+    source_tx: Sender<Received>,
+) -> Result<()> {
+    for msg in messages {
+        if let Some(jetstream) = &jetstream {
+            jetstream
+                .publish_with_headers(msg.subject, msg.headers, msg.payload)
+                .await
+                .map_err(nats_error)?;
+        } else if msg.request {
+            let reply = timeout(
+                request_timeout,
+                client.request_with_headers(msg.subject, msg.headers, msg.payload),
+            )
+            .await?
+            .map_err(nats_error)?;
+            source_tx
+                .send(Received::Reply(reply, msg.correlation))
+                .await?;
+        } else {
+            publish(&client, msg).await?;
+        }
+    }
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl Sink for NatsSink {
+    async fn connect(&mut self, ctx: &SinkContext, _attempt: &Attempt) -> Result<bool> {
+        let client = super::connect(&self.config).await?;
+        self.jetstream = self
+            .config
+            .jetstream_publish
+            .then(|| jetstream::new(client.clone()));
+        self.client = Some(client);
+        info!("{ctx} Connected to {}", self.config.url);
+        Ok(true)
+    }
+
+    async fn on_event(
+        &mut self,
+        _input: &str,
+        event: Event,
+        ctx: &SinkContext,
+        serializer: &mut EventSerializer,
+        start: u64,
+    ) -> Result<SinkReply> {
+        let client = self
+            .client
+            .as_ref()
+            .ok_or_else(|| Error::from(ErrorKind::ClientNotAvailable("NATS", "not connected")))?;
+        let ingest_ns = event.ingest_ns;
+        let mut messages = Vec::with_capacity(event.len());
+        for (value, meta) in event.value_meta_iter() {
+            let nats_meta = meta.get(NATS_META_KEY);
+            let subject = nats_meta
+                .get_str("subject")
+                .or(self.config.subject.as_deref())
+                .ok_or_else(|| {
+                    Error::from(format!(
+                        "{ctx} No `subject` configured or in `$nats.subject`"
+                    ))
+                })?;
+            let headers = nats_meta
+                .get("headers")
+                .map(value_to_headers)
+                .transpose()?
+                .unwrap_or_default();
+            let request = nats_meta.get_bool("request").unwrap_or(self.config.request);
+            let correlation = meta.get("correlation").map(Value::clone_static);
+            for payload in serializer.serialize(value, ingest_ns)? {
+                messages.push(Outgoing {
+                    subject: subject.to_string(),
+                    reply: nats_meta.get_str("reply").map(ToString::to_string),
+                    headers: headers.clone(),
+                    payload: payload.into(),
+                    request,
+                    correlation: correlation.clone(),
+                });
+            }
+        }
+
+        if self.jetstream.is_none() && messages.iter().all(|m| !m.request) {
+            for msg in messages {
+                publish(client, msg).await?;
+            }
+            return Ok(SinkReply::ACK);
+        }
+
+        // requests and JetStream publishes are waited for outside of the sink
+        let cf = event.transactional.then(|| ContraflowData::from(&event));
+        let fut = deliver(
+            client.clone(),
+            self.jetstream.clone(),
+            messages,
+            Duration::from_millis(self.config.request_timeout_ms),
+            self.source_tx.clone(),
+        );
+        let reply_tx = self.reply_tx.clone();
+        let ctx = ctx.clone();
+        task::spawn(async move {
+            let reply = match fut.await {
+                Ok(()) => cf.map(|cf| AsyncSinkReply::Ack(cf, nanotime() - start)),
+                Err(e) => {
+                    error!("{ctx} Error delivering NATS message: {e}");
+                    cf.map(AsyncSinkReply::Fail)
+                }
+            };
+            if let Some(reply) = reply {
+                log_error!(
+                    reply_tx.send(reply).await,
+                    "{ctx} Error sending async sink reply: {e}"
+                );
+            }
+        });
+        Ok(SinkReply::NONE)
+    }
+
+    async fn on_stop(&mut self, ctx: &SinkContext) -> Result<()> {
+        if let Some(client) = self.client.take() {
+            if let Err(e) = client.flush().await {
+                warn!("{ctx} Error flushing NATS messages: {e}");
+            }
+        }
+        self.jetstream = None;
+        Ok(())
+    }
+
+    fn auto_ack(&self) -> bool {
+        false
+    }
+}
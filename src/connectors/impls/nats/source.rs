@@ -0,0 +1,213 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{headers_to_value, nats_error, Config, JetStream, Received, NATS_META_KEY};
+use crate::connectors::prelude::*;
+use async_nats::jetstream::{
+    self,
+    consumer::{pull, AckPolicy},
+    AckKind,
+};
+use async_std::{
+    channel::{Receiver, Sender},
+    stream::StreamExt,
+    task::JoinHandle,
+};
+use std::collections::HashMap;
+
+pub(super) struct NatsSource {
+    config: Config,
+    tx: Sender<Received>,
+    rx: Receiver<Received>,
+    origin_uri: EventOriginUri,
+    client: Option<async_nats::Client>,
+    tasks: Vec<JoinHandle<()>>,
+    /// JetStream messages waiting for their event to be acked, by pull id
+    pending: HashMap<u64, jetstream::Message>,
+}
+
+impl NatsSource {
+    pub(super) fn new(config: Config, tx: Sender<Received>, rx: Receiver<Received>) -> Self {
+        let origin_uri = EventOriginUri {
+            scheme: "tremor-nats".to_string(),
+            host: config.url.host_or_local().to_string(),
+            port: Some(config.url.port_or_dflt()),
+            path: vec![],
+        };
+        Self {
+            config,
+            tx,
+            rx,
+            origin_uri,
+            client: None,
+            tasks: Vec::new(),
+            pending: HashMap::new(),
+        }
+    }
+}
+
+/// forwards messages of a subscription to the source, ends with an error once it is closed
+async fn subscription(mut subscriber: async_nats::Subscriber, tx: Sender<Received>) -> Result<()> {
+    while let Some(msg) = subscriber.next().await {
+        tx.send(Received::Core(msg)).await?;
+    }
+    Err("NATS subscription closed".into())
+}
+
+async fn durable_consumer(
+    client: async_nats::Client,
+    config: JetStream,
+    tx: Sender<Received>,
+) -> Result<()> {
+    let context = jetstream::new(client);
+    let stream = context
+        .get_stream(&config.stream)
+        .await
+        .map_err(nats_error)?;
+    let consumer: jetstream::consumer::PullConsumer = stream
+        .get_or_create_consumer(
+            &config.consumer,
+            pull::Config {
+                durable_name: Some(config.consumer.clone()),
+                filter_subject: config.filter_subject.clone().unwrap_or_default(),
+                ack_policy: AckPolicy::Explicit,
+                ..pull::Config::default()
+            },
+        )
+        .await
+        .map_err(nats_error)?;
+    let mut messages = consumer.messages().await.map_err(nats_error)?;
+    while let Some(msg) = messages.next().await {
+        tx.send(Received::JetStream(msg.map_err(nats_error)?))
+            .await?;
+    }
+    Err("JetStream consumer closed".into())
+}
+
+fn nats_meta(msg: &async_nats::Message) -> Value<'static> {
+    let mut meta = literal!({
+        "subject": msg.subject.to_string(),
+    });
+    if let Some(reply) = &msg.reply {
+        meta.try_insert("reply", reply.to_string());
+    }
+    if let Some(headers) = &msg.headers {
+        meta.try_insert("headers", headers_to_value(headers));
+    }
+    meta
+}
+
+#[async_trait::async_trait]
+impl Source for NatsSource {
+    async fn connect(&mut self, ctx: &SourceContext, _attempt: &Attempt) -> Result<bool> {
+        for task in self.tasks.drain(..) {
+            task.cancel().await;
+        }
+        // unacknowledged JetStream messages are redelivered to the new consumer
+        self.pending.clear();
+
+        let client = super::connect(&self.config).await?;
+        for subject in &self.config.subscriptions {
+            let subscriber = if let Some(group) = &self.config.queue_group {
+                client.queue_subscribe(subject.clone(), group.clone()).await
+            } else {
+                client.subscribe(subject.clone()).await
+            }
+            .map_err(nats_error)?;
+            self.tasks.push(spawn_task(
+                ctx.clone(),
+                subscription(subscriber, self.tx.clone()),
+            ));
+        }
+        if let Some(config) = &self.config.jetstream {
+            self.tasks.push(spawn_task(
+                ctx.clone(),
+                durable_consumer(client.clone(), config.clone(), self.tx.clone()),
+            ));
+        }
+        self.client = Some(client);
+        info!("{ctx} Connected to {}", self.config.url);
+        Ok(true)
+    }
+
+    async fn pull_data(&mut self, pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
+        let (payload, meta) = match self.rx.recv().await? {
+            Received::Core(msg) => {
+                let meta = literal!({ NATS_META_KEY: nats_meta(&msg) });
+                (msg.payload, meta)
+            }
+            Received::JetStream(msg) => {
+                let mut nats = nats_meta(&msg.message);
+                if let Ok(info) = msg.info() {
+                    nats.try_insert(
+                        "jetstream",
+                        literal!({
+                            "stream": info.stream.to_string(),
+                            "stream_sequence": info.stream_sequence,
+                            "delivered": info.delivered,
+                        }),
+                    );
+                }
+                let payload = msg.message.payload.clone();
+                self.pending.insert(*pull_id, msg);
+                (payload, literal!({ NATS_META_KEY: nats }))
+            }
+            Received::Reply(msg, correlation) => {
+                let mut meta = literal!({ NATS_META_KEY: nats_meta(&msg) });
+                if let Some(correlation) = correlation {
+                    meta.try_insert("correlation", correlation);
+                }
+                (msg.payload, meta)
+            }
+        };
+        Ok(SourceReply::Data {
+            origin_uri: self.origin_uri.clone(),
+            data: payload.to_vec(),
+            meta: Some(meta),
+            stream: None,
+            port: None,
+            codec_overwrite: None,
+        })
+    }
+
+    async fn ack(&mut self, _stream_id: u64, pull_id: u64, _ctx: &SourceContext) -> Result<()> {
+        if let Some(msg) = self.pending.remove(&pull_id) {
+            msg.ack().await.map_err(nats_error)?;
+        }
+        Ok(())
+    }
+
+    async fn fail(&mut self, _stream_id: u64, pull_id: u64, _ctx: &SourceContext) -> Result<()> {
+        if let Some(msg) = self.pending.remove(&pull_id) {
+            msg.ack_with(AckKind::Nak).await.map_err(nats_error)?;
+        }
+        Ok(())
+    }
+
+    async fn on_stop(&mut self, _ctx: &SourceContext) -> Result<()> {
+        for task in self.tasks.drain(..) {
+            task.cancel().await;
+        }
+        self.client = None;
+        Ok(())
+    }
+
+    fn is_transactional(&self) -> bool {
+        self.config.jetstream.is_some()
+    }
+
+    fn asynchronous(&self) -> bool {
+        true
+    }
+}
@@ -13,7 +13,9 @@
 // limitations under the License.
 
 mod common;
+mod http;
 mod id;
+mod json;
 mod logs;
 mod metrics;
 mod resource;
@@ -12,10 +12,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::{common::OtelDefaults, logs, metrics, trace};
+//! `OpenTelemetry` exporter
+//!
+//! Sends OTLP exports either via gRPC or via OTLP/HTTP, where requests are `POST`ed to
+//! `/v1/traces`, `/v1/metrics` and `/v1/logs` below the path of the configured url
+//! as binary protobuf or JSON, optionally gzip compressed.
+
+use super::{
+    common::{Encoding, OtelDefaults, Protocol},
+    http::{self as otlp_http, GZIP},
+    json::OtlpRequest,
+    logs, metrics, trace,
+};
 use crate::connectors::prelude::*;
+use crate::connectors::utils::tls::{tls_client_config, TLSClientConfig};
+use crate::errors::err_connector_def;
+use either::Either;
+use http_client::{h1::H1Client, HttpClient};
+use http_types::{headers, Method, Request};
+use std::sync::Arc;
 use tonic::transport::Channel as TonicChannel;
 use tonic::transport::Endpoint as TonicEndpoint;
+use tonic::transport::{Certificate, ClientTlsConfig, Identity};
 use tremor_otelapis::opentelemetry::proto::collector::{
     logs::v1::{logs_service_client::LogsServiceClient, ExportLogsServiceRequest},
     metrics::v1::{metrics_service_client::MetricsServiceClient, ExportMetricsServiceRequest},
@@ -31,6 +49,18 @@ pub(crate) struct Config {
     /// The hostname or IP address for the remote OpenTelemetry collector endpoint
     #[serde(default = "Default::default")]
     pub(crate) url: Url<OtelDefaults>,
+    /// `grpc` or `http`
+    #[serde(default = "Default::default")]
+    pub(crate) protocol: Protocol,
+    /// Payload encoding for the `http` protocol
+    #[serde(default = "Default::default")]
+    pub(crate) encoding: Encoding,
+    /// gzip compress request bodies, only applies to the `http` protocol
+    #[serde(default = "Default::default")]
+    pub(crate) gzip: bool,
+    /// Optional TLS client configuration, `true` uses the system certificates
+    #[serde(with = "either::serde_untagged_optional", default = "Default::default")]
+    pub(crate) tls: Option<Either<TLSClientConfig, bool>>,
     #[serde(default = "default_true")]
     pub(crate) logs: bool,
     /// Enables the trace service
@@ -47,6 +77,8 @@ impl ConfigImpl for Config {}
 pub(crate) struct Client {
     config: Config,
     origin_uri: EventOriginUri,
+    tls: Option<TLSClientConfig>,
+    http_tls_config: Option<Arc<rustls::ClientConfig>>,
 }
 
 // #[cfg_attr(coverage, no_coverage)]
@@ -67,7 +99,7 @@ impl ConnectorBuilder for Builder {
 
     async fn build_cfg(
         &self,
-        _id: &Alias,
+        id: &Alias,
         _: &ConnectorConfig,
         config: &Value,
         _kill_switch: &KillSwitch,
@@ -79,8 +111,28 @@ impl ConnectorBuilder for Builder {
             port: config.url.port(),
             path: vec![],
         };
+        let tls = match config.tls.as_ref() {
+            Some(Either::Right(true)) => Some(TLSClientConfig::default()),
+            Some(Either::Left(tls_config)) => Some(tls_config.clone()),
+            Some(Either::Right(false)) | None => None,
+        };
+        let http_tls_config = match (config.protocol, tls.as_ref()) {
+            (Protocol::Http, Some(tls)) => Some(Arc::new(tls_client_config(tls).await?)),
+            (Protocol::Http, None) if config.url.scheme() == "https" => {
+                return Err(err_connector_def(
+                    id,
+                    "missing tls config with 'https' url. Set 'tls' to 'true' or provide a full tls config.",
+                ));
+            }
+            _ => None,
+        };
 
-        Ok(Box::new(Client { config, origin_uri }))
+        Ok(Box::new(Client {
+            config,
+            origin_uri,
+            tls,
+            http_tls_config,
+        }))
     }
 }
 
@@ -91,6 +143,26 @@ pub(crate) struct RemoteOpenTelemetryEndpoint {
     trace_client: TraceServiceClient<TonicChannel>,
 }
 
+/// Where exports are sent to
+enum Remote {
+    Grpc(RemoteOpenTelemetryEndpoint),
+    Http(H1Client),
+}
+
+/// Why an export failed
+enum ExportError {
+    /// the collector could not be reached
+    Connection(String),
+    /// the collector refused the export
+    Rejected(String),
+}
+
+impl From<tonic::Status> for ExportError {
+    fn from(status: tonic::Status) -> Self {
+        Self::Connection(status.to_string())
+    }
+}
+
 #[async_trait::async_trait]
 impl Connector for Client {
     fn codec_requirements(&self) -> CodecReq {
@@ -105,6 +177,8 @@ impl Connector for Client {
         let sink = OtelSink {
             origin_uri: self.origin_uri.clone(),
             config: self.config.clone(),
+            tls: self.tls.clone(),
+            http_tls_config: self.http_tls_config.clone(),
             remote: None,
         };
         builder.spawn(sink, sink_context).map(Some)
@@ -115,23 +189,92 @@ impl Connector for Client {
 struct OtelSink {
     origin_uri: EventOriginUri,
     config: Config,
-    remote: Option<RemoteOpenTelemetryEndpoint>,
+    tls: Option<TLSClientConfig>,
+    http_tls_config: Option<Arc<rustls::ClientConfig>>,
+    remote: Option<Remote>,
+}
+
+async fn grpc_tls_config(
+    config: &TLSClientConfig,
+    url: &Url<OtelDefaults>,
+) -> Result<ClientTlsConfig> {
+    let domain = config
+        .domain
+        .clone()
+        .unwrap_or_else(|| url.host_or_local().to_string());
+    let mut tls_config = ClientTlsConfig::new().domain_name(domain);
+    if let Some(cafile) = &config.cafile {
+        let ca = async_std::fs::read(cafile).await?;
+        tls_config = tls_config.ca_certificate(Certificate::from_pem(ca));
+    }
+    if let (Some(cert), Some(key)) = (&config.cert, &config.key) {
+        let cert = async_std::fs::read(cert).await?;
+        let key = async_std::fs::read(key).await?;
+        tls_config = tls_config.identity(Identity::from_pem(cert, key));
+    }
+    Ok(tls_config)
+}
+
+/// `POST`s an export to the OTLP/HTTP endpoint of its signal
+async fn export_http<T: OtlpRequest>(
+    client: &H1Client,
+    config: &Config,
+    request: &T,
+) -> std::result::Result<(), ExportError> {
+    let mut url = config.url.url().clone();
+    let path = format!("{}{}", url.path().trim_end_matches('/'), T::PATH);
+    url.set_path(&path);
+    let body = otlp_http::encode(request, config.encoding, config.gzip)
+        .map_err(|e| ExportError::Rejected(e.to_string()))?;
+    let mut http_request = Request::new(Method::Post, url);
+    http_request.insert_header(headers::CONTENT_TYPE, config.encoding.content_type());
+    if config.gzip {
+        http_request.insert_header(headers::CONTENT_ENCODING, GZIP);
+    }
+    http_request.set_body(body);
+    let mut response = client
+        .send(http_request)
+        .await
+        .map_err(|e| ExportError::Connection(e.to_string()))?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(())
+    } else {
+        let body = response.body_string().await.unwrap_or_default();
+        Err(ExportError::Rejected(format!("{status} {body}")))
+    }
 }
 
 #[async_trait::async_trait()]
 impl Sink for OtelSink {
     async fn connect(&mut self, _ctx: &SinkContext, _attempt: &Attempt) -> Result<bool> {
-        let endpoint = self.config.url.to_string();
-        let channel = TonicEndpoint::from_shared(endpoint)
-            .map_err(|e| format!("Unable to connect to remote otel endpoint: {e}"))?
-            .connect()
-            .await?;
-
-        self.remote = Some(RemoteOpenTelemetryEndpoint {
-            logs_client: LogsServiceClient::new(channel.clone()),
-            metrics_client: MetricsServiceClient::new(channel.clone()),
-            trace_client: TraceServiceClient::new(channel),
-        });
+        let remote = match self.config.protocol {
+            Protocol::Grpc => {
+                let endpoint = self.config.url.to_string();
+                let mut endpoint = TonicEndpoint::from_shared(endpoint)
+                    .map_err(|e| format!("Unable to connect to remote otel endpoint: {e}"))?;
+                if let Some(tls) = &self.tls {
+                    endpoint =
+                        endpoint.tls_config(grpc_tls_config(tls, &self.config.url).await?)?;
+                }
+                let channel = endpoint.connect().await?;
+
+                Remote::Grpc(RemoteOpenTelemetryEndpoint {
+                    logs_client: LogsServiceClient::new(channel.clone()),
+                    metrics_client: MetricsServiceClient::new(channel.clone()),
+                    trace_client: TraceServiceClient::new(channel),
+                })
+            }
+            Protocol::Http => {
+                let client_config = http_client::Config::new()
+                    .set_tcp_no_delay(true)
+                    .set_tls_config(self.http_tls_config.clone());
+                let client = H1Client::try_from(client_config)
+                    .map_err(|e| format!("Invalid HTTP Client config: {e}."))?;
+                Remote::Http(client)
+            }
+        };
+        self.remote = Some(remote);
 
         Ok(true)
     }
@@ -145,14 +288,22 @@ impl Sink for OtelSink {
     ) -> Result<SinkReply> {
         if let Some(remote) = &mut self.remote {
             for value in event.value_iter() {
-                let err = if self.config.metrics && value.contains_key("metrics") {
+                let res = if self.config.metrics && value.contains_key("metrics") {
                     let request = ExportMetricsServiceRequest {
                         resource_metrics: ctx.bail_err(
                             metrics::resource_metrics_to_pb(Some(value)),
                             "Error converting payload to otel metrics",
                         )?,
                     };
-                    remote.metrics_client.export(request).await.err()
+                    match remote {
+                        Remote::Grpc(grpc) => grpc
+                            .metrics_client
+                            .export(request)
+                            .await
+                            .map(|_| ())
+                            .map_err(ExportError::from),
+                        Remote::Http(client) => export_http(client, &self.config, &request).await,
+                    }
                 } else if self.config.logs && value.contains_key("logs") {
                     let request = ExportLogsServiceRequest {
                         resource_logs: ctx.bail_err(
@@ -160,7 +311,15 @@ impl Sink for OtelSink {
                             "Error converting payload to otel logs",
                         )?,
                     };
-                    remote.logs_client.export(request).await.err()
+                    match remote {
+                        Remote::Grpc(grpc) => grpc
+                            .logs_client
+                            .export(request)
+                            .await
+                            .map(|_| ())
+                            .map_err(ExportError::from),
+                        Remote::Http(client) => export_http(client, &self.config, &request).await,
+                    }
                 } else if self.config.trace && value.contains_key("trace") {
                     let request = ExportTraceServiceRequest {
                         resource_spans: ctx.bail_err(
@@ -168,15 +327,30 @@ impl Sink for OtelSink {
                             "Error converting payload to otel span",
                         )?,
                     };
-                    remote.trace_client.export(request).await.err()
+                    match remote {
+                        Remote::Grpc(grpc) => grpc
+                            .trace_client
+                            .export(request)
+                            .await
+                            .map(|_| ())
+                            .map_err(ExportError::from),
+                        Remote::Http(client) => export_http(client, &self.config, &request).await,
+                    }
                 } else {
                     warn!("{ctx} Invalid or disabled otel payload: {value}");
-                    None
+                    Ok(())
                 };
-                if let Some(e) = err {
-                    error!("Failed to dispatch otel/gRPC logs message: {}", e);
-                    ctx.notifier().connection_lost().await?;
-                    return Ok(SinkReply::fail_or_none(event.transactional));
+                match res {
+                    Ok(()) => (),
+                    Err(ExportError::Connection(e)) => {
+                        error!("{ctx} Failed to dispatch otel message: {e}");
+                        ctx.notifier().connection_lost().await?;
+                        return Ok(SinkReply::fail_or_none(event.transactional));
+                    }
+                    Err(ExportError::Rejected(e)) => {
+                        error!("{ctx} Otel message rejected by the collector: {e}");
+                        return Ok(SinkReply::fail_or_none(event.transactional));
+                    }
                 }
             }
 
@@ -32,6 +32,55 @@ impl url::Defaults for OtelDefaults {
     const PORT: u16 = 4317;
 }
 
+/// Transport used to exchange OTLP messages
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Protocol {
+    /// OTLP/gRPC
+    Grpc,
+    /// OTLP/HTTP, `POST`s to `/v1/traces`, `/v1/metrics` and `/v1/logs`
+    Http,
+}
+
+impl Default for Protocol {
+    fn default() -> Self {
+        Self::Grpc
+    }
+}
+
+/// Payload encoding of OTLP/HTTP messages
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Encoding {
+    /// binary protobuf
+    Protobuf,
+    /// the OTLP/JSON mapping of the protobuf messages
+    Json,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Self::Protobuf
+    }
+}
+
+impl Encoding {
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            Self::Protobuf => "application/x-protobuf",
+            Self::Json => "application/json",
+        }
+    }
+
+    pub(crate) fn from_content_type(essence: &str) -> Option<Self> {
+        match essence {
+            "application/x-protobuf" => Some(Self::Protobuf),
+            "application/json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
 pub(crate) const EMPTY: Vec<Value> = Vec::new();
 
 pub(crate) fn any_value_to_json(pb: AnyValue) -> Value<'static> {
@@ -0,0 +1,144 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! OTLP/HTTP message bodies
+
+use super::{common::Encoding, json::OtlpRequest};
+use crate::errors::Result;
+use std::io::{Read, Write};
+use tremor_value::prelude::*;
+
+pub(crate) const GZIP: &str = "gzip";
+
+/// Decodes a request body, `gzipped` if the `Content-Encoding` says so.
+/// Bodies decompressing to more than `limit` bytes are rejected.
+pub(crate) fn decode<T: OtlpRequest>(
+    body: Vec<u8>,
+    encoding: Encoding,
+    gzipped: bool,
+    limit: usize,
+) -> Result<T> {
+    let mut body = if gzipped { gunzip(&body, limit)? } else { body };
+    match encoding {
+        Encoding::Protobuf => T::decode(body.as_slice())
+            .map_err(|e| format!("Invalid OTLP protobuf message: {e}").into()),
+        Encoding::Json => {
+            let json = tremor_value::parse_to_value(&mut body)?;
+            T::from_json(&json)
+        }
+    }
+}
+
+/// Encodes a request body
+pub(crate) fn encode<T: OtlpRequest>(
+    request: &T,
+    encoding: Encoding,
+    gzip: bool,
+) -> Result<Vec<u8>> {
+    let body = match encoding {
+        Encoding::Protobuf => request.encode_to_vec(),
+        Encoding::Json => request.to_json().encode().into_bytes(),
+    };
+    if gzip {
+        let mut encoder = libflate::gzip::Encoder::new(Vec::new())?;
+        encoder.write_all(&body)?;
+        Ok(encoder.finish().into_result()?)
+    } else {
+        Ok(body)
+    }
+}
+
+/// The body of a successful export response, partial success is never reported
+pub(crate) fn empty_response(encoding: Encoding) -> Vec<u8> {
+    match encoding {
+        Encoding::Protobuf => Vec::new(),
+        Encoding::Json => b"{}".to_vec(),
+    }
+}
+
+fn gunzip(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let decoder = libflate::gzip::MultiDecoder::new(data)?;
+    let mut decompressed = Vec::new();
+    // one more byte than allowed, to detect bodies over the limit
+    let max = u64::try_from(limit).unwrap_or(u64::MAX).saturating_add(1);
+    decoder.take(max).read_to_end(&mut decompressed)?;
+    if decompressed.len() > limit {
+        return Err(format!("Decompressed body exceeds the limit of {limit} bytes").into());
+    }
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: usize = 1024 * 1024;
+    use tremor_otelapis::opentelemetry::proto::{
+        collector::logs::v1::ExportLogsServiceRequest,
+        logs::v1::{InstrumentationLibraryLogs, LogRecord, ResourceLogs},
+    };
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        #[allow(deprecated)]
+        let request = ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: None,
+                instrumentation_library_logs: vec![InstrumentationLibraryLogs {
+                    instrumentation_library: None,
+                    logs: vec![LogRecord {
+                        time_unix_nano: 42,
+                        severity_number: 9,
+                        severity_text: "INFO".into(),
+                        name: "snot".into(),
+                        body: None,
+                        attributes: vec![],
+                        dropped_attributes_count: 0,
+                        flags: 0,
+                        trace_id: vec![],
+                        span_id: vec![],
+                    }],
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }],
+        };
+        for encoding in [Encoding::Protobuf, Encoding::Json] {
+            for gzip in [false, true] {
+                let body = encode(&request, encoding, gzip)?;
+                let decoded: ExportLogsServiceRequest = decode(body, encoding, gzip, LIMIT)?;
+                assert_eq!(request, decoded);
+            }
+        }
+        assert!(
+            decode::<ExportLogsServiceRequest>(b"{}".to_vec(), Encoding::Json, true, LIMIT)
+                .is_err()
+        );
+        assert!(
+            decode::<ExportLogsServiceRequest>(vec![0xff], Encoding::Protobuf, false, LIMIT)
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn gunzip_limit() -> Result<()> {
+        let mut encoder = libflate::gzip::Encoder::new(Vec::new())?;
+        encoder.write_all(&[b' '; 1024])?;
+        let body = encoder.finish().into_result()?;
+        assert_eq!(1024, gunzip(&body, 1024)?.len());
+        assert!(gunzip(&body, 1023).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,1129 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! OTLP/JSON, the protobuf JSON mapping of the OTLP export requests as used by OTLP/HTTP
+//!
+//! Field names are lowerCamelCase, the original proto field names are accepted as well.
+//! 64 bit integers are written as strings and accepted as strings or numbers,
+//! enums are numbers, trace and span ids are hex strings and all other bytes are base64.
+
+// the protos still carry deprecated fields like `labels`, they are mapped all the same
+#![allow(deprecated)]
+
+use crate::errors::{Error, Result};
+use tremor_otelapis::opentelemetry::proto::{
+    collector::{
+        logs::v1::ExportLogsServiceRequest, metrics::v1::ExportMetricsServiceRequest,
+        trace::v1::ExportTraceServiceRequest,
+    },
+    common::v1::{
+        any_value, AnyValue, ArrayValue, InstrumentationLibrary, KeyValue, KeyValueList,
+        StringKeyValue,
+    },
+    logs::v1::{InstrumentationLibraryLogs, LogRecord, ResourceLogs},
+    metrics::v1::{
+        exemplar, metric::Data, number_data_point, summary_data_point::ValueAtQuantile, Exemplar,
+        Gauge, Histogram, HistogramDataPoint, InstrumentationLibraryMetrics, IntDataPoint,
+        IntExemplar, IntGauge, IntHistogram, IntHistogramDataPoint, IntSum, Metric,
+        NumberDataPoint, ResourceMetrics, Sum, Summary, SummaryDataPoint,
+    },
+    resource::v1::Resource,
+    trace::v1::{
+        span::{Event, Link},
+        InstrumentationLibrarySpans, ResourceSpans, Span, Status,
+    },
+};
+use tremor_value::{literal, prelude::*, Value};
+
+/// An OTLP export request, sent as protobuf or as OTLP/JSON
+pub(crate) trait OtlpRequest: prost_otel::Message + Default {
+    /// Path of the OTLP/HTTP endpoint receiving this request
+    const PATH: &'static str;
+
+    fn from_json(json: &Value<'_>) -> Result<Self>;
+
+    fn to_json(&self) -> Value<'static>;
+}
+
+impl OtlpRequest for ExportTraceServiceRequest {
+    const PATH: &'static str = "/v1/traces";
+
+    fn from_json(json: &Value<'_>) -> Result<Self> {
+        Ok(Self {
+            resource_spans: list(json, "resourceSpans", resource_spans_from_json)?,
+        })
+    }
+
+    fn to_json(&self) -> Value<'static> {
+        literal!({ "resourceSpans": list_to_json(&self.resource_spans, resource_spans_to_json) })
+    }
+}
+
+impl OtlpRequest for ExportMetricsServiceRequest {
+    const PATH: &'static str = "/v1/metrics";
+
+    fn from_json(json: &Value<'_>) -> Result<Self> {
+        Ok(Self {
+            resource_metrics: list(json, "resourceMetrics", resource_metrics_from_json)?,
+        })
+    }
+
+    fn to_json(&self) -> Value<'static> {
+        literal!({
+            "resourceMetrics": list_to_json(&self.resource_metrics, resource_metrics_to_json)
+        })
+    }
+}
+
+impl OtlpRequest for ExportLogsServiceRequest {
+    const PATH: &'static str = "/v1/logs";
+
+    fn from_json(json: &Value<'_>) -> Result<Self> {
+        Ok(Self {
+            resource_logs: list(json, "resourceLogs", resource_logs_from_json)?,
+        })
+    }
+
+    fn to_json(&self) -> Value<'static> {
+        literal!({ "resourceLogs": list_to_json(&self.resource_logs, resource_logs_to_json) })
+    }
+}
+
+fn invalid(name: &str, expected: &str) -> Error {
+    format!("Invalid OTLP/JSON: `{name}` is not {expected}").into()
+}
+
+/// `someField` -> `some_field`
+fn proto_name(name: &str) -> String {
+    let mut res = String::with_capacity(name.len() + 4);
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            res.push('_');
+            res.push(c.to_ascii_lowercase());
+        } else {
+            res.push(c);
+        }
+    }
+    res
+}
+
+/// Looks up a field by its JSON name or its proto field name, `null` counts as missing
+fn field<'a, 'v>(json: &'a Value<'v>, name: &str) -> Option<&'a Value<'v>> {
+    json.get(name)
+        .or_else(|| json.get(proto_name(name).as_str()))
+        .filter(|v| !v.is_null())
+}
+
+fn string(json: &Value<'_>, name: &str) -> Result<String> {
+    field(json, name).map_or_else(
+        || Ok(String::new()),
+        |v| {
+            v.as_str()
+                .map(ToString::to_string)
+                .ok_or_else(|| invalid(name, "a string"))
+        },
+    )
+}
+
+fn boolean(json: &Value<'_>, name: &str) -> Result<bool> {
+    field(json, name).map_or(Ok(false), |v| {
+        v.as_bool().ok_or_else(|| invalid(name, "a boolean"))
+    })
+}
+
+fn as_uint(v: &Value<'_>, name: &str) -> Result<u64> {
+    v.as_u64()
+        .or_else(|| v.as_str().and_then(|s| s.parse().ok()))
+        .ok_or_else(|| invalid(name, "an unsigned integer"))
+}
+
+fn as_int(v: &Value<'_>, name: &str) -> Result<i64> {
+    v.as_i64()
+        .or_else(|| v.as_str().and_then(|s| s.parse().ok()))
+        .ok_or_else(|| invalid(name, "an integer"))
+}
+
+fn as_double(v: &Value<'_>, name: &str) -> Result<f64> {
+    v.cast_f64()
+        .or_else(|| match v.as_str()? {
+            "NaN" => Some(f64::NAN),
+            "Infinity" => Some(f64::INFINITY),
+            "-Infinity" => Some(f64::NEG_INFINITY),
+            s => s.parse().ok(),
+        })
+        .ok_or_else(|| invalid(name, "a number"))
+}
+
+fn uint(json: &Value<'_>, name: &str) -> Result<u64> {
+    field(json, name).map_or(Ok(0), |v| as_uint(v, name))
+}
+
+fn uint32(json: &Value<'_>, name: &str) -> Result<u32> {
+    u32::try_from(uint(json, name)?).map_err(|_| invalid(name, "a 32 bit unsigned integer"))
+}
+
+fn int(json: &Value<'_>, name: &str) -> Result<i64> {
+    field(json, name).map_or(Ok(0), |v| as_int(v, name))
+}
+
+fn int32(json: &Value<'_>, name: &str) -> Result<i32> {
+    i32::try_from(int(json, name)?).map_err(|_| invalid(name, "a 32 bit integer"))
+}
+
+fn double(json: &Value<'_>, name: &str) -> Result<f64> {
+    field(json, name).map_or(Ok(0.0), |v| as_double(v, name))
+}
+
+/// trace and span ids
+fn id(json: &Value<'_>, name: &str) -> Result<Vec<u8>> {
+    hex::decode(string(json, name)?).map_err(|_| invalid(name, "a hex encoded id"))
+}
+
+fn list<T>(json: &Value<'_>, name: &str, f: impl Fn(&Value<'_>) -> Result<T>) -> Result<Vec<T>> {
+    field(json, name).map_or_else(
+        || Ok(Vec::new()),
+        |v| {
+            v.as_array()
+                .ok_or_else(|| invalid(name, "an array"))?
+                .iter()
+                .map(f)
+                .collect()
+        },
+    )
+}
+
+fn message<T>(
+    json: &Value<'_>,
+    name: &str,
+    f: impl Fn(&Value<'_>) -> Result<T>,
+) -> Result<Option<T>> {
+    field(json, name).map(f).transpose()
+}
+
+fn list_to_json<T>(data: &[T], f: impl Fn(&T) -> Value<'static>) -> Value<'static> {
+    data.iter().map(f).collect()
+}
+
+fn uint_to_json(v: u64) -> Value<'static> {
+    Value::from(v.to_string())
+}
+
+fn int_to_json(v: i64) -> Value<'static> {
+    Value::from(v.to_string())
+}
+
+/// JSON has no representation for `NaN` and the infinities
+fn double_to_json(v: f64) -> Value<'static> {
+    if v.is_nan() {
+        Value::from("NaN")
+    } else if v.is_infinite() && v.is_sign_positive() {
+        Value::from("Infinity")
+    } else if v.is_infinite() {
+        Value::from("-Infinity")
+    } else {
+        Value::from(v)
+    }
+}
+
+fn id_to_json(id: &[u8]) -> Value<'static> {
+    Value::from(hex::encode(id))
+}
+
+fn any_value_from_json(json: &Value<'_>) -> Result<AnyValue> {
+    use any_value::Value as Inner;
+    let value = if let Some(v) = field(json, "stringValue") {
+        let v = v
+            .as_str()
+            .ok_or_else(|| invalid("stringValue", "a string"))?;
+        Some(Inner::StringValue(v.to_string()))
+    } else if field(json, "boolValue").is_some() {
+        Some(Inner::BoolValue(boolean(json, "boolValue")?))
+    } else if field(json, "intValue").is_some() {
+        Some(Inner::IntValue(int(json, "intValue")?))
+    } else if field(json, "doubleValue").is_some() {
+        Some(Inner::DoubleValue(double(json, "doubleValue")?))
+    } else if let Some(v) = field(json, "arrayValue") {
+        Some(Inner::ArrayValue(ArrayValue {
+            values: list(v, "values", any_value_from_json)?,
+        }))
+    } else if let Some(v) = field(json, "kvlistValue") {
+        Some(Inner::KvlistValue(KeyValueList {
+            values: list(v, "values", key_value_from_json)?,
+        }))
+    } else if let Some(v) = field(json, "bytesValue") {
+        let v = v
+            .as_str()
+            .ok_or_else(|| invalid("bytesValue", "a string"))?;
+        Some(Inner::BytesValue(base64::decode(v)?))
+    } else {
+        None
+    };
+    Ok(AnyValue { value })
+}
+
+fn any_value_to_json(data: &AnyValue) -> Value<'static> {
+    use any_value::Value as Inner;
+    match &data.value {
+        Some(Inner::StringValue(v)) => literal!({ "stringValue": v.clone() }),
+        Some(Inner::BoolValue(v)) => literal!({ "boolValue": *v }),
+        Some(Inner::IntValue(v)) => literal!({ "intValue": int_to_json(*v) }),
+        Some(Inner::DoubleValue(v)) => literal!({ "doubleValue": double_to_json(*v) }),
+        Some(Inner::ArrayValue(v)) => {
+            literal!({ "arrayValue": { "values": list_to_json(&v.values, any_value_to_json) } })
+        }
+        Some(Inner::KvlistValue(v)) => {
+            literal!({ "kvlistValue": { "values": list_to_json(&v.values, key_value_to_json) } })
+        }
+        Some(Inner::BytesValue(v)) => literal!({ "bytesValue": base64::encode(v) }),
+        None => Value::object(),
+    }
+}
+
+fn key_value_from_json(json: &Value<'_>) -> Result<KeyValue> {
+    Ok(KeyValue {
+        key: string(json, "key")?,
+        value: message(json, "value", any_value_from_json)?,
+    })
+}
+
+fn key_value_to_json(data: &KeyValue) -> Value<'static> {
+    literal!({
+        "key": data.key.clone(),
+        "value": data.value.as_ref().map(any_value_to_json).unwrap_or_default()
+    })
+}
+
+fn string_key_value_from_json(json: &Value<'_>) -> Result<StringKeyValue> {
+    Ok(StringKeyValue {
+        key: string(json, "key")?,
+        value: string(json, "value")?,
+    })
+}
+
+fn string_key_value_to_json(data: &StringKeyValue) -> Value<'static> {
+    literal!({ "key": data.key.clone(), "value": data.value.clone() })
+}
+
+fn resource_from_json(json: &Value<'_>) -> Result<Resource> {
+    Ok(Resource {
+        attributes: list(json, "attributes", key_value_from_json)?,
+        dropped_attributes_count: uint32(json, "droppedAttributesCount")?,
+    })
+}
+
+fn resource_to_json(data: &Resource) -> Value<'static> {
+    literal!({
+        "attributes": list_to_json(&data.attributes, key_value_to_json),
+        "droppedAttributesCount": data.dropped_attributes_count
+    })
+}
+
+fn instrumentation_library_from_json(json: &Value<'_>) -> Result<InstrumentationLibrary> {
+    Ok(InstrumentationLibrary {
+        name: string(json, "name")?,
+        version: string(json, "version")?,
+    })
+}
+
+fn instrumentation_library_to_json(data: &InstrumentationLibrary) -> Value<'static> {
+    literal!({ "name": data.name.clone(), "version": data.version.clone() })
+}
+
+fn resource_spans_from_json(json: &Value<'_>) -> Result<ResourceSpans> {
+    Ok(ResourceSpans {
+        resource: message(json, "resource", resource_from_json)?,
+        instrumentation_library_spans: list(
+            json,
+            "instrumentationLibrarySpans",
+            instrumentation_library_spans_from_json,
+        )?,
+        schema_url: string(json, "schemaUrl")?,
+    })
+}
+
+fn resource_spans_to_json(data: &ResourceSpans) -> Value<'static> {
+    literal!({
+        "resource": data.resource.as_ref().map(resource_to_json).unwrap_or_default(),
+        "instrumentationLibrarySpans": list_to_json(
+            &data.instrumentation_library_spans,
+            instrumentation_library_spans_to_json
+        ),
+        "schemaUrl": data.schema_url.clone()
+    })
+}
+
+fn instrumentation_library_spans_from_json(
+    json: &Value<'_>,
+) -> Result<InstrumentationLibrarySpans> {
+    Ok(InstrumentationLibrarySpans {
+        instrumentation_library: message(
+            json,
+            "instrumentationLibrary",
+            instrumentation_library_from_json,
+        )?,
+        spans: list(json, "spans", span_from_json)?,
+        schema_url: string(json, "schemaUrl")?,
+    })
+}
+
+fn instrumentation_library_spans_to_json(data: &InstrumentationLibrarySpans) -> Value<'static> {
+    literal!({
+        "instrumentationLibrary": data
+            .instrumentation_library
+            .as_ref()
+            .map(instrumentation_library_to_json)
+            .unwrap_or_default(),
+        "spans": list_to_json(&data.spans, span_to_json),
+        "schemaUrl": data.schema_url.clone()
+    })
+}
+
+fn span_from_json(json: &Value<'_>) -> Result<Span> {
+    Ok(Span {
+        trace_id: id(json, "traceId")?,
+        span_id: id(json, "spanId")?,
+        trace_state: string(json, "traceState")?,
+        parent_span_id: id(json, "parentSpanId")?,
+        name: string(json, "name")?,
+        kind: int32(json, "kind")?,
+        start_time_unix_nano: uint(json, "startTimeUnixNano")?,
+        end_time_unix_nano: uint(json, "endTimeUnixNano")?,
+        attributes: list(json, "attributes", key_value_from_json)?,
+        dropped_attributes_count: uint32(json, "droppedAttributesCount")?,
+        events: list(json, "events", event_from_json)?,
+        dropped_events_count: uint32(json, "droppedEventsCount")?,
+        links: list(json, "links", link_from_json)?,
+        dropped_links_count: uint32(json, "droppedLinksCount")?,
+        status: message(json, "status", status_from_json)?,
+    })
+}
+
+fn span_to_json(data: &Span) -> Value<'static> {
+    literal!({
+        "traceId": id_to_json(&data.trace_id),
+        "spanId": id_to_json(&data.span_id),
+        "traceState": data.trace_state.clone(),
+        "parentSpanId": id_to_json(&data.parent_span_id),
+        "name": data.name.clone(),
+        "kind": data.kind,
+        "startTimeUnixNano": uint_to_json(data.start_time_unix_nano),
+        "endTimeUnixNano": uint_to_json(data.end_time_unix_nano),
+        "attributes": list_to_json(&data.attributes, key_value_to_json),
+        "droppedAttributesCount": data.dropped_attributes_count,
+        "events": list_to_json(&data.events, event_to_json),
+        "droppedEventsCount": data.dropped_events_count,
+        "links": list_to_json(&data.links, link_to_json),
+        "droppedLinksCount": data.dropped_links_count,
+        "status": data.status.as_ref().map(status_to_json).unwrap_or_default()
+    })
+}
+
+fn event_from_json(json: &Value<'_>) -> Result<Event> {
+    Ok(Event {
+        time_unix_nano: uint(json, "timeUnixNano")?,
+        name: string(json, "name")?,
+        attributes: list(json, "attributes", key_value_from_json)?,
+        dropped_attributes_count: uint32(json, "droppedAttributesCount")?,
+    })
+}
+
+fn event_to_json(data: &Event) -> Value<'static> {
+    literal!({
+        "timeUnixNano": uint_to_json(data.time_unix_nano),
+        "name": data.name.clone(),
+        "attributes": list_to_json(&data.attributes, key_value_to_json),
+        "droppedAttributesCount": data.dropped_attributes_count
+    })
+}
+
+fn link_from_json(json: &Value<'_>) -> Result<Link> {
+    Ok(Link {
+        trace_id: id(json, "traceId")?,
+        span_id: id(json, "spanId")?,
+        trace_state: string(json, "traceState")?,
+        attributes: list(json, "attributes", key_value_from_json)?,
+        dropped_attributes_count: uint32(json, "droppedAttributesCount")?,
+    })
+}
+
+fn link_to_json(data: &Link) -> Value<'static> {
+    literal!({
+        "traceId": id_to_json(&data.trace_id),
+        "spanId": id_to_json(&data.span_id),
+        "traceState": data.trace_state.clone(),
+        "attributes": list_to_json(&data.attributes, key_value_to_json),
+        "droppedAttributesCount": data.dropped_attributes_count
+    })
+}
+
+fn status_from_json(json: &Value<'_>) -> Result<Status> {
+    Ok(Status {
+        deprecated_code: int32(json, "deprecatedCode")?,
+        message: string(json, "message")?,
+        code: int32(json, "code")?,
+    })
+}
+
+fn status_to_json(data: &Status) -> Value<'static> {
+    literal!({
+        "deprecatedCode": data.deprecated_code,
+        "message": data.message.clone(),
+        "code": data.code
+    })
+}
+
+fn resource_logs_from_json(json: &Value<'_>) -> Result<ResourceLogs> {
+    Ok(ResourceLogs {
+        resource: message(json, "resource", resource_from_json)?,
+        instrumentation_library_logs: list(
+            json,
+            "instrumentationLibraryLogs",
+            instrumentation_library_logs_from_json,
+        )?,
+        schema_url: string(json, "schemaUrl")?,
+    })
+}
+
+fn resource_logs_to_json(data: &ResourceLogs) -> Value<'static> {
+    literal!({
+        "resource": data.resource.as_ref().map(resource_to_json).unwrap_or_default(),
+        "instrumentationLibraryLogs": list_to_json(
+            &data.instrumentation_library_logs,
+            instrumentation_library_logs_to_json
+        ),
+        "schemaUrl": data.schema_url.clone()
+    })
+}
+
+fn instrumentation_library_logs_from_json(json: &Value<'_>) -> Result<InstrumentationLibraryLogs> {
+    Ok(InstrumentationLibraryLogs {
+        instrumentation_library: message(
+            json,
+            "instrumentationLibrary",
+            instrumentation_library_from_json,
+        )?,
+        logs: list(json, "logs", log_record_from_json)?,
+        schema_url: string(json, "schemaUrl")?,
+    })
+}
+
+fn instrumentation_library_logs_to_json(data: &InstrumentationLibraryLogs) -> Value<'static> {
+    literal!({
+        "instrumentationLibrary": data
+            .instrumentation_library
+            .as_ref()
+            .map(instrumentation_library_to_json)
+            .unwrap_or_default(),
+        "logs": list_to_json(&data.logs, log_record_to_json),
+        "schemaUrl": data.schema_url.clone()
+    })
+}
+
+fn log_record_from_json(json: &Value<'_>) -> Result<LogRecord> {
+    Ok(LogRecord {
+        time_unix_nano: uint(json, "timeUnixNano")?,
+        severity_number: int32(json, "severityNumber")?,
+        severity_text: string(json, "severityText")?,
+        name: string(json, "name")?,
+        body: message(json, "body", any_value_from_json)?,
+        attributes: list(json, "attributes", key_value_from_json)?,
+        dropped_attributes_count: uint32(json, "droppedAttributesCount")?,
+        flags: uint32(json, "flags")?,
+        trace_id: id(json, "traceId")?,
+        span_id: id(json, "spanId")?,
+    })
+}
+
+fn log_record_to_json(data: &LogRecord) -> Value<'static> {
+    literal!({
+        "timeUnixNano": uint_to_json(data.time_unix_nano),
+        "severityNumber": data.severity_number,
+        "severityText": data.severity_text.clone(),
+        "name": data.name.clone(),
+        "body": data.body.as_ref().map(any_value_to_json).unwrap_or_default(),
+        "attributes": list_to_json(&data.attributes, key_value_to_json),
+        "droppedAttributesCount": data.dropped_attributes_count,
+        "flags": data.flags,
+        "traceId": id_to_json(&data.trace_id),
+        "spanId": id_to_json(&data.span_id)
+    })
+}
+
+fn resource_metrics_from_json(json: &Value<'_>) -> Result<ResourceMetrics> {
+    Ok(ResourceMetrics {
+        resource: message(json, "resource", resource_from_json)?,
+        instrumentation_library_metrics: list(
+            json,
+            "instrumentationLibraryMetrics",
+            instrumentation_library_metrics_from_json,
+        )?,
+        schema_url: string(json, "schemaUrl")?,
+    })
+}
+
+fn resource_metrics_to_json(data: &ResourceMetrics) -> Value<'static> {
+    literal!({
+        "resource": data.resource.as_ref().map(resource_to_json).unwrap_or_default(),
+        "instrumentationLibraryMetrics": list_to_json(
+            &data.instrumentation_library_metrics,
+            instrumentation_library_metrics_to_json
+        ),
+        "schemaUrl": data.schema_url.clone()
+    })
+}
+
+fn instrumentation_library_metrics_from_json(
+    json: &Value<'_>,
+) -> Result<InstrumentationLibraryMetrics> {
+    Ok(InstrumentationLibraryMetrics {
+        instrumentation_library: message(
+            json,
+            "instrumentationLibrary",
+            instrumentation_library_from_json,
+        )?,
+        metrics: list(json, "metrics", metric_from_json)?,
+        schema_url: string(json, "schemaUrl")?,
+    })
+}
+
+fn instrumentation_library_metrics_to_json(data: &InstrumentationLibraryMetrics) -> Value<'static> {
+    literal!({
+        "instrumentationLibrary": data
+            .instrumentation_library
+            .as_ref()
+            .map(instrumentation_library_to_json)
+            .unwrap_or_default(),
+        "metrics": list_to_json(&data.metrics, metric_to_json),
+        "schemaUrl": data.schema_url.clone()
+    })
+}
+
+fn metric_from_json(json: &Value<'_>) -> Result<Metric> {
+    let data = if let Some(data) = field(json, "intGauge") {
+        Some(Data::IntGauge(IntGauge {
+            data_points: list(data, "dataPoints", int_data_point_from_json)?,
+        }))
+    } else if let Some(data) = field(json, "gauge") {
+        Some(Data::Gauge(Gauge {
+            data_points: list(data, "dataPoints", number_data_point_from_json)?,
+        }))
+    } else if let Some(data) = field(json, "intSum") {
+        Some(Data::IntSum(IntSum {
+            data_points: list(data, "dataPoints", int_data_point_from_json)?,
+            aggregation_temporality: int32(data, "aggregationTemporality")?,
+            is_monotonic: boolean(data, "isMonotonic")?,
+        }))
+    } else if let Some(data) = field(json, "sum") {
+        Some(Data::Sum(Sum {
+            data_points: list(data, "dataPoints", number_data_point_from_json)?,
+            aggregation_temporality: int32(data, "aggregationTemporality")?,
+            is_monotonic: boolean(data, "isMonotonic")?,
+        }))
+    } else if let Some(data) = field(json, "intHistogram") {
+        Some(Data::IntHistogram(IntHistogram {
+            data_points: list(data, "dataPoints", int_histogram_data_point_from_json)?,
+            aggregation_temporality: int32(data, "aggregationTemporality")?,
+        }))
+    } else if let Some(data) = field(json, "histogram") {
+        Some(Data::Histogram(Histogram {
+            data_points: list(data, "dataPoints", histogram_data_point_from_json)?,
+            aggregation_temporality: int32(data, "aggregationTemporality")?,
+        }))
+    } else if let Some(data) = field(json, "summary") {
+        Some(Data::Summary(Summary {
+            data_points: list(data, "dataPoints", summary_data_point_from_json)?,
+        }))
+    } else {
+        None
+    };
+    Ok(Metric {
+        name: string(json, "name")?,
+        description: string(json, "description")?,
+        unit: string(json, "unit")?,
+        data,
+    })
+}
+
+fn metric_to_json(data: &Metric) -> Value<'static> {
+    let mut json = literal!({
+        "name": data.name.clone(),
+        "description": data.description.clone(),
+        "unit": data.unit.clone()
+    });
+    let (key, data) = match &data.data {
+        Some(Data::IntGauge(data)) => (
+            "intGauge",
+            literal!({ "dataPoints": list_to_json(&data.data_points, int_data_point_to_json) }),
+        ),
+        Some(Data::Gauge(data)) => (
+            "gauge",
+            literal!({ "dataPoints": list_to_json(&data.data_points, number_data_point_to_json) }),
+        ),
+        Some(Data::IntSum(data)) => (
+            "intSum",
+            literal!({
+                "dataPoints": list_to_json(&data.data_points, int_data_point_to_json),
+                "aggregationTemporality": data.aggregation_temporality,
+                "isMonotonic": data.is_monotonic
+            }),
+        ),
+        Some(Data::Sum(data)) => (
+            "sum",
+            literal!({
+                "dataPoints": list_to_json(&data.data_points, number_data_point_to_json),
+                "aggregationTemporality": data.aggregation_temporality,
+                "isMonotonic": data.is_monotonic
+            }),
+        ),
+        Some(Data::IntHistogram(data)) => (
+            "intHistogram",
+            literal!({
+                "dataPoints": list_to_json(&data.data_points, int_histogram_data_point_to_json),
+                "aggregationTemporality": data.aggregation_temporality
+            }),
+        ),
+        Some(Data::Histogram(data)) => (
+            "histogram",
+            literal!({
+                "dataPoints": list_to_json(&data.data_points, histogram_data_point_to_json),
+                "aggregationTemporality": data.aggregation_temporality
+            }),
+        ),
+        Some(Data::Summary(data)) => (
+            "summary",
+            literal!({
+                "dataPoints": list_to_json(&data.data_points, summary_data_point_to_json)
+            }),
+        ),
+        None => return json,
+    };
+    json.try_insert(key, data);
+    json
+}
+
+fn int_data_point_from_json(json: &Value<'_>) -> Result<IntDataPoint> {
+    Ok(IntDataPoint {
+        labels: list(json, "labels", string_key_value_from_json)?,
+        start_time_unix_nano: uint(json, "startTimeUnixNano")?,
+        time_unix_nano: uint(json, "timeUnixNano")?,
+        value: int(json, "value")?,
+        exemplars: list(json, "exemplars", int_exemplar_from_json)?,
+    })
+}
+
+fn int_data_point_to_json(data: &IntDataPoint) -> Value<'static> {
+    literal!({
+        "labels": list_to_json(&data.labels, string_key_value_to_json),
+        "startTimeUnixNano": uint_to_json(data.start_time_unix_nano),
+        "timeUnixNano": uint_to_json(data.time_unix_nano),
+        "value": int_to_json(data.value),
+        "exemplars": list_to_json(&data.exemplars, int_exemplar_to_json)
+    })
+}
+
+fn number_data_point_from_json(json: &Value<'_>) -> Result<NumberDataPoint> {
+    let value = if field(json, "asDouble").is_some() {
+        Some(number_data_point::Value::AsDouble(double(
+            json, "asDouble",
+        )?))
+    } else if field(json, "asInt").is_some() {
+        Some(number_data_point::Value::AsInt(int(json, "asInt")?))
+    } else {
+        None
+    };
+    Ok(NumberDataPoint {
+        attributes: list(json, "attributes", key_value_from_json)?,
+        labels: list(json, "labels", string_key_value_from_json)?,
+        start_time_unix_nano: uint(json, "startTimeUnixNano")?,
+        time_unix_nano: uint(json, "timeUnixNano")?,
+        exemplars: list(json, "exemplars", exemplar_from_json)?,
+        value,
+    })
+}
+
+fn number_data_point_to_json(data: &NumberDataPoint) -> Value<'static> {
+    let mut json = literal!({
+        "attributes": list_to_json(&data.attributes, key_value_to_json),
+        "labels": list_to_json(&data.labels, string_key_value_to_json),
+        "startTimeUnixNano": uint_to_json(data.start_time_unix_nano),
+        "timeUnixNano": uint_to_json(data.time_unix_nano),
+        "exemplars": list_to_json(&data.exemplars, exemplar_to_json)
+    });
+    match data.value {
+        Some(number_data_point::Value::AsDouble(v)) => {
+            json.try_insert("asDouble", double_to_json(v))
+        }
+        Some(number_data_point::Value::AsInt(v)) => json.try_insert("asInt", int_to_json(v)),
+        None => None,
+    };
+    json
+}
+
+fn histogram_data_point_from_json(json: &Value<'_>) -> Result<HistogramDataPoint> {
+    Ok(HistogramDataPoint {
+        attributes: list(json, "attributes", key_value_from_json)?,
+        labels: list(json, "labels", string_key_value_from_json)?,
+        start_time_unix_nano: uint(json, "startTimeUnixNano")?,
+        time_unix_nano: uint(json, "timeUnixNano")?,
+        count: uint(json, "count")?,
+        sum: double(json, "sum")?,
+        bucket_counts: list(json, "bucketCounts", |v| as_uint(v, "bucketCounts"))?,
+        explicit_bounds: list(json, "explicitBounds", |v| as_double(v, "explicitBounds"))?,
+        exemplars: list(json, "exemplars", exemplar_from_json)?,
+    })
+}
+
+fn histogram_data_point_to_json(data: &HistogramDataPoint) -> Value<'static> {
+    literal!({
+        "attributes": list_to_json(&data.attributes, key_value_to_json),
+        "labels": list_to_json(&data.labels, string_key_value_to_json),
+        "startTimeUnixNano": uint_to_json(data.start_time_unix_nano),
+        "timeUnixNano": uint_to_json(data.time_unix_nano),
+        "count": uint_to_json(data.count),
+        "sum": double_to_json(data.sum),
+        "bucketCounts": list_to_json(&data.bucket_counts, |v| uint_to_json(*v)),
+        "explicitBounds": list_to_json(&data.explicit_bounds, |v| double_to_json(*v)),
+        "exemplars": list_to_json(&data.exemplars, exemplar_to_json)
+    })
+}
+
+fn int_histogram_data_point_from_json(json: &Value<'_>) -> Result<IntHistogramDataPoint> {
+    Ok(IntHistogramDataPoint {
+        labels: list(json, "labels", string_key_value_from_json)?,
+        start_time_unix_nano: uint(json, "startTimeUnixNano")?,
+        time_unix_nano: uint(json, "timeUnixNano")?,
+        count: uint(json, "count")?,
+        sum: int(json, "sum")?,
+        bucket_counts: list(json, "bucketCounts", |v| as_uint(v, "bucketCounts"))?,
+        explicit_bounds: list(json, "explicitBounds", |v| as_double(v, "explicitBounds"))?,
+        exemplars: list(json, "exemplars", int_exemplar_from_json)?,
+    })
+}
+
+fn int_histogram_data_point_to_json(data: &IntHistogramDataPoint) -> Value<'static> {
+    literal!({
+        "labels": list_to_json(&data.labels, string_key_value_to_json),
+        "startTimeUnixNano": uint_to_json(data.start_time_unix_nano),
+        "timeUnixNano": uint_to_json(data.time_unix_nano),
+        "count": uint_to_json(data.count),
+        "sum": int_to_json(data.sum),
+        "bucketCounts": list_to_json(&data.bucket_counts, |v| uint_to_json(*v)),
+        "explicitBounds": list_to_json(&data.explicit_bounds, |v| double_to_json(*v)),
+        "exemplars": list_to_json(&data.exemplars, int_exemplar_to_json)
+    })
+}
+
+fn summary_data_point_from_json(json: &Value<'_>) -> Result<SummaryDataPoint> {
+    Ok(SummaryDataPoint {
+        attributes: list(json, "attributes", key_value_from_json)?,
+        labels: list(json, "labels", string_key_value_from_json)?,
+        start_time_unix_nano: uint(json, "startTimeUnixNano")?,
+        time_unix_nano: uint(json, "timeUnixNano")?,
+        count: uint(json, "count")?,
+        sum: double(json, "sum")?,
+        quantile_values: list(json, "quantileValues", |json| {
+            Ok(ValueAtQuantile {
+                quantile: double(json, "quantile")?,
+                value: double(json, "value")?,
+            })
+        })?,
+    })
+}
+
+fn summary_data_point_to_json(data: &SummaryDataPoint) -> Value<'static> {
+    literal!({
+        "attributes": list_to_json(&data.attributes, key_value_to_json),
+        "labels": list_to_json(&data.labels, string_key_value_to_json),
+        "startTimeUnixNano": uint_to_json(data.start_time_unix_nano),
+        "timeUnixNano": uint_to_json(data.time_unix_nano),
+        "count": uint_to_json(data.count),
+        "sum": double_to_json(data.sum),
+        "quantileValues": list_to_json(&data.quantile_values, |v| {
+            literal!({ "quantile": double_to_json(v.quantile), "value": double_to_json(v.value) })
+        })
+    })
+}
+
+fn exemplar_from_json(json: &Value<'_>) -> Result<Exemplar> {
+    let value = if field(json, "asDouble").is_some() {
+        Some(exemplar::Value::AsDouble(double(json, "asDouble")?))
+    } else if field(json, "asInt").is_some() {
+        Some(exemplar::Value::AsInt(int(json, "asInt")?))
+    } else {
+        None
+    };
+    Ok(Exemplar {
+        filtered_attributes: list(json, "filteredAttributes", key_value_from_json)?,
+        filtered_labels: list(json, "filteredLabels", string_key_value_from_json)?,
+        time_unix_nano: uint(json, "timeUnixNano")?,
+        span_id: id(json, "spanId")?,
+        trace_id: id(json, "traceId")?,
+        value,
+    })
+}
+
+fn exemplar_to_json(data: &Exemplar) -> Value<'static> {
+    let mut json = literal!({
+        "filteredAttributes": list_to_json(&data.filtered_attributes, key_value_to_json),
+        "filteredLabels": list_to_json(&data.filtered_labels, string_key_value_to_json),
+        "timeUnixNano": uint_to_json(data.time_unix_nano),
+        "spanId": id_to_json(&data.span_id),
+        "traceId": id_to_json(&data.trace_id)
+    });
+    match data.value {
+        Some(exemplar::Value::AsDouble(v)) => json.try_insert("asDouble", double_to_json(v)),
+        Some(exemplar::Value::AsInt(v)) => json.try_insert("asInt", int_to_json(v)),
+        None => None,
+    };
+    json
+}
+
+fn int_exemplar_from_json(json: &Value<'_>) -> Result<IntExemplar> {
+    Ok(IntExemplar {
+        filtered_labels: list(json, "filteredLabels", string_key_value_from_json)?,
+        time_unix_nano: uint(json, "timeUnixNano")?,
+        value: int(json, "value")?,
+        span_id: id(json, "spanId")?,
+        trace_id: id(json, "traceId")?,
+    })
+}
+
+fn int_exemplar_to_json(data: &IntExemplar) -> Value<'static> {
+    literal!({
+        "filteredLabels": list_to_json(&data.filtered_labels, string_key_value_to_json),
+        "timeUnixNano": uint_to_json(data.time_unix_nano),
+        "value": int_to_json(data.value),
+        "spanId": id_to_json(&data.span_id),
+        "traceId": id_to_json(&data.trace_id)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_roundtrip() -> Result<()> {
+        let request = ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                resource: Some(Resource {
+                    attributes: vec![KeyValue {
+                        key: "service.name".into(),
+                        value: Some(AnyValue {
+                            value: Some(any_value::Value::StringValue("snot".into())),
+                        }),
+                    }],
+                    dropped_attributes_count: 0,
+                }),
+                instrumentation_library_spans: vec![InstrumentationLibrarySpans {
+                    instrumentation_library: Some(InstrumentationLibrary {
+                        name: "tremor".into(),
+                        version: "1.0".into(),
+                    }),
+                    spans: vec![Span {
+                        trace_id: vec![1; 16],
+                        span_id: vec![2; 8],
+                        trace_state: String::new(),
+                        parent_span_id: vec![],
+                        name: "badger".into(),
+                        kind: 2,
+                        start_time_unix_nano: u64::MAX,
+                        end_time_unix_nano: 1,
+                        attributes: vec![KeyValue {
+                            key: "bytes".into(),
+                            value: Some(AnyValue {
+                                value: Some(any_value::Value::BytesValue(vec![0, 1, 2])),
+                            }),
+                        }],
+                        dropped_attributes_count: 0,
+                        events: vec![Event {
+                            time_unix_nano: 2,
+                            name: "event".into(),
+                            attributes: vec![],
+                            dropped_attributes_count: 1,
+                        }],
+                        dropped_events_count: 0,
+                        links: vec![],
+                        dropped_links_count: 0,
+                        status: Some(Status {
+                            deprecated_code: 0,
+                            message: "ok".into(),
+                            code: 1,
+                        }),
+                    }],
+                    schema_url: String::new(),
+                }],
+                schema_url: "schema_url".into(),
+            }],
+        };
+        let json = request.to_json();
+        let span = json
+            .get("resourceSpans")
+            .get_idx(0)
+            .get("instrumentationLibrarySpans")
+            .get_idx(0)
+            .get("spans")
+            .get_idx(0);
+        assert_eq!(
+            Some("01010101010101010101010101010101"),
+            span.get_str("traceId")
+        );
+        assert_eq!(
+            Some("18446744073709551615"),
+            span.get_str("startTimeUnixNano")
+        );
+        assert_eq!(
+            Some("AAEC"),
+            span.get("attributes")
+                .get_idx(0)
+                .get("value")
+                .get_str("bytesValue")
+        );
+        assert_eq!(request, ExportTraceServiceRequest::from_json(&json)?);
+        Ok(())
+    }
+
+    #[test]
+    fn metrics_roundtrip() -> Result<()> {
+        let request = ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: None,
+                instrumentation_library_metrics: vec![InstrumentationLibraryMetrics {
+                    instrumentation_library: None,
+                    metrics: vec![
+                        Metric {
+                            name: "gauge".into(),
+                            description: String::new(),
+                            unit: "ms".into(),
+                            data: Some(Data::Gauge(Gauge {
+                                data_points: vec![NumberDataPoint {
+                                    attributes: vec![],
+                                    labels: vec![],
+                                    start_time_unix_nano: 0,
+                                    time_unix_nano: 1,
+                                    exemplars: vec![],
+                                    value: Some(number_data_point::Value::AsInt(-42)),
+                                }],
+                            })),
+                        },
+                        Metric {
+                            name: "histogram".into(),
+                            description: String::new(),
+                            unit: String::new(),
+                            data: Some(Data::Histogram(Histogram {
+                                data_points: vec![HistogramDataPoint {
+                                    attributes: vec![],
+                                    labels: vec![],
+                                    start_time_unix_nano: 0,
+                                    time_unix_nano: 1,
+                                    count: 3,
+                                    sum: f64::INFINITY,
+                                    bucket_counts: vec![1, 2],
+                                    explicit_bounds: vec![0.5],
+                                    exemplars: vec![],
+                                }],
+                                aggregation_temporality: 2,
+                            })),
+                        },
+                    ],
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }],
+        };
+        let json = request.to_json();
+        let metrics = json
+            .get("resourceMetrics")
+            .get_idx(0)
+            .get("instrumentationLibraryMetrics")
+            .get_idx(0)
+            .get("metrics");
+        assert_eq!(
+            Some("-42"),
+            metrics
+                .get_idx(0)
+                .get("gauge")
+                .get("dataPoints")
+                .get_idx(0)
+                .get_str("asInt")
+        );
+        assert_eq!(
+            Some("Infinity"),
+            metrics
+                .get_idx(1)
+                .get("histogram")
+                .get("dataPoints")
+                .get_idx(0)
+                .get_str("sum")
+        );
+        assert_eq!(request, ExportMetricsServiceRequest::from_json(&json)?);
+        Ok(())
+    }
+
+    #[test]
+    fn logs_from_json() -> Result<()> {
+        // as sent by OTLP/HTTP exporters, with numbers for 64 bit values and proto field names
+        let json = literal!({
+            "resourceLogs": [{
+                "instrumentationLibraryLogs": [{
+                    "logs": [{
+                        "time_unix_nano": 1_000_000,
+                        "severityNumber": 9,
+                        "severityText": "INFO",
+                        "body": { "kvlistValue": { "values": [
+                            { "key": "snot", "value": { "intValue": "42" } },
+                            { "key": "badger", "value": { "arrayValue": { "values": [{ "boolValue": true }] } } }
+                        ]}},
+                        "traceId": "5b8efff798038103d269b633813fc60c",
+                        "spanId": "eee19b7ec3c1b174",
+                        "flags": 1
+                    }]
+                }]
+            }]
+        });
+        let request = ExportLogsServiceRequest::from_json(&json)?;
+        let log = &request.resource_logs[0].instrumentation_library_logs[0].logs[0];
+        assert_eq!(1_000_000, log.time_unix_nano);
+        assert_eq!(9, log.severity_number);
+        assert_eq!(8, log.span_id.len());
+        assert_eq!(16, log.trace_id.len());
+        assert_eq!(
+            Some(&literal!({ "intValue": "42" })),
+            log_record_to_json(log)
+                .get("body")
+                .get("kvlistValue")
+                .get("values")
+                .get_idx(0)
+                .get("value")
+        );
+        assert_eq!(
+            request,
+            ExportLogsServiceRequest::from_json(&request.to_json())?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_json() {
+        assert!(ExportLogsServiceRequest::from_json(&literal!({ "resourceLogs": {} })).is_err());
+        assert!(ExportTraceServiceRequest::from_json(&literal!({
+            "resourceSpans": [{ "instrumentationLibrarySpans": [{ "spans": [{ "traceId": "snot" }] }] }]
+        }))
+        .is_err());
+        assert!(ExportMetricsServiceRequest::from_json(&literal!({
+            "resourceMetrics": [{ "instrumentationLibraryMetrics": [{ "metrics": [{ "sum": { "dataPoints": [{ "timeUnixNano": -1 }] } }] }] }]
+        }))
+        .is_err());
+    }
+}
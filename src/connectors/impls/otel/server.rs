@@ -12,20 +12,60 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::{common::OtelDefaults, logs, metrics, trace};
-use crate::connectors::prelude::*;
+//! `OpenTelemetry` collector endpoint
+//!
+//! Receives OTLP exports either via gRPC or via OTLP/HTTP, where requests are `POST`ed to
+//! `/v1/traces`, `/v1/metrics` and `/v1/logs` as binary protobuf or JSON, optionally gzip compressed.
+//! Bodies larger than `max_body_size`, compressed or decompressed, are rejected.
+//! Both transports can be secured with TLS, certificates are reloaded when their files change.
+
+use super::{
+    common::{Encoding, OtelDefaults, Protocol},
+    http::{self as otlp_http, GZIP},
+    json::OtlpRequest,
+    logs, metrics, trace,
+};
+use crate::connectors::{
+    impls::http::server::serve_tls,
+    prelude::*,
+    utils::tls::{ReloadingServerConfig, TLSServerConfig},
+};
 use async_std::channel::{bounded, Receiver, Sender};
-use async_std::task::JoinHandle;
-use tremor_otelapis::all::{self, OpenTelemetryEvents};
+use async_std::net::TcpListener;
+use async_std::task::{self, JoinHandle};
+use http_types::{headers, StatusCode};
+use std::net::SocketAddr;
+use tonic::transport::Server as TonicServer;
+use tremor_otelapis::all::OpenTelemetryEvents;
+use tremor_otelapis::opentelemetry::proto::collector::{
+    logs::v1::{
+        logs_service_server::{LogsService, LogsServiceServer},
+        ExportLogsServiceRequest, ExportLogsServiceResponse,
+    },
+    metrics::v1::{
+        metrics_service_server::{MetricsService, MetricsServiceServer},
+        ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+    },
+    trace::v1::{
+        trace_service_server::{TraceService, TraceServiceServer},
+        ExportTraceServiceRequest, ExportTraceServiceResponse,
+    },
+};
 const CONNECTOR_TYPE: &str = "otel_server";
 
 // TODO Consider concurrency cap?
 
-#[derive(Debug, Clone, Deserialize, Default)]
+#[derive(Debug, Clone, Deserialize)]
 pub(crate) struct Config {
     /// The hostname or IP address for the remote OpenTelemetry collector endpoint
     #[serde(default = "Default::default")]
     pub(crate) url: Url<OtelDefaults>,
+    /// `grpc` or `http`
+    #[serde(default = "Default::default")]
+    pub(crate) protocol: Protocol,
+    /// Optional TLS configuration
+    #[serde(default = "Default::default")]
+    pub(crate) tls: Option<TLSServerConfig>,
     #[serde(default = "default_true")]
     pub(crate) logs: bool,
     /// Enables the trace service
@@ -34,6 +74,14 @@ pub(crate) struct Config {
     /// Enables the metrics service
     #[serde(default = "default_true")]
     pub(crate) metrics: bool,
+    /// Maximum size of an OTLP/HTTP request body in bytes, compressed or decompressed
+    #[serde(default = "default_max_body_size")]
+    pub(crate) max_body_size: usize,
+}
+
+fn default_max_body_size() -> usize {
+    // 16MiB
+    16 * 1024 * 1024
 }
 
 impl ConfigImpl for Config {}
@@ -44,7 +92,9 @@ pub(crate) struct Server {
     #[allow(dead_code)]
     id: String,
     origin_uri: EventOriginUri,
-    accept_task: Option<JoinHandle<Result<()>>>,
+    /// TLS config, reloaded when the certificates change
+    tls_server_config: Option<ReloadingServerConfig>,
+    accept_task: Option<JoinHandle<()>>,
     tx: Sender<OpenTelemetryEvents>,
     rx: Receiver<OpenTelemetryEvents>,
 }
@@ -78,11 +128,18 @@ impl ConnectorBuilder for Builder {
             port: None,
             path: vec![],
         };
+        let config = Config::new(config)?;
+        let tls_server_config = config
+            .tls
+            .as_ref()
+            .map(ReloadingServerConfig::new)
+            .transpose()?;
         let (tx, rx) = bounded(128);
         Ok(Box::new(Server {
-            config: Config::new(config)?,
+            config,
             id: id.to_string(),
             origin_uri,
+            tls_server_config,
             accept_task: None,
             tx,
             rx,
@@ -128,7 +185,7 @@ impl Connector for Server {
             .url
             .port()
             .ok_or("Missing prot for otel server")?;
-        let endpoint = format!("{}:{}", host, port).parse()?;
+        let endpoint: SocketAddr = format!("{}:{}", host, port).parse()?;
 
         if let Some(previous_handle) = self.accept_task.take() {
             previous_handle.cancel().await;
@@ -136,14 +193,233 @@ impl Connector for Server {
 
         let tx = self.tx.clone();
 
-        spawn_task(
-            ctx.clone(),
-            async move { Ok(all::make(endpoint, tx).await?) },
-        );
+        let tls = self.tls_server_config.clone();
+        let accept_task = match self.config.protocol {
+            Protocol::Grpc => spawn_task(ctx.clone(), serve_grpc(endpoint, tls, tx, ctx.clone())),
+            Protocol::Http => {
+                let state = HttpState {
+                    tx,
+                    max_body_size: self.config.max_body_size,
+                };
+                spawn_task(ctx.clone(), serve_http(endpoint, tls, state, ctx.clone()))
+            }
+        };
+        self.accept_task = Some(accept_task);
         Ok(true)
     }
 }
 
+async fn serve_grpc(
+    endpoint: SocketAddr,
+    tls_server_config: Option<ReloadingServerConfig>,
+    tx: Sender<OpenTelemetryEvents>,
+    ctx: ConnectorContext,
+) -> Result<()> {
+    let router = TonicServer::builder()
+        .add_service(TraceServiceServer::new(GrpcService(tx.clone())))
+        .add_service(MetricsServiceServer::new(GrpcService(tx.clone())))
+        .add_service(LogsServiceServer::new(GrpcService(tx)));
+    if let Some(tls_server_config) = tls_server_config {
+        // TLS is terminated here, so the same reloading config as for `http` is used
+        let listener = tokio::net::TcpListener::bind(endpoint).await?;
+        info!(
+            "{ctx} Listening for OTLP/gRPC over TLS on {}",
+            listener.local_addr()?
+        );
+        let (conn_tx, conn_rx) = bounded(128);
+        let accept = task::spawn(accept_tls(listener, tls_server_config, conn_tx, ctx));
+        // ends once the accept task stopped
+        router.serve_with_incoming(conn_rx).await?;
+        if let Some(res) = accept.cancel().await {
+            res?;
+        }
+    } else {
+        router.serve(endpoint).await?;
+    }
+    Ok(())
+}
+
+/// Accepts TLS connections for the gRPC server
+async fn accept_tls(
+    listener: tokio::net::TcpListener,
+    tls_server_config: ReloadingServerConfig,
+    conn_tx: Sender<std::io::Result<tokio_rustls::server::TlsStream<tokio::net::TcpStream>>>,
+    ctx: ConnectorContext,
+) -> Result<()> {
+    loop {
+        let (stream, peer_addr) = listener.accept().await?;
+        let acceptor = tokio_rustls::TlsAcceptor::from(tls_server_config.get());
+        let conn_tx = conn_tx.clone();
+        let ctx = ctx.clone();
+        // handshakes happen in their own task, so a slow client doesn't block others
+        task::spawn(async move {
+            match acceptor.accept(stream).await {
+                Ok(stream) => {
+                    if conn_tx.send(Ok(stream)).await.is_err() {
+                        debug!("{ctx} gRPC server stopped, dropping connection from {peer_addr}");
+                    }
+                }
+                Err(e) => debug!("{ctx} TLS handshake with {peer_addr} failed: {e}"),
+            }
+        });
+    }
+}
+
+/// Forwards gRPC exports to the source
+struct GrpcService(Sender<OpenTelemetryEvents>);
+
+impl GrpcService {
+    async fn forward(&self, event: OpenTelemetryEvents) -> std::result::Result<(), tonic::Status> {
+        self.0
+            .send(event)
+            .await
+            .map_err(|_| tonic::Status::unavailable("otel_server is shutting down"))
+    }
+}
+
+#[async_trait::async_trait]
+impl TraceService for GrpcService {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportTraceServiceRequest>,
+    ) -> std::result::Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
+        let remote = request.remote_addr();
+        self.forward(OpenTelemetryEvents::Trace(request.into_inner(), remote))
+            .await?;
+        Ok(tonic::Response::new(ExportTraceServiceResponse::default()))
+    }
+}
+
+#[async_trait::async_trait]
+impl MetricsService for GrpcService {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportMetricsServiceRequest>,
+    ) -> std::result::Result<tonic::Response<ExportMetricsServiceResponse>, tonic::Status> {
+        let remote = request.remote_addr();
+        self.forward(OpenTelemetryEvents::Metrics(request.into_inner(), remote))
+            .await?;
+        Ok(tonic::Response::new(ExportMetricsServiceResponse::default()))
+    }
+}
+
+#[async_trait::async_trait]
+impl LogsService for GrpcService {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportLogsServiceRequest>,
+    ) -> std::result::Result<tonic::Response<ExportLogsServiceResponse>, tonic::Status> {
+        let remote = request.remote_addr();
+        self.forward(OpenTelemetryEvents::Logs(request.into_inner(), remote))
+            .await?;
+        Ok(tonic::Response::new(ExportLogsServiceResponse::default()))
+    }
+}
+
+/// State of the OTLP/HTTP server
+#[derive(Clone)]
+struct HttpState {
+    tx: Sender<OpenTelemetryEvents>,
+    max_body_size: usize,
+}
+
+async fn serve_http(
+    endpoint: SocketAddr,
+    tls_server_config: Option<ReloadingServerConfig>,
+    state: HttpState,
+    ctx: ConnectorContext,
+) -> Result<()> {
+    let mut server = tide::Server::with_state(state);
+    server
+        .at(ExportTraceServiceRequest::PATH)
+        .post(|req| handle_export::<ExportTraceServiceRequest>(req, OpenTelemetryEvents::Trace));
+    server.at(ExportMetricsServiceRequest::PATH).post(|req| {
+        handle_export::<ExportMetricsServiceRequest>(req, OpenTelemetryEvents::Metrics)
+    });
+    server
+        .at(ExportLogsServiceRequest::PATH)
+        .post(|req| handle_export::<ExportLogsServiceRequest>(req, OpenTelemetryEvents::Logs));
+
+    if let Some(tls_server_config) = tls_server_config {
+        let listener = TcpListener::bind(endpoint).await?;
+        info!(
+            "{ctx} Listening for OTLP/HTTPS on {}",
+            listener.local_addr()?
+        );
+        loop {
+            let (stream, _peer_addr) = listener.accept().await?;
+            task::spawn(serve_tls(
+                server.clone(),
+                tls_server_config.clone(),
+                stream,
+                ctx.clone(),
+            ));
+        }
+    } else {
+        info!("{ctx} Listening for OTLP/HTTP on {endpoint}");
+        server.listen(endpoint).await?;
+    }
+    Ok(())
+}
+
+/// Decodes an OTLP/HTTP export and forwards it to the source, the response uses the encoding of the request
+async fn handle_export<T: OtlpRequest>(
+    mut req: tide::Request<HttpState>,
+    event: fn(T, Option<SocketAddr>) -> OpenTelemetryEvents,
+) -> tide::Result {
+    let encoding = match req
+        .content_type()
+        .and_then(|mime| Encoding::from_content_type(mime.essence()))
+    {
+        Some(encoding) => encoding,
+        None => {
+            return Ok(tide::Response::builder(StatusCode::UnsupportedMediaType)
+                .body(format!(
+                    "Expected Content-Type {} or {}",
+                    Encoding::Protobuf.content_type(),
+                    Encoding::Json.content_type()
+                ))
+                .build());
+        }
+    };
+    let gzipped = match req
+        .header(headers::CONTENT_ENCODING)
+        .map(|v| v.last().as_str())
+    {
+        None | Some("identity") => false,
+        Some(GZIP) => true,
+        Some(other) => {
+            let body = format!("Unsupported Content-Encoding {other}");
+            return Ok(tide::Response::builder(StatusCode::UnsupportedMediaType)
+                .body(body)
+                .build());
+        }
+    };
+    let max_body_size = req.state().max_body_size;
+    if req.len().map_or(false, |len| len > max_body_size) {
+        return Ok(tide::Response::new(StatusCode::PayloadTooLarge));
+    }
+    let remote = req.peer_addr().and_then(|addr| addr.parse().ok());
+    let body = req.body_bytes().await?;
+    if body.len() > max_body_size {
+        return Ok(tide::Response::new(StatusCode::PayloadTooLarge));
+    }
+    match otlp_http::decode(body, encoding, gzipped, max_body_size) {
+        Ok(request) => {
+            if req.state().tx.send(event(request, remote)).await.is_err() {
+                return Ok(tide::Response::new(StatusCode::ServiceUnavailable));
+            }
+            Ok(tide::Response::builder(StatusCode::Ok)
+                .content_type(encoding.content_type())
+                .body(otlp_http::empty_response(encoding))
+                .build())
+        }
+        Err(e) => Ok(tide::Response::builder(StatusCode::BadRequest)
+            .body(e.to_string())
+            .build()),
+    }
+}
+
 struct OtelSource {
     origin_uri: EventOriginUri,
     config: Config,
@@ -218,4 +494,32 @@ mod tests {
 
         Ok(())
     }
+
+    #[async_std::test]
+    async fn grpc_optional_client_auth() -> Result<()> {
+        crate::connectors::tests::setup_for_tls();
+        let alias = Alias::new("flow", "my_otel_server");
+        let with_tls = literal!({
+            "config": {
+                "url": "localhost:4317",
+                "tls": {
+                    "cert": "./tests/localhost.cert",
+                    "key": "./tests/localhost.key",
+                    "cafile": "./tests/localhost.cert",
+                    "client_auth": "optional"
+                }
+            },
+        });
+        let config: ConnectorConfig = crate::config::Connector::from_config(
+            &alias,
+            ConnectorType("otel_server".into()),
+            &with_tls,
+        )?;
+
+        let builder = super::Builder::default();
+        let kill_switch = KillSwitch::dummy();
+        assert!(builder.build(&alias, &config, &kill_switch).await.is_ok());
+
+        Ok(())
+    }
 }
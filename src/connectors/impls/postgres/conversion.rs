@@ -0,0 +1,373 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion between tremor values and PostgreSQL values.
+//!
+//! Values are written as statement parameters of the configured column type and
+//! read from the text representation used by the `pgoutput` plugin.
+
+use std::fmt::{self, Display, Formatter};
+
+use chrono::{TimeZone, Utc};
+use serde::Deserialize;
+use simd_json::{Value, ValueAccess};
+use tokio_postgres::types::ToSql;
+use tremor_value::Value as TValue;
+use value_trait::Writable;
+
+use crate::errors::{Error, ErrorKind, Result};
+
+/// A statement parameter
+pub(super) type Param = Box<dyn ToSql + Sync + Send>;
+
+/// The subset of PostgreSQL types tremor values can be written as.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum PgType {
+    Bool,
+    Int2,
+    Int4,
+    Int8,
+    Float4,
+    Float8,
+    Text,
+    Bytea,
+    /// nanoseconds since epoch
+    Timestamp,
+    /// nanoseconds since epoch
+    Timestamptz,
+    // The following types are sent as text and cast by the server, this saves
+    // us from pulling in a client side representation for each of them.
+    Json,
+    Jsonb,
+    Uuid,
+    Numeric,
+    Inet,
+}
+
+impl Display for PgType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            PgType::Bool => "bool",
+            PgType::Int2 => "int2",
+            PgType::Int4 => "int4",
+            PgType::Int8 => "int8",
+            PgType::Float4 => "float4",
+            PgType::Float8 => "float8",
+            PgType::Text => "text",
+            PgType::Bytea => "bytea",
+            PgType::Timestamp => "timestamp",
+            PgType::Timestamptz => "timestamptz",
+            PgType::Json => "json",
+            PgType::Jsonb => "jsonb",
+            PgType::Uuid => "uuid",
+            PgType::Numeric => "numeric",
+            PgType::Inet => "inet",
+        }
+        .fmt(f)
+    }
+}
+
+impl PgType {
+    fn is_text_cast(self) -> bool {
+        matches!(
+            self,
+            PgType::Json | PgType::Jsonb | PgType::Uuid | PgType::Numeric | PgType::Inet
+        )
+    }
+
+    /// The placeholder for the `idx`th parameter, cast so the server does not
+    /// need to infer the parameter type from the target column.
+    pub(super) fn placeholder(self, idx: usize) -> String {
+        if self.is_text_cast() {
+            format!("${idx}::text::{self}")
+        } else {
+            format!("${idx}::{self}")
+        }
+    }
+}
+
+pub(super) fn convert_value(
+    column_name: &str,
+    value: &TValue,
+    expected_type: PgType,
+) -> Result<Param> {
+    let context = ConversionContext {
+        column_name,
+        value,
+        expected_type,
+    };
+
+    if value.is_null() {
+        return Ok(null_of(expected_type));
+    }
+
+    match expected_type {
+        PgType::Bool => get_and_wrap(context, ValueAccess::as_bool),
+        PgType::Int2 => get_and_wrap(context, ValueAccess::as_i16),
+        PgType::Int4 => get_and_wrap(context, ValueAccess::as_i32),
+        PgType::Int8 => get_and_wrap(context, ValueAccess::as_i64),
+        PgType::Float4 => get_and_wrap(context, ValueAccess::as_f32),
+        PgType::Float8 => get_and_wrap(context, ValueAccess::cast_f64),
+        PgType::Text | PgType::Uuid | PgType::Inet => {
+            get_and_wrap(context, |v: &TValue| v.as_str().map(ToString::to_string))
+        }
+        PgType::Bytea => get_and_wrap(context, |v: &TValue| v.as_bytes().map(<[u8]>::to_vec)),
+        PgType::Timestamp => get_and_wrap(context, |v: &TValue| {
+            v.as_i64().map(|ns| Utc.timestamp_nanos(ns).naive_utc())
+        }),
+        PgType::Timestamptz => get_and_wrap(context, |v: &TValue| {
+            v.as_i64().map(|ns| Utc.timestamp_nanos(ns))
+        }),
+        PgType::Json | PgType::Jsonb => Ok(Box::new(value.encode())),
+        PgType::Numeric => get_and_wrap(context, |v: &TValue| {
+            v.as_str()
+                .map(ToString::to_string)
+                .or_else(|| v.is_number().then(|| v.encode()))
+        }),
+    }
+}
+
+fn null_of(ty: PgType) -> Param {
+    match ty {
+        PgType::Bool => Box::new(None::<bool>),
+        PgType::Int2 => Box::new(None::<i16>),
+        PgType::Int4 => Box::new(None::<i32>),
+        PgType::Int8 => Box::new(None::<i64>),
+        PgType::Float4 => Box::new(None::<f32>),
+        PgType::Float8 => Box::new(None::<f64>),
+        PgType::Bytea => Box::new(None::<Vec<u8>>),
+        PgType::Timestamp => Box::new(None::<chrono::NaiveDateTime>),
+        PgType::Timestamptz => Box::new(None::<chrono::DateTime<Utc>>),
+        PgType::Text
+        | PgType::Json
+        | PgType::Jsonb
+        | PgType::Uuid
+        | PgType::Numeric
+        | PgType::Inet => Box::new(None::<String>),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ConversionContext<'config, 'event> {
+    column_name: &'config str,
+    value: &'event TValue<'event>,
+    expected_type: PgType,
+}
+
+fn get_and_wrap<'config, 'event, Getter, Output>(
+    context: ConversionContext<'config, 'event>,
+    getter: Getter,
+) -> Result<Param>
+where
+    Getter: FnOnce(&'event TValue<'event>) -> Option<Output>,
+    Output: ToSql + Sync + Send + 'static,
+{
+    getter(context.value)
+        .map(|v| Box::new(v) as Param)
+        .ok_or_else(|| {
+            Error::from(ErrorKind::UnexpectedEventFormat(
+                context.column_name.to_string(),
+                context.expected_type.to_string(),
+                context.value.value_type(),
+            ))
+        })
+}
+
+// type oids from `pg_type.dat`
+const BOOL_OID: u32 = 16;
+const BYTEA_OID: u32 = 17;
+const INT8_OID: u32 = 20;
+const INT2_OID: u32 = 21;
+const INT4_OID: u32 = 23;
+const OID_OID: u32 = 26;
+const JSON_OID: u32 = 114;
+const FLOAT4_OID: u32 = 700;
+const FLOAT8_OID: u32 = 701;
+const JSONB_OID: u32 = 3802;
+
+/// Converts a column value in text format to a tremor value.
+///
+/// Values of types without a natural mapping, e.g. `numeric` or `timestamptz`,
+/// stay strings to not lose precision or formatting.
+pub(super) fn decode_text(type_oid: u32, text: &str) -> TValue<'static> {
+    let parsed = match type_oid {
+        BOOL_OID => match text {
+            "t" => Some(TValue::from(true)),
+            "f" => Some(TValue::from(false)),
+            _ => None,
+        },
+        INT2_OID | INT4_OID | INT8_OID => text.parse::<i64>().ok().map(TValue::from),
+        OID_OID => text.parse::<u64>().ok().map(TValue::from),
+        FLOAT4_OID | FLOAT8_OID => text.parse::<f64>().ok().map(TValue::from),
+        JSON_OID | JSONB_OID => {
+            let mut bytes = text.as_bytes().to_vec();
+            let value = tremor_value::parse_to_value(&mut bytes).ok();
+            value.map(TValue::into_static)
+        }
+        BYTEA_OID => text
+            .strip_prefix("\\x")
+            .and_then(|hex| hex::decode(hex).ok())
+            .map(|bytes| TValue::Bytes(bytes.into())),
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| TValue::from(text.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio_postgres::types::Type;
+    use tremor_value::{json, literal};
+
+    /// serializes a parameter the way the client would send it to the server
+    fn to_sql(param: &Param, ty: &Type) -> Option<Vec<u8>> {
+        let mut buf = bytes::BytesMut::new();
+        match param.to_sql_checked(ty, &mut buf).unwrap() {
+            tokio_postgres::types::IsNull::Yes => None,
+            tokio_postgres::types::IsNull::No => Some(buf.to_vec()),
+        }
+    }
+
+    macro_rules! test_value_conversion {
+        ($test_name:ident {
+            $input:expr, $ty:expr, $pg_ty:expr => $output:expr $(,)?
+        }) => {
+            #[test]
+            fn $test_name() {
+                let input_value = TValue::from($input);
+                let column_name = "test_column_name";
+
+                let param = convert_value(column_name, &input_value, $ty).unwrap();
+                let left = to_sql(&param, &$pg_ty);
+                let right: Option<Vec<u8>> = $output;
+
+                assert_eq!(left, right);
+            }
+        };
+    }
+
+    test_value_conversion! {
+        bool_conversion {
+            json! { true }, PgType::Bool, Type::BOOL => Some(vec![1]),
+        }
+    }
+
+    test_value_conversion! {
+        int2_conversion {
+            json! { 42 }, PgType::Int2, Type::INT2 => Some(42_i16.to_be_bytes().to_vec()),
+        }
+    }
+
+    test_value_conversion! {
+        int4_conversion {
+            json! { -42 }, PgType::Int4, Type::INT4 => Some((-42_i32).to_be_bytes().to_vec()),
+        }
+    }
+
+    test_value_conversion! {
+        int8_conversion {
+            json! { 42 }, PgType::Int8, Type::INT8 => Some(42_i64.to_be_bytes().to_vec()),
+        }
+    }
+
+    test_value_conversion! {
+        float8_conversion {
+            json! { 0.5 }, PgType::Float8, Type::FLOAT8 => Some(0.5_f64.to_be_bytes().to_vec()),
+        }
+    }
+
+    test_value_conversion! {
+        float8_from_int {
+            json! { 2 }, PgType::Float8, Type::FLOAT8 => Some(2.0_f64.to_be_bytes().to_vec()),
+        }
+    }
+
+    test_value_conversion! {
+        text_conversion {
+            json! { "snot" }, PgType::Text, Type::TEXT => Some(b"snot".to_vec()),
+        }
+    }
+
+    test_value_conversion! {
+        jsonb_as_text {
+            json! { {"snot": [1, 2]} }, PgType::Jsonb, Type::TEXT => Some(br#"{"snot":[1,2]}"#.to_vec()),
+        }
+    }
+
+    test_value_conversion! {
+        numeric_from_number {
+            json! { 1.5 }, PgType::Numeric, Type::TEXT => Some(b"1.5".to_vec()),
+        }
+    }
+
+    test_value_conversion! {
+        null_conversion {
+            json! { null }, PgType::Int8, Type::INT8 => None,
+        }
+    }
+
+    test_value_conversion! {
+        timestamptz_conversion {
+            // 2000-01-01T00:00:01Z is one second after the PostgreSQL epoch
+            json! { 946_684_801_000_000_000_i64 }, PgType::Timestamptz, Type::TIMESTAMPTZ
+            =>
+            Some(1_000_000_i64.to_be_bytes().to_vec()),
+        }
+    }
+
+    #[test]
+    fn bytea_conversion() {
+        let value = TValue::Bytes(vec![1_u8, 2, 3].into());
+        let param = convert_value("column", &value, PgType::Bytea).unwrap();
+        assert_eq!(Some(vec![1, 2, 3]), to_sql(&param, &Type::BYTEA));
+    }
+
+    #[test]
+    fn mismatched_type() {
+        let value = TValue::from("snot");
+        let err = convert_value("column", &value, PgType::Int4)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("column"), "{err}");
+        assert!(err.contains("int4"), "{err}");
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!("$1::int8", PgType::Int8.placeholder(1));
+        assert_eq!("$2::text::jsonb", PgType::Jsonb.placeholder(2));
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(TValue::from(true), decode_text(BOOL_OID, "t"));
+        assert_eq!(TValue::from(-1), decode_text(INT4_OID, "-1"));
+        assert_eq!(TValue::from(0.5), decode_text(FLOAT8_OID, "0.5"));
+        assert_eq!(
+            literal!({"snot": "badger"}),
+            decode_text(JSONB_OID, r#"{"snot": "badger"}"#)
+        );
+        assert_eq!(
+            TValue::Bytes(vec![0xde_u8, 0xad].into()),
+            decode_text(BYTEA_OID, "\\xdead")
+        );
+        // numeric stays text to not lose precision
+        assert_eq!(TValue::from("1.50"), decode_text(1700, "1.50"));
+        // unparsable values stay text
+        assert_eq!(TValue::from("snot"), decode_text(INT8_OID, "snot"));
+    }
+}
@@ -0,0 +1,376 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parser for the messages of the `pgoutput` logical decoding plugin, protocol version 1.
+//!
+//! See <https://www.postgresql.org/docs/current/protocol-logicalrep-message-formats.html>
+
+use crate::errors::{Error, Result};
+use byteorder::{BigEndian, ReadBytesExt};
+use std::io::{BufRead, Cursor};
+
+/// A column of a relation
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Column {
+    pub(super) name: String,
+    pub(super) type_oid: u32,
+    /// part of the replica identity, usually the primary key
+    pub(super) key: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Relation {
+    pub(super) oid: u32,
+    pub(super) schema: String,
+    pub(super) name: String,
+    pub(super) columns: Vec<Column>,
+}
+
+/// A column value of a row
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Datum {
+    Null,
+    /// an unchanged TOASTed value, which is not sent again
+    Unchanged,
+    Text(String),
+}
+
+pub(super) type Tuple = Vec<Datum>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Message {
+    Begin {
+        /// microseconds since 2000-01-01
+        commit_ts: i64,
+        xid: u32,
+    },
+    Commit {
+        /// the LSN after the transaction, the slot needs to be advanced to this one
+        end_lsn: u64,
+    },
+    Relation(Relation),
+    Insert {
+        relation: u32,
+        new: Tuple,
+    },
+    Update {
+        relation: u32,
+        /// the old key or row, only present if the key changed or with `REPLICA IDENTITY FULL`
+        old: Option<Tuple>,
+        new: Tuple,
+    },
+    Delete {
+        relation: u32,
+        old: Tuple,
+    },
+    Truncate {
+        relations: Vec<u32>,
+    },
+    /// origin, type and other messages we don't care about
+    Other,
+}
+
+fn read_string(cursor: &mut Cursor<&[u8]>) -> Result<String> {
+    let mut buf = Vec::new();
+    cursor.read_until(0, &mut buf)?;
+    if buf.pop() != Some(0) {
+        return Err("Unterminated string in pgoutput message".into());
+    }
+    Ok(String::from_utf8(buf)?)
+}
+
+fn read_tuple(cursor: &mut Cursor<&[u8]>) -> Result<Tuple> {
+    let n = cursor.read_i16::<BigEndian>()?;
+    let mut tuple = Vec::with_capacity(usize::try_from(n)?);
+    for _ in 0..n {
+        let datum = match cursor.read_u8()? {
+            b'n' => Datum::Null,
+            b'u' => Datum::Unchanged,
+            b't' => {
+                let len = usize::try_from(cursor.read_i32::<BigEndian>()?)?;
+                let start = usize::try_from(cursor.position())?;
+                let data = cursor
+                    .get_ref()
+                    .get(start..start + len)
+                    .ok_or_else(|| Error::from("Truncated pgoutput tuple"))?;
+                cursor.set_position(u64::try_from(start + len)?);
+                Datum::Text(String::from_utf8(data.to_vec())?)
+            }
+            other => {
+                return Err(format!("Unknown pgoutput tuple data kind {}", other as char).into())
+            }
+        };
+        tuple.push(datum);
+    }
+    Ok(tuple)
+}
+
+/// Parses a single `pgoutput` message
+pub(super) fn parse(data: &[u8]) -> Result<Message> {
+    let mut cursor = Cursor::new(data);
+    let msg = match cursor.read_u8()? {
+        b'B' => {
+            let _final_lsn = cursor.read_u64::<BigEndian>()?;
+            Message::Begin {
+                commit_ts: cursor.read_i64::<BigEndian>()?,
+                xid: cursor.read_u32::<BigEndian>()?,
+            }
+        }
+        b'C' => {
+            let _flags = cursor.read_u8()?;
+            let _commit_lsn = cursor.read_u64::<BigEndian>()?;
+            Message::Commit {
+                end_lsn: cursor.read_u64::<BigEndian>()?,
+            }
+        }
+        b'R' => {
+            let oid = cursor.read_u32::<BigEndian>()?;
+            let schema = read_string(&mut cursor)?;
+            let name = read_string(&mut cursor)?;
+            let _replica_identity = cursor.read_u8()?;
+            let n = cursor.read_i16::<BigEndian>()?;
+            let mut columns = Vec::with_capacity(usize::try_from(n)?);
+            for _ in 0..n {
+                let flags = cursor.read_u8()?;
+                let name = read_string(&mut cursor)?;
+                let type_oid = cursor.read_u32::<BigEndian>()?;
+                let _type_modifier = cursor.read_i32::<BigEndian>()?;
+                columns.push(Column {
+                    name,
+                    type_oid,
+                    key: flags & 1 == 1,
+                });
+            }
+            Message::Relation(Relation {
+                oid,
+                schema,
+                name,
+                columns,
+            })
+        }
+        b'I' => {
+            let relation = cursor.read_u32::<BigEndian>()?;
+            let _new_marker = cursor.read_u8()?;
+            Message::Insert {
+                relation,
+                new: read_tuple(&mut cursor)?,
+            }
+        }
+        b'U' => {
+            let relation = cursor.read_u32::<BigEndian>()?;
+            let old = match cursor.read_u8()? {
+                b'K' | b'O' => {
+                    let old = read_tuple(&mut cursor)?;
+                    let _new_marker = cursor.read_u8()?;
+                    Some(old)
+                }
+                _ => None,
+            };
+            Message::Update {
+                relation,
+                old,
+                new: read_tuple(&mut cursor)?,
+            }
+        }
+        b'D' => {
+            let relation = cursor.read_u32::<BigEndian>()?;
+            let _old_marker = cursor.read_u8()?;
+            Message::Delete {
+                relation,
+                old: read_tuple(&mut cursor)?,
+            }
+        }
+        b'T' => {
+            let n = cursor.read_u32::<BigEndian>()?;
+            let _options = cursor.read_u8()?;
+            let relations = (0..n)
+                .map(|_| cursor.read_u32::<BigEndian>())
+                .collect::<std::io::Result<_>>()?;
+            Message::Truncate { relations }
+        }
+        _ => Message::Other,
+    };
+    Ok(msg)
+}
+
+/// Parses the textual representation of an LSN, e.g. `16/B374D848`
+pub(super) fn parse_lsn(lsn: &str) -> Result<u64> {
+    let (hi, lo) = lsn
+        .split_once('/')
+        .ok_or_else(|| Error::from(format!("Invalid LSN {lsn}")))?;
+    Ok((u64::from_str_radix(hi, 16)? << 32) | u64::from_str_radix(lo, 16)?)
+}
+
+pub(super) fn format_lsn(lsn: u64) -> String {
+    format!("{:X}/{:X}", lsn >> 32, lsn & 0xFFFF_FFFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tuple(values: &[Option<&str>]) -> Vec<u8> {
+        let mut buf = i16::try_from(values.len()).unwrap().to_be_bytes().to_vec();
+        for value in values {
+            match value {
+                Some(v) => {
+                    buf.push(b't');
+                    buf.extend_from_slice(&i32::try_from(v.len()).unwrap().to_be_bytes());
+                    buf.extend_from_slice(v.as_bytes());
+                }
+                None => buf.push(b'n'),
+            }
+        }
+        buf
+    }
+
+    #[test]
+    fn begin_commit() -> Result<()> {
+        let mut begin = vec![b'B'];
+        begin.extend_from_slice(&42_u64.to_be_bytes());
+        begin.extend_from_slice(&1_000_000_i64.to_be_bytes());
+        begin.extend_from_slice(&7_u32.to_be_bytes());
+        assert_eq!(
+            Message::Begin {
+                commit_ts: 1_000_000,
+                xid: 7
+            },
+            parse(&begin)?
+        );
+
+        let mut commit = vec![b'C', 0];
+        commit.extend_from_slice(&42_u64.to_be_bytes());
+        commit.extend_from_slice(&43_u64.to_be_bytes());
+        commit.extend_from_slice(&1_000_000_i64.to_be_bytes());
+        assert_eq!(Message::Commit { end_lsn: 43 }, parse(&commit)?);
+        Ok(())
+    }
+
+    #[test]
+    fn relation() -> Result<()> {
+        let mut msg = vec![b'R'];
+        msg.extend_from_slice(&16384_u32.to_be_bytes());
+        msg.extend_from_slice(b"public\0snot\0");
+        msg.push(b'd');
+        msg.extend_from_slice(&2_i16.to_be_bytes());
+        msg.push(1);
+        msg.extend_from_slice(b"id\0");
+        msg.extend_from_slice(&23_u32.to_be_bytes());
+        msg.extend_from_slice(&(-1_i32).to_be_bytes());
+        msg.push(0);
+        msg.extend_from_slice(b"badger\0");
+        msg.extend_from_slice(&25_u32.to_be_bytes());
+        msg.extend_from_slice(&(-1_i32).to_be_bytes());
+        assert_eq!(
+            Message::Relation(Relation {
+                oid: 16384,
+                schema: "public".to_string(),
+                name: "snot".to_string(),
+                columns: vec![
+                    Column {
+                        name: "id".to_string(),
+                        type_oid: 23,
+                        key: true
+                    },
+                    Column {
+                        name: "badger".to_string(),
+                        type_oid: 25,
+                        key: false
+                    }
+                ]
+            }),
+            parse(&msg)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dml() -> Result<()> {
+        let mut insert = vec![b'I'];
+        insert.extend_from_slice(&16384_u32.to_be_bytes());
+        insert.push(b'N');
+        insert.extend(tuple(&[Some("1"), None]));
+        assert_eq!(
+            Message::Insert {
+                relation: 16384,
+                new: vec![Datum::Text("1".to_string()), Datum::Null]
+            },
+            parse(&insert)?
+        );
+
+        let mut update = vec![b'U'];
+        update.extend_from_slice(&16384_u32.to_be_bytes());
+        update.push(b'O');
+        update.extend(tuple(&[Some("1"), Some("old")]));
+        update.push(b'N');
+        update.extend(tuple(&[Some("1"), Some("new")]));
+        assert_eq!(
+            Message::Update {
+                relation: 16384,
+                old: Some(vec![
+                    Datum::Text("1".to_string()),
+                    Datum::Text("old".to_string())
+                ]),
+                new: vec![Datum::Text("1".to_string()), Datum::Text("new".to_string())]
+            },
+            parse(&update)?
+        );
+
+        let mut update = vec![b'U'];
+        update.extend_from_slice(&16384_u32.to_be_bytes());
+        update.push(b'N');
+        update.extend(tuple(&[Some("1")]));
+        assert_eq!(
+            Message::Update {
+                relation: 16384,
+                old: None,
+                new: vec![Datum::Text("1".to_string())]
+            },
+            parse(&update)?
+        );
+
+        let mut delete = vec![b'D'];
+        delete.extend_from_slice(&16384_u32.to_be_bytes());
+        delete.push(b'K');
+        delete.extend(tuple(&[Some("1")]));
+        assert_eq!(
+            Message::Delete {
+                relation: 16384,
+                old: vec![Datum::Text("1".to_string())]
+            },
+            parse(&delete)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn truncated() {
+        let mut insert = vec![b'I'];
+        insert.extend_from_slice(&16384_u32.to_be_bytes());
+        insert.push(b'N');
+        insert.extend_from_slice(&1_i16.to_be_bytes());
+        insert.push(b't');
+        insert.extend_from_slice(&10_i32.to_be_bytes());
+        insert.extend_from_slice(b"snot");
+        assert!(parse(&insert).is_err());
+    }
+
+    #[test]
+    fn lsn() -> Result<()> {
+        assert_eq!(0x16_B374_D848, parse_lsn("16/B374D848")?);
+        assert_eq!("16/B374D848", format_lsn(0x16_B374_D848));
+        assert!(parse_lsn("snot").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{
+    conversion::{self, Param},
+    quote_ident, Column, Config,
+};
+use crate::connectors::prelude::*;
+use std::collections::HashMap;
+use tokio_postgres::{types::ToSql, Client};
+
+/// PostgreSQL limits the number of parameters of a statement
+const MAX_PARAMS: usize = 65_535;
+
+pub(super) struct PostgresSink {
+    config: Config,
+    client: Option<Client>,
+}
+
+impl PostgresSink {
+    pub(super) fn new(config: Config) -> Self {
+        Self {
+            config,
+            client: None,
+        }
+    }
+}
+
+/// Builds an `INSERT` of `rows` rows, updating conflicting rows if a `conflict_key` is given.
+fn insert_statement(
+    table: &str,
+    columns: &[Column],
+    conflict_key: &[String],
+    rows: usize,
+) -> String {
+    let column_names = columns
+        .iter()
+        .map(|c| quote_ident(&c.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let values = (0..rows)
+        .map(|row| {
+            let placeholders = columns
+                .iter()
+                .enumerate()
+                .map(|(i, c)| c.type_.placeholder(row * columns.len() + i + 1))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("({placeholders})")
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let on_conflict = if conflict_key.is_empty() {
+        String::new()
+    } else {
+        let key = conflict_key
+            .iter()
+            .map(|k| quote_ident(k))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let updates = columns
+            .iter()
+            .filter(|c| !conflict_key.contains(&c.name))
+            .map(|c| {
+                let name = quote_ident(&c.name);
+                format!("{name} = EXCLUDED.{name}")
+            })
+            .collect::<Vec<_>>();
+        if updates.is_empty() {
+            format!(" ON CONFLICT ({key}) DO NOTHING")
+        } else {
+            format!(" ON CONFLICT ({key}) DO UPDATE SET {}", updates.join(", "))
+        }
+    };
+    format!(
+        "INSERT INTO {} ({column_names}) VALUES {values}{on_conflict}",
+        quote_ident(table)
+    )
+}
+
+fn row_of(columns: &[Column], input: &Value) -> Result<Vec<Param>> {
+    let object = input
+        .as_object()
+        .ok_or_else(|| Error::from(ErrorKind::ExpectedObjectEvent(input.value_type())))?;
+    columns
+        .iter()
+        .map(|Column { name, type_ }| {
+            // missing values are written as NULL
+            const NULL: &Value = &Value::const_null();
+            let cell = object.get(name.as_str()).unwrap_or(NULL);
+            conversion::convert_value(name, cell, *type_)
+        })
+        .collect()
+}
+
+/// Keeps only the last of the rows with the same `conflict_key`, as a single
+/// `INSERT ... ON CONFLICT DO UPDATE` can not affect the same row twice.
+///
+/// Keys are compared after the conversion to their column type.
+fn last_per_key(
+    rows: Vec<Vec<Param>>,
+    columns: &[Column],
+    conflict_key: &[String],
+) -> Vec<Vec<Param>> {
+    let key_columns: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| conflict_key.contains(&c.name))
+        .map(|(i, _)| i)
+        .collect();
+    let keys: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            key_columns
+                .iter()
+                .filter_map(|i| row.get(*i))
+                .map(|param| format!("{param:?}"))
+                .collect()
+        })
+        .collect();
+    let mut last = HashMap::with_capacity(keys.len());
+    for (i, key) in keys.iter().enumerate() {
+        last.insert(key, i);
+    }
+    rows.into_iter()
+        .zip(&keys)
+        .enumerate()
+        .filter(|(i, (_, key))| last.get(key) == Some(i))
+        .map(|(_, (row, _))| row)
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl Sink for PostgresSink {
+    async fn connect(&mut self, ctx: &SinkContext, _attempt: &Attempt) -> Result<bool> {
+        self.client = Some(super::connect(&self.config.url, ctx).await?);
+        Ok(true)
+    }
+
+    async fn on_event(
+        &mut self,
+        _input: &str,
+        event: Event,
+        ctx: &SinkContext,
+        _serializer: &mut EventSerializer,
+        _start: u64,
+    ) -> Result<SinkReply> {
+        let client = self.client.as_mut().ok_or_else(|| {
+            Error::from(ErrorKind::ClientNotAvailable("PostgreSQL", "not connected"))
+        })?;
+        let table = self.config.table.as_deref().unwrap_or_default();
+        let columns = &self.config.columns;
+        let conflict_key = &self.config.conflict_key;
+
+        let mut rows = event
+            .value_iter()
+            .map(|value| row_of(columns, value))
+            .collect::<Result<Vec<_>>>()?;
+        if !conflict_key.is_empty() {
+            rows = last_per_key(rows, columns, conflict_key);
+        }
+
+        // all rows are written or none
+        let res = async {
+            let transaction = client.transaction().await?;
+            for chunk in rows.chunks(MAX_PARAMS / columns.len()) {
+                let statement = insert_statement(table, columns, conflict_key, chunk.len());
+                let params: Vec<&(dyn ToSql + Sync)> = chunk
+                    .iter()
+                    .flatten()
+                    .map(|p| p.as_ref() as &(dyn ToSql + Sync))
+                    .collect();
+                transaction.execute(&statement, &params).await?;
+            }
+            transaction.commit().await
+        }
+        .await;
+        if let Err(e) = res {
+            if e.is_closed() {
+                ctx.notifier().connection_lost().await?;
+            }
+            return Err(e.into());
+        }
+        Ok(SinkReply::NONE)
+    }
+
+    fn auto_ack(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use conversion::PgType;
+
+    fn columns() -> Vec<Column> {
+        vec![
+            Column {
+                name: "id".to_string(),
+                type_: PgType::Int8,
+            },
+            Column {
+                name: "doc".to_string(),
+                type_: PgType::Jsonb,
+            },
+        ]
+    }
+
+    #[test]
+    fn insert() {
+        assert_eq!(
+            r#"INSERT INTO "public"."snot" ("id", "doc") VALUES ($1::int8, $2::text::jsonb), ($3::int8, $4::text::jsonb)"#,
+            insert_statement("public.snot", &columns(), &[], 2)
+        );
+    }
+
+    #[test]
+    fn upsert() {
+        assert_eq!(
+            r#"INSERT INTO "snot" ("id", "doc") VALUES ($1::int8, $2::text::jsonb) ON CONFLICT ("id") DO UPDATE SET "doc" = EXCLUDED."doc""#,
+            insert_statement("snot", &columns(), &["id".to_string()], 1)
+        );
+        assert_eq!(
+            r#"INSERT INTO "snot" ("id", "doc") VALUES ($1::int8, $2::text::jsonb) ON CONFLICT ("id", "doc") DO NOTHING"#,
+            insert_statement(
+                "snot",
+                &columns(),
+                &["id".to_string(), "doc".to_string()],
+                1
+            )
+        );
+    }
+
+    #[test]
+    fn repeated_keys() -> Result<()> {
+        let rows = [
+            literal!({"id": 1, "doc": "first"}),
+            literal!({"id": 2, "doc": "second"}),
+            literal!({"id": 1, "doc": "third"}),
+        ]
+        .iter()
+        .map(|value| row_of(&columns(), value))
+        .collect::<Result<Vec<_>>>()?;
+        let rows = last_per_key(rows, &columns(), &["id".to_string()]);
+        let docs: Vec<String> = rows
+            .iter()
+            .filter_map(|row| row.get(1))
+            .map(|doc| format!("{doc:?}"))
+            .collect();
+        assert_eq!(2, docs.len());
+        assert!(docs.first().map_or(false, |doc| doc.contains("second")));
+        assert!(docs.get(1).map_or(false, |doc| doc.contains("third")));
+        Ok(())
+    }
+
+    #[test]
+    fn rows() -> Result<()> {
+        let row = row_of(&columns(), &literal!({"id": 1, "doc": {"snot": "badger"}}))?;
+        assert_eq!(2, row.len());
+        // missing columns are NULL
+        assert_eq!(2, row_of(&columns(), &literal!({}))?.len());
+        assert!(row_of(&columns(), &literal!([1, 2])).is_err());
+        assert!(row_of(&columns(), &literal!({"id": "snot"})).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,367 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Change data capture from a logical replication slot.
+//!
+//! Changes are peeked from the slot, which leaves them in it until the slot is advanced.
+//! Every peek starts at the slot position, so changes of transactions that were already read
+//! are skipped. Once all changes of a transaction are acked the slot is advanced past it,
+//! transactions without changes for the publication are confirmed right away.
+//! If a peek of `max_changes` does not contain a complete new transaction, the window is doubled
+//! until it does.
+//! A failed change resets the read position to the slot position, streaming all unconfirmed
+//! changes again.
+
+use super::{
+    conversion::decode_text,
+    pgoutput::{self, Datum, Message, Relation, Tuple},
+    Config, POSTGRES_META_KEY,
+};
+use crate::connectors::prelude::*;
+use async_std::task;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+use tokio_postgres::Client;
+
+/// microseconds between the unix epoch and the PostgreSQL epoch, 2000-01-01
+const PG_EPOCH_OFFSET_US: i64 = 946_684_800_000_000;
+
+/// A change read from the slot, not yet emitted
+struct Change {
+    data: Value<'static>,
+    meta: Value<'static>,
+    /// end of the transaction the change is part of
+    end_lsn: u64,
+}
+
+/// A transaction read from the slot, not yet confirmed
+struct Transaction {
+    end_lsn: u64,
+    /// changes not yet acked
+    pending: usize,
+}
+
+pub(super) struct PostgresSource {
+    config: Config,
+    slot: String,
+    publication: String,
+    origin_uri: EventOriginUri,
+    client: Option<Client>,
+    relations: HashMap<u32, Relation>,
+    buffer: VecDeque<Change>,
+    /// transactions in the order they were read
+    transactions: VecDeque<Transaction>,
+    /// pull id to end of the transaction of the emitted change
+    emitted: HashMap<u64, u64>,
+    /// slot position, everything before was acked
+    confirmed_lsn: u64,
+    /// everything before was read
+    read_lsn: u64,
+    /// number of changes to peek at
+    window: usize,
+}
+
+impl PostgresSource {
+    pub(super) fn new(config: Config) -> Self {
+        let slot = config.slot.clone().unwrap_or_default();
+        let publication = config.publication.clone().unwrap_or_default();
+        let origin_uri = EventOriginUri {
+            scheme: "tremor-postgres".to_string(),
+            host: config.url.host_or_local().to_string(),
+            port: Some(config.url.port_or_dflt()),
+            path: vec![slot.clone()],
+        };
+        let window = config.max_changes as usize;
+        Self {
+            config,
+            slot,
+            publication,
+            origin_uri,
+            client: None,
+            relations: HashMap::new(),
+            buffer: VecDeque::new(),
+            transactions: VecDeque::new(),
+            emitted: HashMap::new(),
+            confirmed_lsn: 0,
+            read_lsn: 0,
+            window,
+        }
+    }
+
+    fn client(&self) -> Result<&Client> {
+        self.client
+            .as_ref()
+            .ok_or_else(|| ErrorKind::ClientNotAvailable("PostgreSQL", "not connected").into())
+    }
+
+    /// forget about everything not yet confirmed, so it is read again
+    fn rewind(&mut self) {
+        self.buffer.clear();
+        self.transactions.clear();
+        self.emitted.clear();
+        self.read_lsn = self.confirmed_lsn;
+    }
+
+    async fn slot_position(&self) -> Result<Option<u64>> {
+        let row = self
+            .client()?
+            .query_opt(
+                "SELECT confirmed_flush_lsn::text FROM pg_replication_slots WHERE slot_name = $1",
+                &[&self.slot],
+            )
+            .await?;
+        row.and_then(|row| row.get::<_, Option<String>>(0))
+            .map(|lsn| pgoutput::parse_lsn(&lsn))
+            .transpose()
+    }
+
+    /// peeks at the slot and buffers the changes of transactions not yet read
+    ///
+    /// Returns `true` if the window was full without a complete new transaction and was enlarged,
+    /// so there is more to fetch right away.
+    async fn fetch(&mut self) -> Result<bool> {
+        let window = i32::try_from(self.window).unwrap_or(i32::MAX);
+        let rows = self
+            .client()?
+            .query(
+                "SELECT data FROM pg_logical_slot_peek_binary_changes($1, NULL, $2, 'proto_version', '1', 'publication_names', $3)",
+                &[&self.slot, &window, &self.publication],
+            )
+            .await?;
+        let full = rows.len() >= self.window;
+        let read_lsn = self.read_lsn;
+
+        let mut changes = Vec::new();
+        let mut xid = 0;
+        let mut commit_ts = 0;
+        for row in rows {
+            let data: Vec<u8> = row.get(0);
+            match pgoutput::parse(&data)? {
+                Message::Begin {
+                    commit_ts: ts,
+                    xid: id,
+                } => {
+                    changes.clear();
+                    xid = id;
+                    commit_ts = ts;
+                }
+                Message::Commit { end_lsn } => {
+                    if end_lsn > self.read_lsn {
+                        let meta = literal!({
+                            POSTGRES_META_KEY: {
+                                "lsn": pgoutput::format_lsn(end_lsn),
+                                "xid": xid,
+                                "commit_ts": (commit_ts + PG_EPOCH_OFFSET_US) * 1000,
+                            }
+                        });
+                        self.transactions.push_back(Transaction {
+                            end_lsn,
+                            pending: changes.len(),
+                        });
+                        self.buffer.extend(changes.drain(..).map(|data| Change {
+                            data,
+                            meta: meta.clone(),
+                            end_lsn,
+                        }));
+                        self.read_lsn = end_lsn;
+                    }
+                    changes.clear();
+                }
+                Message::Relation(relation) => {
+                    self.relations.insert(relation.oid, relation);
+                }
+                Message::Insert { relation, new } => {
+                    changes.push(self.change("insert", relation, None, Some(&new))?);
+                }
+                Message::Update { relation, old, new } => {
+                    changes.push(self.change("update", relation, old.as_ref(), Some(&new))?);
+                }
+                Message::Delete { relation, old } => {
+                    changes.push(self.change("delete", relation, Some(&old), None)?);
+                }
+                Message::Truncate { relations } => {
+                    for relation in relations {
+                        changes.push(self.change("truncate", relation, None, None)?);
+                    }
+                }
+                Message::Other => (),
+            }
+        }
+        if full && self.read_lsn == read_lsn && self.window < i32::MAX as usize {
+            // a transaction larger than the window, it needs to be peeked at as a whole
+            self.window = self.window.saturating_mul(2).min(i32::MAX as usize);
+            Ok(true)
+        } else {
+            self.window = self.config.max_changes as usize;
+            Ok(false)
+        }
+    }
+
+    /// advances the slot past the transactions at the front with all changes acked
+    async fn advance(&mut self, ctx: &SourceContext) -> Result<()> {
+        let mut confirmable = None;
+        while let Some(tx) = self.transactions.front() {
+            if tx.pending > 0 {
+                break;
+            }
+            confirmable = Some(tx.end_lsn);
+            self.transactions.pop_front();
+        }
+        if let Some(lsn) = confirmable {
+            self.client()?
+                .execute(
+                    "SELECT pg_replication_slot_advance($1, $2::text::pg_lsn)",
+                    &[&self.slot, &pgoutput::format_lsn(lsn)],
+                )
+                .await?;
+            debug!("{ctx} Advanced replication slot {} to {lsn}", self.slot);
+            self.confirmed_lsn = lsn;
+        }
+        Ok(())
+    }
+
+    fn change(
+        &self,
+        op: &'static str,
+        relation: u32,
+        old: Option<&Tuple>,
+        new: Option<&Tuple>,
+    ) -> Result<Value<'static>> {
+        let relation = self
+            .relations
+            .get(&relation)
+            .ok_or_else(|| Error::from(format!("Change for unknown relation {relation}")))?;
+        let row = |tuple: &Tuple| {
+            let mut row = Object::with_capacity(tuple.len());
+            for (column, datum) in relation.columns.iter().zip(tuple) {
+                let value = match datum {
+                    Datum::Null => Value::null(),
+                    Datum::Text(text) => decode_text(column.type_oid, text),
+                    // the value is not part of the change
+                    Datum::Unchanged => continue,
+                };
+                row.insert(column.name.clone().into(), value);
+            }
+            Value::from(row)
+        };
+        let key: Vec<Value<'static>> = relation
+            .columns
+            .iter()
+            .filter(|c| c.key)
+            .map(|c| Value::from(c.name.clone()))
+            .collect();
+        Ok(literal!({
+            "op": op,
+            "schema": relation.schema.clone(),
+            "table": relation.name.clone(),
+            "key": key,
+            "old": old.map(row),
+            "new": new.map(row),
+        }))
+    }
+}
+
+#[async_trait::async_trait]
+impl Source for PostgresSource {
+    async fn connect(&mut self, ctx: &SourceContext, _attempt: &Attempt) -> Result<bool> {
+        self.client = Some(super::connect(&self.config.url, ctx).await?);
+        self.relations.clear();
+
+        let position = if let Some(position) = self.slot_position().await? {
+            position
+        } else if self.config.create_slot {
+            self.client()?
+                .execute(
+                    "SELECT pg_create_logical_replication_slot($1, 'pgoutput')",
+                    &[&self.slot],
+                )
+                .await?;
+            info!("{ctx} Created replication slot {}", self.slot);
+            self.slot_position().await?.unwrap_or_default()
+        } else {
+            return Err(format!("Replication slot {} does not exist", self.slot).into());
+        };
+        self.confirmed_lsn = position;
+        self.rewind();
+        Ok(true)
+    }
+
+    async fn pull_data(&mut self, pull_id: &mut u64, ctx: &SourceContext) -> Result<SourceReply> {
+        loop {
+            if let Some(Change {
+                data,
+                meta,
+                end_lsn,
+            }) = self.buffer.pop_front()
+            {
+                self.emitted.insert(*pull_id, end_lsn);
+                return Ok(SourceReply::Structured {
+                    origin_uri: self.origin_uri.clone(),
+                    payload: (data, meta).into(),
+                    stream: DEFAULT_STREAM_ID,
+                    port: None,
+                });
+            }
+            let more = self.fetch().await?;
+            // confirm transactions without changes
+            self.advance(ctx).await?;
+            if self.buffer.is_empty() && !more {
+                task::sleep(Duration::from_millis(self.config.poll_interval_ms)).await;
+            }
+        }
+    }
+
+    async fn ack(&mut self, _stream_id: u64, pull_id: u64, ctx: &SourceContext) -> Result<()> {
+        let end_lsn = if let Some(end_lsn) = self.emitted.remove(&pull_id) {
+            end_lsn
+        } else {
+            // emitted before a rewind
+            return Ok(());
+        };
+        if let Some(tx) = self
+            .transactions
+            .iter_mut()
+            .find(|tx| tx.end_lsn == end_lsn)
+        {
+            tx.pending = tx.pending.saturating_sub(1);
+        }
+        self.advance(ctx).await
+    }
+
+    async fn fail(&mut self, _stream_id: u64, pull_id: u64, ctx: &SourceContext) -> Result<()> {
+        if self.emitted.contains_key(&pull_id) {
+            warn!(
+                "{ctx} Change failed, streaming again from {}",
+                pgoutput::format_lsn(self.confirmed_lsn)
+            );
+            self.rewind();
+        }
+        Ok(())
+    }
+
+    async fn on_stop(&mut self, _ctx: &SourceContext) -> Result<()> {
+        self.client = None;
+        Ok(())
+    }
+
+    fn is_transactional(&self) -> bool {
+        true
+    }
+
+    fn asynchronous(&self) -> bool {
+        false
+    }
+}
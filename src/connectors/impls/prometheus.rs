@@ -0,0 +1,86 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Prometheus integration
+//!
+//! The `prometheus_remote_write` source receives Prometheus `remote_write` requests,
+//! the `prometheus_exporter` sink serves the metrics it receives for scraping.
+//! Both use the metrics event format of the `metrics` connector:
+//!
+//! ```json
+//! {
+//!   "measurement": "http_requests_total",
+//!   "tags": {"method": "GET"},
+//!   "fields": {"value": 42.0},
+//!   "timestamp": 1665150000000000000
+//! }
+//! ```
+
+mod exposition;
+
+pub(crate) mod exporter;
+pub(crate) mod prompb;
+pub(crate) mod remote_write;
+
+use crate::connectors::{
+    impls::http::server::serve_tls,
+    prelude::*,
+    utils::{tls::ReloadingServerConfig, url},
+};
+use async_std::net::TcpListener;
+use async_std::task;
+use std::net::SocketAddr;
+
+/// The field carrying the value of a sample
+const VALUE_FIELD: &str = "value";
+
+pub(crate) struct PrometheusDefaults;
+impl url::Defaults for PrometheusDefaults {
+    const SCHEME: &'static str = "http";
+    const HOST: &'static str = "localhost";
+    const PORT: u16 = 9090;
+}
+
+/// The address to listen on
+fn endpoint(url: &Url<PrometheusDefaults>) -> Result<SocketAddr> {
+    let host = url.host_str().ok_or("Missing host")?;
+    let port = url.port().ok_or("Missing port")?;
+    Ok(format!("{host}:{port}").parse()?)
+}
+
+/// Serves `server` on `endpoint`, via HTTPS if there is a TLS config
+async fn listen<State: Clone + Send + Sync + 'static>(
+    server: tide::Server<State>,
+    endpoint: SocketAddr,
+    tls_server_config: Option<ReloadingServerConfig>,
+    ctx: ConnectorContext,
+) -> Result<()> {
+    if let Some(tls_server_config) = tls_server_config {
+        let listener = TcpListener::bind(endpoint).await?;
+        info!("{ctx} Listening for HTTPS on {}", listener.local_addr()?);
+        loop {
+            let (stream, _peer_addr) = listener.accept().await?;
+            task::spawn(serve_tls(
+                server.clone(),
+                tls_server_config.clone(),
+                stream,
+                ctx.clone(),
+            ));
+        }
+    } else {
+        info!("{ctx} Listening for HTTP on {endpoint}");
+        server.listen(endpoint).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,237 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Prometheus exporter
+//!
+//! Keeps the latest sample of every series it receives as metrics events and serves them
+//! in the text exposition format on `path` for scraping. The type and help text of a metric family
+//! are taken from the `$prometheus_exporter.type` and `$prometheus_exporter.help` metadata,
+//! metrics without a type are exported as `untyped`. Series that have not been updated
+//! for `expire_after_ms` are no longer exported.
+
+use super::{
+    endpoint,
+    exposition::{FamilyMeta, Registry},
+    listen, PrometheusDefaults,
+};
+use crate::connectors::{
+    impls::metrics::verify_metrics_value,
+    prelude::*,
+    utils::tls::{ReloadingServerConfig, TLSServerConfig},
+};
+use async_std::sync::RwLock;
+use async_std::task::JoinHandle;
+use http_types::StatusCode;
+use std::sync::Arc;
+use tremor_common::time::nanotime;
+
+pub(crate) const CONNECTOR_TYPE: &str = "prometheus_exporter";
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// the address to listen on
+    #[serde(default = "Default::default")]
+    url: Url<PrometheusDefaults>,
+    /// the path metrics are served on
+    #[serde(default = "default_path")]
+    path: String,
+    /// Optional TLS configuration
+    #[serde(default = "Default::default")]
+    tls: Option<TLSServerConfig>,
+    /// stop exporting series that have not been updated for this many milliseconds
+    #[serde(default = "Default::default")]
+    expire_after_ms: Option<u64>,
+}
+
+fn default_path() -> String {
+    "/metrics".to_string()
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Debug, Default)]
+pub(crate) struct Builder {}
+
+#[async_trait::async_trait]
+impl ConnectorBuilder for Builder {
+    fn connector_type(&self) -> ConnectorType {
+        CONNECTOR_TYPE.into()
+    }
+
+    async fn build_cfg(
+        &self,
+        _alias: &Alias,
+        _config: &ConnectorConfig,
+        connector_config: &Value,
+        _kill_switch: &KillSwitch,
+    ) -> Result<Box<dyn Connector>> {
+        let config = Config::new(connector_config)?;
+        let tls_server_config = config
+            .tls
+            .as_ref()
+            .map(ReloadingServerConfig::new)
+            .transpose()?;
+        Ok(Box::new(Exporter {
+            config,
+            tls_server_config,
+            registry: Arc::new(RwLock::new(Registry::default())),
+            accept_task: None,
+        }))
+    }
+}
+
+struct Exporter {
+    config: Config,
+    tls_server_config: Option<ReloadingServerConfig>,
+    registry: Arc<RwLock<Registry>>,
+    accept_task: Option<JoinHandle<()>>,
+}
+
+#[derive(Clone)]
+struct State {
+    registry: Arc<RwLock<Registry>>,
+    expire_after_ms: Option<u64>,
+}
+
+#[async_trait::async_trait]
+impl Connector for Exporter {
+    fn codec_requirements(&self) -> CodecReq {
+        CodecReq::Structured
+    }
+
+    async fn create_sink(
+        &mut self,
+        sink_context: SinkContext,
+        builder: SinkManagerBuilder,
+    ) -> Result<Option<SinkAddr>> {
+        let sink = ExporterSink {
+            registry: self.registry.clone(),
+        };
+        builder.spawn(sink, sink_context).map(Some)
+    }
+
+    async fn connect(&mut self, ctx: &ConnectorContext, _attempt: &Attempt) -> Result<bool> {
+        let endpoint = endpoint(&self.config.url)?;
+        if let Some(previous_handle) = self.accept_task.take() {
+            previous_handle.cancel().await;
+        }
+
+        let mut server = tide::Server::with_state(State {
+            registry: self.registry.clone(),
+            expire_after_ms: self.config.expire_after_ms,
+        });
+        server.at(&self.config.path).get(handle_scrape);
+        self.accept_task = Some(spawn_task(
+            ctx.clone(),
+            listen(
+                server,
+                endpoint,
+                self.tls_server_config.clone(),
+                ctx.clone(),
+            ),
+        ));
+        Ok(true)
+    }
+
+    async fn on_stop(&mut self, _ctx: &ConnectorContext) -> Result<()> {
+        if let Some(accept_task) = self.accept_task.take() {
+            accept_task.cancel().await;
+        }
+        Ok(())
+    }
+}
+
+async fn handle_scrape(req: tide::Request<State>) -> tide::Result {
+    let state = req.state();
+    let body = if let Some(expire_after_ms) = state.expire_after_ms {
+        let mut registry = state.registry.write().await;
+        registry.expire(nanotime().saturating_sub(expire_after_ms * 1_000_000));
+        registry.render()
+    } else {
+        state.registry.read().await.render()
+    };
+    Ok(tide::Response::builder(StatusCode::Ok)
+        .content_type(CONTENT_TYPE)
+        .body(body)
+        .build())
+}
+
+fn family_meta(meta: Option<&Value>) -> Result<FamilyMeta> {
+    Ok(meta
+        .map(|meta| tremor_value::structurize(meta.clone_static()))
+        .transpose()?
+        .unwrap_or_default())
+}
+
+struct ExporterSink {
+    registry: Arc<RwLock<Registry>>,
+}
+
+#[async_trait::async_trait()]
+impl Sink for ExporterSink {
+    async fn on_event(
+        &mut self,
+        _input: &str,
+        event: Event,
+        ctx: &SinkContext,
+        _serializer: &mut EventSerializer,
+        _start: u64,
+    ) -> Result<SinkReply> {
+        let now = nanotime();
+        let mut registry = self.registry.write().await;
+        let mut res = SinkReply::NONE;
+        for (value, meta) in event.value_meta_iter() {
+            let updated = verify_metrics_value(value)
+                .and_then(|()| family_meta(ctx.extract_meta(meta)))
+                .and_then(|family_meta| registry.update(value, &family_meta, now));
+            if let Err(e) = updated {
+                error!("{ctx} Invalid metrics event: {e}");
+                res = SinkReply::FAIL;
+            }
+        }
+        Ok(res)
+    }
+
+    fn auto_ack(&self) -> bool {
+        true
+    }
+
+    fn asynchronous(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn unknown_config() {
+        let config = literal!({
+            "url": "http://localhost:9090",
+            "snot": "badger"
+        });
+        let res = Builder::default()
+            .build_cfg(
+                &Alias::new("flow", "prometheus"),
+                &ConnectorConfig::default(),
+                &config,
+                &KillSwitch::dummy(),
+            )
+            .await;
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,413 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The latest samples of all metric families, rendered in the Prometheus text exposition format
+//!
+//! Every field of a metrics event is a metric family, named after the measurement and the field,
+//! where the `value` field is named after the measurement alone. Its tags are the labels of the series.
+//! Counters, gauges and untyped metrics have numeric fields, histogram fields are objects with
+//! the cumulative counts per upper bound, the sum and the count of the observations:
+//!
+//! ```json
+//! {"buckets": {"0.1": 3, "1": 7, "+Inf": 9}, "sum": 4.2, "count": 9}
+//! ```
+
+use super::VALUE_FIELD;
+use crate::errors::{Error, Result};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use tremor_value::prelude::*;
+
+/// The type of a metric family
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Kind {
+    Counter,
+    Gauge,
+    Histogram,
+    #[default]
+    Untyped,
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Counter => "counter",
+            Self::Gauge => "gauge",
+            Self::Histogram => "histogram",
+            Self::Untyped => "untyped",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SampleValue {
+    Number(f64),
+    Histogram {
+        /// upper bound and cumulative count, sorted by upper bound
+        buckets: Vec<(f64, f64)>,
+        sum: f64,
+        count: f64,
+    },
+}
+
+impl SampleValue {
+    fn from_value(kind: Kind, value: &Value) -> Result<Self> {
+        if kind == Kind::Histogram {
+            let buckets = value
+                .get_object("buckets")
+                .ok_or_else(|| Error::from("Histogram without buckets"))?;
+            let mut buckets = buckets
+                .iter()
+                .map(|(le, count)| {
+                    let le = match le.as_ref() {
+                        "+Inf" | "inf" => f64::INFINITY,
+                        le => le
+                            .parse::<f64>()
+                            .map_err(|_| Error::from(format!("Invalid bucket bound {le}")))?,
+                    };
+                    let count = count
+                        .cast_f64()
+                        .ok_or_else(|| Error::from("Bucket count is not a number"))?;
+                    Ok((le, count))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            buckets.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+            let count = value
+                .get("count")
+                .and_then(ValueAccess::cast_f64)
+                .ok_or_else(|| Error::from("Histogram without count"))?;
+            if buckets.last().map_or(true, |(le, _)| le.is_finite()) {
+                buckets.push((f64::INFINITY, count));
+            }
+            Ok(Self::Histogram {
+                buckets,
+                sum: value
+                    .get("sum")
+                    .and_then(ValueAccess::cast_f64)
+                    .unwrap_or_default(),
+                count,
+            })
+        } else {
+            value
+                .cast_f64()
+                .map(Self::Number)
+                .ok_or_else(|| Error::from("Metric value is not a number"))
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Series {
+    value: SampleValue,
+    /// when the series was last updated, in nanoseconds
+    updated: u64,
+}
+
+type Labels = Vec<(String, String)>;
+
+#[derive(Debug, Default)]
+struct Family {
+    kind: Kind,
+    help: Option<String>,
+    series: BTreeMap<Labels, Series>,
+}
+
+/// The type and help text of metric families, as given in the event metadata
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct FamilyMeta {
+    #[serde(default, rename = "type")]
+    pub(crate) kind: Kind,
+    #[serde(default)]
+    pub(crate) help: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Registry {
+    families: BTreeMap<String, Family>,
+}
+
+impl Registry {
+    /// Records the samples of a metrics event, which needs to be valid
+    ///
+    /// # Errors
+    ///   * if a field does not fit the type of its family
+    pub(crate) fn update(&mut self, event: &Value, meta: &FamilyMeta, now: u64) -> Result<()> {
+        let measurement = event.get_str("measurement").unwrap_or_default();
+        let mut labels: Labels = event
+            .get_object("tags")
+            .map(|tags| {
+                tags.iter()
+                    .map(|(name, value)| {
+                        let value = value
+                            .as_str()
+                            .map_or_else(|| value.encode(), ToString::to_string);
+                        (label_name(name), value)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        labels.sort();
+        if let Some(fields) = event.get_object("fields") {
+            for (field, value) in fields.iter() {
+                let name = if field == VALUE_FIELD {
+                    metric_name(measurement)
+                } else {
+                    metric_name(&format!("{measurement}_{field}"))
+                };
+                let value = SampleValue::from_value(meta.kind, value)?;
+                let family = self.families.entry(name).or_default();
+                if family.kind != meta.kind {
+                    // the series of a family all need to be of the same type
+                    family.series.clear();
+                    family.kind = meta.kind;
+                }
+                if meta.help.is_some() {
+                    family.help = meta.help.clone();
+                }
+                family.series.insert(
+                    labels.clone(),
+                    Series {
+                        value,
+                        updated: now,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes all series that were last updated before `deadline`
+    pub(crate) fn expire(&mut self, deadline: u64) {
+        for family in self.families.values_mut() {
+            family.series.retain(|_, series| series.updated >= deadline);
+        }
+        self.families.retain(|_, family| !family.series.is_empty());
+    }
+
+    /// Renders all metric families in the text exposition format
+    pub(crate) fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, family) in &self.families {
+            if let Some(help) = &family.help {
+                let help = help.replace('\\', "\\\\").replace('\n', "\\n");
+                // writing to a string never fails
+                let _ = writeln!(out, "# HELP {name} {help}");
+            }
+            let _ = writeln!(out, "# TYPE {name} {}", family.kind.as_str());
+            for (labels, series) in &family.series {
+                match &series.value {
+                    SampleValue::Number(value) => {
+                        write_sample(&mut out, name, labels, None, *value);
+                    }
+                    SampleValue::Histogram {
+                        buckets,
+                        sum,
+                        count,
+                    } => {
+                        let bucket_name = format!("{name}_bucket");
+                        for (le, bucket_count) in buckets {
+                            let le = format_float(*le);
+                            write_sample(&mut out, &bucket_name, labels, Some(&le), *bucket_count);
+                        }
+                        write_sample(&mut out, &format!("{name}_sum"), labels, None, *sum);
+                        write_sample(&mut out, &format!("{name}_count"), labels, None, *count);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn write_sample(out: &mut String, name: &str, labels: &Labels, le: Option<&str>, value: f64) {
+    out.push_str(name);
+    if !labels.is_empty() || le.is_some() {
+        out.push('{');
+        let le = le.map(|le| ("le", le));
+        let labels = labels
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .chain(le);
+        for (i, (name, value)) in labels.enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            let _ = write!(out, "{name}=\"{value}\"");
+        }
+        out.push('}');
+    }
+    let _ = writeln!(out, " {}", format_float(value));
+}
+
+fn format_float(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value == f64::INFINITY {
+        "+Inf".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// metric names may only contain ASCII letters, digits, underscores and colons and may not start with a digit
+fn metric_name(name: &str) -> String {
+    sanitize(name, |c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+/// label names may only contain ASCII letters, digits and underscores and may not start with a digit
+fn label_name(name: &str) -> String {
+    sanitize(name, |c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn sanitize(name: &str, valid: impl Fn(char) -> bool) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if valid(c) { c } else { '_' })
+        .collect();
+    if sanitized
+        .chars()
+        .next()
+        .map_or(true, |c| c.is_ascii_digit())
+    {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tremor_value::literal;
+
+    #[test]
+    fn counters_and_gauges() -> Result<()> {
+        let mut registry = Registry::default();
+        let counter = FamilyMeta {
+            kind: Kind::Counter,
+            help: Some("Number of requests\nhandled".to_string()),
+        };
+        registry.update(
+            &literal!({
+                "measurement": "http_requests_total",
+                "tags": {"method": "GET", "path": "/\"snot\""},
+                "fields": {"value": 3},
+                "timestamp": 1
+            }),
+            &counter,
+            1,
+        )?;
+        registry.update(
+            &literal!({
+                "measurement": "http_requests_total",
+                "tags": {"method": "POST", "path": "/"},
+                "fields": {"value": 1},
+                "timestamp": 1
+            }),
+            &counter,
+            1,
+        )?;
+        // the latest value wins
+        registry.update(
+            &literal!({
+                "measurement": "http_requests_total",
+                "tags": {"path": "/", "method": "POST"},
+                "fields": {"value": 2},
+                "timestamp": 2
+            }),
+            &counter,
+            2,
+        )?;
+        registry.update(
+            &literal!({
+                "measurement": "system.cpu",
+                "tags": {"1core": 1},
+                "fields": {"idle": 0.5, "user": 0.25},
+                "timestamp": 1
+            }),
+            &FamilyMeta {
+                kind: Kind::Gauge,
+                help: None,
+            },
+            2,
+        )?;
+        assert_eq!(
+            "# HELP http_requests_total Number of requests\\nhandled
+# TYPE http_requests_total counter
+http_requests_total{method=\"GET\",path=\"/\\\"snot\\\"\"} 3
+http_requests_total{method=\"POST\",path=\"/\"} 2
+# TYPE system_cpu_idle gauge
+system_cpu_idle{_1core=\"1\"} 0.5
+# TYPE system_cpu_user gauge
+system_cpu_user{_1core=\"1\"} 0.25
+",
+            registry.render()
+        );
+
+        registry.expire(2);
+        assert!(!registry.render().contains("method=\"GET\""));
+        assert!(registry.render().contains("method=\"POST\""));
+        Ok(())
+    }
+
+    #[test]
+    fn histograms() -> Result<()> {
+        let mut registry = Registry::default();
+        let meta = FamilyMeta {
+            kind: Kind::Histogram,
+            help: None,
+        };
+        registry.update(
+            &literal!({
+                "measurement": "latency_seconds",
+                "tags": {},
+                "fields": {"value": {"buckets": {"1": 7, "0.1": 3}, "sum": 4.5, "count": 9}},
+                "timestamp": 1
+            }),
+            &meta,
+            1,
+        )?;
+        assert_eq!(
+            "# TYPE latency_seconds histogram
+latency_seconds_bucket{le=\"0.1\"} 3
+latency_seconds_bucket{le=\"1\"} 7
+latency_seconds_bucket{le=\"+Inf\"} 9
+latency_seconds_sum 4.5
+latency_seconds_count 9
+",
+            registry.render()
+        );
+
+        // a histogram needs buckets
+        assert!(registry
+            .update(
+                &literal!({
+                    "measurement": "latency_seconds",
+                    "tags": {},
+                    "fields": {"value": 1},
+                    "timestamp": 1
+                }),
+                &meta,
+                1,
+            )
+            .is_err());
+        Ok(())
+    }
+}
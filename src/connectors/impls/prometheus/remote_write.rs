@@ -0,0 +1,427 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Prometheus remote write receiver
+//!
+//! Accepts snappy compressed protobuf `WriteRequest`s `POST`ed to `path` and emits every sample
+//! as a metrics event. The `__name__` label is the measurement, all other labels are tags,
+//! the sample value is the `value` field. Samples that are not finite, such as staleness markers,
+//! are skipped. The type, help and unit of a metric family are added to the event metadata
+//! if the request contains them. Requests larger than `max_body_size` bytes, compressed or
+//! decompressed, are rejected.
+
+use super::{
+    endpoint, listen,
+    prompb::{MetricMetadata, MetricType, WriteRequest},
+    PrometheusDefaults, VALUE_FIELD,
+};
+use crate::connectors::{
+    prelude::*, utils::tls::ReloadingServerConfig, utils::tls::TLSServerConfig,
+};
+use async_std::channel::{bounded, Receiver, Sender};
+use async_std::task::JoinHandle;
+use http_types::{headers, StatusCode};
+use prost::Message;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+
+pub(crate) const CONNECTOR_TYPE: &str = "prometheus_remote_write";
+const NAME_LABEL: &str = "__name__";
+const SNAPPY: &str = "snappy";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// the address to listen on
+    #[serde(default = "Default::default")]
+    url: Url<PrometheusDefaults>,
+    /// the path remote write requests are sent to
+    #[serde(default = "default_path")]
+    path: String,
+    /// Optional TLS configuration
+    #[serde(default = "Default::default")]
+    tls: Option<TLSServerConfig>,
+    /// maximum size of a request body in bytes, compressed or decompressed
+    #[serde(default = "default_max_body_size")]
+    max_body_size: usize,
+}
+
+fn default_path() -> String {
+    "/api/v1/write".to_string()
+}
+
+fn default_max_body_size() -> usize {
+    // 32MiB
+    32 * 1024 * 1024
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Debug, Default)]
+pub(crate) struct Builder {}
+
+#[async_trait::async_trait]
+impl ConnectorBuilder for Builder {
+    fn connector_type(&self) -> ConnectorType {
+        CONNECTOR_TYPE.into()
+    }
+
+    async fn build_cfg(
+        &self,
+        _alias: &Alias,
+        _config: &ConnectorConfig,
+        connector_config: &Value,
+        _kill_switch: &KillSwitch,
+    ) -> Result<Box<dyn Connector>> {
+        let config = Config::new(connector_config)?;
+        let tls_server_config = config
+            .tls
+            .as_ref()
+            .map(ReloadingServerConfig::new)
+            .transpose()?;
+        let (tx, rx) = bounded(QSIZE.load(Ordering::Relaxed));
+        Ok(Box::new(RemoteWrite {
+            config,
+            tls_server_config,
+            accept_task: None,
+            tx,
+            rx,
+        }))
+    }
+}
+
+struct RemoteWrite {
+    config: Config,
+    tls_server_config: Option<ReloadingServerConfig>,
+    accept_task: Option<JoinHandle<()>>,
+    tx: Sender<(WriteRequest, Option<SocketAddr>)>,
+    rx: Receiver<(WriteRequest, Option<SocketAddr>)>,
+}
+
+#[async_trait::async_trait]
+impl Connector for RemoteWrite {
+    fn codec_requirements(&self) -> CodecReq {
+        CodecReq::Structured
+    }
+
+    async fn create_source(
+        &mut self,
+        source_context: SourceContext,
+        builder: SourceManagerBuilder,
+    ) -> Result<Option<SourceAddr>> {
+        let source = RemoteWriteSource {
+            origin_uri: EventOriginUri {
+                scheme: "tremor-prometheus-remote-write".to_string(),
+                host: hostname(),
+                port: None,
+                path: vec![],
+            },
+            rx: self.rx.clone(),
+            pending: VecDeque::new(),
+        };
+        builder.spawn(source, source_context).map(Some)
+    }
+
+    async fn connect(&mut self, ctx: &ConnectorContext, _attempt: &Attempt) -> Result<bool> {
+        let endpoint = endpoint(&self.config.url)?;
+        if let Some(previous_handle) = self.accept_task.take() {
+            previous_handle.cancel().await;
+        }
+
+        let mut server = tide::Server::with_state(State {
+            tx: self.tx.clone(),
+            max_body_size: self.config.max_body_size,
+        });
+        server.at(&self.config.path).post(handle_write);
+        self.accept_task = Some(spawn_task(
+            ctx.clone(),
+            listen(
+                server,
+                endpoint,
+                self.tls_server_config.clone(),
+                ctx.clone(),
+            ),
+        ));
+        Ok(true)
+    }
+
+    async fn on_stop(&mut self, _ctx: &ConnectorContext) -> Result<()> {
+        if let Some(accept_task) = self.accept_task.take() {
+            accept_task.cancel().await;
+        }
+        Ok(())
+    }
+}
+
+/// State of the remote write server
+#[derive(Clone)]
+struct State {
+    tx: Sender<(WriteRequest, Option<SocketAddr>)>,
+    max_body_size: usize,
+}
+
+async fn handle_write(mut req: tide::Request<State>) -> tide::Result {
+    match req
+        .header(headers::CONTENT_ENCODING)
+        .map(|v| v.last().as_str())
+    {
+        Some(SNAPPY) => (),
+        other => {
+            let body = format!("Expected Content-Encoding {SNAPPY}, got {other:?}");
+            return Ok(tide::Response::builder(StatusCode::UnsupportedMediaType)
+                .body(body)
+                .build());
+        }
+    }
+    let max_body_size = req.state().max_body_size;
+    if req.len().map_or(false, |len| len > max_body_size) {
+        return Ok(tide::Response::new(StatusCode::PayloadTooLarge));
+    }
+    let remote = req.peer_addr().and_then(|addr| addr.parse().ok());
+    let body = req.body_bytes().await?;
+    if body.len() > max_body_size {
+        return Ok(tide::Response::new(StatusCode::PayloadTooLarge));
+    }
+    match decode(&body, max_body_size) {
+        Ok(request) => {
+            if req.state().tx.send((request, remote)).await.is_err() {
+                return Ok(tide::Response::new(StatusCode::ServiceUnavailable));
+            }
+            Ok(tide::Response::new(StatusCode::NoContent))
+        }
+        Err(e) => Ok(tide::Response::builder(StatusCode::BadRequest)
+            .body(e.to_string())
+            .build()),
+    }
+}
+
+/// decodes a request, `limit` is the maximum decompressed size
+fn decode(body: &[u8], limit: usize) -> Result<WriteRequest> {
+    // checked before decompressing, the buffer is allocated with the length from the header
+    let len = snap::raw::decompress_len(body)?;
+    if len > limit {
+        return Err(
+            format!("Decompressed body of {len} bytes exceeds the limit of {limit} bytes").into(),
+        );
+    }
+    let data = snap::raw::Decoder::new().decompress_vec(body)?;
+    Ok(WriteRequest::decode(data.as_slice())?)
+}
+
+/// Converts all finite samples of `request` into metrics events and their metadata
+fn to_metrics(request: WriteRequest) -> Vec<(Value<'static>, Option<Value<'static>>)> {
+    let metadata: HashMap<String, MetricMetadata> = request
+        .metadata
+        .into_iter()
+        .map(|metadata| (metadata.metric_family_name.clone(), metadata))
+        .collect();
+    let mut metrics = Vec::new();
+    for series in request.timeseries {
+        let mut measurement = String::new();
+        let mut tags = Value::object_with_capacity(series.labels.len());
+        for label in series.labels {
+            if label.name == NAME_LABEL {
+                measurement = label.value;
+            } else {
+                tags.try_insert(label.name, label.value);
+            }
+        }
+        let meta = family_metadata(&metadata, &measurement).map(|metadata| {
+            let kind = MetricType::from_i32(metadata.r#type).unwrap_or(MetricType::Unknown);
+            literal!({
+                "type": kind.as_str(),
+                "help": metadata.help.clone(),
+                "unit": metadata.unit.clone()
+            })
+        });
+        for sample in series.samples {
+            if !sample.value.is_finite() {
+                continue;
+            }
+            let timestamp = u64::try_from(sample.timestamp)
+                .unwrap_or_default()
+                .saturating_mul(1_000_000);
+            let mut fields = Value::object_with_capacity(1);
+            fields.try_insert(VALUE_FIELD, sample.value);
+            metrics.push((
+                literal!({
+                    "measurement": measurement.clone(),
+                    "tags": tags.clone(),
+                    "fields": fields,
+                    "timestamp": timestamp
+                }),
+                meta.clone(),
+            ));
+        }
+    }
+    metrics
+}
+
+/// The metadata of the family a series belongs to, the series of histograms and summaries have suffixes
+fn family_metadata<'m>(
+    metadata: &'m HashMap<String, MetricMetadata>,
+    name: &str,
+) -> Option<&'m MetricMetadata> {
+    metadata.get(name).or_else(|| {
+        ["_bucket", "_sum", "_count"]
+            .iter()
+            .find_map(|suffix| name.strip_suffix(suffix))
+            .and_then(|family| metadata.get(family))
+    })
+}
+
+struct RemoteWriteSource {
+    origin_uri: EventOriginUri,
+    rx: Receiver<(WriteRequest, Option<SocketAddr>)>,
+    /// metrics of the last request that still need to be sent on
+    pending: VecDeque<(Value<'static>, Value<'static>, EventOriginUri)>,
+}
+
+#[async_trait::async_trait()]
+impl Source for RemoteWriteSource {
+    async fn pull_data(&mut self, _pull_id: &mut u64, ctx: &SourceContext) -> Result<SourceReply> {
+        while self.pending.is_empty() {
+            let (request, remote) = self.rx.recv().await?;
+            let mut origin_uri = self.origin_uri.clone();
+            if let Some(remote) = remote {
+                origin_uri.host = remote.ip().to_string();
+                origin_uri.port = Some(remote.port());
+            }
+            self.pending
+                .extend(to_metrics(request).into_iter().map(|(metric, meta)| {
+                    let meta = ctx.meta(meta.unwrap_or_else(|| Value::object_with_capacity(0)));
+                    (metric, meta, origin_uri.clone())
+                }));
+        }
+        let (metric, meta, origin_uri) = self
+            .pending
+            .pop_front()
+            .ok_or_else(|| Error::from("No pending metrics"))?;
+        Ok(SourceReply::Structured {
+            origin_uri,
+            payload: (metric, meta).into(),
+            stream: DEFAULT_STREAM_ID,
+            port: None,
+        })
+    }
+
+    fn is_transactional(&self) -> bool {
+        false
+    }
+
+    fn asynchronous(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::impls::prometheus::prompb::{Label, Sample, TimeSeries};
+
+    fn label(name: &str, value: &str) -> Label {
+        Label {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn decode_and_normalize() -> Result<()> {
+        let request = WriteRequest {
+            timeseries: vec![
+                TimeSeries {
+                    labels: vec![
+                        label(NAME_LABEL, "http_requests_total"),
+                        label("method", "GET"),
+                    ],
+                    samples: vec![
+                        Sample {
+                            value: 1.0,
+                            timestamp: 1_665_150_000_000,
+                        },
+                        Sample {
+                            value: f64::NAN,
+                            timestamp: 1_665_150_015_000,
+                        },
+                    ],
+                },
+                TimeSeries {
+                    labels: vec![
+                        label(NAME_LABEL, "latency_seconds_bucket"),
+                        label("le", "0.1"),
+                    ],
+                    samples: vec![Sample {
+                        value: 3.0,
+                        timestamp: 1_665_150_000_000,
+                    }],
+                },
+            ],
+            metadata: vec![MetricMetadata {
+                r#type: MetricType::Histogram as i32,
+                metric_family_name: "latency_seconds".to_string(),
+                help: "Request latency".to_string(),
+                unit: "seconds".to_string(),
+            }],
+        };
+        let body = snap::raw::Encoder::new().compress_vec(&request.encode_to_vec())?;
+        let metrics = to_metrics(decode(&body, default_max_body_size())?);
+
+        assert_eq!(
+            vec![
+                (
+                    literal!({
+                        "measurement": "http_requests_total",
+                        "tags": {"method": "GET"},
+                        "fields": {"value": 1.0},
+                        "timestamp": 1_665_150_000_000_000_000_u64
+                    }),
+                    None
+                ),
+                (
+                    literal!({
+                        "measurement": "latency_seconds_bucket",
+                        "tags": {"le": "0.1"},
+                        "fields": {"value": 3.0},
+                        "timestamp": 1_665_150_000_000_000_000_u64
+                    }),
+                    Some(literal!({
+                        "type": "histogram",
+                        "help": "Request latency",
+                        "unit": "seconds"
+                    }))
+                )
+            ],
+            metrics
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_body() -> Result<()> {
+        // not snappy compressed
+        assert!(decode(b"snot", default_max_body_size()).is_err());
+        // not a protobuf message
+        let body = snap::raw::Encoder::new().compress_vec(b"\xff\xff")?;
+        assert!(decode(&body, default_max_body_size()).is_err());
+        // too large once decompressed
+        let body = snap::raw::Encoder::new().compress_vec(&[0; 1024])?;
+        let err = decode(&body, 1023).err().map(|e| e.to_string());
+        assert_eq!(
+            Some("Decompressed body of 1024 bytes exceeds the limit of 1023 bytes".to_string()),
+            err
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,376 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Replays dead letters, the events sources send to their `err` port when data fails to be preprocessed or decoded.
+//!
+//! The `data` of every dead letter received by the sink is emitted by the source again,
+//! running through the preprocessors and codec configured for this connector.
+//! Every dead letter is replayed on its own stream, which is ended right away, so data buffered
+//! by preprocessors is flushed. The event metadata and the `origin_uri` of the dead letter are kept.
+//! Dead letters are acked once all of their replayed events are, including the events
+//! emitted when their stream is ended, failed once any of them fails and acked if they do
+//! not result in any events. Dead letters of codec failures carry no data if the source
+//! sending them disables `dead_letter_data`, dead letters without data are failed.
+
+use crate::connectors::prelude::*;
+use async_std::channel::{bounded, Receiver, Sender};
+use std::collections::HashMap;
+use tremor_common::time::nanotime;
+
+const CONNECTOR_TYPE: &str = "replay";
+
+#[derive(Default, Debug)]
+pub(crate) struct Builder {}
+
+#[async_trait::async_trait]
+impl ConnectorBuilder for Builder {
+    fn connector_type(&self) -> ConnectorType {
+        CONNECTOR_TYPE.into()
+    }
+
+    async fn build(
+        &self,
+        _alias: &Alias,
+        _config: &ConnectorConfig,
+        _kill_switch: &KillSwitch,
+    ) -> Result<Box<dyn Connector>> {
+        let (tx, rx) = bounded(QSIZE.load(Ordering::Relaxed));
+        Ok(Box::new(Replay { tx, rx }))
+    }
+}
+
+/// a dead letter handed from the sink to the source
+struct DeadLetter {
+    data: Vec<u8>,
+    meta: Value<'static>,
+    origin_uri: Option<EventOriginUri>,
+    /// for acking or failing the dead letter event
+    contraflow: Option<(ContraflowData, Sender<AsyncSinkReply>)>,
+    start: u64,
+}
+
+struct Replay {
+    tx: Sender<DeadLetter>,
+    rx: Receiver<DeadLetter>,
+}
+
+#[async_trait::async_trait]
+impl Connector for Replay {
+    async fn create_source(
+        &mut self,
+        source_context: SourceContext,
+        builder: SourceManagerBuilder,
+    ) -> Result<Option<SourceAddr>> {
+        let source = ReplaySource {
+            rx: self.rx.clone(),
+            origin_uri: EventOriginUri {
+                scheme: "tremor-replay".to_string(),
+                host: hostname(),
+                port: None,
+                path: vec![],
+            },
+            next_stream: DEFAULT_STREAM_ID + 1,
+            ending: None,
+            pending: HashMap::new(),
+        };
+        builder.spawn(source, source_context).map(Some)
+    }
+
+    async fn create_sink(
+        &mut self,
+        sink_context: SinkContext,
+        builder: SinkManagerBuilder,
+    ) -> Result<Option<SinkAddr>> {
+        let sink = ReplaySink {
+            tx: self.tx.clone(),
+            reply_tx: builder.reply_tx(),
+        };
+        builder.spawn(sink, sink_context).map(Some)
+    }
+
+    fn codec_requirements(&self) -> CodecReq {
+        CodecReq::Required
+    }
+}
+
+/// Extracts the data, metadata and origin of a dead letter
+fn dead_letter(
+    value: &Value,
+    meta: &Value,
+) -> Option<(Vec<u8>, Value<'static>, Option<EventOriginUri>)> {
+    let data = value
+        .get_bytes("data")
+        .filter(|data| !data.is_empty())?
+        .to_vec();
+    let origin_uri = value
+        .get_str("origin_uri")
+        .and_then(|uri| EventOriginUri::parse(uri).ok());
+    let mut meta = meta.clone_static();
+    // the error is added to the metadata of dead letters
+    if let Some(meta) = meta.as_object_mut() {
+        meta.remove("error");
+    }
+    Some((data, meta, origin_uri))
+}
+
+struct ReplaySink {
+    tx: Sender<DeadLetter>,
+    reply_tx: Sender<AsyncSinkReply>,
+}
+
+#[async_trait::async_trait]
+impl Sink for ReplaySink {
+    async fn on_event(
+        &mut self,
+        _input: &str,
+        event: Event,
+        ctx: &SinkContext,
+        _serializer: &mut EventSerializer,
+        start: u64,
+    ) -> Result<SinkReply> {
+        let contraflow = if event.transactional {
+            Some((ContraflowData::from(&event), self.reply_tx.clone()))
+        } else {
+            None
+        };
+        let mut res = SinkReply::NONE;
+        for (value, meta) in event.value_meta_iter() {
+            if let Some((data, meta, origin_uri)) = dead_letter(value, meta) {
+                let dead_letter = DeadLetter {
+                    data,
+                    meta,
+                    origin_uri,
+                    contraflow: contraflow.clone(),
+                    start,
+                };
+                if self.tx.send(dead_letter).await.is_err() {
+                    error!("{ctx} Error handing dead letter to the source.");
+                    res = SinkReply::FAIL;
+                }
+            } else {
+                error!("{ctx} Invalid dead letter without data.");
+                res = SinkReply::FAIL;
+            }
+        }
+        Ok(res)
+    }
+
+    fn auto_ack(&self) -> bool {
+        false
+    }
+
+    fn asynchronous(&self) -> bool {
+        true
+    }
+}
+
+/// a dead letter replayed by the source
+struct Pending {
+    /// for acking or failing the dead letter event
+    contraflow: (ContraflowData, Sender<AsyncSinkReply>),
+    start: u64,
+    /// the pull the data of the dead letter was emitted with
+    data_pull: u64,
+    /// if the data pull resulted in events
+    data_events: bool,
+    /// the pull the stream was ended with, once it is ended, and if it resulted in events
+    end_pull: Option<(u64, bool)>,
+    /// the latest pull of the replay acked so far
+    acked: Option<u64>,
+}
+
+impl Pending {
+    /// if the last pull of the replay resulting in events is acked.
+    /// Preprocessors might buffer data, so the last events are only emitted when the stream is ended.
+    fn is_acked(&self) -> bool {
+        let last_pull = match self.end_pull {
+            // the stream is not ended yet
+            None => return false,
+            Some((end_pull, true)) => Some(end_pull),
+            Some((_, false)) => self.data_events.then_some(self.data_pull),
+        };
+        // the replay did not result in any events if there is no last pull
+        last_pull.map_or(true, |last_pull| {
+            self.acked.map_or(false, |acked| acked >= last_pull)
+        })
+    }
+}
+
+struct ReplaySource {
+    rx: Receiver<DeadLetter>,
+    origin_uri: EventOriginUri,
+    /// the stream of the next dead letter
+    next_stream: u64,
+    /// the stream of the last dead letter, ended with the next pull
+    ending: Option<(u64, EventOriginUri, Value<'static>)>,
+    /// dead letter events to ack or fail, by the stream of their replay
+    pending: HashMap<u64, Pending>,
+}
+
+impl ReplaySource {
+    async fn reply(&mut self, stream_id: u64, ack: bool, ctx: &SourceContext) {
+        if let Some(Pending {
+            contraflow: (contraflow, reply_tx),
+            start,
+            ..
+        }) = self.pending.remove(&stream_id)
+        {
+            let reply = if ack {
+                AsyncSinkReply::Ack(contraflow, nanotime().saturating_sub(start))
+            } else {
+                AsyncSinkReply::Fail(contraflow)
+            };
+            ctx.swallow_err(reply_tx.send(reply).await, "Error replying to dead letter");
+        }
+    }
+
+    /// acks the dead letter replayed on `stream_id` once all of its events are acked
+    async fn ack_if_done(&mut self, stream_id: u64, ctx: &SourceContext) {
+        if self
+            .pending
+            .get(&stream_id)
+            .map_or(false, Pending::is_acked)
+        {
+            self.reply(stream_id, true, ctx).await;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Source for ReplaySource {
+    async fn pull_data(&mut self, pull_id: &mut u64, _ctx: &SourceContext) -> Result<SourceReply> {
+        if let Some((stream, origin_uri, meta)) = self.ending.take() {
+            if let Some(pending) = self.pending.get_mut(&stream) {
+                // until `on_no_events` tells otherwise
+                pending.end_pull = Some((*pull_id, true));
+            }
+            return Ok(SourceReply::EndStream {
+                origin_uri,
+                stream,
+                meta: Some(meta),
+            });
+        }
+        let DeadLetter {
+            data,
+            meta,
+            origin_uri,
+            contraflow,
+            start,
+        } = self.rx.recv().await?;
+        let stream = self.next_stream;
+        self.next_stream += 1;
+        if let Some(contraflow) = contraflow {
+            self.pending.insert(
+                stream,
+                Pending {
+                    contraflow,
+                    start,
+                    data_pull: *pull_id,
+                    data_events: true,
+                    end_pull: None,
+                    acked: None,
+                },
+            );
+        }
+        let origin_uri = origin_uri.unwrap_or_else(|| self.origin_uri.clone());
+        self.ending = Some((stream, origin_uri.clone(), meta.clone()));
+        Ok(SourceReply::Data {
+            origin_uri,
+            data,
+            meta: Some(meta),
+            stream: Some(stream),
+            port: None,
+            codec_overwrite: None,
+        })
+    }
+
+    async fn on_no_events(&mut self, pull_id: u64, stream: u64, ctx: &SourceContext) -> Result<()> {
+        if let Some(pending) = self.pending.get_mut(&stream) {
+            if pending.data_pull == pull_id {
+                pending.data_events = false;
+            } else if let Some((end_pull, end_events)) = pending.end_pull.as_mut() {
+                if *end_pull == pull_id {
+                    *end_events = false;
+                }
+            }
+        }
+        self.ack_if_done(stream, ctx).await;
+        Ok(())
+    }
+
+    async fn ack(&mut self, stream_id: u64, pull_id: u64, ctx: &SourceContext) -> Result<()> {
+        if let Some(pending) = self.pending.get_mut(&stream_id) {
+            pending.acked = pending.acked.max(Some(pull_id));
+        }
+        self.ack_if_done(stream_id, ctx).await;
+        Ok(())
+    }
+
+    async fn fail(&mut self, stream_id: u64, _pull_id: u64, ctx: &SourceContext) -> Result<()> {
+        // failing any replayed event fails the dead letter
+        self.reply(stream_id, false, ctx).await;
+        Ok(())
+    }
+
+    fn is_transactional(&self) -> bool {
+        true
+    }
+
+    fn asynchronous(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dead_letter() {
+        let value = literal!({
+            "error": "SIMD JSON error: InternalError at character 0 ('}')",
+            "source": "flow::in",
+            "stream_id": 0,
+            "pull_id": 1,
+            "stage": "codec",
+            "name": "json",
+            "data": Value::Bytes("}".as_bytes().into()),
+            "origin_uri": "tremor-file://localhost/snot.json"
+        });
+        let meta = literal!({
+            "error": "SIMD JSON error: InternalError at character 0 ('}')",
+            "file": {"path": "snot.json"}
+        });
+        let (data, meta, origin_uri) = dead_letter(&value, &meta).expect("invalid dead letter");
+        assert_eq!(b"}".to_vec(), data);
+        assert_eq!(literal!({"file": {"path": "snot.json"}}), meta);
+        assert_eq!(
+            Some(EventOriginUri {
+                scheme: "tremor-file".to_string(),
+                host: "localhost".to_string(),
+                port: None,
+                path: vec!["snot.json".to_string()]
+            }),
+            origin_uri
+        );
+
+        assert!(dead_letter(&literal!({"error": "snot"}), &Value::object()).is_none());
+        // codec failures only carry data if requested
+        assert!(dead_letter(
+            &literal!({"error": "snot", "data": Value::Bytes(b"".as_slice().into())}),
+            &Value::object()
+        )
+        .is_none());
+    }
+}
@@ -13,5 +13,6 @@
 // limitations under the License.
 
 mod auth;
+mod checkpoint;
 pub(crate) mod reader;
 pub(crate) mod streamer;
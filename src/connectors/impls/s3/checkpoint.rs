@@ -0,0 +1,369 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tracks which keys of a bucket have been fully processed
+//!
+//! A key is done once the object has been read completely and all pulls that carried its data
+//! have been acknowledged. Acks for a stream are expected to arrive in order, so an ack
+//! for a pull also resolves all earlier pulls of that stream.
+//!
+//! S3 lists keys in lexicographical order, so the checkpoint is a high-water mark
+//! (`start_after`) below which all keys are done, plus the done keys above it that are still waiting
+//! for an earlier key to complete.
+//!
+//! Tracking is synchronous, the changed checkpoint is taken via `KeyTracker::changes` and written
+//! to a local file separately.
+
+use crate::errors::Result;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+
+/// The persisted checkpoint
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+struct Checkpoint {
+    /// all listed keys up to and including this one are done
+    start_after: Option<String>,
+    /// done keys after `start_after`
+    completed: BTreeSet<String>,
+}
+
+/// A changed checkpoint, not yet written
+#[derive(Debug)]
+pub(crate) struct PendingCheckpoint {
+    path: PathBuf,
+    checkpoint: Checkpoint,
+}
+
+impl PendingCheckpoint {
+    /// Writes the checkpoint to its file
+    ///
+    /// # Errors
+    ///   * if the checkpoint can not be written
+    pub(crate) async fn write(self) -> Result<()> {
+        // write and rename, so a crash never leaves a partially written checkpoint behind
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        async_std::fs::write(&tmp, simd_json::to_vec(&self.checkpoint)?).await?;
+        async_std::fs::rename(&tmp, &self.path).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum KeyState {
+    InFlight,
+    Done,
+    Failed,
+}
+
+#[derive(Debug)]
+struct StreamState {
+    key: String,
+    /// pulls that have not been acked or failed yet
+    pending: BTreeSet<u64>,
+    /// the object has been read completely
+    ended: bool,
+    failed: bool,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct KeyTracker {
+    path: Option<PathBuf>,
+    start_after: Option<String>,
+    keys: BTreeMap<String, KeyState>,
+    streams: HashMap<u64, StreamState>,
+    /// stream id for the next claimed key, unique across reconnects
+    next_stream: u64,
+    /// the checkpoint changed since it was last taken
+    changed: bool,
+}
+
+impl KeyTracker {
+    /// Loads the checkpoint from `path` if the file exists, without a `path` nothing is persisted
+    ///
+    /// # Errors
+    ///   * if the checkpoint file can not be read or is invalid
+    pub(crate) async fn load(path: Option<PathBuf>) -> Result<Self> {
+        let checkpoint = match &path {
+            Some(path) if async_std::path::Path::new(path).exists().await => {
+                let mut data = async_std::fs::read(path).await?;
+                simd_json::from_slice::<Checkpoint>(&mut data).map_err(|e| {
+                    format!("Invalid checkpoint file {}: {e}", path.to_string_lossy())
+                })?
+            }
+            _ => Checkpoint::default(),
+        };
+        Ok(Self {
+            path,
+            start_after: checkpoint.start_after,
+            keys: checkpoint
+                .completed
+                .into_iter()
+                .map(|key| (key, KeyState::Done))
+                .collect(),
+            streams: HashMap::new(),
+            next_stream: 0,
+            changed: false,
+        })
+    }
+
+    /// The key after which listing can start
+    pub(crate) fn start_after(&self) -> Option<&str> {
+        self.start_after.as_deref()
+    }
+
+    /// Returns the stream to read `key` with, if it still needs to be read
+    pub(crate) fn claim(&mut self, key: &str) -> Option<u64> {
+        if self.start_after.as_deref() >= Some(key) {
+            return None;
+        }
+        match self.keys.get(key) {
+            Some(KeyState::Done | KeyState::InFlight) => None,
+            Some(KeyState::Failed) | None => {
+                let stream = self.next_stream;
+                self.next_stream += 1;
+                self.keys.insert(key.to_string(), KeyState::InFlight);
+                self.streams.insert(
+                    stream,
+                    StreamState {
+                        key: key.to_string(),
+                        pending: BTreeSet::new(),
+                        ended: false,
+                        failed: false,
+                    },
+                );
+                Some(stream)
+            }
+        }
+    }
+
+    /// Forgets about all keys in flight, they are read again
+    pub(crate) fn reset(&mut self) {
+        self.streams.clear();
+        self.keys.retain(|_, state| *state == KeyState::Done);
+    }
+
+    /// Data of `stream` has been sent with `pull_id`
+    pub(crate) fn pulled(&mut self, stream: u64, pull_id: u64) {
+        if let Some(state) = self.streams.get_mut(&stream) {
+            state.pending.insert(pull_id);
+        }
+    }
+
+    /// The object read via `stream` has been read completely
+    pub(crate) fn ended(&mut self, stream: u64) {
+        if let Some(state) = self.streams.get_mut(&stream) {
+            state.ended = true;
+        }
+        self.check_done(stream);
+    }
+
+    /// Reading the object via `stream` failed
+    pub(crate) fn stream_failed(&mut self, stream: u64) {
+        if let Some(state) = self.streams.get_mut(&stream) {
+            state.ended = true;
+            state.failed = true;
+        }
+        self.check_done(stream);
+    }
+
+    /// All pulls of `stream` up to `pull_id` have been acknowledged
+    pub(crate) fn ack(&mut self, stream: u64, pull_id: u64) {
+        self.resolve(stream, pull_id, false);
+        self.check_done(stream);
+    }
+
+    /// Events of `stream` from `pull_id` failed
+    pub(crate) fn fail(&mut self, stream: u64, pull_id: u64) {
+        self.resolve(stream, pull_id, true);
+        self.check_done(stream);
+    }
+
+    /// The data of `stream` pulled with `pull_id` did not create any events
+    pub(crate) fn no_events(&mut self, stream: u64, pull_id: u64) {
+        if let Some(state) = self.streams.get_mut(&stream) {
+            state.pending.remove(&pull_id);
+        }
+        self.check_done(stream);
+    }
+
+    /// The checkpoint to write, if it changed since it was last taken
+    pub(crate) fn changes(&mut self) -> Option<PendingCheckpoint> {
+        if !self.changed {
+            return None;
+        }
+        self.changed = false;
+        let path = self.path.clone()?;
+        let checkpoint = Checkpoint {
+            start_after: self.start_after.clone(),
+            completed: self
+                .keys
+                .iter()
+                .filter(|(_, state)| **state == KeyState::Done)
+                .map(|(key, _)| key.clone())
+                .collect(),
+        };
+        Some(PendingCheckpoint { path, checkpoint })
+    }
+
+    fn resolve(&mut self, stream: u64, pull_id: u64, failed: bool) {
+        if let Some(state) = self.streams.get_mut(&stream) {
+            state.pending = state.pending.split_off(&(pull_id + 1));
+            state.failed |= failed;
+        }
+    }
+
+    fn check_done(&mut self, stream: u64) {
+        let finished = self
+            .streams
+            .get(&stream)
+            .map_or(false, |state| state.ended && state.pending.is_empty());
+        if !finished {
+            return;
+        }
+        if let Some(StreamState { key, failed, .. }) = self.streams.remove(&stream) {
+            if failed {
+                self.keys.insert(key, KeyState::Failed);
+            } else {
+                self.keys.insert(key, KeyState::Done);
+                self.advance();
+                self.changed = true;
+            }
+        }
+    }
+
+    /// moves the high-water mark past all leading done keys
+    fn advance(&mut self) {
+        while let Some((key, KeyState::Done)) = self.keys.iter().next() {
+            let key = key.clone();
+            self.keys.remove(&key);
+            self.start_after = Some(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn persist(tracker: &mut KeyTracker) -> Result<()> {
+        if let Some(checkpoint) = tracker.changes() {
+            checkpoint.write().await?;
+        }
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn done_after_all_pulls_are_acked() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("checkpoint.json");
+        let mut tracker = KeyTracker::load(Some(path.clone())).await?;
+
+        assert_eq!(Some(0), tracker.claim("a"));
+        assert_eq!(Some(1), tracker.claim("b"));
+        assert_eq!(None, tracker.claim("a"));
+        tracker.pulled(0, 0);
+        tracker.pulled(1, 1);
+        tracker.pulled(0, 2);
+        tracker.pulled(1, 3);
+        tracker.ended(0);
+        tracker.ended(1);
+
+        // `b` is done before `a`
+        tracker.ack(1, 3);
+        assert_eq!(None, tracker.start_after());
+        tracker.ack(0, 0);
+        assert_eq!(None, tracker.start_after());
+        tracker.ack(0, 2);
+        assert_eq!(Some("b"), tracker.start_after());
+        assert_eq!(None, tracker.claim("b"));
+
+        // nothing is written until the changes are taken
+        assert!(KeyTracker::load(Some(path.clone()))
+            .await?
+            .start_after()
+            .is_none());
+        persist(&mut tracker).await?;
+        assert!(tracker.changes().is_none());
+
+        let tracker = KeyTracker::load(Some(path)).await?;
+        assert_eq!(Some("b"), tracker.start_after());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn completed_keys_above_the_high_water_mark_are_persisted() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("checkpoint.json");
+        let mut tracker = KeyTracker::load(Some(path.clone())).await?;
+
+        assert_eq!(Some(0), tracker.claim("a"));
+        assert_eq!(Some(1), tracker.claim("b"));
+        tracker.pulled(0, 0);
+        tracker.pulled(1, 1);
+        tracker.ended(0);
+        tracker.ended(1);
+        tracker.fail(0, 0);
+        tracker.ack(1, 1);
+        assert_eq!(None, tracker.start_after());
+        persist(&mut tracker).await?;
+
+        let mut tracker = KeyTracker::load(Some(path)).await?;
+        assert_eq!(None, tracker.start_after());
+        assert!(tracker.claim("a").is_some());
+        assert_eq!(None, tracker.claim("b"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn failed_keys_are_read_again() -> Result<()> {
+        let mut tracker = KeyTracker::load(None).await?;
+        assert_eq!(Some(0), tracker.claim("a"));
+        tracker.pulled(0, 0);
+        tracker.stream_failed(0);
+        assert_eq!(None, tracker.claim("a"));
+        tracker.ack(0, 0);
+        assert_eq!(Some(1), tracker.claim("a"));
+
+        // keys in flight are read again after a reset, with a new stream
+        assert_eq!(None, tracker.claim("a"));
+        tracker.reset();
+        assert_eq!(Some(2), tracker.claim("a"));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn pulls_without_events() -> Result<()> {
+        let mut tracker = KeyTracker::load(None).await?;
+        assert_eq!(Some(0), tracker.claim("a"));
+        tracker.pulled(0, 0);
+        tracker.pulled(0, 1);
+        tracker.ended(0);
+        // only the pull without events is resolved
+        tracker.no_events(0, 1);
+        assert_eq!(None, tracker.start_after());
+        tracker.ack(0, 0);
+        assert_eq!(Some("a"), tracker.start_after());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn invalid_checkpoint() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("checkpoint.json");
+        async_std::fs::write(&path, b"snot").await?;
+        assert!(KeyTracker::load(Some(path)).await.is_err());
+        Ok(())
+    }
+}
@@ -14,11 +14,15 @@
 use crate::connectors::prelude::*;
 use futures::stream::TryStreamExt;
 use std::error::Error as StdError;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
 
 use async_std::channel::{self, Receiver, Sender};
 use async_std::task::{self, JoinHandle};
 
 use super::auth;
+use super::checkpoint::KeyTracker;
 use aws_sdk_s3 as s3;
 use s3::model::Object;
 use s3::types::ByteStream;
@@ -49,6 +53,15 @@ pub(crate) struct S3SourceConfig {
 
     #[serde(default = "S3SourceConfig::default_max_connections")]
     max_connections: usize,
+
+    /// keep listing the bucket for new keys every `poll_interval_ms`
+    #[serde(default = "Default::default")]
+    tail: bool,
+    #[serde(default = "S3SourceConfig::default_poll_interval_ms")]
+    poll_interval_ms: u64,
+
+    /// file to persist the keys that have been fully processed in
+    checkpoint: Option<String>,
 }
 
 struct KeyPayload {
@@ -69,6 +82,15 @@ impl S3SourceConfig {
     fn default_max_connections() -> usize {
         10
     }
+
+    fn default_poll_interval_ms() -> u64 {
+        60_000
+    }
+
+    /// Only with acks we know when a key is done
+    fn is_transactional(&self) -> bool {
+        self.tail || self.checkpoint.is_some()
+    }
 }
 
 impl ConfigImpl for S3SourceConfig {}
@@ -93,9 +115,10 @@ impl ConnectorBuilder for Builder {
 
         // TODO: display a warning if chunksize lesser than some quantity
         Ok(Box::new(S3SourceConnector {
-            handles: Vec::with_capacity(config.max_connections),
+            handles: Vec::with_capacity(config.max_connections + 1),
             config,
             tx: None,
+            tracker: None,
         }))
     }
 }
@@ -103,6 +126,7 @@ impl ConnectorBuilder for Builder {
 struct S3SourceConnector {
     config: S3SourceConfig,
     tx: Option<Sender<SourceReply>>,
+    tracker: Option<Arc<Mutex<KeyTracker>>>,
     handles: Vec<JoinHandle<Result<()>>>,
 }
 
@@ -114,9 +138,16 @@ impl Connector for S3SourceConnector {
         builder: SourceManagerBuilder,
     ) -> Result<Option<SourceAddr>> {
         let (tx, rx) = channel::bounded(QSIZE.load(Ordering::Relaxed));
-        let s3_source = ChannelSource::from_channel(tx.clone(), rx);
+        let tracker = KeyTracker::load(self.config.checkpoint.as_ref().map(PathBuf::from)).await?;
+        let tracker = Arc::new(Mutex::new(tracker));
+        let s3_source = S3Source {
+            rx,
+            tracker: tracker.clone(),
+            transactional: self.config.is_transactional(),
+        };
 
         self.tx = Some(tx);
+        self.tracker = Some(tracker);
 
         let addr = builder.spawn(s3_source, source_context)?;
         Ok(Some(addr))
@@ -182,12 +213,31 @@ impl Connector for S3SourceConnector {
             self.handles.push(handle);
         }
 
+        // keys that were in flight when the previous connection was lost are read again
+        let tracker = self
+            .tracker
+            .clone()
+            .ok_or_else(|| ErrorKind::S3Error("key tracker not initialized".to_string()))?;
+        lock(&tracker).reset();
+
         // spawn key fetcher task
         let bucket = self.config.bucket.clone();
         let prefix = self.config.prefix.clone();
-        task::Builder::new()
+        let poll_interval = self
+            .config
+            .tail
+            .then_some(Duration::from_millis(self.config.poll_interval_ms));
+        let handle = task::Builder::new()
             .name("fetch_key_task".to_owned())
-            .spawn(fetch_keys_task(client, bucket, prefix, tx_key))?;
+            .spawn(fetch_keys_task(
+                client,
+                bucket,
+                prefix,
+                tracker,
+                poll_interval,
+                tx_key,
+            ))?;
+        self.handles.push(handle);
 
         Ok(true)
     }
@@ -205,54 +255,147 @@ impl Connector for S3SourceConnector {
     }
 }
 
+/// Lists the keys and hands the ones that still need to be read to the fetcher tasks.
+///
+/// With a `poll_interval` the bucket is listed again after every `poll_interval`,
+/// starting after the highest key all keys up to which are done.
 async fn fetch_keys_task(
     client: S3Client,
     bucket: String,
     prefix: Option<String>,
+    tracker: Arc<Mutex<KeyTracker>>,
+    poll_interval: Option<Duration>,
     sender: Sender<KeyPayload>,
 ) -> Result<()> {
-    let fetch_keys = |continuation_token: Option<String>| async {
+    let fetch_keys = |start_after: Option<String>, continuation_token: Option<String>| async {
         Result::<_>::Ok(
             client
                 .list_objects_v2()
                 .bucket(bucket.clone())
                 .set_prefix(prefix.clone())
+                .set_start_after(start_after)
                 .set_continuation_token(continuation_token)
                 .send()
                 .await?,
         )
     };
 
-    // fetch first page of keys.
-    let mut continuation_token: Option<String> = None;
-    let mut resp = fetch_keys(continuation_token.take()).await?;
-    debug!("Fetched {} keys of {}.", resp.key_count(), resp.max_keys());
-
-    let mut stream = 0; // for the Channel Source
     loop {
-        if let Some(entries) = resp.contents.take() {
-            for object_data in entries {
-                let p = KeyPayload {
-                    object_data,
-                    stream,
-                };
-                sender.send(p).await?;
-                stream += 1;
+        let start_after = lock(&tracker).start_after().map(ToString::to_string);
+        // fetch first page of keys.
+        let mut continuation_token: Option<String> = None;
+        let mut resp = fetch_keys(start_after.clone(), continuation_token.take()).await?;
+        debug!("Fetched {} keys of {}.", resp.key_count(), resp.max_keys());
+
+        loop {
+            if let Some(entries) = resp.contents.take() {
+                for object_data in entries {
+                    // the stream ids are kept by the tracker, so they stay unique across reconnects
+                    let stream = match object_data.key() {
+                        Some(key) => lock(&tracker).claim(key),
+                        None => None,
+                    };
+                    if let Some(stream) = stream {
+                        let p = KeyPayload {
+                            object_data,
+                            stream,
+                        };
+                        sender.send(p).await?;
+                    }
+                }
+            }
+
+            if resp.is_truncated {
+                continuation_token = resp.next_continuation_token().map(ToString::to_string);
+            } else {
+                // No more pages to fetch.
+                break;
             }
+
+            resp = fetch_keys(start_after.clone(), continuation_token.take()).await?;
         }
 
-        if resp.is_truncated {
-            continuation_token = resp.next_continuation_token().map(ToString::to_string);
+        if let Some(poll_interval) = poll_interval {
+            task::sleep(poll_interval).await;
         } else {
-            // No more pages to fetch.
             break;
         }
-
-        resp = fetch_keys(continuation_token.take()).await?;
     }
     Ok(())
 }
 
+fn lock(tracker: &Mutex<KeyTracker>) -> MutexGuard<KeyTracker> {
+    tracker.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Forwards the replies of the fetcher tasks and tracks which keys are done
+struct S3Source {
+    rx: Receiver<SourceReply>,
+    tracker: Arc<Mutex<KeyTracker>>,
+    transactional: bool,
+}
+
+impl S3Source {
+    /// writes the checkpoint if it changed
+    async fn persist(&self) -> Result<()> {
+        let changes = lock(&self.tracker).changes();
+        if let Some(checkpoint) = changes {
+            checkpoint.write().await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Source for S3Source {
+    async fn pull_data(&mut self, pull_id: &mut u64, ctx: &SourceContext) -> Result<SourceReply> {
+        // keys completed by the previous reply, written before waiting as the reply must not get lost
+        ctx.swallow_err(self.persist().await, "Error writing checkpoint");
+        let reply = self.rx.recv().await?;
+        if self.transactional {
+            let mut tracker = lock(&self.tracker);
+            match &reply {
+                SourceReply::Data {
+                    stream: Some(stream),
+                    ..
+                } => tracker.pulled(*stream, *pull_id),
+                SourceReply::EndStream { stream, .. } => tracker.ended(*stream),
+                SourceReply::StreamFail(stream) => tracker.stream_failed(*stream),
+                _ => (),
+            }
+        }
+        Ok(reply)
+    }
+
+    async fn on_no_events(
+        &mut self,
+        pull_id: u64,
+        stream: u64,
+        _ctx: &SourceContext,
+    ) -> Result<()> {
+        lock(&self.tracker).no_events(stream, pull_id);
+        self.persist().await
+    }
+
+    async fn ack(&mut self, stream_id: u64, pull_id: u64, _ctx: &SourceContext) -> Result<()> {
+        lock(&self.tracker).ack(stream_id, pull_id);
+        self.persist().await
+    }
+
+    async fn fail(&mut self, stream_id: u64, pull_id: u64, _ctx: &SourceContext) -> Result<()> {
+        lock(&self.tracker).fail(stream_id, pull_id);
+        self.persist().await
+    }
+
+    fn is_transactional(&self) -> bool {
+        self.transactional
+    }
+
+    fn asynchronous(&self) -> bool {
+        true
+    }
+}
+
 struct S3Instance {
     ctx: ConnectorContext,
     client: S3Client,
@@ -11,9 +11,13 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+use crate::connectors::sink::ContraflowData;
+use crate::connectors::utils::columnar::{self, ColumnarFile};
 use crate::Event;
 use crate::{connectors::prelude::*, errors::err_connector_def};
+use async_std::channel::Sender;
 use std::mem;
+use tremor_common::time::nanotime;
 use value_trait::ValueAccess;
 
 use super::auth;
@@ -33,6 +37,11 @@ pub(crate) struct S3Config {
 
     #[serde(default = "S3Config::fivembs")]
     min_part_size: usize,
+
+    /// write columnar files instead of using the codec,
+    /// events are acked once the file they are in has been uploaded
+    #[serde(default = "Default::default")]
+    columnar: Option<columnar::Config>,
 }
 
 // Defaults for the config.
@@ -104,6 +113,8 @@ impl Connector for S3Connector {
             upload_id: "".to_owned(),
             part_number: 0,
             min_part_size: self.config.min_part_size,
+            columnar_file: None,
+            reply_tx: builder.reply_tx(),
         };
 
         let addr = builder.spawn(s3_sink, sink_context)?;
@@ -111,7 +122,11 @@ impl Connector for S3Connector {
     }
 
     fn codec_requirements(&self) -> CodecReq {
-        CodecReq::Required
+        if self.config.columnar.is_some() {
+            CodecReq::Structured
+        } else {
+            CodecReq::Required
+        }
     }
 }
 
@@ -128,6 +143,10 @@ struct S3Sink {
     part_number: i32,
     min_part_size: usize,
     parts: Vec<CompletedPart>,
+
+    /// the columnar file written to the current key
+    columnar_file: Option<ColumnarFile>,
+    reply_tx: Sender<AsyncSinkReply>,
 }
 
 #[async_trait::async_trait]
@@ -160,20 +179,40 @@ impl Sink for S3Sink {
         _start: u64,
     ) -> Result<SinkReply> {
         let ingest_id = event.ingest_ns;
+        let contraflow = event.transactional.then(|| ContraflowData::from(&event));
 
-        for (event, meta) in event.value_meta_iter() {
-            // Handle no key in meta.
-
+        // the values of the event grouped by consecutive keys,
+        // checked before anything is written, so a failing event leaves nothing behind
+        let mut runs: Vec<(String, Vec<&Value>)> = Vec::new();
+        for (value, meta) in event.value_meta_iter() {
             let s3_meta = S3Meta::new(meta);
-
-            let object_key = if let Some(key) = s3_meta.get_object_key().map(ToString::to_string) {
+            let object_key = if let Some(key) = s3_meta.get_object_key() {
                 key
             } else {
                 self.current_key.clear();
                 error!("{ctx}: missing '$s3_streamer.key' meta data in event");
                 return Ok(SinkReply::FAIL);
             };
+            match runs.last_mut() {
+                Some((key, values)) if key.as_str() == object_key => values.push(value),
+                _ => runs.push((object_key.to_string(), vec![value])),
+            }
+        }
+        if let Some(config) = &self.config.columnar {
+            for (key, values) in &runs {
+                // a new key starts a new file
+                let checked = match self.columnar_file.as_ref() {
+                    Some(file) if *key == self.current_key => file.check(values),
+                    _ => ColumnarFile::new(config).check(values),
+                };
+                if let Err(e) = checked {
+                    error!("{ctx} Error writing columnar records: {e}");
+                    return Ok(SinkReply::FAIL);
+                }
+            }
+        }
 
+        for (object_key, values) in runs {
             if object_key != self.current_key {
                 // we switched keys:
                 // 1. finish the current upload, if any
@@ -181,24 +220,42 @@ impl Sink for S3Sink {
                 self.prepare_new_multipart(object_key, ctx).await?;
             }
 
-            // Handle the aggregation.
-            for data in serializer.serialize(event, ingest_id)? {
-                self.buffer.extend(data);
-                if self.buffer.len() >= self.min_part_size {
-                    self.upload_part(ctx).await?;
+            if let Some(config) = &self.config.columnar {
+                let file = self
+                    .columnar_file
+                    .get_or_insert_with(|| ColumnarFile::new(config));
+                if let Err(e) = file.write(&values) {
+                    error!("{ctx} Error writing columnar records: {e}");
+                    return Ok(SinkReply::FAIL);
+                }
+                self.flush_columnar_file(ctx).await?;
+            } else {
+                // Handle the aggregation.
+                for value in values {
+                    for data in serializer.serialize(value, ingest_id)? {
+                        self.buffer.extend(data);
+                        if self.buffer.len() >= self.min_part_size {
+                            self.upload_part(ctx).await?;
+                        }
+                    }
                 }
             }
         }
+        // the event is acked or failed together with the file its last value went to
+        if let (Some(file), Some(contraflow)) = (self.columnar_file.as_mut(), contraflow) {
+            file.track(contraflow);
+        }
         Ok(SinkReply::NONE)
     }
 
-    // Required later
     async fn on_signal(
         &mut self,
         _signal: Event,
-        _ctx: &SinkContext,
+        ctx: &SinkContext,
         _serializer: &mut EventSerializer,
     ) -> Result<SinkReply> {
+        // row groups of columnar files also need to be written when no events arrive
+        self.flush_columnar_file(ctx).await?;
         Ok(SinkReply::default())
     }
 
@@ -215,7 +272,8 @@ impl Sink for S3Sink {
     fn auto_ack(&self) -> bool {
         // TODO: record all the events we currently buffer for a multipart
         // and only ever ack them all once the multipart is uploaded
-        true
+        // columnar files already do this
+        self.config.columnar.is_none()
     }
 }
 
@@ -296,7 +354,48 @@ impl S3Sink {
         Ok(())
     }
 
+    /// writes a row group of the columnar file if one is due
+    async fn flush_columnar_file(&mut self, ctx: &SinkContext) -> Result<()> {
+        if let Some(file) = self.columnar_file.as_mut() {
+            if file.needs_flush() {
+                self.buffer.append(&mut file.flush()?);
+                if self.buffer.len() >= self.min_part_size {
+                    self.upload_part(ctx).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     async fn complete_multipart(&mut self, ctx: &SinkContext) -> Result<()> {
+        if let Some(mut file) = self.columnar_file.take() {
+            let start = nanotime();
+            let contraflow = file.take_contraflow();
+            let res = match file.finish() {
+                Ok(mut data) => {
+                    self.buffer.append(&mut data);
+                    self.upload_and_complete(ctx).await
+                }
+                Err(e) => Err(e),
+            };
+            if let Some(contraflow) = contraflow {
+                let reply = if res.is_ok() {
+                    AsyncSinkReply::Ack(contraflow, nanotime() - start)
+                } else {
+                    AsyncSinkReply::Fail(contraflow)
+                };
+                ctx.swallow_err(
+                    self.reply_tx.send(reply).await,
+                    "Error sending columnar file ack/fail",
+                );
+            }
+            res
+        } else {
+            self.upload_and_complete(ctx).await
+        }
+    }
+
+    async fn upload_and_complete(&mut self, ctx: &SinkContext) -> Result<()> {
         // Upload the last part if any.
         if !self.buffer.is_empty() {
             self.upload_part(ctx).await?;
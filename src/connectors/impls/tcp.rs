@@ -68,9 +68,9 @@ impl TcpReader<TcpStream> {
     }
 }
 
-impl TcpReader<ReadHalf<async_tls::server::TlsStream<TcpStream>>> {
+impl TcpReader<ReadHalf<async_rustls::server::TlsStream<TcpStream>>> {
     fn tls_server(
-        stream: ReadHalf<async_tls::server::TlsStream<TcpStream>>,
+        stream: ReadHalf<async_rustls::server::TlsStream<TcpStream>>,
         underlying_stream: TcpStream,
         buffer: Vec<u8>,
         alias: Alias,
@@ -182,9 +182,9 @@ impl TcpWriter<TcpStream> {
         }
     }
 }
-impl TcpWriter<WriteHalf<async_tls::server::TlsStream<TcpStream>>> {
+impl TcpWriter<WriteHalf<async_rustls::server::TlsStream<TcpStream>>> {
     fn tls_server(
-        tls_stream: WriteHalf<async_tls::server::TlsStream<TcpStream>>,
+        tls_stream: WriteHalf<async_rustls::server::TlsStream<TcpStream>>,
         underlying_stream: TcpStream,
     ) -> Self {
         Self {
@@ -17,7 +17,7 @@ use crate::{
         prelude::*,
         sink::channel_sink::ChannelSinkMsg,
         utils::{
-            tls::{load_server_config, TLSServerConfig},
+            tls::{client_cert_meta, ReloadingServerConfig, TLSServerConfig},
             ConnectionMeta,
         },
     },
@@ -29,9 +29,7 @@ use async_std::{
     prelude::*,
     task::JoinHandle,
 };
-use async_tls::TlsAcceptor;
 use futures::io::AsyncReadExt;
-use rustls::ServerConfig;
 use simd_json::ValueAccess;
 use std::sync::{atomic::AtomicBool, Arc};
 
@@ -52,7 +50,7 @@ impl ConfigImpl for Config {}
 #[allow(clippy::module_name_repetitions)]
 pub(crate) struct TcpServer {
     config: Config,
-    tls_server_config: Option<ServerConfig>,
+    tls_server_config: Option<ReloadingServerConfig>,
     sink_tx: Sender<ChannelSinkMsg<ConnectionMeta>>,
     sink_rx: Receiver<ChannelSinkMsg<ConnectionMeta>>,
     /// marker that the sink is connected
@@ -79,7 +77,7 @@ impl ConnectorBuilder for Builder {
             return Err(err_connector_def(id, "Missing port for TCP server"));
         }
         let tls_server_config = if let Some(tls_config) = config.tls.as_ref() {
-            Some(load_server_config(tls_config)?)
+            Some(ReloadingServerConfig::new(tls_config)?)
         } else {
             None
         };
@@ -146,7 +144,7 @@ impl Connector for TcpServer {
 
 struct TcpServerSource {
     config: Config,
-    tls_server_config: Option<ServerConfig>,
+    tls_server_config: Option<ReloadingServerConfig>,
     accept_task: Option<JoinHandle<()>>,
     connection_rx: Receiver<SourceReply>,
     runtime: ChannelSourceRuntime,
@@ -157,7 +155,7 @@ struct TcpServerSource {
 impl TcpServerSource {
     fn new(
         config: Config,
-        tls_server_config: Option<ServerConfig>,
+        tls_server_config: Option<ReloadingServerConfig>,
         sink_runtime: ChannelSinkRuntime<ConnectionMeta>,
         sink_is_connected: Arc<AtomicBool>,
     ) -> Self {
@@ -217,19 +215,28 @@ impl Source for TcpServerSource {
                             path: path.clone(), // captures server port
                         };
 
-                        let tls_acceptor: Option<TlsAcceptor> = tls_server_config
-                            .clone()
-                            .map(|sc| TlsAcceptor::from(Arc::new(sc)));
-                        if let Some(acceptor) = tls_acceptor {
-                            let tls_stream = acceptor.accept(stream.clone()).await?;
-                            let (tls_read_stream, tls_write_sink) = tls_stream.split();
-                            let meta = ctx.meta(literal!({
+                        if let Some(tls_server_config) = tls_server_config.as_ref() {
+                            // picks up rotated certificates for every new connection
+                            let tls_stream =
+                                match tls_server_config.acceptor().accept(stream.clone()).await {
+                                    Ok(tls_stream) => tls_stream,
+                                    Err(e) => {
+                                        warn!("{ctx} TLS handshake with {peer_addr} failed: {e}");
+                                        continue;
+                                    }
+                                };
+                            let mut meta = literal!({
                                 "tls": true,
                                 "peer": {
                                     "host": peer_addr.ip().to_string(),
                                     "port": peer_addr.port()
                                 }
-                            }));
+                            });
+                            if let Some(client_cert) = client_cert_meta(tls_stream.get_ref().1) {
+                                meta.try_insert("client_cert", client_cert);
+                            }
+                            let meta = ctx.meta(meta);
+                            let (tls_read_stream, tls_write_sink) = tls_stream.split();
 
                             // we only register a writer when we actually have something connected to the sink
                             // the connected sink will not be driven by the sink task anyways (no calls to on_event/on_signal)
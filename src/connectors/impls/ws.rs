@@ -154,10 +154,10 @@ impl WsWriter<async_std::net::TcpStream> {
     }
 }
 
-impl WsWriter<async_tls::server::TlsStream<async_std::net::TcpStream>> {
+impl WsWriter<async_rustls::server::TlsStream<async_std::net::TcpStream>> {
     fn new_tls_server(
         sink: SplitSink<
-            WebSocketStream<async_tls::server::TlsStream<async_std::net::TcpStream>>,
+            WebSocketStream<async_rustls::server::TlsStream<async_std::net::TcpStream>>,
             Message,
         >,
     ) -> Self {
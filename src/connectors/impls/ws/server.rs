@@ -13,14 +13,12 @@
 // limitations under the License.
 
 use super::{WsReader, WsWriter};
-use crate::connectors::utils::tls::{load_server_config, TLSServerConfig};
+use crate::connectors::utils::tls::{client_cert_meta, ReloadingServerConfig, TLSServerConfig};
 use crate::connectors::{prelude::*, utils::ConnectionMeta};
 use async_std::task::JoinHandle;
 use async_std::{net::TcpListener, prelude::FutureExt};
-use async_tls::TlsAcceptor;
 use async_tungstenite::accept_async;
 use futures::StreamExt;
-use rustls::ServerConfig;
 use simd_json::ValueAccess;
 use std::net::SocketAddr;
 use std::sync::atomic::AtomicBool;
@@ -44,7 +42,7 @@ pub(crate) struct WsServer {
     accept_task: Option<JoinHandle<()>>,
     sink_runtime: Option<ChannelSinkRuntime<ConnectionMeta>>,
     source_runtime: Option<ChannelSourceRuntime>,
-    tls_server_config: Option<ServerConfig>,
+    tls_server_config: Option<ReloadingServerConfig>,
     /// marker that the sink is actually connected to some pipeline
     sink_is_connected: Arc<AtomicBool>,
 }
@@ -67,7 +65,7 @@ impl ConnectorBuilder for Builder {
         let config = Config::new(raw_config)?;
 
         let tls_server_config = if let Some(tls_config) = config.tls.as_ref() {
-            Some(load_server_config(tls_config)?)
+            Some(ReloadingServerConfig::new(tls_config)?)
         } else {
             None
         };
@@ -201,13 +199,22 @@ impl Connector for WsServer {
                             path: path.clone(), // captures server port
                         };
 
-                        let tls_acceptor: Option<TlsAcceptor> = tls_server_config
-                            .clone()
-                            .map(|sc| TlsAcceptor::from(Arc::new(sc)));
-                        if let Some(acceptor) = tls_acceptor {
-                            let meta = ctx.meta(WsServer::meta(peer_addr, true));
+                        if let Some(tls_server_config) = tls_server_config.as_ref() {
                             // TODO: this should live in its own task, as it requires rome roundtrips :()
-                            let tls_stream = acceptor.accept(tcp_stream).await?;
+                            // picks up rotated certificates for every new connection
+                            let tls_stream =
+                                match tls_server_config.acceptor().accept(tcp_stream).await {
+                                    Ok(tls_stream) => tls_stream,
+                                    Err(e) => {
+                                        warn!("{ctx} TLS handshake with {peer_addr} failed: {e}");
+                                        continue;
+                                    }
+                                };
+                            let mut meta = WsServer::meta(peer_addr, true);
+                            if let Some(client_cert) = client_cert_meta(tls_stream.get_ref().1) {
+                                meta.try_insert("client_cert", client_cert);
+                            }
+                            let meta = ctx.meta(meta);
                             let ws_stream = accept_async(tls_stream).await?;
                             debug!("{ctx} new connection from {peer_addr}");
 
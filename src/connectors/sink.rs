@@ -14,6 +14,8 @@
 
 #![allow(clippy::module_name_repetitions)]
 
+/// Batching of events in the `SinkManager`, before they are handed to the sink
+pub(crate) mod batch;
 /// Providing a `Sink` implementation for connectors handling multiple Streams
 pub(crate) mod channel_sink;
 /// Utility for limiting concurrency (by sending `CB::Close` messages when a maximum concurrency value is reached)
@@ -21,11 +23,13 @@ pub(crate) mod concurrency_cap;
 /// Providing a `Sink` implementation for connectors handling only a single Stream
 pub(crate) mod single_stream_sink;
 
+use self::batch::Batch;
 pub(crate) use self::channel_sink::SinkMeta;
 use super::{utils::metrics::SinkReporter, CodecReq};
 use crate::codec::{self, Codec};
 use crate::config::{
-    Codec as CodecConfig, Connector as ConnectorConfig, Postprocessor as PostprocessorConfig,
+    Batch as BatchConfig, Codec as CodecConfig, Connector as ConnectorConfig,
+    Postprocessor as PostprocessorConfig,
 };
 use crate::connectors::utils::reconnect::{Attempt, ConnectionLostNotifier};
 use crate::connectors::{Alias, ConnectorType, Context, Msg, QuiescenceBeacon, StreamDone};
@@ -42,7 +46,7 @@ pub(crate) use single_stream_sink::{SingleStreamSink, SingleStreamSinkRuntime};
 use std::borrow::Borrow;
 use std::collections::{btree_map::Entry, BTreeMap, HashSet};
 use std::fmt::Display;
-use tremor_common::ids::{SinkId, SourceId};
+use tremor_common::ids::{Id, SinkId, SourceId};
 use tremor_common::time::nanotime;
 use tremor_pipeline::{CbAction, Event, EventId, OpMeta, SignalKind, DEFAULT_STREAM_ID};
 use tremor_script::{ast::DeployEndpoint, EventPayload};
@@ -348,6 +352,7 @@ pub(crate) struct SinkManagerBuilder {
     serializer: EventSerializer,
     reply_channel: (Sender<AsyncSinkReply>, Receiver<AsyncSinkReply>),
     metrics_reporter: SinkReporter,
+    batch: Option<BatchConfig>,
 }
 
 impl SinkManagerBuilder {
@@ -392,6 +397,13 @@ pub(crate) fn builder(
     qsize: usize,
     metrics_reporter: SinkReporter,
 ) -> Result<SinkManagerBuilder> {
+    if config.batch.is_some() && connector_codec_requirement == CodecReq::Structured {
+        return Err(format!(
+            "The {} connector {alias} can not batch events, as it does not serialize them.",
+            config.connector_type
+        )
+        .into());
+    }
     // resolve codec and processors
     let postprocessor_configs = config.postprocessors.clone().unwrap_or_default();
     let serializer = EventSerializer::new(
@@ -409,6 +421,7 @@ pub(crate) fn builder(
         serializer,
         reply_channel,
         metrics_reporter,
+        batch: config.batch.clone(),
     })
 }
 
@@ -427,6 +440,8 @@ pub(crate) struct EventSerializer {
     // stream data
     // TODO: clear out state from codec, postprocessors and enable reuse
     streams: BTreeMap<u64, (Box<dyn Codec>, Postprocessors)>,
+    // already serialized payload of a batch, returned by the next serialization
+    prepared: Option<Vec<u8>>,
 }
 
 impl EventSerializer {
@@ -461,9 +476,16 @@ impl EventSerializer {
             codec_config,
             postprocessor_configs,
             streams: BTreeMap::new(),
+            prepared: None,
         })
     }
 
+    /// Sets the already serialized `payload` to be returned by the next serialization,
+    /// instead of serializing the value passed to it, until it is taken or replaced
+    pub(crate) fn prepare(&mut self, payload: Option<Vec<u8>>) {
+        self.prepared = payload;
+    }
+
     /// drop a stream
     pub(crate) fn drop_stream(&mut self, stream_id: u64) {
         self.streams.remove(&stream_id);
@@ -510,6 +532,9 @@ impl EventSerializer {
         stream_id: u64,
         codec_overwrite: Option<&String>,
     ) -> Result<Vec<Vec<u8>>> {
+        if let Some(payload) = self.prepared.take() {
+            return Ok(vec![payload]);
+        }
         if stream_id == DEFAULT_STREAM_ID {
             // no codec_overwrite for the default stream
             postprocess(
@@ -585,6 +610,8 @@ where
     drains_received: HashSet<SourceId>, // TODO: use a bitset for both?
     drain_channel: Option<Sender<Msg>>,
     state: SinkState,
+    /// events waiting to be handed to the sink as one batched event
+    batch: Option<Batch>,
 }
 
 impl<S> SinkManager<S>
@@ -596,8 +623,10 @@ where
             serializer,
             reply_channel,
             metrics_reporter,
+            batch,
             ..
         } = builder;
+        let uid = ctx.uid.id();
         Self {
             sink,
             ctx,
@@ -611,13 +640,15 @@ where
             drains_received: HashSet::new(),
             drain_channel: None,
             state: SinkState::Initialized,
+            batch: batch.map(|config| Batch::new(config, uid)),
         }
     }
     #[allow(clippy::too_many_lines)]
     async fn run(mut self) -> Result<()> {
         use SinkState::{Drained, Draining, Initialized, Paused, Running, Stopped};
-        let from_sink = self.reply_rx.map(SinkMsgWrapper::FromSink);
-        let to_sink = self.rx.map(SinkMsgWrapper::ToSink);
+        // the receivers are cloned, as handling events needs the whole manager
+        let from_sink = self.reply_rx.clone().map(SinkMsgWrapper::FromSink);
+        let to_sink = self.rx.clone().map(SinkMsgWrapper::ToSink);
         let mut from_and_to_sink_channel = PriorityMerge::new(from_sink, to_sink);
         while let Some(msg_wrapper) = from_and_to_sink_channel.next().await {
             match msg_wrapper {
@@ -667,6 +698,7 @@ where
                         }
                         SinkMsg::Stop(sender) => {
                             info!("{} Stopping...", &self.ctx);
+                            self.flush_batch().await;
                             self.state = Stopped;
                             self.ctx.swallow_err(
                                 sender.send(self.sink.on_stop(&self.ctx).await).await,
@@ -738,54 +770,30 @@ where
                             send_contraflow(&self.pipelines, &self.ctx, cf).await;
                         }
                         SinkMsg::Event { event, port } => {
-                            let cf_builder = ContraflowData::from(&event);
-
                             self.metrics_reporter.increment_in();
                             if let Some(t) = self.metrics_reporter.periodic_flush(event.ingest_ns) {
                                 self.metrics_reporter
                                     .send_sink_metrics(self.sink.metrics(t, &self.ctx).await);
                             }
-                            // TODO: fix additional clones here for merge
-                            //       (hg) - I don't think we can do this w/o a clone since we need
-                            //              them here and in the on_event
-                            self.merged_operator_meta.merge(event.op_meta.clone());
-                            let transactional = event.transactional;
-                            let start = nanotime();
-                            let res = self
-                                .sink
-                                .on_event(
-                                    port.borrow(),
-                                    event,
-                                    &self.ctx,
-                                    &mut self.serializer,
-                                    start,
-                                )
-                                .await;
-                            let duration = nanotime() - start;
-                            match res {
-                                Ok(replies) => {
-                                    // TODO: send metric for duration
-                                    handle_replies(
-                                        replies,
-                                        duration,
-                                        cf_builder,
-                                        &self.pipelines,
-                                        &self.ctx,
-                                        transactional && self.sink.auto_ack(),
-                                    )
-                                    .await;
-                                }
-                                Err(_e) => {
-                                    // sink error that is not signalled via SinkReply::Fail (not handled)
-                                    // TODO: error logging? This could fill the logs quickly. Rather emit a metrics event with the logging info?
-                                    if transactional {
-                                        let cf = cf_builder.into_fail();
-                                        send_contraflow(&self.pipelines, &self.ctx, cf).await;
-                                    }
-                                }
-                            };
+                            if self.batch.is_some() {
+                                self.batch_event(port, event).await;
+                            } else {
+                                self.handle_event(port, event).await;
+                            }
                         }
                         SinkMsg::Signal { signal } => {
+                            // hand on batched events before draining or once they waited long enough
+                            let flush_batch = match signal.kind {
+                                Some(SignalKind::Drain(_)) => true,
+                                Some(SignalKind::Tick) => self
+                                    .batch
+                                    .as_ref()
+                                    .map_or(false, |batch| batch.is_expired(nanotime())),
+                                _ => false,
+                            };
+                            if flush_batch {
+                                self.flush_batch().await;
+                            }
                             // special treatment
                             match signal.kind {
                                 Some(SignalKind::Drain(source_uid)) => {
@@ -870,6 +878,89 @@ where
         info!("[Sink::{}] Terminating Sink Task.", &self.ctx.alias);
         Ok(())
     }
+
+    /// hand an event to the sink and send the contraflow for its reply
+    async fn handle_event(&mut self, port: Cow<'static, str>, event: Event) {
+        let cf_builder = ContraflowData::from(&event);
+        // TODO: fix additional clones here for merge
+        //       (hg) - I don't think we can do this w/o a clone since we need
+        //              them here and in the on_event
+        self.merged_operator_meta.merge(event.op_meta.clone());
+        let transactional = event.transactional;
+        let start = nanotime();
+        let res = self
+            .sink
+            .on_event(port.borrow(), event, &self.ctx, &mut self.serializer, start)
+            .await;
+        let duration = nanotime() - start;
+        match res {
+            Ok(replies) => {
+                // TODO: send metric for duration
+                handle_replies(
+                    replies,
+                    duration,
+                    cf_builder,
+                    &self.pipelines,
+                    &self.ctx,
+                    transactional && self.sink.auto_ack(),
+                )
+                .await;
+            }
+            Err(_e) => {
+                // sink error that is not signalled via SinkReply::Fail (not handled)
+                // TODO: error logging? This could fill the logs quickly. Rather emit a metrics event with the logging info?
+                if transactional {
+                    let cf = cf_builder.into_fail();
+                    send_contraflow(&self.pipelines, &self.ctx, cf).await;
+                }
+            }
+        };
+    }
+
+    /// serialize an event into the batch, handing the batch to the sink once it is full
+    async fn batch_event(&mut self, port: Cow<'static, str>, event: Event) {
+        // events whose values carry different metadata can not share the metadata of a batch
+        if !Batch::can_batch(&event) {
+            self.flush_batch().await;
+            self.handle_event(port, event).await;
+            return;
+        }
+        let payload = match Batch::serialize(&event, &mut self.serializer) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("{} Error serializing event for batching: {e}", self.ctx);
+                if event.transactional {
+                    let cf = ContraflowData::from(event).into_fail();
+                    send_contraflow(&self.pipelines, &self.ctx, cf).await;
+                }
+                return;
+            }
+        };
+        // close the batch if the event would exceed it or needs to be routed differently
+        if self
+            .batch
+            .as_ref()
+            .map_or(false, |batch| !batch.accepts(&port, &event, payload.len()))
+        {
+            self.flush_batch().await;
+        }
+        if let Some(batch) = self.batch.as_mut() {
+            batch.push(port, &event, payload, nanotime());
+        }
+        if self.batch.as_ref().map_or(false, Batch::is_full) {
+            self.flush_batch().await;
+        }
+    }
+
+    /// hand all batched events to the sink
+    async fn flush_batch(&mut self) {
+        if let Some((port, event, payload)) = self.batch.as_mut().and_then(Batch::take) {
+            self.serializer.prepare(Some(payload));
+            self.handle_event(port, event).await;
+            // in case the sink did not serialize the batched event
+            self.serializer.prepare(None);
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -881,6 +972,12 @@ pub(crate) struct ContraflowData {
 }
 
 impl ContraflowData {
+    /// Tracks the event of `other` as well, so acking or failing covers both
+    pub(crate) fn track(&mut self, other: ContraflowData) {
+        self.event_id.track(&other.event_id);
+        self.ingest_ns = self.ingest_ns.min(other.ingest_ns);
+        self.op_meta.merge(other.op_meta);
+    }
     pub(crate) fn into_ack(self, duration: u64) -> Event {
         Event::cb_ack_with_timing(self.ingest_ns, self.event_id, self.op_meta, duration)
     }
@@ -0,0 +1,406 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Batching of events in the `SinkManager`
+//!
+//! Events are serialized with the connector codec and postprocessors before they are added
+//! to the batch. The batch is handed to the sink once it holds `max_events` events,
+//! `max_bytes` serialized bytes or its first event has waited for `linger_ms`.
+//! An event that would exceed `max_bytes`, was received on another port, originates from other
+//! streams or carries other metadata than the batched events closes the batch, so the metadata used
+//! for routing the batch (e.g. connection, request, key or partition) is the same for all its events.
+//! The batch is handed on as a single event carrying this metadata,
+//! the serializer of the sink returns the combined payload of all batched events
+//! for its value, so it is written at once. The ids of all contained events are tracked,
+//! so they are acked or failed together.
+
+use super::{ContraflowData, EventSerializer};
+use crate::config::Batch as Config;
+use crate::errors::Result;
+use beef::Cow;
+use std::collections::HashSet;
+use std::mem;
+use tremor_pipeline::Event;
+use tremor_value::{prelude::*, Value};
+
+pub(crate) struct Batch {
+    config: Config,
+    /// uid of the connector, to look up the streams events originate from
+    uid: u64,
+    /// the serialized events
+    payload: Vec<u8>,
+    /// metadata shared by all batched events
+    meta: Value<'static>,
+    /// the streams of the connector the batched events originate from
+    streams: HashSet<u64>,
+    /// number of events in the batch
+    len: usize,
+    /// when the first event was added to the batch
+    first_ns: u64,
+    /// the port the events of the batch were received on
+    port: Cow<'static, str>,
+    transactional: bool,
+    contraflow: Option<ContraflowData>,
+}
+
+impl Batch {
+    pub(crate) fn new(config: Config, uid: u64) -> Self {
+        Self {
+            config,
+            uid,
+            payload: Vec::new(),
+            meta: Value::null(),
+            streams: HashSet::new(),
+            len: 0,
+            first_ns: 0,
+            port: Cow::const_str(""),
+            transactional: false,
+            contraflow: None,
+        }
+    }
+
+    /// the metadata shared by all values of `event`,
+    /// `None` if it has no values or they carry different metadata
+    fn common_meta(event: &Event) -> Option<&Value> {
+        let mut metas = event.value_meta_iter().map(|(_, meta)| meta);
+        let first = metas.next()?;
+        metas.all(|meta| meta == first).then_some(first)
+    }
+
+    /// if `event` can be batched at all, which requires all of its values to carry the same metadata
+    pub(crate) fn can_batch(event: &Event) -> bool {
+        Self::common_meta(event).is_some()
+    }
+
+    /// Serializes the values of `event`
+    ///
+    /// # Errors
+    ///   * if the event can not be serialized
+    pub(crate) fn serialize(event: &Event, serializer: &mut EventSerializer) -> Result<Vec<u8>> {
+        let mut payload = Vec::new();
+        for value in event.value_iter() {
+            for chunk in serializer.serialize(value, event.ingest_ns)? {
+                payload.extend(chunk);
+            }
+        }
+        Ok(payload)
+    }
+
+    /// if `event`, received on `port` and serialized to `payload_len` bytes,
+    /// can be added to this batch without exceeding `max_bytes` or mixing events routed differently
+    pub(crate) fn accepts(&self, port: &str, event: &Event, payload_len: usize) -> bool {
+        self.len == 0
+            || (&*self.port == port
+                && self
+                    .config
+                    .max_bytes
+                    .map_or(true, |max| self.payload.len() + payload_len <= max)
+                && Self::common_meta(event) == Some(&self.meta)
+                && event.id.get_streams(self.uid) == self.streams)
+    }
+
+    /// Adds `event` and its serialized `payload` to the batch
+    pub(crate) fn push(
+        &mut self,
+        port: Cow<'static, str>,
+        event: &Event,
+        mut payload: Vec<u8>,
+        now: u64,
+    ) {
+        if self.len == 0 {
+            self.first_ns = now;
+            self.port = port;
+            self.meta = Self::common_meta(event).map_or_else(Value::object, Value::clone_static);
+            self.streams = event.id.get_streams(self.uid);
+        }
+        let contraflow = ContraflowData::from(event);
+        match self.contraflow.as_mut() {
+            Some(tracked) => tracked.track(contraflow),
+            None => self.contraflow = Some(contraflow),
+        }
+        self.transactional |= event.transactional;
+        self.payload.append(&mut payload);
+        self.len += 1;
+    }
+
+    /// if one of the size bounds is reached
+    pub(crate) fn is_full(&self) -> bool {
+        self.config.max_events.map_or(false, |max| self.len >= max)
+            || self
+                .config
+                .max_bytes
+                .map_or(false, |max| self.payload.len() >= max)
+    }
+
+    /// if the first event of the batch has waited for `linger_ms`
+    pub(crate) fn is_expired(&self, now: u64) -> bool {
+        self.len > 0
+            && self.config.linger_ms.map_or(false, |linger_ms| {
+                now.saturating_sub(self.first_ns) >= linger_ms * 1_000_000
+            })
+    }
+
+    /// Takes the batched event, the port its events were received on and their combined payload,
+    /// `None` if the batch is empty
+    pub(crate) fn take(&mut self) -> Option<(Cow<'static, str>, Event, Vec<u8>)> {
+        let contraflow = self.contraflow.take()?;
+        let payload = mem::take(&mut self.payload);
+        let meta = mem::replace(&mut self.meta, Value::null());
+        let port = mem::replace(&mut self.port, Cow::const_str(""));
+        self.streams.clear();
+        self.len = 0;
+        let event = Event {
+            id: contraflow.event_id,
+            ingest_ns: contraflow.ingest_ns,
+            op_meta: contraflow.op_meta,
+            data: (Value::null(), meta).into(),
+            transactional: mem::take(&mut self.transactional),
+            ..Event::default()
+        };
+        Some((port, event, payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Codec as CodecConfig;
+    use crate::connectors::{Alias, CodecReq, ConnectorType};
+    use tremor_pipeline::EventId;
+    use tremor_value::literal;
+
+    fn event(stream_id: u64, event_id: u64, value: Value<'static>) -> Event {
+        Event {
+            id: EventId::new(1, stream_id, event_id, event_id),
+            ingest_ns: event_id,
+            data: (value, literal!({ "key": "snot" })).into(),
+            transactional: event_id == 2,
+            ..Event::default()
+        }
+    }
+
+    fn serializer(codec: &str) -> Result<EventSerializer> {
+        EventSerializer::new(
+            Some(CodecConfig::from(codec)),
+            CodecReq::Required,
+            vec!["separate".into()],
+            &ConnectorType::from("test"),
+            &Alias::new("flow", "test"),
+        )
+    }
+
+    /// adds `event` to `batch`, if it is accepted
+    fn push(
+        batch: &mut Batch,
+        event: &Event,
+        serializer: &mut EventSerializer,
+        now: u64,
+    ) -> Result<bool> {
+        let payload = Batch::serialize(event, serializer)?;
+        let accepted = batch.accepts("in", event, payload.len());
+        if accepted {
+            batch.push("in".into(), event, payload, now);
+        }
+        Ok(accepted)
+    }
+
+    #[test]
+    fn bounds() -> Result<()> {
+        let mut serializer = serializer("string")?;
+        let mut batch = Batch::new(
+            Config {
+                max_events: Some(3),
+                max_bytes: Some(12),
+                linger_ms: Some(1),
+            },
+            1,
+        );
+        assert!(batch.take().is_none());
+        assert!(!batch.is_expired(u64::MAX));
+
+        assert!(push(
+            &mut batch,
+            &event(0, 1, Value::from("snot")),
+            &mut serializer,
+            10
+        )?);
+        assert!(!batch.is_full());
+        assert!(!batch.is_expired(1_000_009));
+        assert!(batch.is_expired(1_000_010));
+        let other = event(0, 3, Value::from("x"));
+        assert!(!batch.accepts("err", &other, 2));
+        // the batch would exceed `max_bytes`
+        assert!(!batch.accepts("in", &other, 8));
+
+        assert!(push(
+            &mut batch,
+            &event(0, 2, Value::from("badger")),
+            &mut serializer,
+            20
+        )?);
+        assert!(batch.is_full());
+
+        let (port, batched, payload) = batch.take().ok_or("empty batch")?;
+        assert_eq!("in", &*port);
+        assert_eq!(b"snot\nbadger\n".to_vec(), payload);
+        assert!(!batched.is_batch);
+        assert!(batched.transactional);
+        assert_eq!(1, batched.ingest_ns);
+        assert_eq!(&literal!({"key": "snot"}), batched.data.suffix().meta());
+        assert!(batched.id.is_tracking(&EventId::new(1, 0, 1, 1)));
+        assert!(batched.id.is_tracking(&EventId::new(1, 0, 2, 2)));
+
+        // the batch is reset
+        assert!(batch.take().is_none());
+        assert!(!batch.is_full());
+        assert!(batch.accepts("err", &other, 13));
+        Ok(())
+    }
+
+    #[test]
+    fn batched_events() -> Result<()> {
+        let mut serializer = serializer("string")?;
+        let mut batch = Batch::new(
+            Config {
+                max_events: Some(2),
+                max_bytes: None,
+                linger_ms: None,
+            },
+            1,
+        );
+        let mut batched = event(0, 1, Value::array());
+        batched.is_batch = true;
+        batched.data = (
+            literal!([
+                {"data": {"value": "snot", "meta": {"key": "snot"}, "ingest_ns": 1, "kind": null}},
+                {"data": {"value": "badger", "meta": {"key": "snot"}, "ingest_ns": 1, "kind": null}}
+            ]),
+            Value::object(),
+        )
+            .into();
+        assert!(Batch::can_batch(&batched));
+        assert!(push(&mut batch, &batched, &mut serializer, 0)?);
+        assert!(!batch.is_full());
+        assert!(push(
+            &mut batch,
+            &event(0, 2, Value::from("fleek")),
+            &mut serializer,
+            0
+        )?);
+        assert!(batch.is_full());
+
+        let (_, batched, payload) = batch.take().ok_or("empty batch")?;
+        assert_eq!(b"snot\nbadger\nfleek\n".to_vec(), payload);
+        assert_eq!(&literal!({"key": "snot"}), batched.data.suffix().meta());
+
+        // batched events with different metadata can not be batched
+        let mut mixed = event(0, 3, Value::array());
+        mixed.is_batch = true;
+        mixed.data = (
+            literal!([
+                {"data": {"value": "snot", "meta": {"key": "snot"}, "ingest_ns": 1, "kind": null}},
+                {"data": {"value": "badger", "meta": {"key": "badger"}, "ingest_ns": 1, "kind": null}}
+            ]),
+            Value::object(),
+        )
+            .into();
+        assert!(!Batch::can_batch(&mixed));
+        Ok(())
+    }
+
+    #[test]
+    fn mixed_meta_and_streams() -> Result<()> {
+        let mut serializer = serializer("string")?;
+        let mut batch = Batch::new(
+            Config {
+                max_events: Some(10),
+                max_bytes: None,
+                linger_ms: None,
+            },
+            1,
+        );
+        assert!(push(
+            &mut batch,
+            &event(0, 1, Value::from("snot")),
+            &mut serializer,
+            0
+        )?);
+        // other metadata, e.g. another key, partition or request
+        let mut other_meta = event(0, 2, Value::from("badger"));
+        other_meta.data = (Value::from("badger"), literal!({"key": "badger"})).into();
+        assert!(!push(&mut batch, &other_meta, &mut serializer, 0)?);
+        // another stream, e.g. another connection
+        assert!(!push(
+            &mut batch,
+            &event(1, 3, Value::from("badger")),
+            &mut serializer,
+            0
+        )?);
+        // events of another connector do not originate from any of its streams
+        let mut other_connector = event(0, 4, Value::from("badger"));
+        other_connector.id = EventId::new(2, 0, 4, 4);
+        assert!(!push(&mut batch, &other_connector, &mut serializer, 0)?);
+        assert!(push(
+            &mut batch,
+            &event(0, 5, Value::from("badger")),
+            &mut serializer,
+            0
+        )?);
+
+        let (_, batched, payload) = batch.take().ok_or("empty batch")?;
+        assert_eq!(b"snot\nbadger\n".to_vec(), payload);
+        assert!(batched.id.is_tracking(&EventId::new(1, 0, 1, 1)));
+        assert!(batched.id.is_tracking(&EventId::new(1, 0, 5, 5)));
+        assert!(!batched.id.is_tracking(&EventId::new(1, 1, 3, 3)));
+
+        // an empty batch accepts events from any stream
+        assert!(push(&mut batch, &other_meta, &mut serializer, 0)?);
+        assert!(!push(
+            &mut batch,
+            &event(0, 6, Value::from("snot")),
+            &mut serializer,
+            0
+        )?);
+        Ok(())
+    }
+
+    #[test]
+    fn serialization_error() -> Result<()> {
+        let mut serializer = serializer("influx")?;
+        let mut batch = Batch::new(
+            Config {
+                max_events: Some(2),
+                max_bytes: None,
+                linger_ms: None,
+            },
+            1,
+        );
+        assert!(push(
+            &mut batch,
+            &event(
+                0,
+                1,
+                literal!({"measurement": "snot", "tags": {}, "fields": {"value": 1}, "timestamp": 1})
+            ),
+            &mut serializer,
+            0,
+        )?);
+        // influx can only encode measurements
+        assert!(Batch::serialize(&event(0, 2, Value::from("badger")), &mut serializer).is_err());
+        let (_, batched, payload) = batch.take().ok_or("empty batch")?;
+        assert_eq!(b"snot value=1i 1\n".to_vec(), payload);
+        assert!(!batched.id.is_tracking(&EventId::new(1, 0, 2, 2)));
+        Ok(())
+    }
+}
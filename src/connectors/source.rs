@@ -426,7 +426,12 @@ pub(crate) fn builder(
             .clone()
             .unwrap_or_else(|| CodecConfig::from(opt)),
     };
-    let streams = Streams::new(source_uid, codec_config, preprocessor_configs);
+    let streams = Streams::new(
+        source_uid,
+        codec_config,
+        preprocessor_configs,
+        config.dead_letter_data,
+    );
 
     Ok(SourceManagerBuilder {
         qsize,
@@ -441,6 +446,7 @@ struct Streams {
     uid: SourceId,
     codec_config: CodecConfig,
     preprocessor_configs: Vec<PreprocessorConfig>,
+    dead_letter_data: bool,
     states: BTreeMap<u64, StreamState>,
 }
 
@@ -453,6 +459,7 @@ impl Streams {
         uid: SourceId,
         codec_config: config::Codec,
         preprocessor_configs: Vec<PreprocessorConfig>,
+        dead_letter_data: bool,
     ) -> Self {
         let states = BTreeMap::new();
         // We used to initialize the default stream here,
@@ -461,6 +468,7 @@ impl Streams {
             uid,
             codec_config,
             preprocessor_configs,
+            dead_letter_data,
             states,
         }
     }
@@ -486,6 +494,7 @@ impl Streams {
                     &self.codec_config,
                     None,
                     &self.preprocessor_configs,
+                    self.dead_letter_data,
                 )?;
                 e.insert(state)
             }
@@ -499,6 +508,7 @@ impl Streams {
             &self.codec_config,
             codec_overwrite,
             &self.preprocessor_configs,
+            self.dead_letter_data,
         )
     }
 
@@ -509,6 +519,7 @@ impl Streams {
         codec_config: &CodecConfig,
         codec_overwrite: Option<String>,
         preprocessor_configs: &[PreprocessorConfig],
+        dead_letter_data: bool,
     ) -> Result<StreamState> {
         let codec = if let Some(codec_overwrite) = codec_overwrite {
             codec::resolve(&codec_overwrite.as_str().into())?
@@ -522,6 +533,7 @@ impl Streams {
             idgen,
             codec,
             preprocessors,
+            dead_letter_data,
         })
     }
 }
@@ -532,6 +544,8 @@ struct StreamState {
     idgen: EventIdGenerator,
     codec: Box<dyn Codec>,
     preprocessors: Preprocessors,
+    /// if data failing to decode is kept for the error event
+    dead_letter_data: bool,
 }
 
 /// possible states of a source implementation
@@ -1026,7 +1040,8 @@ where
                 None,
                 &meta.unwrap_or_else(Value::object),
                 self.is_transactional,
-            );
+            )
+            .await;
             if results.is_empty() {
                 let res = self
                     .source
@@ -1097,7 +1112,8 @@ where
                 data,
                 &meta.unwrap_or_else(Value::object),
                 self.is_transactional,
-            );
+            )
+            .await;
             if results.is_empty() {
                 let expr = self.source.on_no_events(pull_id, stream, &self.ctx).await;
                 self.ctx.swallow_err(expr, "Error on no events callback");
@@ -1124,7 +1140,8 @@ where
                 data,
                 &meta,
                 self.is_transactional,
-            );
+            )
+            .await;
             // finish up the stream immediately
             let mut last_events = build_last_events(
                 &self.ctx.alias,
@@ -1135,7 +1152,8 @@ where
                 None,
                 &meta,
                 self.is_transactional,
-            );
+            )
+            .await;
             results.append(&mut last_events);
 
             if results.is_empty() {
@@ -1283,7 +1301,7 @@ where
 /// build any number of `Event`s from a given Source Transport Unit (`data`)
 /// preprocessor or codec errors are turned into events to the ERR port of the source/connector
 #[allow(clippy::too_many_arguments)]
-fn build_events(
+async fn build_events(
     alias: &Alias,
     stream_state: &mut StreamState,
     ingest_ns: &mut u64,
@@ -1301,9 +1319,19 @@ fn build_events(
         alias,
     ) {
         Ok(processed) => {
+            let processed = split_records(stream_state.codec.as_ref(), processed);
             let mut res = Vec::with_capacity(processed.len());
             for chunk in processed {
+                // decoding may modify the chunk in place, so it is copied for the dead letter unless disabled
+                let raw = if stream_state.dead_letter_data {
+                    chunk.clone()
+                } else {
+                    Vec::new()
+                };
+                // resolve what decoding the chunk needs, e.g. a schema, without blocking in the codec
+                let resolved = stream_state.codec.resolve(&chunk).await;
                 let line_value = EventPayload::try_new::<Option<Error>, _>(chunk, |mut_data| {
+                    resolved.map_err(Some)?;
                     match stream_state.codec.decode(mut_data, *ingest_ns) {
                         Ok(None) => Err(None),
                         Err(e) => Err(Some(e)),
@@ -1318,7 +1346,19 @@ fn build_events(
                     Err(None) => continue,
                     Err(Some(e)) => (
                         ERR,
-                        make_error(alias, &e, stream_state.stream_id, pull_id, meta.clone()),
+                        make_error(
+                            alias,
+                            &e,
+                            Failure {
+                                stage: CODEC_STAGE,
+                                name: stream_state.codec.name(),
+                                data: raw,
+                            },
+                            stream_state,
+                            pull_id,
+                            origin_uri,
+                            meta.clone(),
+                        ),
                     ),
                 };
                 let event = build_event(
@@ -1335,7 +1375,19 @@ fn build_events(
         }
         Err(e) => {
             // preprocessor error
-            let err_payload = make_error(alias, &e, stream_state.stream_id, pull_id, meta.clone());
+            let err_payload = make_error(
+                alias,
+                &e.error,
+                Failure {
+                    stage: PREPROCESSOR_STAGE,
+                    name: &e.name,
+                    data: e.data,
+                },
+                stream_state,
+                pull_id,
+                origin_uri,
+                meta.clone(),
+            );
             let event = build_event(
                 stream_state,
                 pull_id,
@@ -1352,7 +1404,7 @@ fn build_events(
 /// build any number of `Event`s from a given Source Transport Unit (`data`)
 /// preprocessor or codec errors are turned into events to the ERR port of the source/connector
 #[allow(clippy::too_many_arguments)]
-fn build_last_events(
+async fn build_last_events(
     alias: &Alias,
     stream_state: &mut StreamState,
     ingest_ns: &mut u64,
@@ -1364,9 +1416,19 @@ fn build_last_events(
 ) -> Vec<(Cow<'static, str>, Event)> {
     match finish(stream_state.preprocessors.as_mut_slice(), alias) {
         Ok(processed) => {
+            let processed = split_records(stream_state.codec.as_ref(), processed);
             let mut res = Vec::with_capacity(processed.len());
             for chunk in processed {
+                // decoding may modify the chunk in place, so it is copied for the dead letter unless disabled
+                let raw = if stream_state.dead_letter_data {
+                    chunk.clone()
+                } else {
+                    Vec::new()
+                };
+                // resolve what decoding the chunk needs, e.g. a schema, without blocking in the codec
+                let resolved = stream_state.codec.resolve(&chunk).await;
                 let line_value = EventPayload::try_new::<Option<Error>, _>(chunk, |mut_data| {
+                    resolved.map_err(Some)?;
                     match stream_state.codec.decode(mut_data, *ingest_ns) {
                         Ok(None) => Err(None),
                         Err(e) => Err(Some(e)),
@@ -1381,7 +1443,19 @@ fn build_last_events(
                     Err(None) => continue,
                     Err(Some(e)) => (
                         ERR,
-                        make_error(alias, &e, stream_state.stream_id, pull_id, meta.clone()),
+                        make_error(
+                            alias,
+                            &e,
+                            Failure {
+                                stage: CODEC_STAGE,
+                                name: stream_state.codec.name(),
+                                data: raw,
+                            },
+                            stream_state,
+                            pull_id,
+                            origin_uri,
+                            meta.clone(),
+                        ),
                     ),
                 };
                 let event = build_event(
@@ -1397,8 +1471,20 @@ fn build_last_events(
             res
         }
         Err(e) => {
-            // preprocessor error
-            let err_payload = make_error(alias, &e, stream_state.stream_id, pull_id, meta.clone());
+            // preprocessor error, there is no data as finishing only flushes buffered data
+            let err_payload = make_error(
+                alias,
+                &e.error,
+                Failure {
+                    stage: PREPROCESSOR_STAGE,
+                    name: &e.name,
+                    data: e.data,
+                },
+                stream_state,
+                pull_id,
+                origin_uri,
+                meta.clone(),
+            );
             let event = build_event(
                 stream_state,
                 pull_id,
@@ -1412,20 +1498,54 @@ fn build_last_events(
     }
 }
 
-/// create an error payload
+/// split chunks holding several records, if the codec supports it
+fn split_records(codec: &dyn Codec, chunks: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+    let mut records = Vec::with_capacity(chunks.len());
+    for chunk in chunks {
+        match codec.split_records(&chunk) {
+            Some(split) => records.extend(split),
+            None => records.push(chunk),
+        }
+    }
+    records
+}
+
+/// the stage of preprocessing data
+const PREPROCESSOR_STAGE: &str = "preprocessor";
+/// the stage of decoding data
+const CODEC_STAGE: &str = "codec";
+
+/// data that could not be turned into events
+struct Failure<'f> {
+    /// the stage that failed
+    stage: &'static str,
+    /// the name of the failed preprocessor or codec
+    name: &'f str,
+    /// the data of the failed stage, the data before preprocessing for the preprocessor stage,
+    /// the preprocessed record for the codec stage, empty if it is not kept
+    data: Vec<u8>,
+}
+
+/// create an error payload, a dead letter carrying the failed data so it can be replayed
 fn make_error(
     connector_alias: &Alias,
     error: &Error,
-    stream_id: u64,
+    failure: Failure,
+    stream_state: &StreamState,
     pull_id: u64,
+    origin_uri: &EventOriginUri,
     mut meta: Value<'static>,
 ) -> EventPayload {
     let e_string = error.to_string();
     let data = literal!({
         "error": e_string.clone(),
         "source": connector_alias.to_string(),
-        "stream_id": stream_id,
-        "pull_id": pull_id
+        "stream_id": stream_state.stream_id,
+        "pull_id": pull_id,
+        "stage": failure.stage,
+        "name": failure.name.to_string(),
+        "data": Value::Bytes(Cow::owned(failure.data)),
+        "origin_uri": origin_uri.to_string()
     });
     meta.try_insert("error", e_string);
     EventPayload::from(ValueAndMeta::from_parts(data, meta))
@@ -0,0 +1,107 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::ConnectorHarness;
+use crate::{connectors::impls::file, errors::Result};
+use arrow::array::{Array, StringArray, UInt64Array};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use tremor_common::ports::IN;
+use tremor_pipeline::{Event, EventId};
+use tremor_value::literal;
+
+#[async_std::test]
+async fn file_connector_parquet() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("out.parquet");
+    let defn = literal!({
+        "config": {
+            "path": path.display().to_string(),
+            "mode": "truncate",
+            "columnar": {
+                "format": "parquet",
+                "max_rows": 2
+            }
+        }
+    });
+
+    let harness = ConnectorHarness::new(function_name!(), &file::Builder::default(), &defn).await?;
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    harness.consume_initial_sink_contraflow().await?;
+
+    for i in 0..3_u64 {
+        let event = Event {
+            id: EventId::new(0, 0, i, i),
+            transactional: true,
+            data: (
+                literal!({"snot": format!("badger{i}"), "count": i}),
+                literal!({}),
+            )
+                .into(),
+            ..Event::default()
+        };
+        harness.send_to_sink(event, IN).await?;
+    }
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+
+    let data = std::fs::read(&path)?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(data))?;
+    // row groups of 2 and 1 records
+    assert_eq!(2, builder.metadata().num_row_groups());
+    let batches = builder
+        .build()?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+    assert_eq!(3, rows);
+    let batch = &batches[0];
+    let count = batch
+        .column(0)
+        .as_any()
+        .downcast_ref::<UInt64Array>()
+        .expect("count is not an uint64 column");
+    assert_eq!(0, count.value(0));
+    let snot = batch
+        .column(1)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .expect("snot is not a string column");
+    assert_eq!("badger0", snot.value(0));
+    Ok(())
+}
+
+#[async_std::test]
+async fn file_connector_parquet_append() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let dir = tempfile::tempdir()?;
+    let defn = literal!({
+        "config": {
+            "path": dir.path().join("out.parquet").display().to_string(),
+            "mode": "append",
+            "columnar": {
+                "format": "parquet"
+            }
+        }
+    });
+    assert!(
+        ConnectorHarness::new(function_name!(), &file::Builder::default(), &defn)
+            .await
+            .is_err()
+    );
+    Ok(())
+}
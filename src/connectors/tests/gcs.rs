@@ -0,0 +1,196 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{free_port::find_free_tcp_port, ConnectorHarness};
+use crate::{connectors::impls::gcs::reader, errors::Result};
+use async_std::{net::TcpStream, task};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tide::{Request, Response, StatusCode};
+use tremor_value::{literal, prelude::*, Value};
+
+const BUCKET: &str = "bucket";
+
+struct FakeObject {
+    name: &'static str,
+    data: Vec<u8>,
+    gzip: bool,
+}
+
+type State = Arc<Vec<FakeObject>>;
+
+fn gzip(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = libflate::gzip::Encoder::new(Vec::new())?;
+    encoder.write_all(data)?;
+    Ok(encoder.finish().into_result()?)
+}
+
+fn object_resource(object: &FakeObject) -> Value<'static> {
+    literal!({
+        "kind": "storage#object",
+        "name": object.name,
+        "bucket": BUCKET,
+        "size": object.data.len().to_string(),
+        "contentType": "application/json",
+        "generation": "1664446531377861",
+        "updated": "2022-09-29T10:15:31.390Z",
+        "contentEncoding": object.gzip.then_some("gzip")
+    })
+}
+
+/// lists one object per page to exercise pagination
+async fn list(req: Request<State>) -> tide::Result {
+    let mut prefix = None;
+    let mut page_token = None;
+    for (key, value) in req.url().query_pairs() {
+        match key.as_ref() {
+            "prefix" => prefix = Some(value.to_string()),
+            "pageToken" => page_token = value.parse::<usize>().ok(),
+            _ => (),
+        }
+    }
+    let listed: Vec<&FakeObject> = req
+        .state()
+        .iter()
+        .filter(|object| {
+            prefix
+                .as_ref()
+                .map_or(true, |prefix| object.name.starts_with(prefix.as_str()))
+        })
+        .collect();
+    let idx = page_token.unwrap_or_default();
+    let page = literal!({
+        "kind": "storage#objects",
+        "items": listed.get(idx).map(|object| vec![object_resource(object)]).unwrap_or_default(),
+        "nextPageToken": (idx + 1 < listed.len()).then(|| (idx + 1).to_string())
+    });
+    let mut response = Response::new(StatusCode::Ok);
+    response.set_content_type("application/json");
+    response.set_body(page.encode());
+    Ok(response)
+}
+
+async fn download(req: Request<State>) -> tide::Result {
+    // object names in these tests only contain `/` as a character that needs escaping
+    let name = req.param("name")?.replace("%2F", "/");
+    assert_eq!(Some("alt=media"), req.url().query());
+    if let Some(object) = req.state().iter().find(|object| object.name == name) {
+        let mut response = Response::new(StatusCode::Ok);
+        if object.gzip {
+            response.insert_header("Content-Encoding", "gzip");
+        }
+        response.set_body(object.data.clone());
+        Ok(response)
+    } else {
+        Ok(Response::new(StatusCode::NotFound))
+    }
+}
+
+async fn fake_gcs(port: u16) -> Result<task::JoinHandle<std::io::Result<()>>> {
+    let objects = vec![
+        FakeObject {
+            name: "logs/a.json",
+            data: b"{\"snot\":1}\n{\"snot\":2}\n".to_vec(),
+            gzip: false,
+        },
+        FakeObject {
+            name: "logs/b.txt",
+            data: b"not json".to_vec(),
+            gzip: false,
+        },
+        FakeObject {
+            name: "logs/c.json.gz",
+            data: gzip(b"{\"badger\":3}\n")?,
+            gzip: true,
+        },
+        FakeObject {
+            name: "other/d.json",
+            data: b"{\"other\":4}\n".to_vec(),
+            gzip: false,
+        },
+    ];
+    let mut app = tide::with_state(Arc::new(objects));
+    app.at("/storage/v1/b/:bucket/o").get(list);
+    app.at("/storage/v1/b/:bucket/o/:name").get(download);
+    let handle = task::spawn(app.listen(format!("127.0.0.1:{port}")));
+
+    let start = Instant::now();
+    while TcpStream::connect(("127.0.0.1", port)).await.is_err() {
+        if start.elapsed() > Duration::from_secs(10) {
+            return Err("Fake GCS server not listening".into());
+        }
+        task::sleep(Duration::from_millis(50)).await;
+    }
+    Ok(handle)
+}
+
+#[async_std::test]
+async fn connector_gcs_reader() -> Result<()> {
+    let _ = env_logger::try_init();
+    let port = find_free_tcp_port().await?;
+    let server = fake_gcs(port).await?;
+
+    let defn = literal!({
+        "codec": "json",
+        "preprocessors": ["separate"],
+        "config": {
+            "url": format!("http://127.0.0.1:{port}/storage/v1"),
+            "bucket": BUCKET,
+            "prefix": "logs/",
+            "glob": "logs/*.json*"
+        }
+    });
+    let harness = ConnectorHarness::new("gcs_reader", &reader::Builder::default(), &defn).await?;
+    let out = harness.out().expect("No pipe connected to port OUT");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    for (data, name) in [
+        (literal!({"snot": 1}), "logs/a.json"),
+        (literal!({"snot": 2}), "logs/a.json"),
+        (literal!({"badger": 3}), "logs/c.json.gz"),
+    ] {
+        let event = out.get_event().await?;
+        assert_eq!(&data, event.data.suffix().value());
+        let meta = event.data.suffix().meta().get("gcs_reader");
+        assert_eq!(Some(BUCKET), meta.get_str("bucket"));
+        assert_eq!(Some(name), meta.get_str("name"));
+    }
+    out.expect_no_event_for(Duration::from_millis(500)).await?;
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    server.cancel().await;
+    Ok(())
+}
+
+#[async_std::test]
+async fn connector_gcs_reader_unreachable() -> Result<()> {
+    let _ = env_logger::try_init();
+    let port = find_free_tcp_port().await?;
+
+    let defn = literal!({
+        "codec": "json",
+        "config": {
+            "url": format!("http://127.0.0.1:{port}/storage/v1"),
+            "bucket": BUCKET,
+            "max_retries": 1,
+            "default_backoff_base_time": 1_000_000
+        }
+    });
+    let harness = ConnectorHarness::new("gcs_reader", &reader::Builder::default(), &defn).await?;
+    assert!(harness.start().await.is_err());
+    Ok(())
+}
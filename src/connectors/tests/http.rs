@@ -14,3 +14,4 @@
 
 mod client;
 mod server;
+mod sse;
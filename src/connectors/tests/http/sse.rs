@@ -0,0 +1,120 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    connectors::{
+        impls::http::{server, sse_client},
+        tests::{free_port, ConnectorHarness},
+    },
+    errors::Result,
+};
+use async_std::{net::TcpStream, task};
+use std::time::{Duration, Instant};
+use tremor_common::ports::IN;
+use tremor_pipeline::Event;
+use tremor_value::{literal, prelude::*};
+
+#[async_std::test]
+async fn sse_roundtrip() -> Result<()> {
+    let _ = env_logger::try_init();
+    let port = free_port::find_free_tcp_port().await?;
+    let url = format!("http://localhost:{port}/events");
+    let server_defn = literal!({
+        "codec": "json",
+        "config": {
+            "url": format!("http://localhost:{port}/"),
+            "sse": true
+        }
+    });
+    let server =
+        ConnectorHarness::new("sse_server", &server::Builder::default(), &server_defn).await?;
+    server.start().await?;
+    server.wait_for_connected().await?;
+
+    // wait until the http server is actually up
+    let start = Instant::now();
+    while TcpStream::connect(("localhost", port)).await.is_err() {
+        if start.elapsed() > Duration::from_secs(30) {
+            return Err(format!("HTTP Server not listening on port {port}").into());
+        }
+        task::sleep(Duration::from_millis(100)).await;
+    }
+
+    let client_defn = literal!({
+        "codec": "json",
+        "config": {
+            "url": url,
+            "last_event_id": "0"
+        }
+    });
+    let client = ConnectorHarness::new(
+        function_name!(),
+        &sse_client::Builder::default(),
+        &client_defn,
+    )
+    .await?;
+    let client_out = client.out().expect("No pipe connected to port OUT");
+    client.start().await?;
+    client.wait_for_connected().await?;
+
+    // the server emits the stream request
+    let request = server
+        .out()
+        .expect("No pipe connected to port OUT")
+        .get_event()
+        .await?;
+    let meta = request.data.suffix().meta().get("http_server");
+    assert_eq!(Some(true), meta.get_bool("sse"));
+    assert_eq!(
+        Some("0"),
+        meta.get("request")
+            .get("headers")
+            .get("last-event-id")
+            .get_idx(0)
+            .as_str()
+    );
+    let request_id = meta.get_u64("request_id").expect("no request_id");
+
+    let event = Event {
+        data: (
+            literal!({"snot": "badger"}),
+            literal!({
+                "http_server": {
+                    "request_id": request_id,
+                    "sse": {
+                        "event": "update",
+                        "id": "1"
+                    }
+                }
+            }),
+        )
+            .into(),
+        ..Event::default()
+    };
+    server.send_to_sink(event, IN).await?;
+
+    let event = client_out.get_event().await?;
+    assert_eq!(&literal!({"snot": "badger"}), event.data.suffix().value());
+    assert_eq!(
+        &literal!({"sse_client": {"event": "update", "id": "1"}}),
+        event.data.suffix().meta()
+    );
+
+    let (out, err) = client.stop().await?;
+    assert!(out.is_empty());
+    assert!(err.is_empty());
+    let (_out, err) = server.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
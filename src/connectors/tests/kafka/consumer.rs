@@ -235,7 +235,11 @@ async fn transactional_retry() -> Result<()> {
             "error": "SIMD JSON error: InternalError at character 0 ('}')",
             "source": "test::transactional_retry",
             "stream_id": 8589934592_u64,
-            "pull_id": 1u64
+            "pull_id": 1u64,
+            "stage": "codec",
+            "name": "sorted-json",
+            "data": Value::Bytes(Cow::owned("}\n".as_bytes().to_vec())),
+            "origin_uri": format!("tremor-kafka://{broker}/{topic}/2/1")
         }),
         e5.data.suffix().value()
     );
@@ -455,7 +459,11 @@ async fn custom_no_retry() -> Result<()> {
             "error": "SIMD JSON error: InternalError at character 0 ('}')",
             "source": "test::custom_no_retry",
             "stream_id": 8589934592_u64,
-            "pull_id": 1u64
+            "pull_id": 1u64,
+            "stage": "codec",
+            "name": "sorted-json",
+            "data": Value::Bytes(Cow::owned("}\n".as_bytes().to_vec())),
+            "origin_uri": format!("tremor-kafka://{broker}/{topic}/2/1")
         }),
         e5.data.suffix().value()
     );
@@ -664,7 +672,11 @@ async fn performance() -> Result<()> {
             "error": "SIMD JSON error: InternalError at character 0 ('}')",
             "source": "test::performance",
             "stream_id": 8589934592_u64,
-            "pull_id": 1u64
+            "pull_id": 1u64,
+            "stage": "codec",
+            "name": "sorted-json",
+            "data": Value::Bytes(Cow::owned("}\n".as_bytes().to_vec())),
+            "origin_uri": format!("tremor-kafka://{broker}/{topic}/2/1")
         }),
         e5.data.suffix().value()
     );
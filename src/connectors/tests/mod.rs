@@ -17,6 +17,8 @@
 //!
 //! ....
 
+#[cfg(feature = "amqp-integration")]
+mod amqp;
 #[cfg(feature = "clickhouse-integration")]
 mod clickhouse;
 #[cfg(feature = "crononome-integration")]
@@ -28,7 +30,11 @@ mod file;
 #[cfg(feature = "file-integration")]
 mod file_non_existent;
 #[cfg(feature = "file-integration")]
+mod file_parquet;
+#[cfg(feature = "file-integration")]
 mod file_xz;
+#[cfg(feature = "gcs-integration")]
+mod gcs;
 #[cfg(feature = "gcp-integration")]
 mod gpubsub;
 #[cfg(feature = "http-integration")]
@@ -37,7 +43,19 @@ mod http;
 mod kafka;
 #[cfg(feature = "metronome-integration")]
 mod metronome;
+#[cfg(feature = "mqtt-integration")]
+mod mqtt;
+#[cfg(feature = "nats-integration")]
+mod nats;
+#[cfg(feature = "otel-integration")]
+mod otel;
 mod pause_resume;
+#[cfg(feature = "postgres-integration")]
+mod postgres;
+#[cfg(feature = "prometheus-integration")]
+mod prometheus;
+#[cfg(feature = "replay-integration")]
+mod replay;
 #[cfg(feature = "s3-integration")]
 mod s3;
 #[cfg(feature = "net-integration")]
@@ -312,7 +330,11 @@ impl ConnectorHarness {
         self.get_pipe(OUT)
     }
 
-    #[cfg(any(feature = "kafka-integration", feature = "es-integration",))]
+    #[cfg(any(
+        feature = "kafka-integration",
+        feature = "es-integration",
+        feature = "replay-integration"
+    ))]
 
     /// get the err pipeline - if any
     pub(crate) fn err(&self) -> Option<&TestPipeline> {
@@ -323,7 +345,15 @@ impl ConnectorHarness {
         feature = "es-integration",
         feature = "socket-integration",
         feature = "net-integration",
-        feature = "ws-integration"
+        feature = "ws-integration",
+        feature = "mqtt-integration",
+        feature = "amqp-integration",
+        feature = "postgres-integration",
+        feature = "nats-integration",
+        feature = "otel-integration",
+        feature = "file-integration",
+        feature = "prometheus-integration",
+        feature = "replay-integration"
     ))]
     pub(crate) async fn send_to_sink(&self, event: Event, port: Cow<'static, str>) -> Result<()> {
         self.addr.send_sink(SinkMsg::Event { event, port }).await
@@ -344,7 +374,16 @@ impl ConnectorHarness {
             .await
     }
 
-    #[cfg(any(feature = "kafka-integration", feature = "wal-integration"))]
+    #[cfg(any(
+        feature = "kafka-integration",
+        feature = "wal-integration",
+        feature = "mqtt-integration",
+        feature = "amqp-integration",
+        feature = "postgres-integration",
+        feature = "nats-integration",
+        feature = "s3-integration",
+        feature = "replay-integration"
+    ))]
     pub(crate) async fn send_contraflow(&self, cb: CbAction, id: EventId) -> Result<()> {
         self.addr.send_source(SourceMsg::Cb(cb, id)).await
     }
@@ -416,6 +455,12 @@ impl TestPipeline {
         feature = "es-integration",
         feature = "s3-integration",
         feature = "net-integration",
+        feature = "mqtt-integration",
+        feature = "amqp-integration",
+        feature = "postgres-integration",
+        feature = "nats-integration",
+        feature = "otel-integration",
+        feature = "replay-integration",
     ))]
     pub(crate) async fn get_contraflow(&self) -> Result<Event> {
         match self.rx_cf.recv().timeout(Duration::from_secs(20)).await?? {
@@ -497,7 +542,14 @@ impl TestPipeline {
 #[cfg(any(
     feature = "http-integration",
     feature = "ws-integration",
-    feature = "s3-integration"
+    feature = "s3-integration",
+    feature = "mqtt-integration",
+    feature = "amqp-integration",
+    feature = "postgres-integration",
+    feature = "nats-integration",
+    feature = "otel-integration",
+    feature = "gcs-integration",
+    feature = "prometheus-integration"
 ))]
 mod free_port {
 
@@ -0,0 +1,104 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{free_port::find_free_tcp_port, ConnectorHarness};
+use crate::{connectors::impls::mqtt, errors::Result};
+use testcontainers::{clients::Cli as DockerCli, images::generic::GenericImage, RunnableImage};
+use tremor_common::ports::IN;
+use tremor_pipeline::{CbAction, Event, EventId};
+use tremor_value::{literal, prelude::*};
+
+const IMAGE: &str = "eclipse-mosquitto";
+// 1.6 allows anonymous connections from other hosts out of the box
+const VERSION: &str = "1.6";
+
+async fn roundtrip(version: &str) -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let docker = DockerCli::default();
+    let port = find_free_tcp_port().await?;
+    let image =
+        RunnableImage::from(GenericImage::new(IMAGE, VERSION)).with_mapped_port((port, 1883_u16));
+    let container = docker.run(image);
+    let port = container.get_host_port_ipv4(1883);
+
+    let connector_config = literal!({
+        "reconnect": {
+            "retry": {
+                "interval_ms": 1000_u64,
+                "max_retries": 10_u64
+            }
+        },
+        "codec": "json-sorted",
+        "config": {
+            "url": format!("mqtt://127.0.0.1:{port}"),
+            "version": version,
+            "client_id": format!("tremor-test-{version}"),
+            "subscriptions": ["tremor/test/#"],
+            "topic": "tremor/test/out",
+            "qos": 1
+        }
+    });
+    let harness = ConnectorHarness::new(
+        function_name!(),
+        &mqtt::Builder::default(),
+        &connector_config,
+    )
+    .await?;
+    let out = harness.out().expect("No pipe connected to port OUT");
+    let in_pipe = harness.get_pipe(IN).expect("No pipe connected to port IN");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    harness.consume_initial_sink_contraflow().await?;
+
+    let id = EventId::new(0, 0, 1, 1);
+    let event = Event {
+        id: id.clone(),
+        transactional: true,
+        data: (literal!({"snot": "badger"}), literal!({})).into(),
+        ..Event::default()
+    };
+    harness.send_to_sink(event, IN).await?;
+
+    // the sink acks once the broker acknowledged the publish
+    let cf = in_pipe.get_contraflow().await?;
+    assert_eq!(CbAction::Ack, cf.cb);
+    assert_eq!(id, cf.id);
+
+    // the source receives what the sink published
+    let event = out.get_event().await?;
+    assert_eq!(&literal!({"snot": "badger"}), event.data.suffix().value());
+    let meta = event.data.suffix().meta();
+    assert_eq!(Some("tremor/test/out"), meta.get("mqtt").get_str("topic"));
+    assert_eq!(Some(1), meta.get("mqtt").get_u8("qos"));
+    assert_eq!(Some(false), meta.get("mqtt").get_bool("retain"));
+    assert!(event.transactional);
+    harness.send_contraflow(CbAction::Ack, event.id).await?;
+
+    let (out_events, err_events) = harness.stop().await?;
+    assert!(out_events.is_empty());
+    assert!(err_events.is_empty());
+    drop(container);
+    Ok(())
+}
+
+#[async_std::test]
+async fn roundtrip_v3() -> Result<()> {
+    roundtrip("3.1.1").await
+}
+
+#[async_std::test]
+async fn roundtrip_v5() -> Result<()> {
+    roundtrip("5").await
+}
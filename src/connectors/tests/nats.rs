@@ -0,0 +1,201 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{free_port::find_free_tcp_port, ConnectorHarness};
+use crate::{connectors::impls::nats, errors::Result};
+use async_std::task;
+use std::time::Duration;
+use testcontainers::{clients::Cli as DockerCli, images::generic::GenericImage, RunnableImage};
+use tremor_common::ports::IN;
+use tremor_pipeline::{CbAction, Event, EventId};
+use tremor_value::{literal, prelude::*};
+
+const IMAGE: &str = "nats";
+const VERSION: &str = "2.9";
+
+/// starts a NATS server with JetStream enabled, returns its url
+fn nats_image(port: u16) -> RunnableImage<GenericImage> {
+    RunnableImage::from((GenericImage::new(IMAGE, VERSION), vec!["-js".to_string()]))
+        .with_mapped_port((port, 4222_u16))
+}
+
+async fn wait_for_nats(url: &str) -> Result<async_nats::Client> {
+    let mut attempts = 0;
+    loop {
+        match async_nats::connect(url).await {
+            Ok(client) => return Ok(client),
+            Err(e) if attempts < 30 => {
+                debug!("NATS not ready yet: {e}");
+                attempts += 1;
+                task::sleep(Duration::from_secs(1)).await;
+            }
+            Err(e) => return Err(format!("NATS not reachable: {e}").into()),
+        }
+    }
+}
+
+#[async_std::test]
+async fn pub_sub() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let docker = DockerCli::default();
+    let port = find_free_tcp_port().await?;
+    let container = docker.run(nats_image(port));
+    let port = container.get_host_port_ipv4(4222);
+    let url = format!("nats://127.0.0.1:{port}");
+    wait_for_nats(&url).await?;
+
+    let connector_config = literal!({
+        "codec": "json-sorted",
+        "config": {
+            "url": url,
+            "subscriptions": ["tremor.>"],
+            "queue_group": "tremor",
+            "subject": "tremor.test"
+        }
+    });
+    let harness = ConnectorHarness::new(
+        function_name!(),
+        &nats::Builder::default(),
+        &connector_config,
+    )
+    .await?;
+    let out = harness.out().expect("No pipe connected to port OUT");
+    let in_pipe = harness.get_pipe(IN).expect("No pipe connected to port IN");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    harness.consume_initial_sink_contraflow().await?;
+
+    let id = EventId::new(0, 0, 1, 1);
+    let event = Event {
+        id: id.clone(),
+        transactional: true,
+        data: (
+            literal!({"snot": "badger"}),
+            literal!({"nats": {"headers": {"tremor": "rocks"}}}),
+        )
+            .into(),
+        ..Event::default()
+    };
+    harness.send_to_sink(event, IN).await?;
+    let cf = in_pipe.get_contraflow().await?;
+    assert_eq!(CbAction::Ack, cf.cb);
+    assert_eq!(id, cf.id);
+
+    let event = out.get_event().await?;
+    assert_eq!(&literal!({"snot": "badger"}), event.data.suffix().value());
+    let meta = event.data.suffix().meta();
+    assert_eq!(Some("tremor.test"), meta.get("nats").get_str("subject"));
+    assert_eq!(
+        Some(&literal!({"tremor": ["rocks"]})),
+        meta.get("nats").get("headers")
+    );
+    // core NATS is fire and forget
+    assert!(!event.transactional);
+
+    let (out_events, err_events) = harness.stop().await?;
+    assert!(out_events.is_empty());
+    assert!(err_events.is_empty());
+    drop(container);
+    Ok(())
+}
+
+#[async_std::test]
+async fn jetstream() -> Result<()> {
+    let _ = env_logger::try_init();
+
+    let docker = DockerCli::default();
+    let port = find_free_tcp_port().await?;
+    let container = docker.run(nats_image(port));
+    let port = container.get_host_port_ipv4(4222);
+    let url = format!("nats://127.0.0.1:{port}");
+    let client = wait_for_nats(&url).await?;
+    async_nats::jetstream::new(client)
+        .create_stream(async_nats::jetstream::stream::Config {
+            name: "tremor".to_string(),
+            subjects: vec!["js.>".to_string()],
+            ..async_nats::jetstream::stream::Config::default()
+        })
+        .await
+        .map_err(|e| format!("Error creating stream: {e}"))?;
+
+    let connector_config = literal!({
+        "codec": "json-sorted",
+        "config": {
+            "url": url,
+            "jetstream": {
+                "stream": "tremor",
+                "consumer": "tremor"
+            },
+            "subject": "js.test",
+            "jetstream_publish": true
+        }
+    });
+    let harness = ConnectorHarness::new(
+        function_name!(),
+        &nats::Builder::default(),
+        &connector_config,
+    )
+    .await?;
+    let out = harness.out().expect("No pipe connected to port OUT");
+    let in_pipe = harness.get_pipe(IN).expect("No pipe connected to port IN");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    harness.consume_initial_sink_contraflow().await?;
+
+    let id = EventId::new(0, 0, 1, 1);
+    let event = Event {
+        id: id.clone(),
+        transactional: true,
+        data: (literal!({"snot": "badger"}), literal!({})).into(),
+        ..Event::default()
+    };
+    harness.send_to_sink(event, IN).await?;
+    // acked once the stream stored the message
+    let cf = in_pipe.get_contraflow().await?;
+    assert_eq!(CbAction::Ack, cf.cb);
+    assert_eq!(id, cf.id);
+
+    let event = out.get_event().await?;
+    assert_eq!(&literal!({"snot": "badger"}), event.data.suffix().value());
+    let meta = event.data.suffix().meta();
+    assert_eq!(Some("js.test"), meta.get("nats").get_str("subject"));
+    assert_eq!(
+        Some(1),
+        meta.get("nats").get("jetstream").get_i64("delivered")
+    );
+    assert!(event.transactional);
+
+    // failed events are redelivered
+    harness.send_contraflow(CbAction::Fail, event.id).await?;
+    let event = out.get_event().await?;
+    assert_eq!(&literal!({"snot": "badger"}), event.data.suffix().value());
+    assert_eq!(
+        Some(2),
+        event
+            .data
+            .suffix()
+            .meta()
+            .get("nats")
+            .get("jetstream")
+            .get_i64("delivered")
+    );
+    harness.send_contraflow(CbAction::Ack, event.id).await?;
+
+    let (out_events, err_events) = harness.stop().await?;
+    assert!(out_events.is_empty());
+    assert!(err_events.is_empty());
+    drop(container);
+    Ok(())
+}
@@ -0,0 +1,192 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{free_port::find_free_tcp_port, setup_for_tls, ConnectorHarness};
+use crate::{
+    connectors::impls::otel::{client, server},
+    errors::Result,
+};
+use async_std::{net::TcpStream, task};
+use std::time::{Duration, Instant};
+use tremor_common::ports::IN;
+use tremor_pipeline::{CbAction, Event, EventId};
+use tremor_value::{literal, prelude::*, Value};
+
+fn trace() -> Value<'static> {
+    literal!({
+        "trace": [{
+            "instrumentation_library_spans": [{
+                "spans": [{
+                    "name": "snot",
+                    "span_id": "eee19b7ec3c1b174",
+                    "trace_id": "5b8efff798038103d269b633813fc60c",
+                    "parent_span_id": "",
+                    "trace_state": "",
+                    "kind": 1,
+                    "start_time_unix_nano": 1,
+                    "end_time_unix_nano": 2,
+                    "attributes": { "badger": 42 },
+                    "events": [],
+                    "links": []
+                }],
+                "schema_url": ""
+            }],
+            "schema_url": ""
+        }]
+    })
+}
+
+async fn wait_for_port(port: u16) -> Result<()> {
+    let start = Instant::now();
+    while TcpStream::connect(("127.0.0.1", port)).await.is_err() {
+        if start.elapsed() > Duration::from_secs(30) {
+            return Err(format!("OTLP Server not listening on port {port}").into());
+        }
+        task::sleep(Duration::from_millis(100)).await;
+    }
+    Ok(())
+}
+
+async fn roundtrip(server_config: Value<'static>, client_config: Value<'static>) -> Result<()> {
+    let server = ConnectorHarness::new(
+        "otel_server",
+        &server::Builder::default(),
+        &literal!({ "config": server_config.clone() }),
+    )
+    .await?;
+    let server_out = server.out().expect("No pipe connected to port OUT");
+    server.start().await?;
+    server.wait_for_connected().await?;
+    let port = server_config
+        .get_str("url")
+        .and_then(|url| url.rsplit(':').next())
+        .and_then(|port| port.parse().ok())
+        .expect("no port in url");
+    wait_for_port(port).await?;
+
+    let client = ConnectorHarness::new(
+        "otel_client",
+        &client::Builder::default(),
+        &literal!({ "config": client_config }),
+    )
+    .await?;
+    let in_pipe = client.get_pipe(IN).expect("No pipe connected to port IN");
+    client.start().await?;
+    client.wait_for_connected().await?;
+    client.consume_initial_sink_contraflow().await?;
+
+    let id = EventId::new(0, 0, 1, 1);
+    let event = Event {
+        id: id.clone(),
+        transactional: true,
+        data: (trace(), literal!({})).into(),
+        ..Event::default()
+    };
+    client.send_to_sink(event, IN).await?;
+    let cf = in_pipe.get_contraflow().await?;
+    assert_eq!(CbAction::Ack, cf.cb);
+    assert_eq!(id, cf.id);
+
+    let event = server_out.get_event().await?;
+    let span = event
+        .data
+        .suffix()
+        .value()
+        .get("trace")
+        .get_idx(0)
+        .get("instrumentation_library_spans")
+        .get_idx(0)
+        .get("spans")
+        .get_idx(0);
+    assert_eq!(Some("snot"), span.get_str("name"));
+    assert_eq!(Some("eee19b7ec3c1b174"), span.get_str("span_id"));
+    assert_eq!(
+        Some("5b8efff798038103d269b633813fc60c"),
+        span.get_str("trace_id")
+    );
+    assert_eq!(Some(42), span.get("attributes").get_i64("badger"));
+
+    let (_out, err) = client.stop().await?;
+    assert!(err.is_empty());
+    let (_out, err) = server.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn http_protobuf_gzip() -> Result<()> {
+    let _ = env_logger::try_init();
+    let port = find_free_tcp_port().await?;
+    roundtrip(
+        literal!({
+            "url": format!("http://127.0.0.1:{port}"),
+            "protocol": "http"
+        }),
+        literal!({
+            "url": format!("http://127.0.0.1:{port}"),
+            "protocol": "http",
+            "gzip": true
+        }),
+    )
+    .await
+}
+
+#[async_std::test]
+async fn https_json() -> Result<()> {
+    let _ = env_logger::try_init();
+    setup_for_tls();
+    let port = find_free_tcp_port().await?;
+    roundtrip(
+        literal!({
+            "url": format!("https://127.0.0.1:{port}"),
+            "protocol": "http",
+            "tls": {
+                "cert": "./tests/localhost.cert",
+                "key": "./tests/localhost.key"
+            }
+        }),
+        literal!({
+            "url": format!("https://localhost:{port}"),
+            "protocol": "http",
+            "encoding": "json",
+            "tls": {
+                "cafile": "./tests/localhost.cert"
+            }
+        }),
+    )
+    .await
+}
+
+#[async_std::test]
+async fn grpc_tls() -> Result<()> {
+    let _ = env_logger::try_init();
+    setup_for_tls();
+    let port = find_free_tcp_port().await?;
+    roundtrip(
+        literal!({
+            "url": format!("https://127.0.0.1:{port}"),
+            "tls": {
+                "cert": "./tests/localhost.cert",
+                "key": "./tests/localhost.key"
+            }
+        }),
+        literal!({
+            "url": format!("https://localhost:{port}"),
+            "tls": {
+                "cafile": "./tests/localhost.cert"
+            }
+        }),
+    )
+    .await
+}
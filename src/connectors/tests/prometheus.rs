@@ -0,0 +1,189 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{free_port::find_free_tcp_port, ConnectorHarness};
+use crate::{
+    connectors::impls::prometheus::{
+        exporter,
+        prompb::{Label, Sample, TimeSeries, WriteRequest},
+        remote_write,
+    },
+    errors::Result,
+};
+use async_std::{net::TcpStream, task};
+use http_types::{Method, StatusCode, Url};
+use prost::Message;
+use std::time::{Duration, Instant};
+use tremor_common::ports::IN;
+use tremor_pipeline::{Event, EventId};
+use tremor_value::{literal, prelude::*};
+
+async fn wait_for_port(port: u16) -> Result<()> {
+    let start = Instant::now();
+    while TcpStream::connect(("127.0.0.1", port)).await.is_err() {
+        if start.elapsed() > Duration::from_secs(30) {
+            return Err(format!("Server not listening on port {port}").into());
+        }
+        task::sleep(Duration::from_millis(100)).await;
+    }
+    Ok(())
+}
+
+#[async_std::test]
+async fn remote_write() -> Result<()> {
+    let _ = env_logger::try_init();
+    let port = find_free_tcp_port().await?;
+    let defn = literal!({
+        "config": {
+            "url": format!("http://127.0.0.1:{port}")
+        }
+    });
+    let harness = ConnectorHarness::new(
+        "prometheus_remote_write",
+        &remote_write::Builder::default(),
+        &defn,
+    )
+    .await?;
+    let out = harness.out().expect("No pipe connected to port OUT");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    wait_for_port(port).await?;
+
+    let request = WriteRequest {
+        timeseries: vec![TimeSeries {
+            labels: vec![
+                Label {
+                    name: "__name__".to_string(),
+                    value: "up".to_string(),
+                },
+                Label {
+                    name: "job".to_string(),
+                    value: "snot".to_string(),
+                },
+            ],
+            samples: vec![Sample {
+                value: 1.0,
+                timestamp: 1_665_150_000_000,
+            }],
+        }],
+        metadata: vec![],
+    };
+    let body = snap::raw::Encoder::new().compress_vec(&request.encode_to_vec())?;
+    let url = Url::parse(&format!("http://127.0.0.1:{port}/api/v1/write"))?;
+    let req = surf::Request::builder(Method::Post, url.clone())
+        .header("Content-Encoding", "snappy")
+        .content_type("application/x-protobuf")
+        .body_bytes(body)
+        .build();
+    let res = surf::client().send(req).await?;
+    assert_eq!(StatusCode::NoContent, res.status());
+
+    let event = out.get_event().await?;
+    assert_eq!(
+        &literal!({
+            "measurement": "up",
+            "tags": {"job": "snot"},
+            "fields": {"value": 1.0},
+            "timestamp": 1_665_150_000_000_000_000_u64
+        }),
+        event.data.suffix().value()
+    );
+
+    // requests need to be snappy compressed
+    let req = surf::Request::builder(Method::Post, url)
+        .content_type("application/x-protobuf")
+        .body_bytes(request.encode_to_vec())
+        .build();
+    let res = surf::client().send(req).await?;
+    assert_eq!(StatusCode::UnsupportedMediaType, res.status());
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
+
+#[async_std::test]
+async fn exporter() -> Result<()> {
+    let _ = env_logger::try_init();
+    let port = find_free_tcp_port().await?;
+    let defn = literal!({
+        "config": {
+            "url": format!("http://127.0.0.1:{port}")
+        }
+    });
+    let harness =
+        ConnectorHarness::new("prometheus_exporter", &exporter::Builder::default(), &defn).await?;
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    harness.consume_initial_sink_contraflow().await?;
+    wait_for_port(port).await?;
+
+    let events = [
+        (
+            literal!({
+                "measurement": "requests_total",
+                "tags": {"method": "GET"},
+                "fields": {"value": 3},
+                "timestamp": 1
+            }),
+            literal!({"prometheus_exporter": {"type": "counter", "help": "Handled requests"}}),
+        ),
+        (
+            literal!({
+                "measurement": "latency_seconds",
+                "tags": {"method": "GET"},
+                "fields": {"value": {"buckets": {"0.5": 1, "1": 2}, "sum": 1.25, "count": 2}},
+                "timestamp": 1
+            }),
+            literal!({"prometheus_exporter": {"type": "histogram"}}),
+        ),
+    ];
+    for (i, (value, meta)) in events.into_iter().enumerate() {
+        let event = Event {
+            id: EventId::new(0, 0, i as u64, i as u64),
+            data: (value, meta).into(),
+            ..Event::default()
+        };
+        harness.send_to_sink(event, IN).await?;
+    }
+
+    let expected = "# TYPE latency_seconds histogram
+latency_seconds_bucket{method=\"GET\",le=\"0.5\"} 1
+latency_seconds_bucket{method=\"GET\",le=\"1\"} 2
+latency_seconds_bucket{method=\"GET\",le=\"+Inf\"} 2
+latency_seconds_sum{method=\"GET\"} 1.25
+latency_seconds_count{method=\"GET\"} 2
+# HELP requests_total Handled requests
+# TYPE requests_total counter
+requests_total{method=\"GET\"} 3
+";
+    // the events are handled asynchronously
+    let start = Instant::now();
+    loop {
+        let mut res = surf::get(format!("http://127.0.0.1:{port}/metrics")).await?;
+        assert_eq!(StatusCode::Ok, res.status());
+        let body = res.body_string().await?;
+        if body == expected {
+            break;
+        }
+        if start.elapsed() > Duration::from_secs(10) {
+            assert_eq!(expected, body);
+        }
+        task::sleep(Duration::from_millis(100)).await;
+    }
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
@@ -0,0 +1,151 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::ConnectorHarness;
+use crate::{connectors::impls::replay, errors::Result};
+use beef::Cow;
+use tremor_common::{
+    ids::{Id, SourceId},
+    ports::IN,
+};
+use tremor_pipeline::{CbAction, Event, EventIdGenerator};
+use tremor_value::{literal, prelude::*, Value};
+
+#[async_std::test]
+async fn replay() -> Result<()> {
+    let _ = env_logger::try_init();
+    let config = literal!({
+        "codec": "json",
+        "preprocessors": ["separate"]
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &replay::Builder::default(), &config).await?;
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+    harness.consume_initial_sink_contraflow().await?;
+
+    let out = harness.out().expect("No pipeline connected to replay out.");
+    let err = harness.err().expect("No pipeline connected to replay err.");
+    let in_pipe = harness
+        .get_pipe(IN)
+        .expect("No pipeline connected to replay in.");
+
+    let mut id_gen = EventIdGenerator::new(SourceId::new(1));
+
+    // a dead letter with fixed up data is decoded, the stream is ended, so the data is not buffered by the preprocessor
+    let dead_letter_id = id_gen.next_id();
+    let event = Event {
+        id: dead_letter_id.clone(),
+        data: (
+            literal!({
+                "error": "SIMD JSON error: InternalError at character 0 ('}')",
+                "stage": "codec",
+                "name": "json",
+                "data": Value::Bytes(Cow::owned(b"{\"snot\":\"badger\"}".to_vec())),
+                "origin_uri": "tremor-file://localhost/snot.json"
+            }),
+            literal!({
+                "error": "SIMD JSON error: InternalError at character 0 ('}')",
+                "file": {"path": "snot.json"}
+            }),
+        )
+            .into(),
+        transactional: true,
+        ..Event::default()
+    };
+    harness.send_to_sink(event, IN).await?;
+    let event = out.get_event().await?;
+    assert_eq!(&literal!({"snot": "badger"}), event.data.suffix().value());
+    assert_eq!(
+        &literal!({"file": {"path": "snot.json"}}),
+        event.data.suffix().meta()
+    );
+    assert_eq!(
+        Some("tremor-file://localhost/snot.json".to_string()),
+        event.origin_uri.as_ref().map(ToString::to_string)
+    );
+
+    // acking the replayed event acks the dead letter
+    harness
+        .send_contraflow(CbAction::Ack, event.id.clone())
+        .await?;
+    let cf = in_pipe.get_contraflow().await?;
+    assert_eq!(CbAction::Ack, cf.cb);
+    assert!(cf.id.is_tracking(&dead_letter_id));
+
+    // the preprocessor emits the first line of a dead letter with the data and buffers the second
+    // until the stream is ended, the dead letter is failed if the event of the second pull is
+    let two_lines = |id| Event {
+        id,
+        data: (
+            literal!({
+                "data": Value::Bytes(Cow::owned(b"{\"snot\":1}\n{\"snot\":2}".to_vec()))
+            }),
+            Value::object(),
+        )
+            .into(),
+        transactional: true,
+        ..Event::default()
+    };
+    let dead_letter_id = id_gen.next_id();
+    harness
+        .send_to_sink(two_lines(dead_letter_id.clone()), IN)
+        .await?;
+    let first = out.get_event().await?;
+    assert_eq!(&literal!({"snot": 1}), first.data.suffix().value());
+    let second = out.get_event().await?;
+    assert_eq!(&literal!({"snot": 2}), second.data.suffix().value());
+    harness.send_contraflow(CbAction::Ack, first.id).await?;
+    harness.send_contraflow(CbAction::Fail, second.id).await?;
+    let cf = in_pipe.get_contraflow().await?;
+    assert_eq!(CbAction::Fail, cf.cb);
+    assert!(cf.id.is_tracking(&dead_letter_id));
+
+    // and only acked once the event of the second pull is acked
+    let dead_letter_id = id_gen.next_id();
+    harness
+        .send_to_sink(two_lines(dead_letter_id.clone()), IN)
+        .await?;
+    let first = out.get_event().await?;
+    let second = out.get_event().await?;
+    harness.send_contraflow(CbAction::Ack, first.id).await?;
+    harness.send_contraflow(CbAction::Ack, second.id).await?;
+    let cf = in_pipe.get_contraflow().await?;
+    assert_eq!(CbAction::Ack, cf.cb);
+    assert!(cf.id.is_tracking(&dead_letter_id));
+
+    // a dead letter with still invalid data ends up on the err port again
+    let event = Event {
+        id: id_gen.next_id(),
+        data: (
+            literal!({
+                "data": Value::Bytes(Cow::owned(b"}".to_vec()))
+            }),
+            Value::object(),
+        )
+            .into(),
+        ..Event::default()
+    };
+    harness.send_to_sink(event, IN).await?;
+    let event = err.get_event().await?;
+    let value = event.data.suffix().value();
+    assert_eq!(Some("codec"), value.get_str("stage"));
+    assert_eq!(Some("json"), value.get_str("name"));
+    assert_eq!(Some(&b"}"[..]), value.get_bytes("data"));
+
+    let (out_events, err_events) = harness.stop().await?;
+    assert!(out_events.is_empty());
+    assert!(err_events.is_empty());
+    Ok(())
+}
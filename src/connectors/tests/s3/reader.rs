@@ -26,6 +26,7 @@ use aws_sdk_s3::Client;
 use serial_test::serial;
 use std::time::Duration;
 use testcontainers::clients;
+use tremor_pipeline::CbAction;
 use tremor_value::{literal, Value};
 use value_trait::ValueAccess;
 
@@ -269,3 +270,120 @@ async fn connector_s3_reader() -> Result<()> {
 
     Ok(())
 }
+
+#[async_std::test]
+#[serial(s3)]
+async fn connector_s3_reader_tail_checkpoint() -> Result<()> {
+    serial_test::set_max_wait(Duration::from_secs(600));
+
+    let _ = env_logger::try_init();
+    let bucket_name = random_bucket_name("tremor-tail");
+
+    let docker = clients::Cli::default();
+    let (_container, http_port) = spawn_docker(&docker).await;
+
+    wait_for_s3(http_port).await?;
+    create_bucket(&bucket_name, http_port).await?;
+
+    let s3_client: Client = get_client(http_port);
+    let put = |key: &'static str| {
+        s3_client
+            .put_object()
+            .key(key)
+            .bucket(bucket_name.as_str())
+            .body(ByteStream::from_static(key.as_bytes()))
+            .send()
+    };
+    put("logs/0001").await?;
+    put("logs/0002").await?;
+
+    let dir = tempfile::tempdir()?;
+    let checkpoint = dir.path().join("checkpoint.json");
+
+    let mut env = EnvHelper::new();
+    env.set_var("AWS_ACCESS_KEY_ID", MINIO_ROOT_USER);
+    env.set_var("AWS_SECRET_ACCESS_KEY", MINIO_ROOT_PASSWORD);
+    let endpoint = format!("http://localhost:{http_port}");
+    let connector_yaml = literal!({
+        "codec": "binary",
+        "config": {
+            "aws_region": MINIO_REGION,
+            "bucket": bucket_name.clone(),
+            "url": endpoint,
+            "prefix": "logs/",
+            "tail": true,
+            "poll_interval_ms": 200,
+            "checkpoint": checkpoint.to_string_lossy().to_string()
+        }
+    });
+
+    let harness = ConnectorHarness::new(
+        function_name!(),
+        &s3::reader::Builder::default(),
+        &connector_yaml,
+    )
+    .await?;
+    let out_pipe = harness
+        .out()
+        .expect("No pipelines connected to out port of s3-reader");
+    harness.start().await?;
+
+    let mut keys = Vec::new();
+    for _ in 0..2 {
+        let event = out_pipe.get_event().await?;
+        assert!(event.transactional);
+        let key = event
+            .data
+            .suffix()
+            .meta()
+            .get("s3_reader")
+            .get_str("key")
+            .map(ToString::to_string);
+        keys.push(key);
+        harness.send_contraflow(CbAction::Ack, event.id).await?;
+    }
+    keys.sort();
+    assert_eq!(
+        vec![Some("logs/0001".to_string()), Some("logs/0002".to_string())],
+        keys
+    );
+
+    // new keys are picked up by the next listing
+    put("logs/0003").await?;
+    let event = out_pipe.get_event().await?;
+    assert_eq!(
+        Some(b"logs/0003".as_slice()),
+        event.data.suffix().value().as_bytes()
+    );
+    harness.send_contraflow(CbAction::Ack, event.id).await?;
+    out_pipe.expect_no_event_for(Duration::from_secs(1)).await?;
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    let checkpoint_data = async_std::fs::read_to_string(&checkpoint).await?;
+    assert!(checkpoint_data.contains("logs/0003"));
+
+    // after a restart only keys that have not been processed yet are read
+    put("logs/0004").await?;
+    let harness = ConnectorHarness::new(
+        function_name!(),
+        &s3::reader::Builder::default(),
+        &connector_yaml,
+    )
+    .await?;
+    let out_pipe = harness
+        .out()
+        .expect("No pipelines connected to out port of s3-reader");
+    harness.start().await?;
+    let event = out_pipe.get_event().await?;
+    assert_eq!(
+        Some(b"logs/0004".as_slice()),
+        event.data.suffix().value().as_bytes()
+    );
+    out_pipe.expect_no_event_for(Duration::from_secs(1)).await?;
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+
+    Ok(())
+}
@@ -15,7 +15,8 @@
 use std::time::Duration;
 
 use crate::connectors::impls::tcp;
-use crate::connectors::tests::{free_port, ConnectorHarness};
+use crate::connectors::tests::{free_port, setup_for_tls, ConnectorHarness};
+use crate::connectors::utils::tls::{tls_client_connector, TLSClientConfig};
 use crate::errors::Result;
 use async_std::{io::WriteExt, net::TcpStream, prelude::*};
 use tremor_common::ports::IN;
@@ -160,3 +161,78 @@ async fn client_disconnect() -> Result<()> {
     assert!(err.is_empty());
     Ok(())
 }
+
+#[async_std::test]
+async fn tls_client_auth() -> Result<()> {
+    let _ = env_logger::try_init();
+    setup_for_tls();
+
+    let free_port = free_port::find_free_tcp_port().await?;
+    let server_addr = format!("127.0.0.1:{free_port}");
+
+    let defn = literal!({
+      "codec": "string",
+      "preprocessors": ["separate"],
+      "config": {
+        "url": format!("tcp://127.0.0.1:{free_port}"),
+        "tls": {
+          "cert": "./tests/localhost.cert",
+          "key": "./tests/localhost.key",
+          "cafile": "./tests/localhost.cert",
+          "client_auth": "required"
+        }
+      }
+    });
+    let harness =
+        ConnectorHarness::new(function_name!(), &tcp::server::Builder::default(), &defn).await?;
+    let out_pipeline = harness
+        .out()
+        .expect("No pipeline connected to 'out' port of tcp_server connector");
+    harness.start().await?;
+    harness.wait_for_connected().await?;
+
+    let with_cert = tls_client_connector(&TLSClientConfig {
+        cafile: Some("./tests/localhost.cert".into()),
+        domain: None,
+        cert: Some("./tests/localhost.cert".into()),
+        key: Some("./tests/localhost.key".into()),
+    })
+    .await?;
+    let without_cert = tls_client_connector(&TLSClientConfig {
+        cafile: Some("./tests/localhost.cert".into()),
+        ..TLSClientConfig::default()
+    })
+    .await?;
+
+    // a client without certificate is refused
+    let stream = TcpStream::connect(&server_addr).await?;
+    if let Ok(mut refused) = without_cert.connect("localhost", stream).await {
+        // with TLS 1.3 the server rejects the certificate after the client finished the handshake
+        let _ = refused.write_all(b"refused\n").await;
+        let mut buf = vec![0_u8; 16];
+        assert!(!matches!(
+            refused.read(&mut buf).timeout(Duration::from_secs(5)).await,
+            Ok(Ok(n)) if n > 0
+        ));
+    }
+
+    // the server keeps accepting connections
+    let stream = TcpStream::connect(&server_addr).await?;
+    let mut socket = with_cert.connect("localhost", stream).await?;
+    socket.write_all(b"snot\n").await?;
+    socket.flush().await?;
+    let event = out_pipeline.get_event().await?;
+    let (data, meta) = event.data.parts();
+    assert_eq!(&Value::from("snot"), data);
+    let client_cert = meta.get("tcp_server").get("client_cert");
+    assert_eq!(Some("CN=localhost"), client_cert.get_str("subject"));
+    assert_eq!(
+        Some(&literal!(["localhost"])),
+        client_cert.get("san").get("dns")
+    );
+    assert_eq!(Some(64), client_cert.get_str("fingerprint").map(str::len));
+
+    let (_out, err) = harness.stop().await?;
+    assert!(err.is_empty());
+    Ok(())
+}
@@ -0,0 +1,867 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Columnar output for sinks writing files
+//!
+//! Codecs encode one event at a time, columnar formats like Parquet need to see many records
+//! to write a row group. A [`ColumnarFile`] buffers the records of a file in columns
+//! and encodes them as a row group (or record batch) once `max_rows`, `max_bytes` or `max_delay_ms`
+//! is reached. The bytes it hands out are meant to be written or uploaded in order,
+//! after [`ColumnarFile::finish`] they form a valid file.
+//!
+//! The schema is either declared in the config or inferred from the first record of each file.
+//! The type of a field that is `null` in the first record is inferred from its first non-null value,
+//! it is a string if there is none by the time the first row group is written.
+//! Fields missing in a record are written as `null`. Fields not in a declared schema are ignored,
+//! records with fields not in an inferred schema fail, as the file can not hold them.
+//! The records of an event are buffered all or none.
+//!
+//! Sinks using it keep the [`ContraflowData`] of the events in the file around,
+//! so they can be acked or failed once the file has been written.
+
+use crate::connectors::prelude::*;
+use crate::connectors::sink::ContraflowData;
+use arrow::array::{
+    ArrayRef, BinaryArray, BooleanArray, Float64Array, Int64Array, StringArray,
+    TimestampNanosecondArray, UInt64Array,
+};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::WriterProperties;
+use std::io::Write;
+use std::sync::{Arc, Mutex, PoisonError};
+use tremor_common::time::nanotime;
+
+/// File format
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Format {
+    Parquet,
+    ArrowIpc,
+}
+
+/// Parquet compression
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Compression {
+    None,
+    Snappy,
+    Zstd,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self::Snappy
+    }
+}
+
+impl From<Compression> for parquet::basic::Compression {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::None => Self::UNCOMPRESSED,
+            Compression::Snappy => Self::SNAPPY,
+            Compression::Zstd => Self::ZSTD,
+        }
+    }
+}
+
+/// Type of a column
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum FieldType {
+    Bool,
+    Int,
+    Uint,
+    Float,
+    /// strings, arrays and objects are written as JSON
+    String,
+    Binary,
+    /// nanoseconds since the epoch
+    Timestamp,
+}
+
+impl FieldType {
+    fn data_type(self) -> DataType {
+        match self {
+            Self::Bool => DataType::Boolean,
+            Self::Int => DataType::Int64,
+            Self::Uint => DataType::UInt64,
+            Self::Float => DataType::Float64,
+            Self::String => DataType::Utf8,
+            Self::Binary => DataType::Binary,
+            Self::Timestamp => DataType::Timestamp(TimeUnit::Nanosecond, None),
+        }
+    }
+
+    /// the type for a non-null `value`
+    fn infer(value: &Value) -> Self {
+        match value.value_type() {
+            ValueType::Bool => Self::Bool,
+            ValueType::I64 => Self::Int,
+            ValueType::U64 => Self::Uint,
+            ValueType::F64 => Self::Float,
+            ValueType::Custom("bytes") => Self::Binary,
+            _ => Self::String,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FieldConfig {
+    name: String,
+    #[serde(rename = "type")]
+    field_type: FieldType,
+}
+
+/// Columnar output config
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    format: Format,
+    /// columns of the file, inferred from the first record if not given
+    #[serde(default = "Default::default")]
+    schema: Option<Vec<FieldConfig>>,
+    /// maximum number of records in a row group
+    #[serde(default = "default_max_rows")]
+    max_rows: usize,
+    /// maximum (estimated) size of the records in a row group
+    #[serde(default = "default_max_bytes")]
+    max_bytes: usize,
+    /// maximum time a record is buffered before its row group is written
+    #[serde(default = "Default::default")]
+    max_delay_ms: Option<u64>,
+    /// compression of parquet files
+    #[serde(default = "Default::default")]
+    compression: Compression,
+}
+
+impl ConfigImpl for Config {}
+
+fn default_max_rows() -> usize {
+    100_000
+}
+
+fn default_max_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+/// Encodes record batches into a file
+trait BatchWriter: Send {
+    /// Encodes `batch`, returns the bytes that are ready to be written
+    fn write(&mut self, batch: &RecordBatch) -> Result<Vec<u8>>;
+    /// Completes the file, returns the remaining bytes
+    fn finish(self: Box<Self>) -> Result<Vec<u8>>;
+}
+
+/// A `Write` whose contents can be taken out while the writer owning it keeps on writing
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+struct ParquetWriter {
+    buffer: SharedBuffer,
+    writer: ArrowWriter<SharedBuffer>,
+}
+
+impl ParquetWriter {
+    fn new(schema: SchemaRef, compression: Compression) -> Result<Self> {
+        let buffer = SharedBuffer::default();
+        let props = WriterProperties::builder()
+            .set_compression(compression.into())
+            // row groups are cut by `ColumnarFile`
+            .set_max_row_group_size(usize::MAX)
+            .build();
+        let writer = ArrowWriter::try_new(buffer.clone(), schema, Some(props))?;
+        Ok(Self { buffer, writer })
+    }
+}
+
+impl BatchWriter for ParquetWriter {
+    fn write(&mut self, batch: &RecordBatch) -> Result<Vec<u8>> {
+        self.writer.write(batch)?;
+        self.writer.flush()?;
+        Ok(self.buffer.take())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Vec<u8>> {
+        let Self { buffer, writer } = *self;
+        writer.close()?;
+        Ok(buffer.take())
+    }
+}
+
+struct ArrowIpcWriter {
+    buffer: SharedBuffer,
+    writer: arrow::ipc::writer::FileWriter<SharedBuffer>,
+}
+
+impl ArrowIpcWriter {
+    fn new(schema: &Schema) -> Result<Self> {
+        let buffer = SharedBuffer::default();
+        let writer = arrow::ipc::writer::FileWriter::try_new(buffer.clone(), schema)?;
+        Ok(Self { buffer, writer })
+    }
+}
+
+impl BatchWriter for ArrowIpcWriter {
+    fn write(&mut self, batch: &RecordBatch) -> Result<Vec<u8>> {
+        self.writer.write(batch)?;
+        Ok(self.buffer.take())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<Vec<u8>> {
+        self.writer.finish()?;
+        Ok(self.buffer.take())
+    }
+}
+
+/// The buffered values of a column
+enum Column {
+    /// a column without a type yet, with the number of `null`s buffered
+    Null(usize),
+    Bool(Vec<Option<bool>>),
+    Int(Vec<Option<i64>>),
+    Uint(Vec<Option<u64>>),
+    Float(Vec<Option<f64>>),
+    String(Vec<Option<String>>),
+    Binary(Vec<Option<Vec<u8>>>),
+    Timestamp(Vec<Option<i64>>),
+}
+
+/// A single value of a record, converted to the type of its column
+enum Cell {
+    Bool(bool),
+    Int(i64),
+    Uint(u64),
+    Float(f64),
+    String(String),
+    Binary(Vec<u8>),
+}
+
+impl Cell {
+    fn new(name: &str, field_type: FieldType, value: &Value) -> Result<Option<Self>> {
+        if value.is_null() {
+            return Ok(None);
+        }
+        let cell = match field_type {
+            FieldType::Bool => value.as_bool().map(Cell::Bool),
+            FieldType::Int | FieldType::Timestamp => value.as_i64().map(Cell::Int),
+            FieldType::Uint => value.as_u64().map(Cell::Uint),
+            FieldType::Float => value.cast_f64().map(Cell::Float),
+            FieldType::String => match value.value_type() {
+                ValueType::String => value.as_str().map(|s| Cell::String(s.to_string())),
+                ValueType::Array | ValueType::Object => Some(Cell::String(value.encode())),
+                _ => None,
+            },
+            FieldType::Binary => value
+                .as_bytes()
+                .or_else(|| value.as_str().map(str::as_bytes))
+                .map(|bytes| Cell::Binary(bytes.to_vec())),
+        };
+        cell.map(Some).ok_or_else(|| {
+            format!(
+                "Field `{name}` of type {field_type:?} can not hold a value of type {:?}",
+                value.value_type()
+            )
+            .into()
+        })
+    }
+
+    /// estimated size in a row group
+    fn size(&self) -> usize {
+        match self {
+            Cell::Bool(_) => 1,
+            Cell::Int(_) | Cell::Uint(_) | Cell::Float(_) => 8,
+            Cell::String(s) => s.len(),
+            Cell::Binary(b) => b.len(),
+        }
+    }
+}
+
+impl Column {
+    fn new(field_type: Option<FieldType>) -> Self {
+        Self::nulls(field_type, 0)
+    }
+
+    /// a column of `len` `null`s
+    fn nulls(field_type: Option<FieldType>, len: usize) -> Self {
+        match field_type {
+            None => Self::Null(len),
+            Some(FieldType::Bool) => Self::Bool(vec![None; len]),
+            Some(FieldType::Int) => Self::Int(vec![None; len]),
+            Some(FieldType::Uint) => Self::Uint(vec![None; len]),
+            Some(FieldType::Float) => Self::Float(vec![None; len]),
+            Some(FieldType::String) => Self::String(vec![None; len]),
+            Some(FieldType::Binary) => Self::Binary(vec![None; len]),
+            Some(FieldType::Timestamp) => Self::Timestamp(vec![None; len]),
+        }
+    }
+
+    fn push(&mut self, cell: Option<Cell>) {
+        match (self, cell) {
+            // the type of the column is set before the first value is pushed
+            (Self::Null(len), _) => *len += 1,
+            (Self::Bool(values), Some(Cell::Bool(v))) => values.push(Some(v)),
+            (Self::Int(values) | Self::Timestamp(values), Some(Cell::Int(v))) => {
+                values.push(Some(v));
+            }
+            (Self::Uint(values), Some(Cell::Uint(v))) => values.push(Some(v)),
+            (Self::Float(values), Some(Cell::Float(v))) => values.push(Some(v)),
+            (Self::String(values), Some(Cell::String(v))) => values.push(Some(v)),
+            (Self::Binary(values), Some(Cell::Binary(v))) => values.push(Some(v)),
+            // cells are created for the type of their column, so this is `None`
+            (Self::Bool(values), _) => values.push(None),
+            (Self::Int(values) | Self::Timestamp(values), _) => values.push(None),
+            (Self::Uint(values), _) => values.push(None),
+            (Self::Float(values), _) => values.push(None),
+            (Self::String(values), _) => values.push(None),
+            (Self::Binary(values), _) => values.push(None),
+        }
+    }
+
+    fn take(&mut self) -> ArrayRef {
+        match self {
+            // typed before the first row group is written
+            Self::Null(len) => {
+                Arc::new(StringArray::from(vec![None::<String>; std::mem::take(len)]))
+            }
+            Self::Bool(values) => Arc::new(BooleanArray::from(std::mem::take(values))),
+            Self::Int(values) => Arc::new(Int64Array::from(std::mem::take(values))),
+            Self::Uint(values) => Arc::new(UInt64Array::from(std::mem::take(values))),
+            Self::Float(values) => Arc::new(Float64Array::from(std::mem::take(values))),
+            Self::String(values) => Arc::new(StringArray::from(std::mem::take(values))),
+            Self::Binary(values) => {
+                let values = std::mem::take(values);
+                Arc::new(BinaryArray::from(
+                    values.iter().map(Option::as_deref).collect::<Vec<_>>(),
+                ))
+            }
+            Self::Timestamp(values) => {
+                Arc::new(TimestampNanosecondArray::from(std::mem::take(values)))
+            }
+        }
+    }
+}
+
+/// Records converted to the columns of a row group, not yet buffered
+struct Rows {
+    /// types of the fields, including the ones inferred from these records
+    types: Vec<Option<FieldType>>,
+    rows: Vec<Vec<Option<Cell>>>,
+}
+
+/// The schema of a file and the records buffered for its next row group
+struct RowGroup {
+    /// fields without a type have only been `null` so far
+    fields: Vec<(String, Option<FieldType>)>,
+    /// fixed once the first row group is written
+    schema: Option<SchemaRef>,
+    /// inferred from the first record, records with other fields do not fit
+    inferred: bool,
+    columns: Vec<Column>,
+    rows: usize,
+    bytes: usize,
+    /// when the first record of the row group was buffered
+    first_ns: u64,
+}
+
+impl RowGroup {
+    fn new(fields: Vec<(String, Option<FieldType>)>) -> Self {
+        let columns = fields
+            .iter()
+            .map(|(_, field_type)| Column::new(*field_type))
+            .collect();
+        Self {
+            fields,
+            schema: None,
+            inferred: false,
+            columns,
+            rows: 0,
+            bytes: 0,
+            first_ns: 0,
+        }
+    }
+
+    /// a row group with the fields of `record`
+    fn infer(record: &Value) -> Result<Self> {
+        let obj = record
+            .as_object()
+            .ok_or_else(|| Error::from("Only objects can be written as columnar records"))?;
+        let mut fields: Vec<_> = obj
+            .iter()
+            .map(|(name, value)| {
+                let field_type = (!value.is_null()).then(|| FieldType::infer(value));
+                (name.to_string(), field_type)
+            })
+            .collect();
+        fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut row_group = Self::new(fields);
+        row_group.inferred = true;
+        Ok(row_group)
+    }
+
+    /// fields are sorted by name
+    fn has_field(&self, name: &str) -> bool {
+        self.fields
+            .binary_search_by(|(field, _)| field.as_str().cmp(name))
+            .is_ok()
+    }
+
+    /// converts `records` to cells, without buffering them
+    fn convert(&self, records: &[&Value]) -> Result<Rows> {
+        let mut types: Vec<_> = self.fields.iter().map(|(_, t)| *t).collect();
+        let mut rows = Vec::with_capacity(records.len());
+        for record in records {
+            let obj = record
+                .as_object()
+                .ok_or_else(|| Error::from("Only objects can be written as columnar records"))?;
+            if self.inferred {
+                if let Some(name) = obj.keys().find(|name| !self.has_field(name)) {
+                    return Err(format!(
+                        "Field `{name}` is not in the schema inferred from the first record"
+                    )
+                    .into());
+                }
+            }
+            let mut cells = Vec::with_capacity(types.len());
+            for ((name, _), field_type) in self.fields.iter().zip(types.iter_mut()) {
+                let cell = match record.get(name.as_str()) {
+                    Some(value) if !value.is_null() => {
+                        let field_type = *field_type.get_or_insert_with(|| FieldType::infer(value));
+                        Cell::new(name, field_type, value)?
+                    }
+                    _ => None,
+                };
+                cells.push(cell);
+            }
+            rows.push(cells);
+        }
+        Ok(Rows { types, rows })
+    }
+
+    fn push(&mut self, rows: Rows) {
+        for (((_, field_type), column), inferred) in self
+            .fields
+            .iter_mut()
+            .zip(self.columns.iter_mut())
+            .zip(rows.types)
+        {
+            if let (None, Some(inferred)) = (*field_type, inferred) {
+                let len = match column {
+                    Column::Null(len) => *len,
+                    _ => 0,
+                };
+                *field_type = Some(inferred);
+                *column = Column::nulls(Some(inferred), len);
+            }
+        }
+        for cells in rows.rows {
+            if self.rows == 0 {
+                self.first_ns = nanotime();
+            }
+            for (column, cell) in self.columns.iter_mut().zip(cells) {
+                self.bytes += cell.as_ref().map_or(0, Cell::size);
+                column.push(cell);
+            }
+            self.rows += 1;
+        }
+    }
+
+    /// the schema of the file, fields without a type by now become strings
+    fn schema(&mut self) -> SchemaRef {
+        if let Some(schema) = &self.schema {
+            return schema.clone();
+        }
+        for ((_, field_type), column) in self.fields.iter_mut().zip(self.columns.iter_mut()) {
+            if field_type.is_none() {
+                let len = match column {
+                    Column::Null(len) => *len,
+                    _ => 0,
+                };
+                *field_type = Some(FieldType::String);
+                *column = Column::nulls(*field_type, len);
+            }
+        }
+        let schema = Arc::new(Schema::new(
+            self.fields
+                .iter()
+                .map(|(name, field_type)| {
+                    let data_type = field_type.unwrap_or(FieldType::String).data_type();
+                    Field::new(name, data_type, true)
+                })
+                .collect(),
+        ));
+        self.schema = Some(schema.clone());
+        schema
+    }
+
+    fn take(&mut self) -> Result<RecordBatch> {
+        let schema = self.schema();
+        let columns = self.columns.iter_mut().map(Column::take).collect();
+        self.rows = 0;
+        self.bytes = 0;
+        Ok(RecordBatch::try_new(schema, columns)?)
+    }
+}
+
+/// A columnar file that is being written
+pub(crate) struct ColumnarFile {
+    config: Config,
+    row_group: Option<RowGroup>,
+    writer: Option<Box<dyn BatchWriter>>,
+    contraflow: Option<ContraflowData>,
+}
+
+impl ColumnarFile {
+    /// Starts a new file
+    pub(crate) fn new(config: &Config) -> Self {
+        let row_group = config.schema.as_ref().map(|fields| {
+            RowGroup::new(
+                fields
+                    .iter()
+                    .map(|field| (field.name.clone(), Some(field.field_type)))
+                    .collect(),
+            )
+        });
+        Self {
+            config: config.clone(),
+            row_group,
+            writer: None,
+            contraflow: None,
+        }
+    }
+
+    /// Checks that `records` can be written, without buffering them
+    ///
+    /// # Errors
+    ///   * if a record is not an object or does not match the schema
+    pub(crate) fn check(&self, records: &[&Value]) -> Result<()> {
+        match (&self.row_group, records.first()) {
+            (Some(row_group), _) => row_group.convert(records).map(|_| ()),
+            (None, Some(first)) => RowGroup::infer(first)?.convert(records).map(|_| ()),
+            (None, None) => Ok(()),
+        }
+    }
+
+    /// Buffers `records`, which need to be objects. Either all of them are buffered or none.
+    ///
+    /// # Errors
+    ///   * if a record is not an object or does not match the schema
+    pub(crate) fn write(&mut self, records: &[&Value]) -> Result<()> {
+        if let Some(row_group) = self.row_group.as_mut() {
+            let rows = row_group.convert(records)?;
+            row_group.push(rows);
+        } else if let Some(first) = records.first() {
+            let mut row_group = RowGroup::infer(first)?;
+            let rows = row_group.convert(records)?;
+            row_group.push(rows);
+            self.row_group = Some(row_group);
+        }
+        Ok(())
+    }
+
+    /// Tracks an event with records in this file, so it can be acked or failed with it
+    pub(crate) fn track(&mut self, contraflow: ContraflowData) {
+        match self.contraflow.as_mut() {
+            Some(tracked) => tracked.track(contraflow),
+            None => self.contraflow = Some(contraflow),
+        }
+    }
+
+    /// Returns `true` if the buffered records should be written as a row group
+    pub(crate) fn needs_flush(&self) -> bool {
+        self.row_group.as_ref().map_or(false, |row_group| {
+            row_group.rows > 0
+                && (row_group.rows >= self.config.max_rows
+                    || row_group.bytes >= self.config.max_bytes
+                    || self.config.max_delay_ms.map_or(false, |delay| {
+                        nanotime().saturating_sub(row_group.first_ns) >= delay * 1_000_000
+                    }))
+        })
+    }
+
+    /// Encodes the buffered records as a row group, returns the bytes ready to be written
+    ///
+    /// # Errors
+    ///   * if encoding fails
+    pub(crate) fn flush(&mut self) -> Result<Vec<u8>> {
+        match self.row_group.as_mut() {
+            Some(row_group) if row_group.rows > 0 => {
+                let batch = row_group.take()?;
+                let writer = if let Some(writer) = self.writer.as_mut() {
+                    writer
+                } else {
+                    let writer: Box<dyn BatchWriter> = match self.config.format {
+                        Format::Parquet => {
+                            Box::new(ParquetWriter::new(batch.schema(), self.config.compression)?)
+                        }
+                        Format::ArrowIpc => Box::new(ArrowIpcWriter::new(&batch.schema())?),
+                    };
+                    self.writer.insert(writer)
+                };
+                writer.write(&batch)
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Takes the events that were written to this file
+    pub(crate) fn take_contraflow(&mut self) -> Option<ContraflowData> {
+        self.contraflow.take()
+    }
+
+    /// Writes the remaining records and completes the file, returns the remaining bytes of the file
+    ///
+    /// # Errors
+    ///   * if encoding fails
+    pub(crate) fn finish(mut self) -> Result<Vec<u8>> {
+        let mut data = self.flush()?;
+        let writer: Box<dyn BatchWriter> = match (self.writer.take(), self.row_group.as_mut()) {
+            (Some(writer), _) => writer,
+            // no records, write an empty file with the declared schema
+            (None, Some(row_group)) => match self.config.format {
+                Format::Parquet => Box::new(ParquetWriter::new(
+                    row_group.schema(),
+                    self.config.compression,
+                )?),
+                Format::ArrowIpc => Box::new(ArrowIpcWriter::new(&row_group.schema())?),
+            },
+            // no records and no schema, there is nothing to write
+            (None, None) => return Ok(data),
+        };
+        data.append(&mut writer.finish()?);
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use tremor_value::literal;
+
+    fn config(config: Value<'static>) -> Result<Config> {
+        Config::new(&config)
+    }
+
+    /// returns the number of row groups and the records
+    fn read_parquet(data: Vec<u8>) -> Result<(usize, Vec<RecordBatch>)> {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(data))?;
+        let row_groups = builder.metadata().num_row_groups();
+        let batches = builder
+            .build()?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok((row_groups, batches))
+    }
+
+    #[test]
+    fn parquet_inferred_schema() -> Result<()> {
+        let config = config(literal!({
+            "format": "parquet",
+            "max_rows": 2
+        }))?;
+        let mut file = ColumnarFile::new(&config);
+        let mut data = Vec::new();
+        for i in 0_i64..5 {
+            file.write(&[&literal!({
+                "name": format!("snot{i}"),
+                "count": i,
+                "ratio": 0.5,
+                "ok": i % 2 == 0,
+                "tags": ["a", "b"]
+            })])?;
+            if file.needs_flush() {
+                data.append(&mut file.flush()?);
+            }
+        }
+        // a record with a missing field and one that does not fit
+        file.write(&[&literal!({"name": "badger"})])?;
+        assert!(file.write(&[&literal!({"count": "badger"})]).is_err());
+        // fields first seen after the schema was inferred can not be written
+        assert!(file
+            .write(&[&literal!({"name": "badger", "new": 1})])
+            .is_err());
+        assert!(file.write(&[&literal!("badger")]).is_err());
+        // no record of an event is written if one does not fit
+        let records = [literal!({"count": 42}), literal!({"count": "badger"})];
+        assert!(file.check(&[&records[0], &records[1]]).is_err());
+        assert!(file.write(&[&records[0], &records[1]]).is_err());
+        assert!(file.take_contraflow().is_none());
+        let mut rest = file.finish()?;
+        data.append(&mut rest);
+
+        let (row_groups, batches) = read_parquet(data)?;
+        // row groups of 2, 2 and 2 records
+        assert_eq!(3, row_groups);
+        assert_eq!(1, batches.len());
+        let batch = &batches[0];
+        assert_eq!(6, batch.num_rows());
+        let schema = batch.schema();
+        let names: Vec<_> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(vec!["count", "name", "ok", "ratio", "tags"], names);
+        assert_eq!(&DataType::Int64, schema.field(0).data_type());
+        assert_eq!(&DataType::Utf8, schema.field(4).data_type());
+        assert!(batch.column(0).is_null(5));
+        let tags = batch
+            .column(4)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .ok_or("not a string array")?;
+        assert_eq!(r#"["a","b"]"#, tags.value(0));
+        Ok(())
+    }
+
+    #[test]
+    fn parquet_declared_schema() -> Result<()> {
+        let config = config(literal!({
+            "format": "parquet",
+            "compression": "zstd",
+            "schema": [
+                {"name": "ts", "type": "timestamp"},
+                {"name": "value", "type": "float"}
+            ]
+        }))?;
+        let mut file = ColumnarFile::new(&config);
+        file.write(&[
+            &literal!({"ts": 1_000_000_000, "value": 1, "ignored": "snot"}),
+            &literal!({"ts": 2_000_000_000, "value": 2.5}),
+        ])?;
+        assert!(!file.needs_flush());
+        let data = file.finish()?;
+
+        let (row_groups, batches) = read_parquet(data)?;
+        assert_eq!(1, row_groups);
+        let batch = &batches[0];
+        assert_eq!(2, batch.num_columns());
+        assert_eq!(
+            &DataType::Timestamp(TimeUnit::Nanosecond, None),
+            batch.schema().field(0).data_type()
+        );
+        let values = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .ok_or("not a float array")?;
+        assert_eq!(1.0, values.value(0));
+        assert_eq!(2.5, values.value(1));
+        Ok(())
+    }
+
+    #[test]
+    fn parquet_empty_file() -> Result<()> {
+        let config = config(literal!({
+            "format": "parquet",
+            "schema": [{"name": "snot", "type": "string"}]
+        }))?;
+        let data = ColumnarFile::new(&config).finish()?;
+        let (row_groups, batches) = read_parquet(data)?;
+        assert_eq!(0, row_groups);
+        assert!(batches.is_empty());
+
+        // without records and schema there is no file
+        let config = config(literal!({"format": "parquet"}))?;
+        let data = ColumnarFile::new(&config).finish()?;
+        assert!(data.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn arrow_ipc() -> Result<()> {
+        let config = config(literal!({
+            "format": "arrow_ipc",
+            "max_rows": 1
+        }))?;
+        let mut file = ColumnarFile::new(&config);
+        let mut data = Vec::new();
+        for i in 0_u64..3 {
+            file.write(&[&literal!({ "id": i })])?;
+            assert!(file.needs_flush());
+            data.append(&mut file.flush()?);
+        }
+        let mut rest = file.finish()?;
+        data.append(&mut rest);
+
+        let reader = arrow::ipc::reader::FileReader::try_new(std::io::Cursor::new(data), None)?;
+        let batches = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+        assert_eq!(3, batches.len());
+        assert_eq!(&DataType::UInt64, batches[0].schema().field(0).data_type());
+        Ok(())
+    }
+
+    #[test]
+    fn max_bytes() -> Result<()> {
+        let config = config(literal!({
+            "format": "parquet",
+            "max_bytes": 10
+        }))?;
+        let mut file = ColumnarFile::new(&config);
+        file.write(&[&literal!({"name": "snot"})])?;
+        assert!(!file.needs_flush());
+        file.write(&[&literal!({"name": "badger"})])?;
+        assert!(file.needs_flush());
+        Ok(())
+    }
+
+    #[test]
+    fn null_fields_are_inferred_later() -> Result<()> {
+        let config = config(literal!({
+            "format": "parquet",
+            "max_rows": 2
+        }))?;
+        let mut file = ColumnarFile::new(&config);
+        let mut data = Vec::new();
+        file.write(&[&literal!({"count": null, "none": null})])?;
+        file.write(&[&literal!({"count": 42, "none": null})])?;
+        assert!(file.needs_flush());
+        data.append(&mut file.flush()?);
+        // the schema is fixed with the first row group
+        assert!(file.write(&[&literal!({"none": 1})]).is_err());
+        data.append(&mut file.finish()?);
+
+        let (_, batches) = read_parquet(data)?;
+        let schema = batches[0].schema();
+        assert_eq!(&DataType::Int64, schema.field(0).data_type());
+        assert_eq!(&DataType::Utf8, schema.field(1).data_type());
+        let counts = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .ok_or("not an int array")?;
+        assert!(counts.is_null(0));
+        assert_eq!(42, counts.value(1));
+        Ok(())
+    }
+}
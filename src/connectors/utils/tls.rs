@@ -17,11 +17,22 @@
 use std::path::{Path, PathBuf};
 
 use crate::errors::{Error, Kind as ErrorKind, Result};
+use async_rustls::TlsAcceptor;
+use async_std::task;
 use async_tls::TlsConnector;
 use rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
-use rustls::{Certificate, ClientConfig, NoClientAuth, PrivateKey, RootCertStore, ServerConfig};
+use rustls::{
+    AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, Certificate, ClientConfig,
+    NoClientAuth, PrivateKey, RootCertStore, ServerConfig, ServerSession, Session,
+};
 use rustls_native_certs::load_native_certs;
+use sha2::{Digest, Sha256};
 use std::io::{BufReader, Cursor};
+use std::net::IpAddr;
+use std::sync::{Arc, PoisonError, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+use tremor_value::{literal, Value};
+use x509_parser::{extensions::GeneralName, parse_x509_certificate};
 
 lazy_static! {
     static ref SYSTEM_ROOT_CERTS: RootCertStore = {
@@ -39,8 +50,34 @@ lazy_static! {
 
 #[derive(Debug, Clone, Deserialize)]
 pub(crate) struct TLSServerConfig {
+    /// Path to the pem-encoded certificate (-chain) of the server
     pub(crate) cert: PathBuf,
+    /// Path to the private key of the server
     pub(crate) key: PathBuf,
+    /// Path to the pem-encoded certificates of the CAs to verify client certificates with.
+    /// Client certificates are only requested if this is set.
+    #[serde(default = "Default::default")]
+    pub(crate) cafile: Option<PathBuf>,
+    /// Whether clients need to present a certificate, only applies with a `cafile`.
+    /// Defaults to `optional`.
+    #[serde(default = "Default::default")]
+    pub(crate) client_auth: ClientAuth,
+}
+
+/// Client certificate verification mode
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ClientAuth {
+    /// Connections without a valid client certificate are refused
+    Required,
+    /// Clients may connect without a certificate, but presented certificates need to be valid
+    Optional,
+}
+
+impl Default for ClientAuth {
+    fn default() -> Self {
+        Self::Optional
+    }
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -117,12 +154,35 @@ pub(crate) fn load_keys(path: &Path) -> Result<PrivateKey> {
     }
 }
 
+/// Load the passed CA certificates file into a root store
+fn load_roots(path: &Path) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(&cert).map_err(|e| {
+            Error::from(ErrorKind::TLSError(format!(
+                "Invalid CA certificate in {}: {e:?}",
+                path.display()
+            )))
+        })?;
+    }
+    Ok(roots)
+}
+
 pub(crate) fn load_server_config(config: &TLSServerConfig) -> Result<ServerConfig> {
     let certs = load_certs(&config.cert)?;
 
     let keys = load_keys(&config.key)?;
 
-    let mut server_config = ServerConfig::new(NoClientAuth::new());
+    let client_cert_verifier = if let Some(cafile) = config.cafile.as_ref() {
+        let roots = load_roots(cafile)?;
+        match config.client_auth {
+            ClientAuth::Required => AllowAnyAuthenticatedClient::new(roots),
+            ClientAuth::Optional => AllowAnyAnonymousOrAuthenticatedClient::new(roots),
+        }
+    } else {
+        NoClientAuth::new()
+    };
+    let mut server_config = ServerConfig::new(client_cert_verifier);
     server_config
         // set this server to use one cert together with the loaded private key
         .set_single_cert(certs, keys)?;
@@ -130,6 +190,148 @@ pub(crate) fn load_server_config(config: &TLSServerConfig) -> Result<ServerConfi
     Ok(server_config)
 }
 
+/// Interval to check the files of a `ReloadingServerConfig` for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Server TLS config that is reloaded once the certificate, key or CA files change on disk,
+/// so certificates can be rotated without restarting the connector.
+///
+/// The files are checked every `RELOAD_INTERVAL` in the background, until all clones are dropped.
+#[derive(Clone)]
+pub(crate) struct ReloadingServerConfig {
+    config: TLSServerConfig,
+    current: Arc<RwLock<LoadedServerConfig>>,
+}
+
+struct LoadedServerConfig {
+    /// modification time and length of every file the server config was loaded from
+    files: Vec<Option<(SystemTime, u64)>>,
+    server_config: Arc<ServerConfig>,
+}
+
+impl ReloadingServerConfig {
+    pub(crate) fn new(config: &TLSServerConfig) -> Result<Self> {
+        let files = watched_files(config)
+            .map(|path| stamp(std::fs::metadata(path)))
+            .collect();
+        let server_config = Arc::new(load_server_config(config)?);
+        let current = Arc::new(RwLock::new(LoadedServerConfig {
+            files,
+            server_config,
+        }));
+        task::spawn(watch(config.clone(), Arc::downgrade(&current)));
+        Ok(Self {
+            config: config.clone(),
+            current,
+        })
+    }
+
+    /// The current server config
+    pub(crate) fn get(&self) -> Arc<ServerConfig> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .server_config
+            .clone()
+    }
+
+    pub(crate) fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.get())
+    }
+}
+
+fn watched_files(config: &TLSServerConfig) -> impl Iterator<Item = &PathBuf> {
+    [
+        Some(&config.cert),
+        Some(&config.key),
+        config.cafile.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+fn stamp(metadata: std::io::Result<std::fs::Metadata>) -> Option<(SystemTime, u64)> {
+    metadata.and_then(|m| Ok((m.modified()?, m.len()))).ok()
+}
+
+/// checks the files of `current` for changes until it is dropped
+async fn watch(config: TLSServerConfig, current: Weak<RwLock<LoadedServerConfig>>) {
+    loop {
+        task::sleep(RELOAD_INTERVAL).await;
+        if let Some(current) = current.upgrade() {
+            reload(&config, &current).await;
+        } else {
+            break;
+        }
+    }
+}
+
+/// reloads the server config if any of its files changed.
+/// If reloading fails the previous config stays in use until the files change again.
+async fn reload(config: &TLSServerConfig, current: &RwLock<LoadedServerConfig>) {
+    let mut files = Vec::with_capacity(3);
+    for path in watched_files(config) {
+        files.push(stamp(async_std::fs::metadata(path).await));
+    }
+    if current.read().unwrap_or_else(PoisonError::into_inner).files == files {
+        return;
+    }
+    let loaded = load_server_config(config);
+    let mut current = current.write().unwrap_or_else(PoisonError::into_inner);
+    current.files = files;
+    match loaded {
+        Ok(server_config) => {
+            info!(
+                "Reloaded TLS certificate {} and key {}",
+                config.cert.display(),
+                config.key.display()
+            );
+            current.server_config = Arc::new(server_config);
+        }
+        Err(e) => warn!("Error reloading TLS certificates, keeping the previous ones: {e}"),
+    }
+}
+
+/// Metadata about the certificate the client presented:
+/// its `subject`, `issuer`, subject alternative names in `san` and the sha256 `fingerprint`
+pub(crate) fn client_cert_meta(session: &ServerSession) -> Option<Value<'static>> {
+    let certs = session.get_peer_certificates()?;
+    let cert = certs.first()?;
+    let fingerprint = hex::encode(Sha256::digest(&cert.0));
+    // the certificate was verified already, so parsing it does not fail in practice
+    let (_, x509) = parse_x509_certificate(&cert.0).ok()?;
+    let (mut dns, mut ip, mut email, mut uri) = (vec![], vec![], vec![], vec![]);
+    if let Ok(Some(san)) = x509.subject_alternative_name() {
+        for name in &san.value.general_names {
+            match name {
+                GeneralName::DNSName(name) => dns.push(name.to_string()),
+                GeneralName::IPAddress(bytes) => {
+                    let addr = <[u8; 4]>::try_from(*bytes)
+                        .map(IpAddr::from)
+                        .or_else(|_| <[u8; 16]>::try_from(*bytes).map(IpAddr::from));
+                    if let Ok(addr) = addr {
+                        ip.push(addr.to_string());
+                    }
+                }
+                GeneralName::RFC822Name(name) => email.push(name.to_string()),
+                GeneralName::URI(name) => uri.push(name.to_string()),
+                _ => (),
+            }
+        }
+    }
+    Some(literal!({
+        "subject": x509.subject().to_string(),
+        "issuer": x509.issuer().to_string(),
+        "san": {
+            "dns": dns,
+            "ip": ip,
+            "email": email,
+            "uri": uri
+        },
+        "fingerprint": fingerprint
+    }))
+}
+
 /// if we have a cafile configured, we only load it, and no other ca certificates
 /// if there is no cafile configured, we load the default webpki-roots from Mozilla
 pub(crate) async fn tls_client_connector(config: &TLSClientConfig) -> Result<TlsConnector> {
@@ -220,4 +422,61 @@ mod tests {
         assert_eq!(true, client_config.client_auth_cert_resolver.has_certs());
         Ok(())
     }
+
+    #[test]
+    fn server_config_cafile() -> Result<()> {
+        setup_for_tls();
+
+        let tls_config = TLSServerConfig {
+            cert: Path::new("./tests/localhost.cert").to_path_buf(),
+            key: Path::new("./tests/localhost.key").to_path_buf(),
+            cafile: Some(Path::new("./tests/localhost.cert").to_path_buf()),
+            client_auth: ClientAuth::Optional,
+        };
+        assert!(load_server_config(&tls_config).is_ok());
+
+        let file = tempfile::NamedTempFile::new()?;
+        let path = file.into_temp_path();
+        assert!(load_server_config(&TLSServerConfig {
+            cafile: Some(path.to_path_buf()),
+            ..tls_config
+        })
+        .is_err());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn server_config_reload() -> Result<()> {
+        setup_for_tls();
+
+        let dir = tempfile::tempdir()?;
+        let cert = dir.path().join("cert.pem");
+        let key = dir.path().join("key.pem");
+        std::fs::copy("./tests/localhost.cert", &cert)?;
+        std::fs::copy("./tests/localhost.key", &key)?;
+        let config = ReloadingServerConfig::new(&TLSServerConfig {
+            cert,
+            key: key.clone(),
+            cafile: None,
+            client_auth: ClientAuth::default(),
+        })?;
+        let initial = config.get();
+        assert!(Arc::ptr_eq(&initial, &config.get()));
+
+        // unchanged files are not loaded again
+        reload(&config.config, &config.current).await;
+        assert!(Arc::ptr_eq(&initial, &config.get()));
+
+        // an invalid key keeps the previous config in place
+        std::fs::write(&key, b"snot")?;
+        reload(&config.config, &config.current).await;
+        assert!(Arc::ptr_eq(&initial, &config.get()));
+
+        std::fs::copy("./tests/localhost.key", &key)?;
+        reload(&config.config, &config.current).await;
+        let reloaded = config.get();
+        assert!(!Arc::ptr_eq(&initial, &reloaded));
+        assert!(Arc::ptr_eq(&reloaded, &config.get()));
+        Ok(())
+    }
 }
@@ -149,9 +149,12 @@ error_chain! {
     }
     foreign_links {
         AddrParseError(std::net::AddrParseError);
+        AmqpError(lapin::Error);
         AnyhowError(anyhow::Error);
+        ArrowError(arrow::error::ArrowError);
         AsyncChannelRecvError(async_std::channel::RecvError);
         AsyncChannelTryRecvError(async_std::channel::TryRecvError);
+        AvroError(apache_avro::Error);
         Base64Error(base64::DecodeError);
         ChannelReceiveError(std::sync::mpsc::RecvError);
         Clickhouse(clickhouse_rs::errors::Error);
@@ -173,11 +176,18 @@ error_chain! {
         JsonError(simd_json::Error);
         KafkaError(rdkafka::error::KafkaError);
         ModeParseError(file_mode::ModeParseError);
+        MqttClientError(rumqttc::ClientError);
+        MqttConnectionError(rumqttc::ConnectionError);
+        Mqtt5ClientError(rumqttc::v5::ClientError);
+        Mqtt5ConnectionError(rumqttc::v5::ConnectionError);
         MsgPackDecoderError(rmp_serde::decode::Error);
         MsgPackEncoderError(rmp_serde::encode::Error);
         ParseIntError(std::num::ParseIntError);
         ParseFloatError(std::num::ParseFloatError);
-        //Postgres(postgres::Error);
+        ParquetError(parquet::errors::ParquetError);
+        Postgres(tokio_postgres::Error);
+        ProtobufDecodeError(prost::DecodeError);
+        ProtobufDescriptorError(prost_reflect::DescriptorError);
         RegexError(regex::Error);
         ReqwestError(reqwest::Error);
         InvalidHeaderName(reqwest::header::InvalidHeaderName);
@@ -330,6 +340,10 @@ error_chain! {
             description("Deployment not found")
                 display("Deployment \"{}\" not found", alias)
         }
+        FlowStopping(alias: String) {
+            description("Flow is being undeployed")
+                display("Flow \"{}\" is being undeployed", alias)
+        }
         ConnectorNotFound(flow_id: String, alias: String) {
             description("Connector not found")
                 display("Connector \"{}\" not found in Flow \"{}\"", alias, flow_id)
@@ -11,6 +11,11 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+/// durable operator state for stateful pipelines
+mod checkpoint;
+
+use self::checkpoint::Checkpointer;
 use crate::{
     connectors::{self, sink::SinkMsg, source::SourceMsg},
     errors::{pipe_send_e, Result},
@@ -184,6 +189,10 @@ pub(crate) fn spawn(
     let qsize = crate::QSIZE.load(Ordering::Relaxed);
     let mut pipeline = config.to_pipe(operator_id_gen)?;
     pipeline.optimize();
+    let mut checkpointer = Checkpointer::from_query(&pipeline_alias, config)?;
+    if let Some(checkpointer) = &mut checkpointer {
+        checkpointer.restore(&mut pipeline)?;
+    }
 
     let (tx, rx) = bounded::<Box<Msg>>(qsize);
     // We use a unbounded channel for counterflow, while an unbounded channel seems dangerous
@@ -221,6 +230,7 @@ pub(crate) fn spawn(
             cf_rx,
             mgmt_rx,
             tick_handler,
+            checkpointer,
         ))?;
     Ok(addr)
 }
@@ -517,6 +527,7 @@ pub(crate) async fn pipeline_task(
     cf_rx: Receiver<CfMsg>,
     mgmt_rx: Receiver<MgmtMsg>,
     tick_handler: JoinHandle<()>,
+    mut checkpointer: Option<Checkpointer>,
 ) -> Result<()> {
     pipeline.id = id.to_string();
 
@@ -570,6 +581,20 @@ pub(crate) async fn pipeline_task(
                     };
                     error!("{ctx} Error handling signal: {err_str}");
                 } else {
+                    if let Some(checkpointer) = &mut checkpointer {
+                        let res = match signal.kind {
+                            Some(SignalKind::Tick) => {
+                                checkpointer
+                                    .maybe_checkpoint(&pipeline, signal.ingest_ns)
+                                    .await
+                            }
+                            Some(SignalKind::Drain(_)) => checkpointer.checkpoint(&pipeline).await,
+                            _ => Ok(()),
+                        };
+                        if let Err(e) = res {
+                            error!("{ctx} Error checkpointing state: {e}");
+                        }
+                    }
                     maybe_send(send_signal(&id, signal, &mut dests).await);
                     handle_insights(&mut pipeline, &inputs).await;
                     maybe_send(send_events(&mut eventset, &mut dests).await);
@@ -654,6 +679,11 @@ pub(crate) async fn pipeline_task(
             }
             AnyMsg::Mgmt(MgmtMsg::Stop) => {
                 info!("{ctx} Stopping...");
+                if let Some(checkpointer) = &mut checkpointer {
+                    if let Err(e) = checkpointer.checkpoint(&pipeline).await {
+                        error!("{ctx} Error checkpointing state: {e}");
+                    }
+                }
                 break;
             }
             #[cfg(test)]
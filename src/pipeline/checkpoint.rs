@@ -0,0 +1,352 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Durable operator state for stateful pipelines
+//!
+//! A pipeline is declared stateful by providing a `state_path` config directive:
+//!
+//! ```tremor
+//! define pipeline dedup
+//! pipeline
+//!   #!config state_path = "/var/lib/tremor/state"
+//!   #!config state_checkpoint_interval_s = 10
+//!   ...
+//! end;
+//! ```
+//!
+//! The state of all operators, including the internal state of selects like their
+//! window aggregates, is checkpointed into a sled database at
+//! `<state_path>/<flow>/<pipeline>` every `state_checkpoint_interval_s` seconds,
+//! when the pipeline is drained and when it is stopped. It is restored when the pipeline
+//! is deployed again.
+//!
+//! State is encoded as msgpack, so binary data and the distinction between integers
+//! and floats survive a restart.
+
+use super::Alias;
+use crate::{
+    codec::{msgpack::MsgPack, Codec},
+    errors::{Error, Result},
+};
+use std::{collections::HashSet, path::PathBuf};
+use tremor_pipeline::{query::Query, ExecutableGraph};
+use tremor_script::prelude::*;
+
+/// config directive declaring a pipeline stateful and pointing to the state directory
+const STATE_PATH: &str = "state_path";
+/// config directive for the interval in seconds in which state is checkpointed
+const CHECKPOINT_INTERVAL: &str = "state_checkpoint_interval_s";
+/// default checkpoint interval: 1 minute
+const DEFAULT_CHECKPOINT_INTERVAL_S: u64 = 60;
+
+/// Persists and restores the operator state of a pipeline
+pub(crate) struct Checkpointer {
+    db: sled::Db,
+    codec: MsgPack,
+    interval_ns: u64,
+    last_checkpoint_ns: u64,
+}
+
+impl std::fmt::Debug for Checkpointer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Checkpointer({}ns)", self.interval_ns)
+    }
+}
+
+impl Checkpointer {
+    /// Opens the state store for the pipeline `alias` if the `query` declares it stateful,
+    /// returns `None` otherwise.
+    ///
+    /// # Errors
+    ///   * if the config directives are invalid
+    ///   * if the state store can not be opened
+    pub(crate) fn from_query(alias: &Alias, query: &Query) -> Result<Option<Self>> {
+        let config = &query.0.query.config;
+        let path = if let Some(path) = config.get(STATE_PATH) {
+            path.as_str()
+                .map(PathBuf::from)
+                .ok_or_else(|| Error::from(format!("`{STATE_PATH}` must be a string")))?
+        } else {
+            return Ok(None);
+        };
+        let interval_s = if let Some(interval) = config.get(CHECKPOINT_INTERVAL) {
+            interval.as_u64().filter(|i| *i > 0).ok_or_else(|| {
+                Error::from(format!(
+                    "`{CHECKPOINT_INTERVAL}` must be a positive integer"
+                ))
+            })?
+        } else {
+            DEFAULT_CHECKPOINT_INTERVAL_S
+        };
+        let path = path
+            .join(alias.flow_alias().to_string())
+            .join(alias.pipeline_alias());
+        let db = sled::open(&path)?;
+        info!(
+            "[Pipeline::{alias}] Checkpointing state to {} every {interval_s}s.",
+            path.display()
+        );
+        Ok(Some(Self {
+            db,
+            codec: MsgPack {},
+            interval_ns: interval_s * 1_000_000_000,
+            last_checkpoint_ns: 0,
+        }))
+    }
+
+    /// Restores the previously checkpointed state into `pipeline`.
+    ///
+    /// State of operators that no longer exist in the pipeline is ignored.
+    ///
+    /// # Errors
+    ///   * if the state store can not be read or contains invalid data
+    pub(crate) fn restore(&mut self, pipeline: &mut ExecutableGraph) -> Result<()> {
+        for entry in &self.db {
+            let (key, mut data) = entry?;
+            let operator_id = std::str::from_utf8(&key)?;
+            let checkpoint = self.codec.decode(data.as_mut(), 0)?.unwrap_or_default();
+            if !pipeline.restore_operator_state(operator_id, &checkpoint)? {
+                warn!(
+                    "[Pipeline::{}] Ignoring checkpointed state of unknown operator {operator_id}",
+                    pipeline.id
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Checkpoints the state of `pipeline` if the checkpoint interval has passed at `now_ns`.
+    ///
+    /// # Errors
+    ///   * if the state can not be written
+    pub(crate) async fn maybe_checkpoint(
+        &mut self,
+        pipeline: &ExecutableGraph,
+        now_ns: u64,
+    ) -> Result<()> {
+        if now_ns.saturating_sub(self.last_checkpoint_ns) >= self.interval_ns {
+            self.checkpoint(pipeline).await?;
+            self.last_checkpoint_ns = now_ns;
+        }
+        Ok(())
+    }
+
+    /// Checkpoints the state of `pipeline`, replacing the previous checkpoint atomically.
+    ///
+    /// # Errors
+    ///   * if the state can not be written
+    pub(crate) async fn checkpoint(&mut self, pipeline: &ExecutableGraph) -> Result<()> {
+        let mut batch = sled::Batch::default();
+        let mut current = HashSet::new();
+        for (operator_id, state) in pipeline.operator_states()? {
+            batch.insert(operator_id.as_bytes(), self.codec.encode(&state)?);
+            current.insert(operator_id);
+        }
+        // remove the state of operators whose state got reset
+        for key in self.db.iter().keys() {
+            let key = key?;
+            if !std::str::from_utf8(&key).map_or(false, |k| current.contains(k)) {
+                batch.remove(key);
+            }
+        }
+        self.db.apply_batch(batch)?;
+        self.db.flush_async().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tremor_common::ids::OperatorIdGen;
+    use tremor_pipeline::{Event, EventId};
+    use tremor_value::literal;
+
+    fn pipeline(query: &str) -> Result<(Query, ExecutableGraph)> {
+        let aggr_reg = tremor_script::aggr_registry();
+        let query = Query::parse(query, &*tremor_script::FN_REGISTRY.read()?, &aggr_reg)?;
+        let mut idgen = OperatorIdGen::new();
+        let graph = query.to_pipe(&mut idgen)?;
+        Ok((query, graph))
+    }
+
+    #[async_std::test]
+    async fn stateless_pipeline() -> Result<()> {
+        let (query, _) = pipeline("select event from in into out;")?;
+        let alias = Alias::new("flow", "pipeline");
+        assert!(Checkpointer::from_query(&alias, &query)?.is_none());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn checkpoint_and_restore() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let query_str = format!(
+            r#"
+            #!config state_path = "{}"
+            define script counter
+            script
+              match state of
+                case null => let state = 1
+                default => let state = state + 1
+              end;
+              state
+            end;
+            create script counter;
+            select event from in into counter;
+            select event from counter into out;
+            "#,
+            dir.path().display()
+        );
+        let alias = Alias::new("flow", "pipeline");
+        let (query, mut graph) = pipeline(&query_str)?;
+        let mut checkpointer =
+            Checkpointer::from_query(&alias, &query)?.ok_or("expected a checkpointer")?;
+
+        let mut returns = vec![];
+        for i in 0..3 {
+            let event = Event {
+                id: EventId::from_id(0, 0, i),
+                data: literal!({ "snot": "badger" }).into(),
+                ..Event::default()
+            };
+            graph.enqueue("in", event, &mut returns).await?;
+        }
+        checkpointer.checkpoint(&graph).await?;
+        drop(checkpointer);
+
+        // a new instance of the pipeline continues from the checkpointed state
+        let (query, mut graph) = pipeline(&query_str)?;
+        let mut checkpointer =
+            Checkpointer::from_query(&alias, &query)?.ok_or("expected a checkpointer")?;
+        checkpointer.restore(&mut graph)?;
+        let mut returns = vec![];
+        let event = Event {
+            id: EventId::from_id(0, 0, 3),
+            data: literal!({ "snot": "badger" }).into(),
+            ..Event::default()
+        };
+        graph.enqueue("in", event, &mut returns).await?;
+        let (_, event) = returns.pop().ok_or("no event")?;
+        assert_eq!(&Value::from(4), event.data.suffix().value());
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn checkpoint_is_lossless() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let query_str = format!(
+            r#"
+            #!config state_path = "{}"
+            define script keep
+            script
+              match state of
+                case null => let state = {{ "bin": event, "float": 2.0 }}
+                default => null
+              end;
+              state
+            end;
+            create script keep;
+            select event from in into keep;
+            select event from keep into out;
+            "#,
+            dir.path().display()
+        );
+        let alias = Alias::new("flow", "pipeline");
+        let (query, mut graph) = pipeline(&query_str)?;
+        let mut checkpointer =
+            Checkpointer::from_query(&alias, &query)?.ok_or("expected a checkpointer")?;
+        let mut returns = vec![];
+        let event = Event {
+            data: Value::Bytes(b"snot".to_vec().into()).into(),
+            ..Event::default()
+        };
+        graph.enqueue("in", event, &mut returns).await?;
+        checkpointer.checkpoint(&graph).await?;
+        drop(checkpointer);
+
+        let (query, mut graph) = pipeline(&query_str)?;
+        let mut checkpointer =
+            Checkpointer::from_query(&alias, &query)?.ok_or("expected a checkpointer")?;
+        checkpointer.restore(&mut graph)?;
+        let mut returns = vec![];
+        graph.enqueue("in", Event::default(), &mut returns).await?;
+        let (_, event) = returns.pop().ok_or("no event")?;
+        let state = event.data.suffix().value();
+        assert_eq!(Some(&b"snot"[..]), state.get_bytes("bin"));
+        assert_eq!(Some(&Value::from(2.0)), state.get("float"));
+        assert!(state.get("float").map_or(false, Value::is_f64));
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn checkpoint_and_restore_tumbling_window() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let query_str = format!(
+            r#"
+            #!config state_path = "{}"
+            define window by_3 from tumbling
+            with
+              size = 3
+            end;
+            select {{
+              "count": aggr::stats::count(),
+              "sum": aggr::stats::sum(event.x),
+              "xs": aggr::win::collect_flattened(event.x)
+            }}
+            from in[by_3]
+            group by set(event.g)
+            into out;
+            "#,
+            dir.path().display()
+        );
+        let alias = Alias::new("flow", "pipeline");
+        let (query, mut graph) = pipeline(&query_str)?;
+        let mut checkpointer =
+            Checkpointer::from_query(&alias, &query)?.ok_or("expected a checkpointer")?;
+
+        let mut returns = vec![];
+        for (i, x) in [(0, 1), (1, 2)] {
+            let event = Event {
+                id: EventId::from_id(0, 0, i),
+                data: literal!({ "g": "snot", "x": x }).into(),
+                ..Event::default()
+            };
+            graph.enqueue("in", event, &mut returns).await?;
+        }
+        assert!(returns.is_empty());
+        checkpointer.checkpoint(&graph).await?;
+        drop(checkpointer);
+
+        // the window of the new instance of the pipeline holds the two events
+        // from before the restart and closes with the third one
+        let (query, mut graph) = pipeline(&query_str)?;
+        let mut checkpointer =
+            Checkpointer::from_query(&alias, &query)?.ok_or("expected a checkpointer")?;
+        checkpointer.restore(&mut graph)?;
+        let mut returns = vec![];
+        let event = Event {
+            id: EventId::from_id(0, 0, 2),
+            data: literal!({ "g": "snot", "x": 3 }).into(),
+            ..Event::default()
+        };
+        graph.enqueue("in", event, &mut returns).await?;
+        let (_, event) = returns.pop().ok_or("no event")?;
+        assert_eq!(
+            &literal!({ "count": 3, "sum": 6.0, "xs": [1, 2, 3] }),
+            event.data.suffix().value()
+        );
+        Ok(())
+    }
+}
@@ -91,6 +91,23 @@ pub fn make_preprocessors(preprocessors: &[PreprocessorConfig]) -> Result<Prepro
     preprocessors.iter().map(lookup_with_config).collect()
 }
 
+/// A preprocessor failed, keeping its name for reporting the failure
+#[derive(Debug)]
+pub struct PreprocessorError {
+    /// name of the failing preprocessor
+    pub name: String,
+    /// what went wrong
+    pub error: Error,
+    /// the data passed to the preprocessors, empty when finishing them
+    pub data: Vec<u8>,
+}
+
+impl From<PreprocessorError> for Error {
+    fn from(e: PreprocessorError) -> Self {
+        e.error
+    }
+}
+
 /// Canonical way to preprocess data before it is fed to a codec for decoding.
 ///
 /// Preprocessors might split up the given data in multiple chunks. Each of those
@@ -104,23 +121,42 @@ pub fn preprocess(
     ingest_ns: &mut u64,
     data: Vec<u8>,
     alias: &Alias,
-) -> Result<Vec<Vec<u8>>> {
-    let mut data = vec![data];
-    let mut data1 = Vec::new();
-    for pp in preprocessors {
-        data1.clear();
-        for (i, d) in data.iter().enumerate() {
-            match pp.process(ingest_ns, d) {
-                Ok(mut r) => data1.append(&mut r),
-                Err(e) => {
-                    error!("[Connector::{alias}] Preprocessor [{i}] error: {e}");
-                    return Err(e);
+) -> std::result::Result<Vec<Vec<u8>>, PreprocessorError> {
+    if let Some((head, tail)) = preprocessors.split_first_mut() {
+        // the data is kept until all preprocessors succeeded, so it is returned on failure
+        let mut chunks = match head.process(ingest_ns, &data) {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                error!("[Connector::{alias}] Preprocessor [0] error: {e}");
+                return Err(PreprocessorError {
+                    name: head.name().to_string(),
+                    error: e,
+                    data,
+                });
+            }
+        };
+        let mut chunks1 = Vec::new();
+        for pp in tail {
+            chunks1.clear();
+            for (i, d) in chunks.iter().enumerate() {
+                match pp.process(ingest_ns, d) {
+                    Ok(mut r) => chunks1.append(&mut r),
+                    Err(e) => {
+                        error!("[Connector::{alias}] Preprocessor [{i}] error: {e}");
+                        return Err(PreprocessorError {
+                            name: pp.name().to_string(),
+                            error: e,
+                            data,
+                        });
+                    }
                 }
             }
+            std::mem::swap(&mut chunks, &mut chunks1);
         }
-        std::mem::swap(&mut data, &mut data1);
+        Ok(chunks)
+    } else {
+        Ok(vec![data])
     }
-    Ok(data)
 }
 
 /// Canonical way to finish preprocessors up
@@ -128,7 +164,10 @@ pub fn preprocess(
 /// # Errors
 ///
 /// * If a preprocessor failed
-pub fn finish(preprocessors: &mut [Box<dyn Preprocessor>], alias: &Alias) -> Result<Vec<Vec<u8>>> {
+pub fn finish(
+    preprocessors: &mut [Box<dyn Preprocessor>],
+    alias: &Alias,
+) -> std::result::Result<Vec<Vec<u8>>, PreprocessorError> {
     if let Some((head, tail)) = preprocessors.split_first_mut() {
         let mut data = match head.finish(None) {
             Ok(d) => d,
@@ -137,7 +176,11 @@ pub fn finish(preprocessors: &mut [Box<dyn Preprocessor>], alias: &Alias) -> Res
                     "[Connector::{alias}] Preprocessor '{}' finish error: {e}",
                     head.name()
                 );
-                return Err(e);
+                return Err(PreprocessorError {
+                    name: head.name().to_string(),
+                    error: e,
+                    data: Vec::new(),
+                });
             }
         };
         let mut data1 = Vec::new();
@@ -151,7 +194,11 @@ pub fn finish(preprocessors: &mut [Box<dyn Preprocessor>], alias: &Alias) -> Res
                             "[Connector::{alias}] Preprocessor '{}' finish error: {e}",
                             pp.name()
                         );
-                        return Err(e);
+                        return Err(PreprocessorError {
+                            name: pp.name().to_string(),
+                            error: e,
+                            data: Vec::new(),
+                        });
                     }
                 }
             }
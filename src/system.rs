@@ -19,7 +19,7 @@ pub mod flow_supervisor;
 
 use self::flow::Flow;
 use crate::errors::{Error, Kind as ErrorKind, Result};
-use crate::{connectors, QSIZE};
+use crate::{connectors, log_error, QSIZE};
 use async_std::channel::{bounded, Sender};
 use async_std::prelude::*;
 use async_std::task::JoinHandle;
@@ -114,6 +114,9 @@ impl World {
             })
             .await?;
         if let Err(e) = rx.recv().await? {
+            if let ErrorKind::DuplicateFlow(_) | ErrorKind::FlowStopping(_) = e.0 {
+                return Err(e);
+            }
             let err_str = match e {
                 Error(
                     ErrorKind::Script(e)
@@ -137,6 +140,41 @@ impl World {
         }
     }
 
+    /// Drains and stops the flow identified by `flow_id` and removes it from the runtime
+    ///
+    /// # Errors
+    ///  * if the flow is not deployed or fails to stop
+    pub async fn undeploy_flow(&self, flow_id: String) -> Result<()> {
+        self.undeploy(flow::Alias::new(flow_id)).await.map(|_| ())
+    }
+
+    /// Replaces the deployed flow with the same alias as `flow` by `flow`
+    ///
+    /// If `flow` fails to start, the replaced definition is deployed again.
+    ///
+    /// # Errors
+    ///  * if the flow is not deployed or `flow` fails to start
+    pub async fn redeploy_flow(&self, flow: &ast::DeployFlow<'static>) -> Result<()> {
+        let previous = self.undeploy(flow::Alias::from(flow)).await?;
+        if let Err(e) = self.start_flow(flow).await {
+            log_error!(
+                self.start_flow(&previous).await,
+                "Error restoring the previous definition of Flow {alias}: {e}",
+                alias = flow.instance_alias
+            );
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    async fn undeploy(&self, id: flow::Alias) -> Result<ast::DeployFlow<'static>> {
+        let (tx, rx) = bounded(1);
+        self.system
+            .send(flow_supervisor::Msg::UndeployFlow(id, tx))
+            .await?;
+        rx.recv().await?
+    }
+
     /// Registers the given connector type with `type_name` and the corresponding `builder`
     ///
     /// # Errors
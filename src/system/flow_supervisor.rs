@@ -23,7 +23,7 @@ use crate::{
 use async_std::channel::{bounded, Sender};
 use async_std::prelude::*;
 use async_std::task::{self, JoinHandle};
-use hashbrown::{hash_map::Entry, HashMap};
+use hashbrown::{hash_map::Entry, HashMap, HashSet};
 use tremor_common::ids::{ConnectorIdGen, OperatorIdGen};
 use tremor_script::ast::DeployFlow;
 
@@ -46,6 +46,14 @@ pub(crate) enum Msg {
     },
     GetFlows(Sender<Result<Vec<Flow>>>),
     GetFlow(Alias, Sender<Result<Flow>>),
+    /// drain and stop a Flow, replying with its definition once it is stopped
+    UndeployFlow(Alias, Sender<Result<DeployFlow<'static>>>),
+    /// a Flow being undeployed finished stopping
+    FlowStopped {
+        id: Alias,
+        result: Result<()>,
+        reply_tx: Sender<Result<DeployFlow<'static>>>,
+    },
     /// Initiate the Quiescence process
     Drain(Sender<Result<()>>),
     /// stop this manager
@@ -55,6 +63,10 @@ pub(crate) enum Msg {
 #[derive(Debug)]
 pub(crate) struct FlowSupervisor {
     flows: HashMap<Alias, Flow>,
+    /// definitions of the deployed flows
+    definitions: HashMap<Alias, DeployFlow<'static>>,
+    /// flows being undeployed, they are kept until they are stopped
+    stopping: HashSet<Alias>,
     operator_id_gen: OperatorIdGen,
     connector_id_gen: ConnectorIdGen,
     known_connectors: connectors::Known,
@@ -65,6 +77,8 @@ impl FlowSupervisor {
     pub fn new(qsize: usize) -> Self {
         Self {
             flows: HashMap::new(),
+            definitions: HashMap::new(),
+            stopping: HashSet::new(),
             known_connectors: connectors::Known::new(),
             operator_id_gen: OperatorIdGen::new(),
             connector_id_gen: ConnectorIdGen::new(),
@@ -90,9 +104,12 @@ impl FlowSupervisor {
     ) {
         let id = Alias::from(&flow);
         let res = match self.flows.entry(id.clone()) {
+            Entry::Occupied(_occupied) if self.stopping.contains(&id) => {
+                Err(ErrorKind::FlowStopping(id.to_string()).into())
+            }
             Entry::Occupied(_occupied) => Err(ErrorKind::DuplicateFlow(id.to_string()).into()),
             Entry::Vacant(vacant) => Flow::start(
-                flow,
+                flow.clone(),
                 &mut self.operator_id_gen,
                 &mut self.connector_id_gen,
                 &self.known_connectors,
@@ -101,6 +118,7 @@ impl FlowSupervisor {
             .await
             .map(|deploy| {
                 vacant.insert(deploy);
+                self.definitions.insert(id, flow);
             }),
         };
         log_error!(
@@ -129,13 +147,78 @@ impl FlowSupervisor {
         );
     }
 
+    async fn handle_undeploy_flow(
+        &mut self,
+        id: Alias,
+        reply_tx: Sender<Result<DeployFlow<'static>>>,
+        system: &Channel,
+    ) {
+        let flow: Result<Flow> = match self.flows.get(&id) {
+            Some(_) if self.stopping.contains(&id) => {
+                Err(ErrorKind::FlowStopping(id.to_string()).into())
+            }
+            Some(flow) => Ok(flow.clone()),
+            None => Err(ErrorKind::FlowNotFound(id.to_string()).into()),
+        };
+        match flow {
+            Ok(flow) => {
+                // kept until it is stopped, so the alias can't be deployed again in the meantime
+                self.stopping.insert(id.clone());
+                let system = system.clone();
+                task::spawn(async move {
+                    let result = undeploy(&flow).await;
+                    log_error!(
+                        system
+                            .send(Msg::FlowStopped {
+                                id: id.clone(),
+                                result,
+                                reply_tx,
+                            })
+                            .await,
+                        "Error sending FlowStopped for {id}: {e}"
+                    );
+                });
+            }
+            Err(e) => {
+                log_error!(
+                    reply_tx.send(Err(e)).await,
+                    "Error sending UndeployFlow response for {id}: {e}"
+                );
+            }
+        }
+    }
+
+    async fn handle_flow_stopped(
+        &mut self,
+        id: &Alias,
+        result: Result<()>,
+        reply_tx: Sender<Result<DeployFlow<'static>>>,
+    ) {
+        self.stopping.remove(id);
+        self.flows.remove(id);
+        let res = match (result, self.definitions.remove(id)) {
+            (Ok(()), Some(definition)) => Ok(definition),
+            (Ok(()), None) => Err(ErrorKind::FlowNotFound(id.to_string()).into()),
+            (Err(e), _) => Err(e),
+        };
+        log_error!(
+            reply_tx.send(res).await,
+            "Error sending UndeployFlow response for {id}: {e}"
+        );
+    }
+
     async fn handle_stop(&self) -> Result<()> {
         info!("Stopping Manager ...");
         if !self.flows.is_empty() {
             // send stop to each deployment
             let (tx, rx) = bounded(self.flows.len());
             let mut expected_stops: usize = 0;
-            for flow in self.flows.values() {
+            // flows being undeployed are stopped already
+            for flow in self
+                .flows
+                .values()
+                .filter(|f| !self.stopping.contains(f.id()))
+            {
                 log_error!(
                     flow.stop(tx.clone()).await,
                     "Failed to stop Deployment \"{alias}\": {e}",
@@ -167,7 +250,11 @@ impl FlowSupervisor {
             info!("Draining all {num_flows} Flows ...");
             let mut alive_flows = 0_usize;
             let (tx, rx) = bounded(num_flows);
-            for (_, flow) in &self.flows {
+            for (_, flow) in self
+                .flows
+                .iter()
+                .filter(|(id, _)| !self.stopping.contains(*id))
+            {
                 if !log_error!(
                     flow.drain(tx.clone()).await,
                     "Failed to drain Deployment \"{alias}\": {e}",
@@ -197,6 +284,7 @@ impl FlowSupervisor {
         let (tx, rx) = bounded(self.qsize);
         let kill_switch = KillSwitch(tx.clone());
         let task_kill_switch = kill_switch.clone();
+        let task_tx = tx.clone();
         let system_h = task::spawn(async move {
             while let Ok(msg) = rx.recv().await {
                 match msg {
@@ -211,6 +299,14 @@ impl FlowSupervisor {
                     }
                     Msg::GetFlows(reply_tx) => self.handle_get_flows(reply_tx).await,
                     Msg::GetFlow(id, reply_tx) => self.handle_get_flow(id, reply_tx).await,
+                    Msg::UndeployFlow(id, reply_tx) => {
+                        self.handle_undeploy_flow(id, reply_tx, &task_tx).await;
+                    }
+                    Msg::FlowStopped {
+                        id,
+                        result,
+                        reply_tx,
+                    } => self.handle_flow_stopped(&id, result, reply_tx).await,
                     Msg::Stop => {
                         self.handle_stop().await?;
                         break;
@@ -224,3 +320,25 @@ impl FlowSupervisor {
         (system_h, tx, kill_switch)
     }
 }
+
+/// drains the flow, so events in flight are delivered, and stops it
+async fn undeploy(flow: &Flow) -> Result<()> {
+    let alias = flow.id();
+    info!("Undeploying Flow {alias} ...");
+    let (tx, rx) = bounded(1);
+    flow.drain(tx).await?;
+    match rx.recv().timeout(DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT).await {
+        Ok(res) => {
+            log_error!(res?, "Error draining Flow {alias}: {e}");
+        }
+        Err(_) => warn!(
+            "Timeout draining Flow {alias} after {}s",
+            DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT.as_secs()
+        ),
+    }
+    let (tx, rx) = bounded(1);
+    flow.stop(tx).await?;
+    rx.recv()
+        .timeout(DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT)
+        .await??
+}
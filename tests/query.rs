@@ -119,6 +119,8 @@ test_cases!(
     window_by_two_scripted,
     window_by_two,
     window_size_tilted,
+    window_sliding,
+    window_sliding_tilted,
     pp_win,
     pp_script,
     pp_operator,
@@ -148,4 +150,5 @@ test_cases!(
     guard_having,
     history,
     roundrobin,
+    join,
 );
@@ -142,6 +142,7 @@ test_cases!(
     pipeline_unknown_param,
     duplicate_stream_name,
     window_both_settings,
+    window_sliding_bad_slide,
     window_group_by_event_in_target,
     window_event_in_target,
     aggr_arity,
@@ -39,6 +39,10 @@ pub type Result<T> = std::result::Result<T, Error>;
 /// Default API timeout applied to operations triggered by the API. E.g. get flow status
 pub const DEFAULT_API_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// API timeout for deploying and undeploying flows, which includes draining them.
+/// Deployments continue after the timeout, until they finished or were rolled back.
+pub const DEPLOY_API_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Clone)]
 pub struct State {
     pub world: World,
@@ -147,6 +151,17 @@ async fn handle_api_request<
 >(
     req: Request,
     handler_func: F,
+) -> tide::Result {
+    handle_api_request_with_timeout(req, handler_func, DEFAULT_API_TIMEOUT).await
+}
+
+async fn handle_api_request_with_timeout<
+    G: std::future::Future<Output = Result<tide::Response>>,
+    F: Fn(Request) -> G,
+>(
+    req: Request,
+    handler_func: F,
+    timeout: Duration,
 ) -> tide::Result {
     let resource_type = accept(&req);
     let path = req.url().path().to_string();
@@ -155,7 +170,7 @@ async fn handle_api_request<
     // Handle request. If any api error is returned, serialize it into a tide response
     // as well, respecting the requested resource type. (and if there's error during
     // this serialization, fall back to the error's conversion into tide response)
-    let r = match handler_func(req).timeout(DEFAULT_API_TIMEOUT).await {
+    let r = match handler_func(req).timeout(timeout).await {
         Err(e) => {
             error!("[API {method} {path}] Timeout");
             Err(e.into())
@@ -185,11 +200,14 @@ pub fn serve(host: String, world: &World) -> JoinHandle<Result<()>> {
         .get(|r| handle_api_request(r, status::get_runtime_status));
     v1_app
         .at("/flows")
-        .get(|r| handle_api_request(r, flow::list_flows));
+        .get(|r| handle_api_request(r, flow::list_flows))
+        .post(|r| handle_api_request_with_timeout(r, flow::deploy_flows, DEPLOY_API_TIMEOUT));
     v1_app
         .at("/flows/:id")
         .get(|r| handle_api_request(r, flow::get_flow))
-        .patch(|r| handle_api_request(r, flow::patch_flow_status));
+        .patch(|r| handle_api_request(r, flow::patch_flow_status))
+        .put(|r| handle_api_request_with_timeout(r, flow::redeploy_flow, DEPLOY_API_TIMEOUT))
+        .delete(|r| handle_api_request_with_timeout(r, flow::undeploy_flow, DEPLOY_API_TIMEOUT));
     v1_app
         .at("/flows/:id/connectors")
         .get(|r| handle_api_request(r, flow::get_flow_connectors));
@@ -540,6 +558,100 @@ mod tests {
             body
         );
 
+        // deploy a flow
+        let troy = r#"
+        define flow api_deploy
+        flow
+            define connector my_null from `null`;
+            create connector my_null;
+        end;
+        deploy flow api_deploy;
+        "#;
+        let mut res = client
+            .post("/v1/flows")
+            .header(headers::CONTENT_TYPE, ResourceType::Troy.as_str())
+            .body_string(troy.to_string())
+            .await?;
+        assert_eq!(StatusCode::Created, res.status());
+        let body = res.body_json::<Vec<ApiFlowStatusReport>>().await?;
+        assert_eq!(1, body.len());
+        assert_eq!("api_deploy", body[0].alias.as_str());
+        assert_eq!(vec![String::from("my_null")], body[0].connectors);
+
+        // deploying it again conflicts
+        let mut res = client
+            .post("/v1/flows")
+            .header(headers::CONTENT_TYPE, ResourceType::Troy.as_str())
+            .body_string(troy.to_string())
+            .await?;
+        assert_eq!(StatusCode::Conflict, res.status());
+        let _ = res.body_bytes().await?; // consume the body
+
+        // a failed deploy rolls back the flows deployed before
+        let partial = r#"
+        define flow api_deploy_first
+        flow
+            define connector my_null from `null`;
+            create connector my_null;
+        end;
+        define flow api_deploy
+        flow
+            define connector my_null from `null`;
+            create connector my_null;
+        end;
+        deploy flow api_deploy_first;
+        deploy flow api_deploy;
+        "#;
+        let mut res = client
+            .post("/v1/flows")
+            .header(headers::CONTENT_TYPE, ResourceType::Troy.as_str())
+            .body_string(partial.to_string())
+            .await?;
+        assert_eq!(StatusCode::Conflict, res.status());
+        let _ = res.body_bytes().await?; // consume the body
+        let mut res = client.get("/v1/flows/api_deploy_first").await?;
+        assert_eq!(StatusCode::NotFound, res.status());
+        let _ = res.body_bytes().await?; // consume the body
+
+        // invalid troy
+        let mut res = client
+            .post("/v1/flows")
+            .header(headers::CONTENT_TYPE, ResourceType::Troy.as_str())
+            .body_string("define flow".to_string())
+            .await?;
+        assert_eq!(StatusCode::BadRequest, res.status());
+        let body = res.body_json::<StaticValue>().await?.into_value();
+        assert!(body.get_str("error").is_some());
+
+        // replace the flow
+        let troy = r#"
+        define flow api_deploy
+        flow
+            define connector my_other_null from `null`;
+            create connector my_other_null;
+        end;
+        deploy flow api_deploy;
+        "#;
+        let body = client
+            .put("/v1/flows/api_deploy")
+            .header(headers::CONTENT_TYPE, ResourceType::Troy.as_str())
+            .body_string(troy.to_string())
+            .await?
+            .body_json::<ApiFlowStatusReport>()
+            .await?;
+        assert_eq!("api_deploy", body.alias.as_str());
+        assert_eq!(vec![String::from("my_other_null")], body.connectors);
+
+        // undeploy the flow
+        let res = client.delete("/v1/flows/api_deploy").await?;
+        assert_eq!(StatusCode::NoContent, res.status());
+        let mut res = client.get("/v1/flows/api_deploy").await?;
+        assert_eq!(StatusCode::NotFound, res.status());
+        let _ = res.body_bytes().await?; // consume the body
+        let mut res = client.delete("/v1/flows/api_deploy").await?;
+        assert_eq!(StatusCode::NotFound, res.status());
+        let _ = res.body_bytes().await?; // consume the body
+
         // cleanup
         world.stop(ShutdownMode::Graceful).await?;
         world_handle.cancel().await;
@@ -18,6 +18,11 @@ use crate::{
     api::prelude::*,
     model::{ApiConnectorStatusReport, ApiFlowStatusReport, PatchStatus},
 };
+use tremor_script::{
+    deploy::Deploy,
+    highlighter::{Dumb, Highlighter},
+    FN_REGISTRY,
+};
 
 pub(crate) async fn list_flows(req: Request) -> Result<Response> {
     let world = &req.state().world;
@@ -30,6 +35,104 @@ pub(crate) async fn list_flows(req: Request) -> Result<Response> {
     reply(&req, result, StatusCode::Ok)
 }
 
+/// parses troy source, errors are rendered the same way as on the command line
+fn parse_troy(src: &str) -> Result<Deploy> {
+    let aggr_reg = tremor_script::registry::aggr();
+    Deploy::parse(src, &*FN_REGISTRY.read()?, &aggr_reg).map_err(|e| {
+        let mut h = Dumb::new();
+        match h.format_error(&e).and_then(|()| h.finalize()) {
+            Ok(()) => Error::bad_request(h.to_string()),
+            Err(_) => Error::bad_request(e.to_string()),
+        }
+    })
+}
+
+async fn read_troy(req: &mut Request) -> Result<Deploy> {
+    match content_type(req) {
+        Some(ResourceType::Troy) | None => parse_troy(&req.body_string().await?),
+        Some(other) => Err(Error::new(
+            StatusCode::UnsupportedMediaType,
+            format!("Expected {}, got {other}", ResourceType::Troy),
+        )),
+    }
+}
+
+/// deploys all flows of the troy source in the request body, either all of them or none
+///
+/// The flows are deployed in a task of their own, so hitting the request timeout
+/// does not interrupt the deployment, it still finishes or rolls back.
+pub(crate) async fn deploy_flows(mut req: Request) -> Result<Response> {
+    let deployable = read_troy(&mut req).await?;
+    let flows: Vec<_> = deployable.iter_flows().cloned().collect();
+    if flows.is_empty() {
+        return Err(Error::bad_request(
+            "No `deploy flow` statement in the given troy source".into(),
+        ));
+    }
+    let world = req.state().world.clone();
+    let result = async_std::task::spawn(async move {
+        let mut deployed: Vec<String> = Vec::new();
+        let mut result: Vec<ApiFlowStatusReport> = Vec::new();
+        for flow in &flows {
+            let res = async {
+                world.start_flow(flow).await?;
+                deployed.push(flow.instance_alias.clone());
+                let flow = world.get_flow(flow.instance_alias.clone()).await?;
+                flow.report_status().await
+            }
+            .await;
+            match res {
+                Ok(report) => result.push(report.into()),
+                Err(e) => {
+                    // roll back the flows deployed so far
+                    for alias in deployed.into_iter().rev() {
+                        if let Err(e) = world.undeploy_flow(alias.clone()).await {
+                            error!("Error undeploying Flow {alias} after a failed deploy: {e}");
+                        }
+                    }
+                    return Err(Error::from(e));
+                }
+            }
+        }
+        Ok(result)
+    })
+    .await?;
+    reply(&req, result, StatusCode::Created)
+}
+
+/// replaces the flow with the definition of the same alias in the troy source in the request body
+///
+/// Like deploying, this runs in a task of its own, so the previous definition is restored
+/// on failure even if the request timed out.
+pub(crate) async fn redeploy_flow(mut req: Request) -> Result<Response> {
+    let deployable = read_troy(&mut req).await?;
+    let flow_id = req.param("id")?.to_string();
+    let flow = deployable
+        .iter_flows()
+        .find(|flow| flow.instance_alias == flow_id)
+        .cloned()
+        .ok_or_else(|| {
+            Error::bad_request(format!(
+                "No `deploy flow {flow_id}` statement in the given troy source"
+            ))
+        })?;
+    let world = req.state().world.clone();
+    let report = async_std::task::spawn(async move {
+        world.redeploy_flow(&flow).await?;
+        world.get_flow(flow_id).await?.report_status().await
+    })
+    .await?;
+    reply(&req, ApiFlowStatusReport::from(report), StatusCode::Ok)
+}
+
+/// drains and stops a flow
+pub(crate) async fn undeploy_flow(req: Request) -> Result<Response> {
+    let world = &req.state().world;
+    let flow_id = req.param("id")?.to_string();
+    world.undeploy_flow(flow_id).await?;
+    Ok(Response::new(StatusCode::NoContent))
+}
+
 pub(crate) async fn get_flow(req: Request) -> Result<Response> {
     let world = &req.state().world;
     let flow_id = req.param("id")?.to_string();
@@ -104,6 +104,7 @@ impl Ingress {
                         .preprocessor
                         .process(&mut at, unsafe { self.buf.get_unchecked(0..n) })?;
                     for mut data in x {
+                        async_std::task::block_on(self.codec.resolve(&data))?;
                         let event = match self.codec.decode(data.as_mut_slice(), at) {
                             Ok(Some(data)) => data,
                             Ok(None) => continue,
@@ -26,7 +26,13 @@ use crate::{op::EventAndInsights, Event, NodeKind, Operator};
 use beef::Cow;
 use halfbrown::HashMap;
 use tremor_common::{ids::OperatorId, stry};
-use tremor_script::{ast::Helper, ast::Stmt, Value};
+use tremor_script::{
+    ast::Helper,
+    ast::Stmt,
+    prelude::{ValueAccessTrait, ValueTrait},
+    Value,
+};
+use tremor_value::literal;
 
 /// Configuration for a node
 #[derive(Debug, Clone, Default)]
@@ -161,6 +167,14 @@ impl Operator for OperatorNode {
     fn skippable(&self) -> bool {
         self.op.skippable()
     }
+
+    fn snapshot(&self) -> Result<Option<Value<'static>>> {
+        self.op.snapshot()
+    }
+
+    fn restore(&mut self, snapshot: &Value) -> Result<()> {
+        self.op.restore(snapshot)
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -419,6 +433,59 @@ impl ExecutableGraph {
         }
         Some(did_chage)
     }
+    /// Returns the checkpointable state of all operators that hold any, by operator id.
+    ///
+    /// This is a record of the `state` manipulated via the `state` keyword in scripts
+    /// and by operators like `generic::counter`, and the `snapshot` of their internal
+    /// state like the window aggregates of selects.
+    ///
+    /// # Errors
+    /// if the internal state of an operator can not be snapshotted
+    pub fn operator_states(&self) -> Result<Vec<(&str, Value<'static>)>> {
+        let mut states = Vec::new();
+        for (node, state) in self.graph.iter().zip(self.state.ops.iter()) {
+            let snapshot = node.snapshot()?;
+            if !state.is_null() || snapshot.is_some() {
+                states.push((
+                    node.id.as_str(),
+                    literal!({
+                        "state": state.clone(),
+                        "snapshot": snapshot.unwrap_or_else(Value::null),
+                    }),
+                ));
+            }
+        }
+        Ok(states)
+    }
+
+    /// Restores the `checkpoint` taken by `operator_states` of the operator with the
+    /// id `operator_id`.
+    ///
+    /// Returns `false` if there is no such operator in this graph.
+    ///
+    /// # Errors
+    /// if the checkpoint is invalid for the operator
+    pub fn restore_operator_state(
+        &mut self,
+        operator_id: &str,
+        checkpoint: &Value,
+    ) -> Result<bool> {
+        let idx = self.graph.iter().position(|node| node.id == operator_id);
+        if let Some((node, state)) =
+            idx.and_then(|idx| self.graph.get_mut(idx).zip(self.state.ops.get_mut(idx)))
+        {
+            *state = checkpoint
+                .get("state")
+                .map_or_else(Value::null, Value::clone_static);
+            if let Some(snapshot) = checkpoint.get("snapshot").filter(|s| !s.is_null()) {
+                node.restore(snapshot)?;
+            }
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
     /// This is a performance critial function!
     ///
     /// # Errors
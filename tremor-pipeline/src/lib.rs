@@ -876,7 +876,7 @@ fn factory(node: &NodeConfig) -> Result<Box<dyn InitializableOperator>> {
     #[cfg(feature = "bert")]
     use op::bert::{SequenceClassificationFactory, SummerizationFactory};
     use op::debug::EventHistoryFactory;
-    use op::generic::{BatchFactory, CounterFactory};
+    use op::generic::{BatchFactory, CounterFactory, JoinFactory};
     use op::grouper::BucketGrouperFactory;
     use op::identity::PassthroughFactory;
     use op::qos::{BackpressureFactory, PercentileFactory, RoundRobinFactory};
@@ -891,6 +891,7 @@ fn factory(node: &NodeConfig) -> Result<Box<dyn InitializableOperator>> {
             BackpressureFactory::new_boxed()
         }
         ["generic", "counter"] => CounterFactory::new_boxed(),
+        ["generic", "join"] => JoinFactory::new_boxed(),
         ["qos", "backpressure"] => BackpressureFactory::new_boxed(),
         ["qos", "roundrobin"] => RoundRobinFactory::new_boxed(),
         ["qos", "percentile"] => PercentileFactory::new_boxed(),
@@ -141,6 +141,24 @@ pub trait Operator: std::fmt::Debug + Send + Sync {
     fn skippable(&self) -> bool {
         false
     }
+
+    /// A snapshot of the internal state of the operator that is not part of the
+    /// pipeline state, like the window aggregates of a select, for checkpointing.
+    /// Defaults to no internal state.
+    ///
+    /// # Errors
+    /// if the internal state can not be snapshotted
+    fn snapshot(&self) -> Result<Option<Value<'static>>> {
+        Ok(None)
+    }
+
+    /// Restores the internal state from a `snapshot` - defaults to a noop
+    ///
+    /// # Errors
+    /// if the snapshot is invalid
+    fn restore(&mut self, _snapshot: &Value) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// Initialisable trait that can be turned from a `NodeConfig`
@@ -0,0 +1,484 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Windowed stream-stream join
+//!
+//! Joins events received on the `left` port with events received on the `right` port
+//! that share the same key and whose ingest timestamps are at most `within` nanoseconds apart.
+//!
+//! The key is taken from the `$join_key` metadata of an event, so it can be any expression
+//! evaluated in a script before the join. Events without a key are sent to the `err` port.
+//!
+//! Every match is emitted on the `out` port as an event with the value
+//! `{"left": <left value>, "right": <right value>}` and the metadata
+//! `{"left": <left metadata>, "right": <right metadata>}`, keeping the origin of the left event.
+//!
+//! Both sides are buffered per key, up to `max_events` events per side. Events leave the
+//! buffer once they are older than `within`, or when the buffer is full to make room for a new one.
+//! Left events leaving the buffer without being matched are sent to the `unmatched` port
+//! in `inner` mode, and emitted on the `out` port with a `null` right side in `left` mode.
+//! Buffers are expired on events and on ticks.
+//!
+//! ## Configuration
+//!
+//! See [Config](struct.Config.html) for details.
+//!
+//! # Example
+//!
+//! ```trickle
+//! define operator join from generic::join
+//! with
+//!   within = 5000000000,
+//!   mode = "left"
+//! end;
+//! ```
+
+use crate::{op::prelude::*, EventIdGenerator};
+use std::{collections::VecDeque, mem};
+use tremor_script::prelude::*;
+
+const LEFT: &str = "left";
+const RIGHT: &str = "right";
+const UNMATCHED: Cow<'static, str> = Cow::const_str("unmatched");
+
+/// Join semantics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// only emit matches, unmatched left events go to the `unmatched` port
+    Inner,
+    /// emit unmatched left events with a `null` right side
+    Left,
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Self::Inner
+    }
+}
+
+fn default_max_events() -> usize {
+    10_000
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The maximum difference between the ingest timestamps of joined events in nanoseconds
+    pub within: u64,
+    /// Join semantics, `inner` or `left` (default: `inner`)
+    #[serde(default = "Default::default")]
+    pub mode: Mode,
+    /// The maximum number of events buffered for each side (default: 10000)
+    #[serde(default = "default_max_events")]
+    pub max_events: usize,
+}
+
+impl ConfigImpl for Config {}
+
+#[derive(Debug)]
+struct Buffered {
+    event: Event,
+    matched: bool,
+}
+
+/// Buffered events of one side of the join
+#[derive(Debug, Default)]
+struct Side {
+    by_key: HashMap<String, VecDeque<Buffered>>,
+    /// ingest timestamps and keys of the buffered events in arrival order
+    order: VecDeque<(u64, String)>,
+}
+
+impl Side {
+    fn push(&mut self, key: String, event: Event, matched: bool) {
+        self.order.push_back((event.ingest_ns, key.clone()));
+        self.by_key
+            .entry(key)
+            .or_insert_with(VecDeque::new)
+            .push_back(Buffered { event, matched });
+    }
+
+    fn pop_oldest(&mut self) -> Option<Buffered> {
+        let (_, key) = self.order.pop_front()?;
+        let events = self.by_key.get_mut(&key)?;
+        let oldest = events.pop_front();
+        if events.is_empty() {
+            self.by_key.remove(&key);
+        }
+        oldest
+    }
+
+    /// removes all events older than `within` at `now`
+    fn expire(&mut self, now: u64, within: u64) -> Vec<Buffered> {
+        let mut expired = Vec::new();
+        while let Some((ingest_ns, _)) = self.order.front() {
+            if now.saturating_sub(*ingest_ns) <= within {
+                break;
+            }
+            if let Some(oldest) = self.pop_oldest() {
+                expired.push(oldest);
+            }
+        }
+        expired
+    }
+
+    fn len(&self) -> usize {
+        self.order.len()
+    }
+}
+
+#[derive(Debug)]
+struct Join {
+    config: Config,
+    left: Side,
+    right: Side,
+    event_id_gen: EventIdGenerator,
+}
+
+op!(JoinFactory(uid, node) {
+    if let Some(map) = &node.config {
+        let config: Config = Config::new(map)?;
+        Ok(Box::new(Join {
+            config,
+            left: Side::default(),
+            right: Side::default(),
+            event_id_gen: EventIdGenerator::for_operator(uid),
+        }))
+    } else {
+        Err(ErrorKind::MissingOpConfig(node.id.clone()).into())
+    }
+});
+
+/// creates the joined event of `left` and `right`, tracking the ids and operator metadata of both,
+/// it originates from the left event
+fn join(event_id_gen: &mut EventIdGenerator, left: &Event, right: Option<&Event>) -> Result<Event> {
+    let mut id = event_id_gen.next_id();
+    id.track(&left.id);
+    let mut op_meta = left.op_meta.clone();
+    let mut data = left.data.clone();
+    let mut ingest_ns = left.ingest_ns;
+    let mut transactional = left.transactional;
+    if let Some(right) = right {
+        id.track(&right.id);
+        op_meta.merge(right.op_meta.clone());
+        ingest_ns = ingest_ns.max(right.ingest_ns);
+        transactional |= right.transactional;
+        data.consume(
+            right.data.clone(),
+            |this: &mut ValueAndMeta, other: ValueAndMeta| -> Result<()> {
+                let (value, meta) = this.parts_mut();
+                let (right_value, right_meta) = other.into_parts();
+                *value = literal!({ "left": mem::take(value), "right": right_value });
+                *meta = literal!({ "left": mem::take(meta), "right": right_meta });
+                Ok(())
+            },
+        )?;
+    } else {
+        data.rent_mut(|this| {
+            let (value, meta) = this.parts_mut();
+            *value = literal!({ "left": mem::take(value), "right": Value::null() });
+            *meta = literal!({ "left": mem::take(meta), "right": Value::null() });
+        });
+    }
+    Ok(Event {
+        id,
+        data,
+        ingest_ns,
+        origin_uri: left.origin_uri.clone(),
+        op_meta,
+        transactional,
+        ..Event::default()
+    })
+}
+
+impl Join {
+    /// handles left events leaving the buffer
+    fn evicted(
+        &mut self,
+        evicted: Vec<Buffered>,
+        events: &mut Vec<(Cow<'static, str>, Event)>,
+    ) -> Result<()> {
+        for buffered in evicted {
+            if buffered.matched {
+                continue;
+            }
+            match self.config.mode {
+                Mode::Inner => events.push((UNMATCHED, buffered.event)),
+                Mode::Left => {
+                    events.push((OUT, join(&mut self.event_id_gen, &buffered.event, None)?));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn expire(&mut self, now: u64, events: &mut Vec<(Cow<'static, str>, Event)>) -> Result<()> {
+        let expired = self.left.expire(now, self.config.within);
+        self.evicted(expired, events)?;
+        self.right.expire(now, self.config.within);
+        Ok(())
+    }
+}
+
+impl Operator for Join {
+    fn on_event(
+        &mut self,
+        _uid: OperatorId,
+        port: &str,
+        _state: &mut Value<'static>,
+        event: Event,
+    ) -> Result<EventAndInsights> {
+        let is_left = if port.eq_ignore_ascii_case(LEFT) {
+            true
+        } else if port.eq_ignore_ascii_case(RIGHT) {
+            false
+        } else {
+            return Ok(vec![(ERR, event)].into());
+        };
+        let key = if let Some(key) = event.data.suffix().meta().get("join_key") {
+            key.encode()
+        } else {
+            return Ok(vec![(ERR, event)].into());
+        };
+
+        let mut events = Vec::new();
+        self.expire(event.ingest_ns, &mut events)?;
+
+        let mut matches = Vec::new();
+        if is_left {
+            if let Some(buffered) = self.right.by_key.get(&key) {
+                for right in buffered {
+                    matches.push(join(&mut self.event_id_gen, &event, Some(&right.event))?);
+                }
+            }
+        } else if let Some(buffered) = self.left.by_key.get_mut(&key) {
+            for left in buffered.iter_mut() {
+                left.matched = true;
+                matches.push(join(&mut self.event_id_gen, &left.event, Some(&event))?);
+            }
+        }
+        let matched = !matches.is_empty();
+        events.extend(matches.into_iter().map(|event| (OUT, event)));
+
+        let side = if is_left {
+            &mut self.left
+        } else {
+            &mut self.right
+        };
+        let evicted = if side.len() >= self.config.max_events {
+            side.pop_oldest()
+        } else {
+            None
+        };
+        side.push(key, event, matched);
+        if is_left {
+            self.evicted(evicted.into_iter().collect(), &mut events)?;
+        }
+        Ok(events.into())
+    }
+
+    fn handles_signal(&self) -> bool {
+        true
+    }
+
+    fn on_signal(
+        &mut self,
+        _uid: OperatorId,
+        _state: &mut Value<'static>,
+        signal: &mut Event,
+    ) -> Result<EventAndInsights> {
+        let mut events = Vec::new();
+        self.expire(signal.ingest_ns, &mut events)?;
+        Ok(events.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tremor_common::ids::Id;
+    use tremor_script::Value;
+
+    fn op(mode: Mode, max_events: usize) -> Join {
+        Join {
+            config: Config {
+                within: 10,
+                mode,
+                max_events,
+            },
+            left: Side::default(),
+            right: Side::default(),
+            event_id_gen: EventIdGenerator::for_operator(OperatorId::new(0)),
+        }
+    }
+
+    fn event(event_id: u64, ingest_ns: u64, key: &str, value: Value<'static>) -> Event {
+        Event {
+            id: (1, 1, event_id).into(),
+            ingest_ns,
+            data: (value, literal!({ "join_key": key })).into(),
+            ..Event::default()
+        }
+    }
+
+    fn run(op: &mut Join, port: &str, event: Event) -> Vec<(String, Value<'static>)> {
+        let mut state = Value::null();
+        op.on_event(OperatorId::new(0), port, &mut state, event)
+            .expect("could not run join")
+            .events
+            .into_iter()
+            .map(|(port, event)| (port.to_string(), event.data.suffix().value().clone_static()))
+            .collect()
+    }
+
+    #[test]
+    fn inner() {
+        let mut op = op(Mode::Inner, 100);
+
+        assert!(run(&mut op, "left", event(1, 1, "a", Value::from("req a"))).is_empty());
+        assert!(run(&mut op, "left", event(2, 2, "b", Value::from("req b"))).is_empty());
+
+        let mut state = Value::null();
+        let mut r = op
+            .on_event(
+                OperatorId::new(0),
+                "right",
+                &mut state,
+                event(3, 5, "a", Value::from("res a")),
+            )
+            .expect("could not run join");
+        let (port, joined) = r.events.pop().expect("no joined event");
+        assert!(r.events.is_empty());
+        assert_eq!("out", port);
+        assert_eq!(
+            &literal!({"left": "req a", "right": "res a"}),
+            joined.data.suffix().value()
+        );
+        assert_eq!(
+            &literal!({"left": {"join_key": "a"}, "right": {"join_key": "a"}}),
+            joined.data.suffix().meta()
+        );
+        assert_eq!(5, joined.ingest_ns);
+        assert!(joined.id.is_tracking(&(1, 1, 1).into()));
+        assert!(joined.id.is_tracking(&(1, 1, 3).into()));
+
+        // the right event is buffered for later left events with the same key
+        assert_eq!(
+            vec![(
+                "out".to_string(),
+                literal!({"left": "req a 2", "right": "res a"})
+            )],
+            run(&mut op, "left", event(4, 6, "a", Value::from("req a 2")))
+        );
+
+        // only the unmatched left event is emitted on expiry
+        let mut signal = Event {
+            ingest_ns: 14,
+            ..Event::default()
+        };
+        let mut r = op
+            .on_signal(OperatorId::new(0), &mut state, &mut signal)
+            .expect("could not run join");
+        let (port, unmatched) = r.events.pop().expect("no unmatched event");
+        assert!(r.events.is_empty());
+        assert_eq!("unmatched", port);
+        assert_eq!(&Value::from("req b"), unmatched.data.suffix().value());
+
+        // expired events are not joined anymore
+        assert!(run(&mut op, "right", event(5, 17, "a", Value::from("res a 2"))).is_empty());
+    }
+
+    #[test]
+    fn left() {
+        let mut op = op(Mode::Left, 100);
+
+        assert!(run(&mut op, "left", event(1, 1, "a", Value::from("req a"))).is_empty());
+        assert!(run(&mut op, "left", event(2, 5, "b", Value::from("req b"))).is_empty());
+        // the expired unmatched left event is emitted before the new match
+        assert_eq!(
+            vec![
+                (
+                    "out".to_string(),
+                    literal!({"left": "req a", "right": null})
+                ),
+                (
+                    "out".to_string(),
+                    literal!({"left": "req b", "right": "res b"})
+                )
+            ],
+            run(&mut op, "right", event(3, 12, "b", Value::from("res b")))
+        );
+    }
+
+    #[test]
+    fn bounded() {
+        let mut op = op(Mode::Inner, 2);
+
+        assert!(run(&mut op, "left", event(1, 1, "a", Value::from(1))).is_empty());
+        assert!(run(&mut op, "left", event(2, 2, "b", Value::from(2))).is_empty());
+        // the oldest left event makes room for the new one
+        assert_eq!(
+            vec![("unmatched".to_string(), Value::from(1))],
+            run(&mut op, "left", event(3, 3, "c", Value::from(3)))
+        );
+        assert_eq!(2, op.left.len());
+        assert!(run(&mut op, "right", event(4, 4, "a", Value::from(4))).is_empty());
+    }
+
+    #[test]
+    fn origin_and_op_meta() {
+        let mut op = op(Mode::Inner, 100);
+        let origin_uri = EventOriginUri {
+            scheme: "tremor-test".to_string(),
+            host: "localhost".to_string(),
+            port: None,
+            path: vec!["left".to_string()],
+        };
+        let mut left = event(1, 1, "a", Value::from("req a"));
+        left.origin_uri = Some(origin_uri.clone());
+        left.op_meta.insert(OperatorId::new(1), "left");
+        let mut right = event(2, 2, "a", Value::from("resp a"));
+        right.op_meta.insert(OperatorId::new(2), "right");
+
+        let mut state = Value::null();
+        op.on_event(OperatorId::new(0), "left", &mut state, left)
+            .expect("could not run join");
+        let mut events = op
+            .on_event(OperatorId::new(0), "right", &mut state, right)
+            .expect("could not run join")
+            .events;
+        let (_, joined) = events.pop().expect("no joined event");
+        assert_eq!(Some(origin_uri), joined.origin_uri);
+        assert!(joined.op_meta.contains_key(OperatorId::new(1)));
+        assert!(joined.op_meta.contains_key(OperatorId::new(2)));
+    }
+
+    #[test]
+    fn invalid() {
+        let mut op = op(Mode::Inner, 2);
+        let no_key = Event {
+            data: Value::from("snot").into(),
+            ..Event::default()
+        };
+        assert_eq!(
+            vec![("err".to_string(), Value::from("snot"))],
+            run(&mut op, "left", no_key)
+        );
+        assert_eq!(
+            vec![("err".to_string(), Value::from("snot"))],
+            run(&mut op, "in", event(1, 1, "a", Value::from("snot")))
+        );
+    }
+}
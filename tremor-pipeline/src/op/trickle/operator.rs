@@ -103,4 +103,12 @@ impl Operator for TrickleOperator {
     fn skippable(&self) -> bool {
         self.op.skippable()
     }
+
+    fn snapshot(&self) -> Result<Option<Value<'static>>> {
+        self.op.snapshot()
+    }
+
+    fn restore(&mut self, snapshot: &Value) -> Result<()> {
+        self.op.restore(snapshot)
+    }
 }
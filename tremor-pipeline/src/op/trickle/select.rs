@@ -17,11 +17,14 @@
 #[cfg(test)]
 mod test;
 
-use std::mem;
+use std::{cmp::Ordering, collections::HashSet, mem};
 
 use super::window::{self, Group, Window};
 use crate::op::prelude::trickle::window::{GroupWindow, SelectCtx, Trait};
-use crate::{errors::Result, SignalKind};
+use crate::{
+    errors::{Error, Result},
+    SignalKind,
+};
 use crate::{op::prelude::*, EventIdGenerator};
 use crate::{Event, EventId, Operator};
 use halfbrown::Entry;
@@ -46,6 +49,45 @@ pub(crate) struct Select {
     recursion_limit: u32,
     dflt_group: Group,
     max_groups: usize,
+    /// closures of windows since the last tick, for ordering and limiting their events
+    pending: Vec<Closure>,
+}
+
+/// The events emitted by a window for a number of groups, ordered and limited together
+///
+/// A window closing again for a group before the next tick starts the next closure.
+#[derive(Debug)]
+struct Closure {
+    /// name of the window
+    window: Value<'static>,
+    /// the groups the window closed for
+    groups: HashSet<String>,
+    events: Vec<(Cow<'static, str>, Event)>,
+}
+
+/// Adds `events` to the closures of the windows and groups in `emitted`, which they were emitted by
+fn hold(
+    pending: &mut Vec<Closure>,
+    emitted: Vec<(Value<'static>, String)>,
+    events: Vec<(Cow<'static, str>, Event)>,
+) {
+    for ((window, group), event) in emitted.into_iter().zip(events) {
+        if let Some(closure) = pending
+            .iter_mut()
+            .find(|closure| closure.window == window && !closure.groups.contains(&group))
+        {
+            closure.groups.insert(group);
+            closure.events.push(event);
+        } else {
+            let mut groups = HashSet::new();
+            groups.insert(group);
+            pending.push(Closure {
+                window,
+                groups,
+                events: vec![event],
+            });
+        }
+    }
 }
 
 impl Select {
@@ -81,6 +123,7 @@ impl Select {
             recursion_limit: tremor_script::recursion_limit(),
             dflt_group,
             max_groups,
+            pending: Vec::new(),
         }
     }
     const fn opts() -> ExecOpts {
@@ -89,6 +132,202 @@ impl Select {
             aggr: AggrType::Emit,
         }
     }
+
+    /// if emitted events need to be ordered or limited
+    fn is_ordered(&self) -> bool {
+        !self.select.stmt.order_by.is_empty() || self.select.stmt.maybe_limit.is_some()
+    }
+
+    /// orders the events of each pending window closure by the `order by` clause
+    /// and keeps the first `limit` of them
+    fn order(&mut self) -> Result<Vec<(Cow<'static, str>, Event)>> {
+        let Self {
+            select,
+            recursion_limit,
+            pending,
+            ..
+        } = self;
+        let SelectStmt {
+            stmt: select,
+            consts,
+            locals,
+            ..
+        } = select;
+        let local_stack = LocalStack::with_size(*locals);
+        let opts = Self::opts();
+        consts.window = Value::const_null();
+        consts.group = Value::const_null();
+
+        let mut res = Vec::new();
+        for Closure { events, .. } in pending.drain(..) {
+            let mut ordered = Vec::with_capacity(events.len());
+            for (port, event) in events {
+                let ctx = EventContext::new(event.ingest_ns, event.origin_uri.as_ref());
+                let env = env(&ctx, consts.run(), *recursion_limit);
+                let (value, meta) = event.data.parts();
+                let mut keys = Vec::with_capacity(select.order_by.len());
+                for order_by in &select.order_by {
+                    let key = order_by
+                        .expr
+                        .run(opts, &env, value, &NULL, meta, &local_stack)?;
+                    keys.push(key.into_owned().into_static());
+                }
+                ordered.push((keys, port, event));
+            }
+            ordered.sort_by(|(a, _, _), (b, _, _)| {
+                select
+                    .order_by
+                    .iter()
+                    .zip(a.iter().zip(b))
+                    .map(|(order_by, (a, b))| {
+                        if order_by.descending {
+                            b.cmp(a)
+                        } else {
+                            a.cmp(b)
+                        }
+                    })
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+            if let Some(limit) = select.maybe_limit {
+                ordered.truncate(usize::try_from(limit).unwrap_or(usize::MAX));
+            }
+            res.extend(ordered.into_iter().map(|(_, port, event)| (port, event)));
+        }
+        Ok(res)
+    }
+
+    /// closes the windows of all groups that are due at the tick `signal`,
+    /// returning the window and group of every event if they are ordered
+    fn on_tick(
+        &mut self,
+        signal: &Event,
+    ) -> Result<(EventAndInsights, Vec<(Value<'static>, String)>)> {
+        let ordered = self.is_ordered();
+        // we only react on ticks and when we have windows
+        let Self {
+            select,
+            windows,
+            event_id_gen,
+            groups,
+            recursion_limit,
+            ..
+        } = self;
+        let recursion_limit = *recursion_limit;
+
+        // if it isn't a tick or we do not have any windows, or have no
+        // recorded groups, we can just return
+        if signal.kind != Some(SignalKind::Tick) || windows.is_empty() || groups.is_empty() {
+            return Ok((EventAndInsights::default(), Vec::new()));
+        }
+
+        let ingest_ns = signal.ingest_ns;
+        let mut emitted = Vec::new();
+
+        let opts = Self::opts();
+        let SelectStmt {
+            stmt: select,
+            consts,
+            locals,
+            ..
+        } = select;
+        let mut res = EventAndInsights::default();
+
+        let data: ValueAndMeta = (Value::const_null(), Value::object()).into();
+        let op_meta = OpMeta::default();
+        let local_stack = tremor_script::interpreter::LocalStack::with_size(*locals);
+
+        consts.window = Value::const_null();
+        consts.group = Value::const_null();
+        consts.args = Value::const_null();
+
+        let mut ctx = EventContext::new(ingest_ns, None);
+        ctx.cardinality = groups.len();
+
+        let mut to_remove = vec![];
+        for (group_str, g) in groups.iter_mut() {
+            if let Some(w) = &mut g.windows {
+                let mut outgoing_event_id = event_id_gen.next_id();
+                mem::swap(&mut w.id, &mut outgoing_event_id);
+
+                let mut run = consts.run();
+                run.group = &g.value;
+                run.window = &w.name;
+                let window_event = w.window.on_tick(ingest_ns);
+                let mut can_remove = window_event.emit;
+
+                if window_event.emit {
+                    // push
+                    let mut outgoing_event_id = event_id_gen.next_id();
+
+                    mem::swap(&mut outgoing_event_id, &mut w.id);
+                    // remember the ID of the pane we are closing in case this is
+                    // a sliding window
+                    let pane_id = (w.window.panes() > 1).then(|| outgoing_event_id.clone());
+
+                    let mut emitted_by = Vec::new();
+                    let mut ctx = SelectCtx {
+                        select,
+                        local_stack: &local_stack,
+                        opts,
+                        ctx: &ctx,
+                        event_id: outgoing_event_id,
+                        event_id_gen,
+                        ingest_ns,
+                        op_meta: &op_meta,
+                        origin_uri: &None,
+                        transactional: w.transactional,
+                        recursion_limit,
+                        emitted_by: ordered.then_some(&mut emitted_by),
+                    };
+                    // a sliding window emits the data of all panes in its range
+                    w.track_panes(&mut ctx);
+                    if w.should_emit() {
+                        let sliding_aggrs = w.sliding_aggrs()?;
+                        let mut env = env(ctx.ctx, run, recursion_limit);
+                        env.aggrs = sliding_aggrs.as_ref().unwrap_or(&w.aggrs);
+                        if let Some(port_and_event) =
+                            super::select::execute_select_and_having(&ctx, &env, &data)?
+                        {
+                            if let Some(emitted_by) = ctx.emitted_by.as_deref_mut() {
+                                emitted_by.push(w.name.clone());
+                            }
+                            res.events.push(port_and_event);
+                        };
+                    }
+                    // re-initialize aggr state for new window
+                    // reset transactional state for outgoing events
+
+                    if let Some(next) = &mut w.next {
+                        can_remove = next.on_event(
+                            &mut ctx,
+                            run,
+                            &data,
+                            &mut res.events,
+                            Some((w.holds_data, &w.aggrs)),
+                            can_remove,
+                        )?;
+                    }
+                    w.rotate_panes(pane_id);
+                    w.reset();
+                    // we can not remove the group while retained panes still hold data
+                    can_remove = can_remove && !w.panes_hold_data();
+                    emitted.extend(
+                        emitted_by
+                            .into_iter()
+                            .map(|window| (window, group_str.clone())),
+                    );
+                }
+                if can_remove {
+                    to_remove.push(group_str.clone());
+                }
+            }
+        }
+        for g in to_remove {
+            groups.remove(&g);
+        }
+        Ok((res, emitted))
+    }
 }
 
 /// execute the select clause of the statement and filter results by having clause, if provided
@@ -181,6 +420,7 @@ impl Operator for Select {
         _state: &mut Value<'static>,
         mut event: Event,
     ) -> Result<EventAndInsights> {
+        let ordered = self.is_ordered();
         let Self {
             select,
             windows,
@@ -189,8 +429,10 @@ impl Operator for Select {
             recursion_limit,
             dflt_group,
             max_groups,
-            ..
+            pending,
         } = self;
+        // the window and group of every emitted event, if they are ordered
+        let mut emitted = Vec::new();
         let Event {
             ingest_ns,
             ref mut data,
@@ -261,6 +503,8 @@ impl Operator for Select {
 
                 ctx.cardinality = groups.len();
 
+                let group = ordered.then(|| group_str.clone());
+                let mut emitted_by = Vec::new();
                 let sel_ctx = SelectCtx {
                     select,
                     local_stack: &locals,
@@ -273,6 +517,7 @@ impl Operator for Select {
                     origin_uri,
                     transactional,
                     recursion_limit: *recursion_limit,
+                    emitted_by: ordered.then_some(&mut emitted_by),
                 };
 
                 // see if we know the group already, we use the `entry` here so we don't
@@ -309,11 +554,19 @@ impl Operator for Select {
                         }
                     }
                 }
+                if let Some(group) = group {
+                    emitted.extend(emitted_by.into_iter().map(|window| (window, group.clone())));
+                }
             }
             Ok(Res::Data(events.into()))
         })?;
 
-        Ok(res.into_insights(event))
+        let mut res = res.into_insights(event);
+        if ordered {
+            // window events are emitted ordered on the next tick
+            hold(pending, emitted, mem::take(&mut res.events));
+        }
+        Ok(res)
     }
 
     fn on_signal(
@@ -322,114 +575,76 @@ impl Operator for Select {
         _state: &mut Value<'static>,
         signal: &mut Event,
     ) -> Result<EventAndInsights> {
-        // we only react on ticks and when we have windows
-        let Self {
-            select,
-            windows,
-            event_id_gen,
-            groups,
-            recursion_limit,
-            ..
-        } = self;
-        let recursion_limit = *recursion_limit;
-
-        // if it isn't a tick or we do not have any windows, or have no
-        // recorded groups, we can just return
-        if signal.kind != Some(SignalKind::Tick) || windows.is_empty() || groups.is_empty() {
-            return Ok(EventAndInsights::default());
+        let (mut res, emitted) = self.on_tick(signal)?;
+        if signal.kind == Some(SignalKind::Tick) && self.is_ordered() {
+            hold(&mut self.pending, emitted, mem::take(&mut res.events));
+            res.events = self.order()?;
         }
+        Ok(res)
+    }
 
-        let ingest_ns = signal.ingest_ns;
-
-        let opts = Self::opts();
-        let SelectStmt {
-            stmt: select,
-            consts,
-            locals,
-            ..
-        } = select;
-        let mut res = EventAndInsights::default();
-
-        let data: ValueAndMeta = (Value::const_null(), Value::object()).into();
-        let op_meta = OpMeta::default();
-        let local_stack = tremor_script::interpreter::LocalStack::with_size(*locals);
-
-        consts.window = Value::const_null();
-        consts.group = Value::const_null();
-        consts.args = Value::const_null();
-
-        let mut ctx = EventContext::new(ingest_ns, None);
-        ctx.cardinality = groups.len();
-
-        let mut to_remove = vec![];
-        for (group_str, g) in groups.iter_mut() {
-            if let Some(w) = &mut g.windows {
-                let mut outgoing_event_id = event_id_gen.next_id();
-                mem::swap(&mut w.id, &mut outgoing_event_id);
-
-                let mut run = consts.run();
-                run.group = &g.value;
-                run.window = &w.name;
-                let window_event = w.window.on_tick(ingest_ns);
-                let mut can_remove = window_event.emit;
-
-                if window_event.emit {
-                    // push
-                    let mut env = env(&ctx, run, recursion_limit);
-                    env.aggrs = &w.aggrs;
-
-                    let mut outgoing_event_id = event_id_gen.next_id();
-
-                    mem::swap(&mut outgoing_event_id, &mut w.id);
-
-                    let mut ctx = SelectCtx {
-                        select,
-                        local_stack: &local_stack,
-                        opts,
-                        ctx: &ctx,
-                        event_id: outgoing_event_id,
-                        event_id_gen,
-                        ingest_ns,
-                        op_meta: &op_meta,
-                        origin_uri: &None,
-                        transactional: w.transactional,
-                        recursion_limit,
-                    };
-                    if w.holds_data {
-                        if let Some(port_and_event) =
-                            super::select::execute_select_and_having(&ctx, &env, &data)?
-                        {
-                            res.events.push(port_and_event);
-                        };
-                    }
-                    // re-initialize aggr state for new window
-                    // reset transactional state for outgoing events
+    fn handles_signal(&self) -> bool {
+        true
+    }
 
-                    if let Some(next) = &mut w.next {
-                        can_remove = next.on_event(
-                            &mut ctx,
-                            run,
-                            &data,
-                            &mut res.events,
-                            Some((w.holds_data, &w.aggrs)),
-                            can_remove,
-                        )?;
-                    }
-                    w.reset();
-                }
-                if can_remove {
-                    to_remove.push(group_str.clone());
-                }
-            }
+    fn snapshot(&self) -> Result<Option<Value<'static>>> {
+        if self.windows.is_empty() {
+            return Ok(None);
         }
-        for g in to_remove {
-            groups.remove(&g);
+        let mut groups = Value::object_with_capacity(self.groups.len());
+        for (group_str, group) in &self.groups {
+            groups.try_insert(group_str.clone(), group.snapshot()?);
         }
-        Ok(res)
+        let pending: Vec<_> = self
+            .pending
+            .iter()
+            .map(|(port, event)| {
+                let (value, meta) = event.data.parts();
+                literal!({
+                    "port": port.to_string(),
+                    "ingest_ns": event.ingest_ns,
+                    "transactional": event.transactional,
+                    "value": value.clone_static(),
+                    "meta": meta.clone_static(),
+                })
+            })
+            .collect();
+        Ok(Some(literal!({
+            "groups": groups,
+            "pending": pending,
+        })))
     }
 
-    fn handles_signal(&self) -> bool {
-        true
+    fn restore(&mut self, snapshot: &Value) -> Result<()> {
+        let invalid = || Error::from("Invalid select snapshot");
+        let mut groups = HashMap::new();
+        for (group_str, group_snapshot) in snapshot.get_object("groups").ok_or_else(invalid)? {
+            let mut group = self.dflt_group.clone();
+            group.reset();
+            group.restore(group_snapshot)?;
+            groups.insert(group_str.to_string(), group);
+        }
+        let mut pending = Vec::new();
+        for event in snapshot.get_array("pending").ok_or_else(invalid)? {
+            let port = event.get_str("port").ok_or_else(invalid)?.to_string();
+            let data = (
+                event.get("value").ok_or_else(invalid)?.clone_static(),
+                event.get("meta").ok_or_else(invalid)?.clone_static(),
+            );
+            pending.push((
+                port.into(),
+                Event {
+                    id: self.event_id_gen.next_id(),
+                    ingest_ns: event.get_u64("ingest_ns").ok_or_else(invalid)?,
+                    transactional: event.get_bool("transactional").ok_or_else(invalid)?,
+                    data: data.into(),
+                    ..Event::default()
+                },
+            ));
+        }
+        self.groups = groups;
+        self.pending = pending;
+        Ok(())
     }
 }
 
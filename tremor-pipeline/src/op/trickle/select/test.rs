@@ -68,6 +68,8 @@ fn test_stmt(target: ast::ImutExpr) -> ast::Select {
         windows: vec![],
         maybe_group_by: None,
         maybe_having: None,
+        order_by: vec![],
+        maybe_limit: None,
     }
 }
 
@@ -352,6 +354,64 @@ fn select_single_win_on_signal() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn select_sliding_win_on_signal() -> Result<()> {
+    let mut select = select_stmt_from_query(
+        r#"
+        define window window1 from sliding
+        with
+            interval = 4,
+            slide = 2
+        end;
+        select aggr::win::collect_flattened(event) from in[window1] group by event.g into out;
+        "#,
+    )?;
+    let uid = OperatorId::new(42);
+    let mut state = Value::null();
+
+    let event = Event {
+        id: (1, 1, 300).into(),
+        ingest_ns: 2,
+        data: literal!({
+           "g": "group"
+        })
+        .into(),
+        ..Event::default()
+    };
+    let mut eis = select.on_event(uid, "IN", &mut state, event)?;
+    assert_eq!(0, eis.events.len());
+
+    // no emit yet
+    let mut tick1 = test_tick(3);
+    eis = select.on_signal(uid, &mut state, &mut tick1)?;
+    assert_eq!(0, eis.events.len());
+
+    // the first slide is over, emit
+    let mut tick2 = test_tick(4);
+    eis = select.on_signal(uid, &mut state, &mut tick2)?;
+    assert_eq!(1, eis.events.len());
+    assert_eq!(
+        r#"[{"g":"group"}]"#,
+        sorted_serialize(eis.events[0].1.data.parts().0)?
+    );
+
+    // the second slide is over, the event is still in the window range
+    let mut tick3 = test_tick(6);
+    eis = select.on_signal(uid, &mut state, &mut tick3)?;
+    assert_eq!(1, eis.events.len());
+    assert_eq!(
+        r#"[{"g":"group"}]"#,
+        sorted_serialize(eis.events[0].1.data.parts().0)?
+    );
+
+    // the event slid out of the window and the group got removed
+    let mut tick4 = test_tick(8);
+    eis = select.on_signal(uid, &mut state, &mut tick4)?;
+    assert_eq!(0, eis.events.len());
+    assert!(select.groups.is_empty());
+    Ok(())
+}
+
 #[test]
 fn select_multiple_wins_on_signal() -> Result<()> {
     let mut select = select_stmt_from_query(
@@ -968,3 +1028,234 @@ fn tumbling_window_on_number_emit() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn sliding_window_on_number_emit() -> Result<()> {
+    let mut window =
+        window::SlidingOnNumber::from_stmt(4, 2, window::Impl::DEFAULT_MAX_GROUPS, false, None);
+    let vm = literal!({
+       "h2g2" : 42,
+    })
+    .into();
+
+    // panes are delimited by the slide
+    assert_eq!(
+        Actions::all_false(),
+        window.on_event(&vm, ingest_ns(0), &None)?
+    );
+    assert_eq!(
+        Actions::all_true(),
+        window.on_event(&vm, ingest_ns(1), &None)?
+    );
+    assert_eq!(
+        Actions::all_false(),
+        window.on_event(&vm, ingest_ns(2), &None)?
+    );
+    assert_eq!(
+        Actions::all_true(),
+        window.on_event(&vm, ingest_ns(3), &None)?
+    );
+    // and the window spans two of them
+    assert_eq!(2, window::Impl::from(window).panes());
+    Ok(())
+}
+
+#[test]
+fn select_order_by_limit_on_signal() -> Result<()> {
+    let mut select = select_stmt_from_query(
+        r#"
+        define window window1 from tumbling
+        with
+            interval = 2
+        end;
+        select {"g": group[0], "count": aggr::stats::count()} from in[window1] group by event.g into out
+        order by event.count desc, event.g limit 2;
+        "#,
+    )?;
+    let uid = OperatorId::new(42);
+    let mut state = Value::null();
+
+    for (i, g) in ["a", "b", "c", "c", "a", "a"].iter().enumerate() {
+        let event = Event {
+            id: (1, 1, i as u64).into(),
+            ingest_ns: 2,
+            data: literal!({ "g": *g }).into(),
+            ..Event::default()
+        };
+        let eis = select.on_event(uid, "in", &mut state, event)?;
+        assert!(eis.events.is_empty());
+    }
+
+    let mut tick = test_tick(4);
+    let eis = select.on_signal(uid, &mut state, &mut tick)?;
+    let values = eis
+        .events
+        .iter()
+        .map(|(_, event)| sorted_serialize(event.data.parts().0))
+        .collect::<tremor_script::Result<Vec<_>>>()?;
+    assert_eq!(
+        vec![
+            r#"{"count":3,"g":"a"}"#.to_string(),
+            r#"{"count":2,"g":"c"}"#.to_string()
+        ],
+        values
+    );
+    Ok(())
+}
+
+#[test]
+fn select_order_by_on_event() -> Result<()> {
+    let mut select = select_stmt_from_query(
+        r#"
+        define window window1 from tumbling
+        with
+            size = 2
+        end;
+        select {"g": group[0], "last": aggr::win::last(event.v)} from in[window1] group by event.g into out
+        order by event.last desc;
+        "#,
+    )?;
+    let uid = OperatorId::new(42);
+    let mut state = Value::null();
+
+    for (i, (g, v)) in [("a", 5), ("a", 1), ("b", 1), ("b", 2)].iter().enumerate() {
+        let event = Event {
+            id: (1, 1, i as u64).into(),
+            ingest_ns: i as u64,
+            data: literal!({ "g": *g, "v": *v }).into(),
+            ..Event::default()
+        };
+        // events of closed windows are held back until the next tick
+        let eis = select.on_event(uid, "in", &mut state, event)?;
+        assert!(eis.events.is_empty());
+    }
+
+    let mut tick = test_tick(10);
+    let eis = select.on_signal(uid, &mut state, &mut tick)?;
+    let values = eis
+        .events
+        .iter()
+        .map(|(_, event)| sorted_serialize(event.data.parts().0))
+        .collect::<tremor_script::Result<Vec<_>>>()?;
+    assert_eq!(
+        vec![
+            r#"{"g":"b","last":2}"#.to_string(),
+            r#"{"g":"a","last":1}"#.to_string()
+        ],
+        values
+    );
+    // nothing left to emit
+    let mut tick = test_tick(11);
+    assert!(select
+        .on_signal(uid, &mut state, &mut tick)?
+        .events
+        .is_empty());
+    Ok(())
+}
+
+#[test]
+fn select_order_by_limit_per_window() -> Result<()> {
+    let mut select = select_stmt_from_query(
+        r#"
+        define window window1 from tumbling
+        with
+            interval = 2
+        end;
+        define window window2 from tumbling
+        with
+            size = 1
+        end;
+        select {"g": group[0], "count": aggr::stats::count()} from in[window1, window2] group by event.g into out
+        order by event.count desc, event.g limit 2;
+        "#,
+    )?;
+    let uid = OperatorId::new(42);
+    let mut state = Value::null();
+
+    for (i, g) in ["a", "b", "c", "c", "a", "a"].iter().enumerate() {
+        let event = Event {
+            id: (1, 1, i as u64).into(),
+            ingest_ns: 2,
+            data: literal!({ "g": *g }).into(),
+            ..Event::default()
+        };
+        let eis = select.on_event(uid, "in", &mut state, event)?;
+        assert!(eis.events.is_empty());
+    }
+
+    // both windows close on the same tick, each keeps its own top 2 groups
+    let mut tick = test_tick(4);
+    let eis = select.on_signal(uid, &mut state, &mut tick)?;
+    let values = eis
+        .events
+        .iter()
+        .map(|(_, event)| sorted_serialize(event.data.parts().0))
+        .collect::<tremor_script::Result<Vec<_>>>()?;
+    assert_eq!(
+        vec![
+            r#"{"count":3,"g":"a"}"#.to_string(),
+            r#"{"count":2,"g":"c"}"#.to_string(),
+            r#"{"count":3,"g":"a"}"#.to_string(),
+            r#"{"count":2,"g":"c"}"#.to_string()
+        ],
+        values
+    );
+    Ok(())
+}
+
+#[test]
+fn select_order_by_escaped_keyword() -> Result<()> {
+    // `order`, `limit`, `asc` and `desc` are keywords, fields named like them are escaped
+    let mut select = select_stmt_from_query(
+        r#"
+        define window window1 from tumbling
+        with
+            interval = 2
+        end;
+        select {"order": group[0], "limit": aggr::stats::count()} from in[window1] group by event.`desc` into out
+        order by event.`limit` desc limit 1;
+        "#,
+    )?;
+    let uid = OperatorId::new(42);
+    let mut state = Value::null();
+
+    for (i, g) in ["a", "b", "b"].iter().enumerate() {
+        let event = Event {
+            id: (1, 1, i as u64).into(),
+            ingest_ns: 2,
+            data: literal!({ "desc": *g }).into(),
+            ..Event::default()
+        };
+        select.on_event(uid, "in", &mut state, event)?;
+    }
+
+    let mut tick = test_tick(4);
+    let eis = select.on_signal(uid, &mut state, &mut tick)?;
+    let values = eis
+        .events
+        .iter()
+        .map(|(_, event)| sorted_serialize(event.data.parts().0))
+        .collect::<tremor_script::Result<Vec<_>>>()?;
+    assert_eq!(vec![r#"{"limit":2,"order":"b"}"#.to_string()], values);
+    Ok(())
+}
+
+#[test]
+fn select_order_by_invalid() {
+    // ordering requires a window
+    assert!(select_stmt_from_query(
+        r#"select event from in into out order by event.count limit 10;"#
+    )
+    .is_err());
+    // aggregates have to be selected to order by them
+    assert!(select_stmt_from_query(
+        r#"
+        define window window1 from tumbling
+        with
+            size = 2
+        end;
+        select aggr::stats::count() from in[window1] into out order by aggr::stats::max(event.v);
+        "#
+    )
+    .is_err());
+}
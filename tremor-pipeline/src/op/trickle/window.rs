@@ -15,11 +15,12 @@
 use crate::{Event, EventId, EventIdGenerator, OpMeta};
 use beef::Cow;
 use std::borrow::Cow as SCow;
+use std::collections::VecDeque;
 use tremor_common::stry;
 use tremor_script::{
     self,
     ast::{AggrSlice, Aggregates, Consts, RunConsts, Select, WindowDefinition},
-    errors::Result,
+    errors::{Error, Result},
     interpreter::{Env, LocalStack},
     prelude::*,
     Value, NO_AGGRS,
@@ -27,6 +28,12 @@ use tremor_script::{
 
 use super::select::execute_select_and_having;
 
+const INVALID_SNAPSHOT: &str = "Invalid select snapshot";
+
+fn invalid_snapshot() -> Error {
+    Error::from(INVALID_SNAPSHOT)
+}
+
 pub(crate) struct SelectCtx<'run, 'script, 'local> {
     pub(crate) select: &'run Select<'script>,
     pub(crate) local_stack: &'run LocalStack<'local>,
@@ -39,6 +46,8 @@ pub(crate) struct SelectCtx<'run, 'script, 'local> {
     pub(crate) origin_uri: &'run Option<EventOriginUri>,
     pub(crate) transactional: bool,
     pub(crate) recursion_limit: u32,
+    /// the names of the windows emitting events, in the order of the events, if tracked
+    pub(crate) emitted_by: Option<&'run mut Vec<Value<'static>>>,
 }
 
 /// A singular tilt frame (window) inside a group
@@ -63,6 +72,25 @@ pub struct GroupWindow {
     pub(crate) next: Option<Box<GroupWindow>>,
     /// If the window holds any data
     pub(crate) holds_data: bool,
+    /// The closed panes of a sliding window that are still
+    /// within its range, oldest first (always empty for
+    /// tumbling windows)
+    pub(crate) panes: VecDeque<Pane>,
+}
+
+/// A pane of a sliding window, holding the aggregated data
+/// of one `slide` so it can be re-combined with the panes
+/// following it until it moves out of the windows range.
+#[derive(Clone, Debug)]
+pub struct Pane {
+    /// The aggregates of this pane
+    aggrs: Aggregates<'static>,
+    /// The event id(s) of all events tracked in this pane
+    id: EventId,
+    /// If the data of this pane is considered transactional
+    transactional: bool,
+    /// If the pane holds any data
+    holds_data: bool,
 }
 
 impl GroupWindow {
@@ -84,6 +112,7 @@ impl GroupWindow {
                 transactional: false,
                 next: GroupWindow::from_windows(aggrs, id, iter),
                 holds_data: false,
+                panes: VecDeque::new(),
             })
         })
     }
@@ -96,6 +125,116 @@ impl GroupWindow {
         self.holds_data = false;
     }
 
+    /// If this window has anything to emit when it is closed, either
+    /// data in the current window or in any retained pane, or if
+    /// it is configured to emit empty windows.
+    pub(crate) fn should_emit(&self) -> bool {
+        self.holds_data || self.panes_hold_data() || self.window.emit_empty_windows()
+    }
+
+    /// If any of the retained panes of a sliding window holds data
+    pub(crate) fn panes_hold_data(&self) -> bool {
+        self.panes.iter().any(|p| p.holds_data)
+    }
+
+    /// Tracks the event ids and the transactionality of all retained
+    /// panes in the context, so the emitted event covers all events
+    /// within the range of a sliding window.
+    pub(crate) fn track_panes(&self, ctx: &mut SelectCtx) {
+        for pane in &self.panes {
+            ctx.event_id.track(&pane.id);
+            ctx.transactional |= pane.transactional;
+        }
+    }
+
+    /// The aggregates spanning the entire range of a sliding window,
+    /// this is the retained panes merged with the current one.
+    ///
+    /// Returns `None` if there are no retained panes, in that case
+    /// the current aggregates are all there is to emit.
+    pub(crate) fn sliding_aggrs(&self) -> Result<Option<Aggregates<'static>>> {
+        let mut panes = self.panes.iter();
+        if let Some(first) = panes.next() {
+            let mut aggrs = first.aggrs.clone();
+            for pane in panes {
+                stry!(merge_aggrs(&mut aggrs, &pane.aggrs));
+            }
+            stry!(merge_aggrs(&mut aggrs, &self.aggrs));
+            Ok(Some(aggrs))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Closes the current pane of a sliding window by moving its data,
+    /// tracked as `id`, into the retained panes and dropping
+    /// panes that moved out of the windows range.
+    ///
+    /// This needs to happen before the window is reset, for tumbling
+    /// windows `id` is `None` and this is a no-op.
+    pub(crate) fn rotate_panes(&mut self, id: Option<EventId>) {
+        let retained = self.window.panes().saturating_sub(1);
+        if let (Some(id), true) = (id, retained > 0) {
+            self.panes.push_back(Pane {
+                aggrs: self.aggrs.clone(),
+                id,
+                transactional: self.transactional,
+                holds_data: self.holds_data,
+            });
+            while self.panes.len() > retained {
+                self.panes.pop_front();
+            }
+        }
+    }
+
+    /// A snapshot of the data in this window and its retained panes
+    pub(crate) fn snapshot(&self) -> Result<Value<'static>> {
+        let mut panes = Vec::with_capacity(self.panes.len());
+        for pane in &self.panes {
+            panes.push(literal!({
+                "aggrs": stry!(snapshot_aggrs(&pane.aggrs)),
+                "transactional": pane.transactional,
+                "holds_data": pane.holds_data,
+            }));
+        }
+        Ok(literal!({
+            "window": self.window.snapshot(),
+            "aggrs": stry!(snapshot_aggrs(&self.aggrs)),
+            "transactional": self.transactional,
+            "holds_data": self.holds_data,
+            "panes": panes,
+        }))
+    }
+
+    /// Restores the data in this window and its retained panes from a `snapshot`,
+    /// the restored panes are tracked with the id of this window.
+    pub(crate) fn restore(&mut self, snapshot: &Value) -> Result<()> {
+        stry!(self
+            .window
+            .restore(snapshot.get("window").ok_or_else(invalid_snapshot)?));
+        stry!(restore_aggrs(&mut self.aggrs, snapshot.get("aggrs")));
+        self.transactional = snapshot
+            .get_bool("transactional")
+            .ok_or_else(invalid_snapshot)?;
+        self.holds_data = snapshot
+            .get_bool("holds_data")
+            .ok_or_else(invalid_snapshot)?;
+        self.panes.clear();
+        for pane in snapshot.get_array("panes").ok_or_else(invalid_snapshot)? {
+            let mut aggrs = self.aggrs.clone();
+            stry!(restore_aggrs(&mut aggrs, pane.get("aggrs")));
+            self.panes.push_back(Pane {
+                aggrs,
+                id: self.id.clone(),
+                transactional: pane
+                    .get_bool("transactional")
+                    .ok_or_else(invalid_snapshot)?,
+                holds_data: pane.get_bool("holds_data").ok_or_else(invalid_snapshot)?,
+            });
+        }
+        Ok(())
+    }
+
     /// Accumultes data into the window
     pub(crate) fn accumulate(
         &mut self,
@@ -169,13 +308,7 @@ impl GroupWindow {
         self.transactional |= ctx.transactional;
         self.holds_data = true;
         // Ingest the data
-        for (this, prev) in self.aggrs.iter_mut().zip(prev.iter()) {
-            stry!(this.invocable.merge(&prev.invocable).map_err(|e| {
-                let r: Option<&Registry> = None;
-                e.into_err(prev, prev, r)
-            }));
-        }
-        Ok(())
+        merge_aggrs(&mut self.aggrs, prev)
     }
 
     /// This window receives an event either as a root window
@@ -225,32 +358,43 @@ impl GroupWindow {
             std::mem::swap(&mut ctx.event_id, &mut self.id);
             // then create a new event ID for the next window
             self.id = ctx.event_id_gen.next_id();
+            // remember the ID of the pane we are closing in case this is
+            // a sliding window
+            let pane_id = (self.window.panes() > 1).then(|| ctx.event_id.clone());
 
             // for the context the transactionality of any following window
             // is the transactionality of this window (since we propagate
             // the current data along the tilt frames)
             ctx.transactional = self.transactional;
+            // a sliding window emits the data of all panes in its range
+            self.track_panes(ctx);
 
             // Set the window name for emission
 
-            if self.holds_data {
+            if self.should_emit() {
                 let mut consts = consts;
                 consts.window = &self.name;
+                let sliding_aggrs = stry!(self.sliding_aggrs());
                 let env = Env {
                     context: ctx.ctx,
                     consts,
-                    aggrs: &self.aggrs,
+                    aggrs: sliding_aggrs.as_ref().unwrap_or(&self.aggrs),
                     recursion_limit: ctx.recursion_limit,
                 };
 
                 // execute thw select body and apply the `having` to see if we publish an event
                 if let Some(port_and_event) = stry!(execute_select_and_having(ctx, &env, data)) {
+                    if let Some(emitted_by) = ctx.emitted_by.as_deref_mut() {
+                        emitted_by.push(self.name.clone());
+                    }
                     events.push(port_and_event);
                 };
             }
             // if we have another tilt frame after that emit our aggregated data to it
             // this happens after emitting so we keep order of the events from the
-            // smallest to the largest window
+            // smallest to the largest window.
+            // For sliding windows we only hand over the data of the closed pane, as
+            // the retained panes were already handed over when they were closed.
             if let Some(next) = &mut self.next {
                 can_remove = can_remove
                     && stry!(next.on_event(
@@ -262,8 +406,12 @@ impl GroupWindow {
                         can_remove
                     ));
             }
-            // since we emitted we now can reset this window
+            // since we emitted we now can move the data into the panes (for sliding windows)
+            // and reset this window
+            self.rotate_panes(pane_id);
             self.reset();
+            // we can not remove the group while retained panes still hold data
+            can_remove = can_remove && !self.panes_hold_data();
         }
         if window_event.include {
             // if include is set we recorded the event earlier, meaning that
@@ -305,10 +453,46 @@ impl Group {
         while let Some(g) = w {
             g.reset();
             g.window.reset();
+            g.panes.clear();
             w = &mut g.next;
         }
     }
 
+    /// A snapshot of the group value and the data in all its windows
+    pub(crate) fn snapshot(&self) -> Result<Value<'static>> {
+        let mut windows = Vec::new();
+        let mut w = &self.windows;
+        while let Some(g) = w {
+            windows.push(stry!(g.snapshot()));
+            w = &g.next;
+        }
+        Ok(literal!({
+            "value": self.value.clone(),
+            "windows": windows,
+        }))
+    }
+
+    /// Restores the group value and the data in all its windows from a `snapshot`
+    pub(crate) fn restore(&mut self, snapshot: &Value) -> Result<()> {
+        let mut windows = snapshot
+            .get_array("windows")
+            .ok_or_else(invalid_snapshot)?
+            .iter();
+        let mut w = &mut self.windows;
+        while let Some(g) = w {
+            stry!(g.restore(windows.next().ok_or_else(invalid_snapshot)?));
+            w = &mut g.next;
+        }
+        if windows.next().is_some() {
+            return Err(invalid_snapshot());
+        }
+        self.value = snapshot
+            .get("value")
+            .ok_or_else(invalid_snapshot)?
+            .clone_static();
+        Ok(())
+    }
+
     /// The group receives an event we propagate it through
     /// the different windows.
     /// # Returns
@@ -348,6 +532,44 @@ impl Group {
     }
 }
 
+/// Merges the aggregates `src` into `dst`, both need to stem from the same select
+fn merge_aggrs(dst: &mut AggrSlice<'static>, src: &AggrSlice<'static>) -> Result<()> {
+    for (this, src) in dst.iter_mut().zip(src.iter()) {
+        stry!(this.invocable.merge(&src.invocable).map_err(|e| {
+            let r: Option<&Registry> = None;
+            e.into_err(src, src, r)
+        }));
+    }
+    Ok(())
+}
+
+/// A snapshot of the accumulated state of the aggregates `aggrs`
+fn snapshot_aggrs(aggrs: &AggrSlice<'static>) -> Result<Value<'static>> {
+    let mut snapshot = Vec::with_capacity(aggrs.len());
+    for aggr in aggrs {
+        snapshot.push(stry!(aggr.invocable.snapshot().map_err(|e| {
+            let r: Option<&Registry> = None;
+            e.into_err(aggr, aggr, r)
+        })));
+    }
+    Ok(Value::from(snapshot))
+}
+
+/// Restores the aggregates `aggrs` from a `snapshot` taken by `snapshot_aggrs`
+fn restore_aggrs(aggrs: &mut AggrSlice<'static>, snapshot: Option<&Value>) -> Result<()> {
+    let snapshot = snapshot
+        .as_array()
+        .filter(|snapshot| snapshot.len() == aggrs.len())
+        .ok_or_else(invalid_snapshot)?;
+    for (aggr, snapshot) in aggrs.iter_mut().zip(snapshot) {
+        stry!(aggr.invocable.restore(snapshot).map_err(|e| {
+            let r: Option<&Registry> = None;
+            e.into_err(aggr, aggr, r)
+        }));
+    }
+    Ok(())
+}
+
 // Windowing implementaitons and traits
 
 pub trait Trait: std::fmt::Debug {
@@ -384,6 +606,8 @@ impl Window {
 pub enum Impl {
     TumblingCountBased(TumblingOnNumber),
     TumblingTimeBased(TumblingOnTime),
+    SlidingCountBased(SlidingOnNumber),
+    SlidingTimeBased(SlidingOnTime),
 }
 
 impl Impl {
@@ -395,6 +619,47 @@ impl Impl {
         match self {
             Self::TumblingTimeBased(w) => w.reset(),
             Self::TumblingCountBased(w) => w.reset(),
+            Self::SlidingTimeBased(w) => w.pane.reset(),
+            Self::SlidingCountBased(w) => w.pane.reset(),
+        }
+    }
+
+    /// The number of panes that make up the range of this window,
+    /// this is always `1` for tumbling windows
+    pub(crate) fn panes(&self) -> usize {
+        match self {
+            Self::TumblingTimeBased(_) | Self::TumblingCountBased(_) => 1,
+            Self::SlidingTimeBased(w) => w.panes,
+            Self::SlidingCountBased(w) => w.panes,
+        }
+    }
+
+    /// A snapshot of the position of the window
+    pub(crate) fn snapshot(&self) -> Value<'static> {
+        match self {
+            Self::TumblingTimeBased(w) => w.snapshot(),
+            Self::TumblingCountBased(w) => w.snapshot(),
+            Self::SlidingTimeBased(w) => w.pane.snapshot(),
+            Self::SlidingCountBased(w) => w.pane.snapshot(),
+        }
+    }
+
+    /// Restores the position of the window from a `snapshot`
+    pub(crate) fn restore(&mut self, snapshot: &Value) -> Result<()> {
+        match self {
+            Self::TumblingTimeBased(w) => w.restore(snapshot),
+            Self::TumblingCountBased(w) => w.restore(snapshot),
+            Self::SlidingTimeBased(w) => w.pane.restore(snapshot),
+            Self::SlidingCountBased(w) => w.pane.restore(snapshot),
+        }
+    }
+
+    /// If this window emits even if there is no data in its range
+    pub(crate) fn emit_empty_windows(&self) -> bool {
+        match self {
+            Self::TumblingTimeBased(_) | Self::TumblingCountBased(_) => false,
+            Self::SlidingTimeBased(w) => w.emit_empty_windows,
+            Self::SlidingCountBased(w) => w.emit_empty_windows,
         }
     }
 }
@@ -409,6 +674,8 @@ impl Trait for Impl {
         match self {
             Self::TumblingTimeBased(w) => w.on_event(data, ingest_ns, origin_uri),
             Self::TumblingCountBased(w) => w.on_event(data, ingest_ns, origin_uri),
+            Self::SlidingTimeBased(w) => w.on_event(data, ingest_ns, origin_uri),
+            Self::SlidingCountBased(w) => w.on_event(data, ingest_ns, origin_uri),
         }
     }
 
@@ -416,6 +683,8 @@ impl Trait for Impl {
         match self {
             Self::TumblingTimeBased(w) => w.on_tick(ns),
             Self::TumblingCountBased(w) => w.on_tick(ns),
+            Self::SlidingTimeBased(w) => w.on_tick(ns),
+            Self::SlidingCountBased(w) => w.on_tick(ns),
         }
     }
 
@@ -423,6 +692,8 @@ impl Trait for Impl {
         match self {
             Self::TumblingTimeBased(w) => w.max_groups(),
             Self::TumblingCountBased(w) => w.max_groups(),
+            Self::SlidingTimeBased(w) => w.max_groups(),
+            Self::SlidingCountBased(w) => w.max_groups(),
         }
     }
 }
@@ -437,6 +708,16 @@ impl From<TumblingOnTime> for Impl {
         Self::TumblingTimeBased(w)
     }
 }
+impl From<SlidingOnNumber> for Impl {
+    fn from(w: SlidingOnNumber) -> Self {
+        Self::SlidingCountBased(w)
+    }
+}
+impl From<SlidingOnTime> for Impl {
+    fn from(w: SlidingOnTime) -> Self {
+        Self::SlidingTimeBased(w)
+    }
+}
 
 #[derive(Debug, PartialEq, Default, Eq)]
 pub struct Actions {
@@ -502,6 +783,19 @@ impl TumblingOnTime {
         }
     }
 
+    pub(crate) fn snapshot(&self) -> Value<'static> {
+        self.next_window.map_or_else(Value::null, Value::from)
+    }
+
+    pub(crate) fn restore(&mut self, snapshot: &Value) -> Result<()> {
+        self.next_window = if snapshot.is_null() {
+            None
+        } else {
+            Some(snapshot.as_u64().ok_or_else(invalid_snapshot)?)
+        };
+        Ok(())
+    }
+
     fn get_window_event(&mut self, time: u64) -> Actions {
         match self.next_window {
             None => {
@@ -579,6 +873,15 @@ impl TumblingOnNumber {
         self.next_eviction = 0;
         self.count = 0;
     }
+
+    pub(crate) fn snapshot(&self) -> Value<'static> {
+        Value::from(self.count)
+    }
+
+    pub(crate) fn restore(&mut self, snapshot: &Value) -> Result<()> {
+        self.count = snapshot.as_u64().ok_or_else(invalid_snapshot)?;
+        Ok(())
+    }
     pub fn from_stmt(
         size: u64,
         max_groups: usize,
@@ -639,3 +942,99 @@ impl Trait for TumblingOnNumber {
         }
     }
 }
+
+/// A sliding window over time, it spans `interval` ns and
+/// emits every `slide` ns.
+///
+/// Internally the window is split into panes of `slide` ns each, which
+/// are tracked by a tumbling window and combined on emit.
+#[derive(Default, Debug, Clone)]
+pub struct SlidingOnTime {
+    /// The tumbling window delimiting the panes
+    pub(crate) pane: TumblingOnTime,
+    /// The number of panes spanning the window (`interval / slide`)
+    pub(crate) panes: usize,
+    /// Emit a window even if no data is in its range
+    pub(crate) emit_empty_windows: bool,
+}
+
+impl SlidingOnTime {
+    pub fn from_stmt(
+        interval: u64,
+        slide: u64,
+        max_groups: usize,
+        emit_empty_windows: bool,
+        script: Option<&WindowDefinition<'static>>,
+    ) -> Self {
+        Self {
+            pane: TumblingOnTime::from_stmt(slide, max_groups, script),
+            panes: usize::try_from(interval / slide).unwrap_or(usize::MAX),
+            emit_empty_windows,
+        }
+    }
+}
+
+impl Trait for SlidingOnTime {
+    fn max_groups(&self) -> usize {
+        self.pane.max_groups()
+    }
+    fn on_event(
+        &mut self,
+        data: &ValueAndMeta,
+        ingest_ns: u64,
+        origin_uri: &Option<EventOriginUri>,
+    ) -> Result<Actions> {
+        self.pane.on_event(data, ingest_ns, origin_uri)
+    }
+    fn on_tick(&mut self, ns: u64) -> Actions {
+        self.pane.on_tick(ns)
+    }
+}
+
+/// A sliding window over a number of events, it spans `size` events and
+/// emits every `slide` events.
+///
+/// Internally the window is split into panes of `slide` events each, which
+/// are tracked by a tumbling window and combined on emit.
+#[derive(Default, Debug, Clone)]
+pub struct SlidingOnNumber {
+    /// The tumbling window delimiting the panes
+    pub(crate) pane: TumblingOnNumber,
+    /// The number of panes spanning the window (`size / slide`)
+    pub(crate) panes: usize,
+    /// Emit a window even if no data is in its range
+    pub(crate) emit_empty_windows: bool,
+}
+
+impl SlidingOnNumber {
+    pub fn from_stmt(
+        size: u64,
+        slide: u64,
+        max_groups: usize,
+        emit_empty_windows: bool,
+        script: Option<&WindowDefinition<'static>>,
+    ) -> Self {
+        Self {
+            pane: TumblingOnNumber::from_stmt(slide, max_groups, script),
+            panes: usize::try_from(size / slide).unwrap_or(usize::MAX),
+            emit_empty_windows,
+        }
+    }
+}
+
+impl Trait for SlidingOnNumber {
+    fn max_groups(&self) -> usize {
+        self.pane.max_groups()
+    }
+    fn on_event(
+        &mut self,
+        data: &ValueAndMeta,
+        ingest_ns: u64,
+        origin_uri: &Option<EventOriginUri>,
+    ) -> Result<Actions> {
+        self.pane.on_event(data, ingest_ns, origin_uri)
+    }
+    fn on_tick(&mut self, ns: u64) -> Actions {
+        self.pane.on_tick(ns)
+    }
+}
@@ -96,36 +96,72 @@ fn resolve_output_port(port: &(Ident, Ident)) -> OutputPort {
     }
 }
 
+/// Reads the `slide` and `emit_empty_windows` settings of a sliding window
+/// spanning `range` events or nanoseconds
+fn sliding_window_params(with: &Value, range: u64) -> Result<(u64, bool)> {
+    let emit_empty_windows = with
+        .get(WindowDefinition::EMIT_EMPTY_WINDOWS)
+        .and_then(Value::as_bool)
+        .unwrap_or_default();
+    match with.get(WindowDefinition::SLIDE).and_then(Value::as_u64) {
+        Some(slide) if slide > 0 && slide <= range && range % slide == 0 => {
+            Ok((slide, emit_empty_windows))
+        }
+        Some(_) => Err(Error::from(
+            "Bad window configuration, `slide` must be a divisor of `size` or `interval`.",
+        )),
+        None => Err(Error::from(
+            "Bad window configuration, sliding windows require a `slide`.",
+        )),
+    }
+}
+
 pub(crate) fn window_defn_to_impl(d: &WindowDefinition<'static>) -> Result<window::Impl> {
-    use op::trickle::window::{TumblingOnNumber, TumblingOnTime};
-    match &d.kind {
-        WindowKind::Sliding => Err("Sliding windows are not yet implemented".into()),
-        WindowKind::Tumbling => {
-            let script = if d.script.is_some() { Some(d) } else { None };
-            let with = d.params.render()?;
-            let max_groups = with
-                .get(WindowDefinition::MAX_GROUPS)
-                .and_then(Value::as_usize)
-                .unwrap_or(window::Impl::DEFAULT_MAX_GROUPS);
-
-            match (
-                with.get(WindowDefinition::INTERVAL).and_then(Value::as_u64),
-                with.get(WindowDefinition::SIZE).and_then(Value::as_u64),
-            ) {
-                (Some(interval), None) => Ok(window::Impl::from(TumblingOnTime::from_stmt(
-                    interval, max_groups, script,
-                ))),
-                (None, Some(size)) => Ok(window::Impl::from(TumblingOnNumber::from_stmt(
-                    size, max_groups, script,
-                ))),
-                (Some(_), Some(_)) => Err(Error::from(
-                    "Bad window configuration, only one of `size` or `interval` is allowed.",
-                )),
-                (None, None) => Err(Error::from(
-                    "Bad window configuration, either `size` or `interval` is required.",
-                )),
-            }
+    use op::trickle::window::{SlidingOnNumber, SlidingOnTime, TumblingOnNumber, TumblingOnTime};
+    let script = if d.script.is_some() { Some(d) } else { None };
+    let with = d.params.render()?;
+    let max_groups = with
+        .get(WindowDefinition::MAX_GROUPS)
+        .and_then(Value::as_usize)
+        .unwrap_or(window::Impl::DEFAULT_MAX_GROUPS);
+
+    match (
+        &d.kind,
+        with.get(WindowDefinition::INTERVAL).and_then(Value::as_u64),
+        with.get(WindowDefinition::SIZE).and_then(Value::as_u64),
+    ) {
+        (WindowKind::Tumbling, Some(interval), None) => Ok(window::Impl::from(
+            TumblingOnTime::from_stmt(interval, max_groups, script),
+        )),
+        (WindowKind::Tumbling, None, Some(size)) => Ok(window::Impl::from(
+            TumblingOnNumber::from_stmt(size, max_groups, script),
+        )),
+        (WindowKind::Sliding, Some(interval), None) => {
+            let (slide, emit_empty_windows) = sliding_window_params(&with, interval)?;
+            Ok(window::Impl::from(SlidingOnTime::from_stmt(
+                interval,
+                slide,
+                max_groups,
+                emit_empty_windows,
+                script,
+            )))
+        }
+        (WindowKind::Sliding, None, Some(size)) => {
+            let (slide, emit_empty_windows) = sliding_window_params(&with, size)?;
+            Ok(window::Impl::from(SlidingOnNumber::from_stmt(
+                size,
+                slide,
+                max_groups,
+                emit_empty_windows,
+                script,
+            )))
         }
+        (_, Some(_), Some(_)) => Err(Error::from(
+            "Bad window configuration, only one of `size` or `interval` is allowed.",
+        )),
+        (_, None, None) => Err(Error::from(
+            "Bad window configuration, either `size` or `interval` is required.",
+        )),
     }
 }
 /// A Tremor Query
@@ -118,10 +118,16 @@ impl<'script> ConnectorDefinition<'script> {
     pub const METRICS_INTERVAL_S: &'static str = "metrics_interval_s";
     /// param name for reconnct configuration
     pub const RECONNECT: &'static str = "reconnect";
+    /// param name for sink batching configuration
+    pub const BATCH: &'static str = "batch";
+    /// param name for keeping data failing to decode in dead letters
+    pub const DEAD_LETTER_DATA: &'static str = "dead_letter_data";
 
-    const AVAILABLE_PARAMS: [&'static str; 6] = [
+    const AVAILABLE_PARAMS: [&'static str; 8] = [
+        Self::BATCH,
         Self::CODEC,
         Self::CONFIG,
+        Self::DEAD_LETTER_DATA,
         Self::METRICS_INTERVAL_S,
         Self::POSTPROCESSORS,
         Self::PREPROCESSORS,
@@ -354,6 +354,8 @@ impl<'script> WindowDefinition<'script> {
     pub const INTERVAL: &'static str = "interval";
     /// `size` setting
     pub const SIZE: &'static str = "size";
+    /// `slide` setting
+    pub const SLIDE: &'static str = "slide";
 }
 
 /// A select statement
@@ -375,9 +377,25 @@ pub struct Select<'script> {
     pub maybe_group_by: Option<GroupBy<'script>>,
     /// Window
     pub windows: Vec<WindowName>,
+    /// Order-By clause
+    pub order_by: Vec<OrderBy<'script>>,
+    /// Limit clause
+    pub maybe_limit: Option<u64>,
 }
 impl_expr!(Select);
 
+/// An item of an order by clause
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct OrderBy<'script> {
+    /// mid
+    pub mid: Box<NodeMeta>,
+    /// expression evaluated against the emitted event
+    pub expr: ImutExpr<'script>,
+    /// if the order is descending
+    pub descending: bool,
+}
+impl_expr!(OrderBy);
+
 /// A group by clause
 #[derive(Clone, Debug, PartialEq, Serialize)]
 pub enum GroupBy<'script> {
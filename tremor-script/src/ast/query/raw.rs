@@ -23,7 +23,7 @@ use super::{
 };
 use super::{
     error_generic, error_no_locals, BaseExpr, GroupBy, HashMap, Helper, OperatorCreate,
-    OperatorDefinition, OperatorKind, PipelineCreate, PipelineDefinition, Query, Result,
+    OperatorDefinition, OperatorKind, OrderBy, PipelineCreate, PipelineDefinition, Query, Result,
     ScriptCreate, ScriptDefinition, Select, SelectStmt, Serialize, Stmt, StreamStmt, Upable,
     WindowDefinition, WindowKind,
 };
@@ -471,6 +471,8 @@ pub struct SelectRaw<'script> {
     pub(crate) maybe_having: Option<ImutExprRaw<'script>>,
     pub(crate) maybe_group_by: Option<GroupByRaw<'script>>,
     pub(crate) windows: Option<Vec<WindowName>>,
+    pub(crate) order_by: Option<Vec<OrderByRaw<'script>>>,
+    pub(crate) maybe_limit: Option<u64>,
     pub(crate) mid: Box<NodeMeta>,
 }
 impl_expr!(SelectRaw);
@@ -514,8 +516,36 @@ impl<'script> Upable<'script> for SelectRaw<'script> {
         } else {
             vec![]
         };
+        let aggregates = helper.aggregates.len();
+        let order_by = self.order_by.unwrap_or_default().up(helper)?;
+        if helper.has_locals() {
+            if let Some(definitely) = order_by.first() {
+                return error_no_locals(&self.mid.range, definitely);
+            }
+        };
+        // order by expressions are evaluated against emitted events, after the aggregates are reset
+        if helper.aggregates.len() > aggregates {
+            if let Some(order_by) = order_by.first() {
+                return error_generic(
+                    &self.mid.range,
+                    order_by,
+                    &"aggregate functions are not allowed in `order by`, select them and order by the event instead",
+                );
+            }
+        }
+
         let windows: Vec<_> = self.windows.unwrap_or_default().into_iter().collect();
-        if !windows.is_empty() {
+        if windows.is_empty() {
+            if let Some(order_by) = order_by.first() {
+                return error_generic(&self.mid.range, order_by, &"`order by` requires a window");
+            } else if self.maybe_limit.is_some() {
+                return error_generic(
+                    &self.mid.range,
+                    &self.mid.range,
+                    &"`limit` requires a window",
+                );
+            }
+        } else {
             // if we have windows we need to forbid free event references in the target if they are not
             // inside an aggregate function or can be rewritten to a group reference
             TargetEventRef::new(group_by_expressions).rewrite_target(&mut target)?;
@@ -546,6 +576,28 @@ impl<'script> Upable<'script> for SelectRaw<'script> {
             maybe_having,
             maybe_group_by,
             windows,
+            order_by,
+            maybe_limit: self.maybe_limit,
+        })
+    }
+}
+
+/// we're forced to make this pub because of lalrpop
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct OrderByRaw<'script> {
+    pub(crate) mid: Box<NodeMeta>,
+    pub(crate) expr: ImutExprRaw<'script>,
+    pub(crate) descending: bool,
+}
+impl_expr!(OrderByRaw);
+
+impl<'script> Upable<'script> for OrderByRaw<'script> {
+    type Target = OrderBy<'script>;
+    fn up<'registry>(self, helper: &mut Helper<'script, 'registry>) -> Result<Self::Target> {
+        Ok(OrderBy {
+            mid: self.mid,
+            expr: self.expr.up(helper)?,
+            descending: self.descending,
         })
     }
 }
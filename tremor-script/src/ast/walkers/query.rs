@@ -77,6 +77,9 @@ pub trait Walker<'script>: ExprWalker<'script> + QueryVisitor<'script> {
         if let Some(g) = select.maybe_group_by.as_mut() {
             self.walk_group_by(g)?;
         };
+        for o in &mut select.order_by {
+            ImutExprWalker::walk_expr(self, &mut o.expr)?;
+        }
 
         for w in &mut select.windows {
             self.walk_window_name(w)?;
@@ -116,7 +116,10 @@ pub(crate) fn ident_to_token(ident: &str) -> Token {
         "from" => Token::From,
         "where" => Token::Where,
         "with" => Token::With,
-        // "order" => Token::Order,
+        "order" => Token::Order,
+        "limit" => Token::Limit,
+        "asc" => Token::Asc,
+        "desc" => Token::Desc,
         "group" => Token::Group,
         "by" => Token::By,
         "having" => Token::Having,
@@ -354,7 +357,13 @@ pub enum Token<'input> {
     /// The `with` keyword
     With,
     /// The `order` keyword
-    // Order,
+    Order,
+    /// The `limit` keyword
+    Limit,
+    /// The `asc` keyword
+    Asc,
+    /// The `desc` keyword
+    Desc,
     /// the `group` keyword
     Group,
     /// The `by` keyword
@@ -449,7 +458,10 @@ impl<'input> Token<'input> {
                 | Token::Move
                 | Token::Of
                 | Token::Operator
-                // | Token::Order
+                | Token::Order
+                | Token::Limit
+                | Token::Asc
+                | Token::Desc
                 | Token::Patch
                 | Token::Present
                 | Token::Script
@@ -724,7 +736,10 @@ impl<'input> fmt::Display for Token<'input> {
             Token::From => write!(f, "from"),
             Token::Where => write!(f, "where"),
             Token::With => write!(f, "with"),
-            // Token::Order => write!(f, "order"),
+            Token::Order => write!(f, "order"),
+            Token::Limit => write!(f, "limit"),
+            Token::Asc => write!(f, "asc"),
+            Token::Desc => write!(f, "desc"),
             Token::Group => write!(f, "group"),
             Token::By => write!(f, "by"),
             Token::Having => write!(f, "having"),
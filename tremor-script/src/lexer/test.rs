@@ -131,6 +131,12 @@ fn paths() -> Result<()> {
         "  ~ " => Token::Dollar,
         "   ~~~~~~ " => Token::Ident("borp".into(), true),
     };
+    // keywords are escaped to be used as identifiers
+    lex_ok! {
+        "  .`limit`",
+        "  ~ " => Token::Dot,
+        "   ~~~~~~~ " => Token::Ident("limit".into(), true),
+    };
     Ok(())
 }
 
@@ -173,6 +179,18 @@ fn keywords() -> Result<()> {
     lex_ok! {
         " intrinsic ",
         " ~~~~~~~~~ " => Token::Intrinsic, };
+    lex_ok! {
+        " order ",
+        " ~~~~~ " => Token::Order, };
+    lex_ok! {
+        " limit ",
+        " ~~~~~ " => Token::Limit, };
+    lex_ok! {
+        " asc ",
+        " ~~~ " => Token::Asc, };
+    lex_ok! {
+        " desc ",
+        " ~~~~ " => Token::Desc, };
     Ok(())
 }
 
@@ -71,6 +71,18 @@ pub trait TremorAggrFn: DowncastSync + Sync + Send {
     fn warning(&self) -> Option<String> {
         None
     }
+    /// A snapshot of the accumulated state, to be restored by `restore`,
+    /// `None` if the function does not support snapshots.
+    fn snapshot(&self) -> Option<Value<'static>> {
+        None
+    }
+    /// Restores the accumulated state from a `snapshot`
+    ///
+    /// # Errors
+    /// if the snapshot is invalid
+    fn restore(&mut self, _snapshot: &Value) -> FResult<()> {
+        Ok(())
+    }
 }
 impl_downcast!(sync TremorAggrFn);
 
@@ -158,6 +170,14 @@ pub fn mfa(m: &str, f: &str, a: usize) -> Mfa {
     Mfa::new(m, f, a)
 }
 
+/// The error for an aggregate function snapshot that can not be restored
+pub(crate) fn invalid_snapshot(m: &str, f: &str, a: usize) -> FunctionError {
+    FunctionError::RuntimeError {
+        mfa: mfa(m, f, a),
+        error: "Invalid snapshot".to_string(),
+    }
+}
+
 /// Turns an error and a Mfa into a function error
 pub fn to_runtime_error<E: core::fmt::Display>(mfa: Mfa, e: E) -> FunctionError {
     FunctionError::RuntimeError {
@@ -774,6 +794,27 @@ impl TremorAggrFnWrapper {
         use std::borrow::Borrow;
         self.fun.merge(src.fun.borrow())
     }
+
+    /// A snapshot of the accumulated state of the function
+    ///
+    /// # Errors
+    /// if the function does not support snapshots
+    pub fn snapshot(&self) -> FResult<Value<'static>> {
+        self.fun
+            .snapshot()
+            .ok_or_else(|| FunctionError::RuntimeError {
+                mfa: mfa(&self.module, &self.name, *self.fun.arity().start()),
+                error: "Snapshots are not supported".to_string(),
+            })
+    }
+
+    /// Restores the accumulated state of the function from a `snapshot`
+    ///
+    /// # Errors
+    /// if the snapshot is invalid
+    pub fn restore(&mut self, snapshot: &Value) -> FResult<()> {
+        self.fun.restore(snapshot)
+    }
 }
 
 // #[cfg_attr(coverage, no_coverage)]
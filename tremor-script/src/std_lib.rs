@@ -23,12 +23,15 @@ mod array;
 mod base64;
 mod binary;
 mod chash;
+mod crypto;
 mod datetime;
 mod dummy;
 mod float;
+mod hash;
 mod integer;
 mod json;
 mod math;
+mod net;
 mod origin;
 mod path;
 mod random;
@@ -41,6 +44,7 @@ mod system;
 mod test;
 mod r#type;
 mod url;
+mod uuid;
 mod win;
 
 use crate::registry::{Aggr as AggrRegistry, Registry};
@@ -50,12 +54,15 @@ pub fn load(registry: &mut Registry) {
     base64::load(registry);
     binary::load(registry);
     chash::load(registry);
+    crypto::load(registry);
     datetime::load(registry);
     dummy::load(registry);
     float::load(registry);
+    hash::load(registry);
     integer::load(registry);
     json::load(registry);
     math::load(registry);
+    net::load(registry);
     origin::load(registry);
     random::load(registry);
     range::load(registry);
@@ -66,6 +73,7 @@ pub fn load(registry: &mut Registry) {
     test::load(registry);
     r#type::load(registry);
     url::load(registry);
+    uuid::load(registry);
     win::load(registry);
     path::load(registry);
 }
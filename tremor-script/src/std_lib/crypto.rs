@@ -0,0 +1,113 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::prelude::*;
+use crate::registry::Registry;
+use crate::tremor_const_fn;
+use hmac::{digest::KeyInit, Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+fn mac<M: Mac + KeyInit>(key: &[u8], input: &[u8]) -> Result<M, String> {
+    let mut mac = <M as KeyInit>::new_from_slice(key).map_err(|e| e.to_string())?;
+    mac.update(input);
+    Ok(mac)
+}
+
+fn invalid_algorithm(algorithm: &str) -> String {
+    format!("Invalid algorithm {algorithm}, expected one of sha1, sha256 or sha512")
+}
+
+fn sign(algorithm: &str, key: &[u8], input: &[u8]) -> Result<Vec<u8>, String> {
+    match algorithm {
+        "sha1" => mac::<Hmac<Sha1>>(key, input).map(|mac| mac.finalize().into_bytes().to_vec()),
+        "sha256" => mac::<Hmac<Sha256>>(key, input).map(|mac| mac.finalize().into_bytes().to_vec()),
+        "sha512" => mac::<Hmac<Sha512>>(key, input).map(|mac| mac.finalize().into_bytes().to_vec()),
+        other => Err(invalid_algorithm(other)),
+    }
+}
+
+/// Compares the signature in constant time
+fn verify(algorithm: &str, key: &[u8], input: &[u8], signature: &[u8]) -> Result<bool, String> {
+    match algorithm {
+        "sha1" => mac::<Hmac<Sha1>>(key, input).map(|mac| mac.verify_slice(signature).is_ok()),
+        "sha256" => mac::<Hmac<Sha256>>(key, input).map(|mac| mac.verify_slice(signature).is_ok()),
+        "sha512" => mac::<Hmac<Sha512>>(key, input).map(|mac| mac.verify_slice(signature).is_ok()),
+        other => Err(invalid_algorithm(other)),
+    }
+}
+
+pub fn load(registry: &mut Registry) {
+    registry
+        .insert(tremor_const_fn! (crypto|hmac(_context, _algorithm, _key, _input) {
+            if let (Some(algorithm), Some(key), Some(input)) = (_algorithm.as_str(), _key.as_bytes(), _input.as_bytes()) {
+                sign(algorithm, key, input).map(|signature| Value::from(hex::encode(signature))).map_err(to_runtime_error)
+            } else {
+                Err(FunctionError::BadType{ mfa: this_mfa() })
+            }
+        }))
+        .insert(tremor_const_fn! (crypto|hmac_verify(_context, _algorithm, _key, _input, _signature) {
+            // signatures are given as hex strings or as raw bytes
+            let signature = match _signature {
+                Value::String(signature) => Some(hex::decode(signature.as_bytes()).map_err(to_runtime_error)?),
+                Value::Bytes(signature) => Some(signature.to_vec()),
+                _ => None
+            };
+            if let (Some(algorithm), Some(key), Some(input), Some(signature)) = (_algorithm.as_str(), _key.as_bytes(), _input.as_bytes(), signature) {
+                verify(algorithm, key, input, &signature).map(Value::from).map_err(to_runtime_error)
+            } else {
+                Err(FunctionError::BadType{ mfa: this_mfa() })
+            }
+        }));
+}
+
+#[cfg(test)]
+mod test {
+    use crate::registry::fun;
+    use crate::Value;
+
+    #[test]
+    fn hmac() {
+        let f = fun("crypto", "hmac");
+        let key = Value::from("key");
+        let input = Value::Bytes("snot".as_bytes().into());
+        assert_val!(
+            f(&[&Value::from("sha256"), &key, &input]),
+            "9e6afea4ecbc1ebf028da51b95f3123f8d54823b2e1d5ec0b9609a34493995e2"
+        );
+        assert_val!(
+            f(&[&Value::from("sha1"), &key, &Value::from("snot")]),
+            "c792dc2cb075be823f0fa07c0606f1db7d866212"
+        );
+        assert!(f(&[&Value::from("md5"), &key, &input]).is_err());
+    }
+
+    #[test]
+    fn hmac_verify() {
+        let f = fun("crypto", "hmac_verify");
+        let sha256 = Value::from("sha256");
+        let key = Value::from("key");
+        let input = Value::from("snot");
+        let signature =
+            Value::from("9e6afea4ecbc1ebf028da51b95f3123f8d54823b2e1d5ec0b9609a34493995e2");
+        assert_val!(f(&[&sha256, &key, &input, &signature]), true);
+        assert_val!(
+            f(&[&sha256, &Value::from("badger"), &input, &signature]),
+            false
+        );
+        let signature = Value::Bytes(vec![0_u8; 32].into());
+        assert_val!(f(&[&sha256, &key, &input, &signature]), false);
+        assert!(f(&[&sha256, &key, &input, &Value::from("not hex")]).is_err());
+    }
+}
@@ -23,7 +23,12 @@ use crate::datetime::{_parse, has_tz};
 use crate::prelude::*;
 use crate::registry::Registry;
 use crate::{tremor_const_fn, tremor_fn};
-use chrono::{offset::Utc, DateTime, Datelike, NaiveDateTime, SubsecRound, Timelike};
+use chrono::{
+    format::{Item, StrftimeItems},
+    offset::{Offset, TimeZone, Utc},
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, SecondsFormat, SubsecRound, Timelike,
+};
+use chrono_tz::Tz;
 
 macro_rules! time_fn {
     ($name:ident, $fn:ident) => {
@@ -90,7 +95,90 @@ pub fn load(registry: &mut Registry) {
         .insert(time_fn_32!(with_days, _with_days))
         .insert(time_fn_32!(with_weeks, _with_weeks))
         .insert(time_fn_32!(with_years, _with_years))
-        .insert(time_fn!(without_subseconds, _without_subseconds));
+        .insert(time_fn!(without_subseconds, _without_subseconds))
+        .insert(tremor_const_fn!(datetime|parse_tz(_context, _input: String, _input_fmt: String, _tz: String) {
+            timezone(_tz)
+                .and_then(|tz| _parse_tz(_input, _input_fmt, tz))
+                .map(Value::from)
+                .map_err(|error| FunctionError::RuntimeError { mfa: this_mfa(), error })
+        }))
+        .insert(tremor_const_fn!(datetime|format_tz(_context, _datetime, _fmt, _tz) {
+            if let (Some(datetime), Some(fmt), Some(tz)) = (_datetime.as_u64(), _fmt.as_str(), _tz.as_str()) {
+                timezone(tz)
+                    .and_then(|tz| _format_tz(datetime, fmt, tz))
+                    .map(Value::from)
+                    .map_err(|error| FunctionError::RuntimeError { mfa: this_mfa(), error })
+            } else {
+                Err(FunctionError::BadType{ mfa: this_mfa() })
+            }
+        }))
+        .insert(tremor_const_fn!(datetime|components(_context, _datetime, _tz) {
+            if let (Some(datetime), Some(tz)) = (_datetime.as_u64(), _tz.as_str()) {
+                timezone(tz)
+                    .map(|tz| _components(datetime, tz))
+                    .map_err(|error| FunctionError::RuntimeError { mfa: this_mfa(), error })
+            } else {
+                Err(FunctionError::BadType{ mfa: this_mfa() })
+            }
+        }))
+        .insert(tremor_const_fn!(datetime|to_rfc3339(_context, _datetime, _tz) {
+            if let (Some(datetime), Some(tz)) = (_datetime.as_u64(), _tz.as_str()) {
+                timezone(tz)
+                    .map(|tz| Value::from(_to_rfc3339(datetime, tz)))
+                    .map_err(|error| FunctionError::RuntimeError { mfa: this_mfa(), error })
+            } else {
+                Err(FunctionError::BadType{ mfa: this_mfa() })
+            }
+        }))
+        .insert(tremor_const_fn!(datetime|from_rfc3339(_context, _input: String) {
+            _from_rfc3339(_input)
+                .map(Value::from)
+                .map_err(|error| FunctionError::RuntimeError { mfa: this_mfa(), error })
+        }))
+        .insert(tremor_const_fn!(datetime|to_rfc2822(_context, _datetime, _tz) {
+            if let (Some(datetime), Some(tz)) = (_datetime.as_u64(), _tz.as_str()) {
+                timezone(tz)
+                    .map(|tz| Value::from(_to_rfc2822(datetime, tz)))
+                    .map_err(|error| FunctionError::RuntimeError { mfa: this_mfa(), error })
+            } else {
+                Err(FunctionError::BadType{ mfa: this_mfa() })
+            }
+        }))
+        .insert(tremor_const_fn!(datetime|from_rfc2822(_context, _input: String) {
+            _from_rfc2822(_input)
+                .map(Value::from)
+                .map_err(|error| FunctionError::RuntimeError { mfa: this_mfa(), error })
+        }))
+        .insert(tremor_const_fn!(datetime|add_months(_context, _datetime, _months, _tz) {
+            if let (Some(datetime), Some(months), Some(tz)) = (_datetime.as_u64(), _months.as_i64(), _tz.as_str()) {
+                timezone(tz)
+                    .and_then(|tz| _add_months(datetime, months, tz))
+                    .map(Value::from)
+                    .map_err(|error| FunctionError::RuntimeError { mfa: this_mfa(), error })
+            } else {
+                Err(FunctionError::BadType{ mfa: this_mfa() })
+            }
+        }))
+        .insert(tremor_const_fn!(datetime|add_days(_context, _datetime, _days, _tz) {
+            if let (Some(datetime), Some(days), Some(tz)) = (_datetime.as_u64(), _days.as_i64(), _tz.as_str()) {
+                timezone(tz)
+                    .and_then(|tz| _add_days(datetime, days, tz))
+                    .map(Value::from)
+                    .map_err(|error| FunctionError::RuntimeError { mfa: this_mfa(), error })
+            } else {
+                Err(FunctionError::BadType{ mfa: this_mfa() })
+            }
+        }))
+        .insert(tremor_const_fn!(datetime|truncate(_context, _datetime, _unit, _tz) {
+            if let (Some(datetime), Some(unit), Some(tz)) = (_datetime.as_u64(), _unit.as_str(), _tz.as_str()) {
+                timezone(tz)
+                    .and_then(|tz| _truncate(datetime, unit, tz))
+                    .map(Value::from)
+                    .map_err(|error| FunctionError::RuntimeError { mfa: this_mfa(), error })
+            } else {
+                Err(FunctionError::BadType{ mfa: this_mfa() })
+            }
+        }));
 }
 
 pub fn _iso8601(datetime: u64) -> String {
@@ -178,6 +266,140 @@ pub fn _without_subseconds(value: u64) -> u64 {
     value / 1_000_000_000
 }
 
+/// Looks up an IANA timezone like `Europe/Berlin`
+fn timezone(name: &str) -> Result<Tz, String> {
+    name.parse::<Tz>()
+        .map_err(|e| format!("Invalid timezone {name}: {e}"))
+}
+
+fn to_datetime(value: u64, tz: Tz) -> DateTime<Tz> {
+    tz.from_utc_datetime(&to_naive_datetime(value))
+}
+
+fn to_nanos<T: TimeZone>(datetime: &DateTime<T>) -> Result<u64, String> {
+    // `timestamp_nanos` panics for dates after 2262, nanoseconds in a u64 reach until 2554
+    u64::try_from(datetime.timestamp())
+        .map_err(|_| format!("{} is before the unix epoch", datetime.naive_utc()))?
+        .checked_mul(1_000_000_000)
+        .and_then(|nanos| nanos.checked_add(u64::from(datetime.timestamp_subsec_nanos())))
+        .ok_or_else(|| format!("{} is too far in the future", datetime.naive_utc()))
+}
+
+/// Resolves a wall clock time in `tz`, picking the earlier instant for ambiguous times
+fn from_local(local: &NaiveDateTime, tz: Tz) -> Result<u64, String> {
+    tz.from_local_datetime(local)
+        .earliest()
+        .ok_or_else(|| format!("{local} does not exist in timezone {tz}"))
+        .and_then(|datetime| to_nanos(&datetime))
+}
+
+pub fn _parse_tz(input: &str, fmt: &str, tz: Tz) -> Result<u64, String> {
+    if has_tz(fmt) {
+        _parse(input, fmt, true).map_err(|e| e.to_string())
+    } else {
+        let local = NaiveDateTime::parse_from_str(input, fmt)
+            .map_err(|e| format!("Cannot parse {input} with format {fmt}: {e}"))?;
+        from_local(&local, tz)
+    }
+}
+
+pub fn _format_tz(value: u64, fmt: &str, tz: Tz) -> Result<String, String> {
+    let items = StrftimeItems::new(fmt).collect::<Vec<_>>();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(format!("Invalid format {fmt}"));
+    }
+    Ok(to_datetime(value, tz)
+        .format_with_items(items.into_iter())
+        .to_string())
+}
+
+pub fn _components(value: u64, tz: Tz) -> Value<'static> {
+    let datetime = to_datetime(value, tz);
+    literal!({
+        "year": datetime.year(),
+        "month": datetime.month(),
+        "day": datetime.day(),
+        "hour": datetime.hour(),
+        "minute": datetime.minute(),
+        "second": datetime.second(),
+        "nanosecond": datetime.nanosecond(),
+        "weekday": datetime.weekday().number_from_monday(),
+        "offset": datetime.offset().fix().local_minus_utc()
+    })
+}
+
+pub fn _to_rfc3339(value: u64, tz: Tz) -> String {
+    to_datetime(value, tz).to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+pub fn _from_rfc3339(input: &str) -> Result<u64, String> {
+    DateTime::parse_from_rfc3339(input)
+        .map_err(|e| format!("Cannot parse {input} as RFC3339: {e}"))
+        .and_then(|datetime| to_nanos(&datetime))
+}
+
+pub fn _to_rfc2822(value: u64, tz: Tz) -> String {
+    to_datetime(value, tz).to_rfc2822()
+}
+
+pub fn _from_rfc2822(input: &str) -> Result<u64, String> {
+    DateTime::parse_from_rfc2822(input)
+        .map_err(|e| format!("Cannot parse {input} as RFC2822: {e}"))
+        .and_then(|datetime| to_nanos(&datetime))
+}
+
+/// Adds calendar months, clamping the day to the end of shorter months
+pub fn _add_months(value: u64, months: i64, tz: Tz) -> Result<u64, String> {
+    let local = to_datetime(value, tz).naive_local();
+    let date = local.date();
+    let total = i64::from(date.year()) * 12 + i64::from(date.month0()) + months;
+    let date = i32::try_from(total.div_euclid(12))
+        .ok()
+        .and_then(|year| {
+            let month = total.rem_euclid(12) as u32 + 1;
+            (1..=date.day())
+                .rev()
+                .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        })
+        .ok_or_else(|| format!("Cannot add {months} months to {local}"))?;
+    from_local(&date.and_time(local.time()), tz)
+}
+
+/// Adds calendar days, keeping the wall clock time across daylight saving changes
+pub fn _add_days(value: u64, days: i64, tz: Tz) -> Result<u64, String> {
+    let local = to_datetime(value, tz).naive_local();
+    let shifted = local
+        .date()
+        .checked_add_signed(Duration::days(days))
+        .ok_or_else(|| format!("Cannot add {days} days to {local}"))?;
+    from_local(&shifted.and_time(local.time()), tz)
+}
+
+/// Truncates to the start of the given unit, weeks start on monday
+pub fn _truncate(value: u64, unit: &str, tz: Tz) -> Result<u64, String> {
+    let local = to_datetime(value, tz).naive_local();
+    let date = local.date();
+    let (h, m, s) = (local.hour(), local.minute(), local.second());
+    let truncated = match unit {
+        "second" => date.and_hms(h, m, s),
+        "minute" => date.and_hms(h, m, 0),
+        "hour" => date.and_hms(h, 0, 0),
+        "day" => date.and_hms(0, 0, 0),
+        "week" => {
+            let days = i64::from(date.weekday().num_days_from_monday());
+            (date - Duration::days(days)).and_hms(0, 0, 0)
+        }
+        "month" => NaiveDate::from_ymd(date.year(), date.month(), 1).and_hms(0, 0, 0),
+        "year" => NaiveDate::from_ymd(date.year(), 1, 1).and_hms(0, 0, 0),
+        other => {
+            return Err(format!(
+            "Invalid unit {other}, expected one of second, minute, hour, day, week, month or year"
+        ))
+        }
+    };
+    from_local(&truncated, tz)
+}
+
 pub fn _today() -> u64 {
     Utc::today().and_hms(0, 0, 0).timestamp_nanos() as u64
 }
@@ -425,4 +647,147 @@ mod tests {
     pub fn test_with_years() {
         assert_eq!(_with_years(1), 31_536_000_000_000_000);
     }
+
+    fn berlin() -> Tz {
+        timezone("Europe/Berlin").expect("invalid timezone")
+    }
+
+    #[test]
+    pub fn parse_in_timezone() {
+        let fmt = "%Y-%m-%d %H:%M:%S";
+        assert_eq!(
+            _parse_tz("2022-03-27 12:00:00", fmt, berlin()),
+            Ok(1_648_375_200_000_000_000)
+        );
+        // an explicit offset wins over the timezone
+        assert_eq!(
+            _parse_tz(
+                "2022-03-27 12:00:00 +0000",
+                "%Y-%m-%d %H:%M:%S %z",
+                berlin()
+            ),
+            Ok(1_648_382_400_000_000_000)
+        );
+        // skipped by the switch to daylight saving time
+        assert!(_parse_tz("2022-03-27 02:30:00", fmt, berlin()).is_err());
+        assert!(timezone("Snot/Badger").is_err());
+    }
+
+    #[test]
+    pub fn format_in_timezone() {
+        let input = 1_654_039_815_000_000_000;
+        assert_eq!(
+            _format_tz(input, "%Y-%m-%d %H:%M:%S %Z %:z", berlin()),
+            Ok("2022-06-01 01:30:15 CEST +02:00".to_string())
+        );
+        assert!(_format_tz(input, "%Y %Q", berlin()).is_err());
+    }
+
+    #[test]
+    pub fn components_in_timezone() {
+        assert_eq!(
+            _components(1_654_039_815_000_000_000, berlin()),
+            literal!({
+                "year": 2022,
+                "month": 6,
+                "day": 1,
+                "hour": 1,
+                "minute": 30,
+                "second": 15,
+                "nanosecond": 0,
+                "weekday": 3,
+                "offset": 7200
+            })
+        );
+    }
+
+    #[test]
+    pub fn rfc3339_and_rfc2822() {
+        let input = 1_654_039_815_000_000_000;
+        assert_eq!(_to_rfc3339(input, Tz::UTC), "2022-05-31T23:30:15Z");
+        assert_eq!(_to_rfc3339(input, berlin()), "2022-06-01T01:30:15+02:00");
+        assert_eq!(
+            _to_rfc3339(input + 5_000_000, Tz::UTC),
+            "2022-05-31T23:30:15.005Z"
+        );
+        assert_eq!(_from_rfc3339("2022-06-01T01:30:15+02:00"), Ok(input));
+        assert!(_from_rfc3339("1969-12-31T23:59:59Z").is_err());
+        // beyond the range of `timestamp_nanos`
+        assert_eq!(
+            _from_rfc3339("2500-01-01T00:00:00Z"),
+            Ok(16_725_225_600_000_000_000)
+        );
+        assert!(_from_rfc3339("3000-01-01T00:00:00Z").is_err());
+        assert_eq!(
+            _to_rfc2822(input, berlin()),
+            "Wed, 01 Jun 2022 01:30:15 +0200"
+        );
+        assert_eq!(_from_rfc2822("Tue, 31 May 2022 23:30:15 +0000"), Ok(input));
+        assert!(_from_rfc2822("snot").is_err());
+    }
+
+    #[test]
+    pub fn add_months_clamps_the_day() {
+        let input = 1_643_625_000_000_000_000; // 2022-01-31T10:30:00Z
+        assert_eq!(
+            _add_months(input, 1, Tz::UTC),
+            Ok(1_646_044_200_000_000_000)
+        );
+        assert_eq!(
+            _add_months(input, -2, Tz::UTC),
+            Ok(1_638_268_200_000_000_000)
+        );
+        assert_eq!(_add_months(input, 0, Tz::UTC), Ok(input));
+    }
+
+    #[test]
+    pub fn add_days_keeps_the_wall_clock() {
+        // the day daylight saving time starts only has 23 hours
+        assert_eq!(
+            _add_days(1_648_292_400_000_000_000, 1, berlin()),
+            Ok(1_648_375_200_000_000_000)
+        );
+        assert_eq!(
+            _add_days(1_648_375_200_000_000_000, -1, berlin()),
+            Ok(1_648_292_400_000_000_000)
+        );
+    }
+
+    #[test]
+    pub fn truncate_in_timezone() {
+        let input = 1_654_039_815_123_000_000; // 2022-06-01T01:30:15.123+02:00
+        assert_eq!(
+            _truncate(input, "second", berlin()),
+            Ok(1_654_039_815_000_000_000)
+        );
+        assert_eq!(
+            _truncate(input, "minute", berlin()),
+            Ok(1_654_039_800_000_000_000)
+        );
+        assert_eq!(
+            _truncate(input, "hour", berlin()),
+            Ok(1_654_038_000_000_000_000)
+        );
+        assert_eq!(
+            _truncate(input, "day", berlin()),
+            Ok(1_654_034_400_000_000_000)
+        );
+        assert_eq!(
+            _truncate(input, "day", Tz::UTC),
+            Ok(1_653_955_200_000_000_000)
+        );
+        assert_eq!(
+            _truncate(input, "week", berlin()),
+            Ok(1_653_861_600_000_000_000)
+        );
+        assert_eq!(
+            _truncate(input, "month", berlin()),
+            Ok(1_654_034_400_000_000_000)
+        );
+        assert_eq!(
+            _truncate(input, "year", berlin()),
+            Ok(1_640_991_600_000_000_000)
+        );
+        assert!(_truncate(input, "fortnight", berlin()).is_err());
+    }
 }
@@ -0,0 +1,93 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::prelude::*;
+use crate::registry::Registry;
+use crate::tremor_const_fn;
+use md5::Md5;
+use murmur3::murmur3_32;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+
+macro_rules! digest_fn {
+    ($name:ident, $digest:ty) => {
+        tremor_const_fn! (hash|$name(_context, _input) {
+            _input.as_bytes().map(|input| Value::from(hex::encode(<$digest>::digest(input)))).ok_or_else(||FunctionError::BadType{ mfa: this_mfa() })
+        })
+    };
+}
+
+pub fn load(registry: &mut Registry) {
+    registry
+        .insert(digest_fn!(md5, Md5))
+        .insert(digest_fn!(sha1, Sha1))
+        .insert(digest_fn!(sha256, Sha256))
+        .insert(digest_fn!(sha512, Sha512))
+        .insert(tremor_const_fn! (hash|xxh64(_context, _input) {
+            _input.as_bytes().map(|input| Value::from(xxhash_rust::xxh64::xxh64(input, 0))).ok_or_else(||FunctionError::BadType{ mfa: this_mfa() })
+        }))
+        .insert(tremor_const_fn! (hash|xxh3(_context, _input) {
+            _input.as_bytes().map(|input| Value::from(xxhash_rust::xxh3::xxh3_64(input))).ok_or_else(||FunctionError::BadType{ mfa: this_mfa() })
+        }))
+        .insert(tremor_const_fn! (hash|murmur3(_context, _input) {
+            if let Some(mut input) = _input.as_bytes() {
+                murmur3_32(&mut input, 0).map(Value::from).map_err(to_runtime_error)
+            } else {
+                Err(FunctionError::BadType{ mfa: this_mfa() })
+            }
+        }));
+}
+
+#[cfg(test)]
+mod test {
+    use crate::registry::fun;
+    use crate::Value;
+
+    #[test]
+    fn digests() {
+        let v = Value::from("snot");
+        assert_val!(
+            fun("hash", "md5")(&[&v]),
+            "d832124e005651232af313575b210bc1"
+        );
+        assert_val!(
+            fun("hash", "sha1")(&[&v]),
+            "cd2fa4e40d991bc8d8032f1ff042cec638fb76cb"
+        );
+        assert_val!(
+            fun("hash", "sha256")(&[&v]),
+            "4c499dc1f10efacdd446a9e7a66e885aad59ac870e4bbb88311a3dd70c09e966"
+        );
+        assert_val!(
+            fun("hash", "sha512")(&[&v]),
+            "f2701cdb34b51bd4bc6841bbd78cc285bc4fa444cc9b5f4a3e3c0d8941eace4e618a9b2d510309c77e81b0f2047985955425f335f24a73ae553bded9d765d105"
+        );
+        let v = Value::Bytes("snot".as_bytes().into());
+        assert_val!(
+            fun("hash", "md5")(&[&v]),
+            "d832124e005651232af313575b210bc1"
+        );
+        assert!(fun("hash", "md5")(&[&Value::from(42)]).is_err());
+    }
+
+    #[test]
+    fn non_cryptographic() {
+        let v = Value::from("");
+        assert_val!(fun("hash", "xxh64")(&[&v]), 17_241_709_254_077_376_921_u64);
+        assert_val!(fun("hash", "xxh3")(&[&v]), 3_244_421_341_483_603_138_u64);
+        let v = Value::Bytes("hello".as_bytes().into());
+        assert_val!(fun("hash", "murmur3")(&[&v]), 613_153_351_u32);
+        assert!(fun("hash", "murmur3")(&[&Value::from(true)]).is_err());
+    }
+}
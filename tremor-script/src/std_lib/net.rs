@@ -0,0 +1,421 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::prelude::*;
+use crate::registry::Registry;
+use crate::tremor_const_fn;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+macro_rules! classify_fn {
+    ($name:ident, $fn:path) => {
+        tremor_const_fn! (net|$name(_context, _address) {
+            ip(_address).map(|ip| Value::from($fn(&ip))).map_err(to_runtime_error)
+        })
+    };
+}
+
+/// Treats IPv4 addresses mapped into IPv6, like `::ffff:10.0.0.1`, as IPv4 addresses
+fn unmap(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) if matches!(v6.segments(), [0, 0, 0, 0, 0, 0xffff, _, _]) => {
+            let [.., a, b, c, d] = v6.octets();
+            IpAddr::V4(Ipv4Addr::new(a, b, c, d))
+        }
+        ip => ip,
+    }
+}
+
+fn parse_ip(input: &str) -> Result<IpAddr, String> {
+    IpAddr::from_str(input)
+        .map(unmap)
+        .map_err(|_| format!("Invalid IP address {input}"))
+}
+
+fn ip(value: &Value) -> Result<IpAddr, String> {
+    value.as_str().map_or_else(
+        || Err(format!("Invalid IP address {}", value.encode())),
+        parse_ip,
+    )
+}
+
+/// The address as an integer, with its width in bits
+fn to_bits(ip: IpAddr) -> (u128, u32) {
+    match ip {
+        IpAddr::V4(ip) => (u128::from(u32::from(ip)), 32),
+        IpAddr::V6(ip) => (u128::from(ip), 128),
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn from_bits(bits: u128, width: u32) -> IpAddr {
+    if width == 32 {
+        IpAddr::V4(Ipv4Addr::from(bits as u32))
+    } else {
+        IpAddr::V6(Ipv6Addr::from(bits))
+    }
+}
+
+fn octets(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    }
+}
+
+fn is_private(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private(),
+        // unique local addresses, fc00::/7
+        IpAddr::V6(ip) => ip.segments()[0] & 0xfe00 == 0xfc00,
+    }
+}
+
+fn is_link_local(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_link_local(),
+        // fe80::/10
+        IpAddr::V6(ip) => ip.segments()[0] & 0xffc0 == 0xfe80,
+    }
+}
+
+/// The range of addresses of a CIDR, addresses without a prefix length are a network of their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Network {
+    start: u128,
+    end: u128,
+    width: u32,
+}
+
+impl Network {
+    fn parse(input: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid CIDR {input}");
+        let (address, prefix) = input
+            .split_once('/')
+            .map_or((input, None), |(address, prefix)| (address, Some(prefix)));
+        let (bits, width) = to_bits(parse_ip(address).map_err(|_| invalid())?);
+        let prefix = prefix.map_or(Ok(width), |prefix| {
+            prefix.parse::<u32>().map_err(|_| invalid())
+        })?;
+        if prefix > width {
+            return Err(invalid());
+        }
+        let host = if prefix == width {
+            0
+        } else {
+            u128::MAX >> (128 - (width - prefix))
+        };
+        Ok(Self {
+            start: bits & !host,
+            end: bits | host,
+            width,
+        })
+    }
+
+    fn from_value(value: &Value) -> Result<Self, String> {
+        value.as_str().map_or_else(
+            || Err(format!("Invalid CIDR {}", value.encode())),
+            Self::parse,
+        )
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        let (bits, width) = to_bits(ip);
+        width == self.width && self.start <= bits && bits <= self.end
+    }
+
+    fn netmask(&self) -> u128 {
+        (u128::MAX >> (128 - self.width)) & !(self.end - self.start)
+    }
+}
+
+fn in_cidr(ip: IpAddr, cidrs: &Value) -> Result<bool, String> {
+    if let Some(cidrs) = cidrs.as_array() {
+        for cidr in cidrs {
+            if Network::from_value(cidr)?.contains(ip) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    } else {
+        Network::from_value(cidrs).map(|network| network.contains(ip))
+    }
+}
+
+/// Sorts ranges and merges overlapping or adjacent ones
+fn merge(mut ranges: Vec<(u128, u128)>) -> Vec<(u128, u128)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(u128, u128)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Compiles CIDRs into sorted, disjoint ranges of addresses as bytes per IP version
+fn cidr_set(cidrs: &[Value]) -> Result<Value<'static>, String> {
+    let mut v4 = Vec::new();
+    let mut v6 = Vec::new();
+    for cidr in cidrs {
+        let network = Network::from_value(cidr)?;
+        if network.width == 32 {
+            v4.push((network.start, network.end));
+        } else {
+            v6.push((network.start, network.end));
+        }
+    }
+    let ranges = |ranges: Vec<(u128, u128)>, width: u32| {
+        merge(ranges)
+            .into_iter()
+            .map(|(start, end)| {
+                Value::from(vec![
+                    Value::Bytes(octets(from_bits(start, width)).into()),
+                    Value::Bytes(octets(from_bits(end, width)).into()),
+                ])
+            })
+            .collect::<Value>()
+    };
+    let v4 = ranges(v4, 32);
+    let v6 = ranges(v6, 128);
+    Ok(literal!({ "v4": v4, "v6": v6 }))
+}
+
+fn bound<'value>(range: &'value Value, idx: usize) -> Option<&'value [u8]> {
+    range.get_idx(idx).and_then(Value::as_bytes)
+}
+
+fn in_cidr_set(ip: IpAddr, set: &Value) -> Option<bool> {
+    let key = if ip.is_ipv4() { "v4" } else { "v6" };
+    let address = octets(ip);
+    let address = address.as_slice();
+    let ranges = set.get_array(key)?;
+    // big endian bytes of the same length compare like the addresses
+    let idx =
+        ranges.partition_point(|range| bound(range, 0).map_or(false, |start| start <= address));
+    Some(
+        idx.checked_sub(1)
+            .and_then(|idx| ranges.get(idx))
+            .and_then(|range| bound(range, 1))
+            .map_or(false, |end| address <= end),
+    )
+}
+
+pub fn load(registry: &mut Registry) {
+    registry
+        .insert(tremor_const_fn! (net|is_valid(_context, _input) {
+            Ok(Value::from(ip(_input).is_ok()))
+        }))
+        .insert(tremor_const_fn! (net|version(_context, _address) {
+            ip(_address).map(|ip| Value::from(if ip.is_ipv4() { 4 } else { 6 })).map_err(to_runtime_error)
+        }))
+        .insert(tremor_const_fn! (net|normalize(_context, _address) {
+            ip(_address).map(|ip| Value::from(ip.to_string())).map_err(to_runtime_error)
+        }))
+        .insert(classify_fn!(is_private, is_private))
+        .insert(classify_fn!(is_loopback, IpAddr::is_loopback))
+        .insert(classify_fn!(is_multicast, IpAddr::is_multicast))
+        .insert(classify_fn!(is_link_local, is_link_local))
+        .insert(classify_fn!(is_unspecified, IpAddr::is_unspecified))
+        .insert(tremor_const_fn! (net|to_integer(_context, _address) {
+            match ip(_address).map_err(to_runtime_error)? {
+                IpAddr::V4(ip) => Ok(Value::from(u32::from(ip))),
+                IpAddr::V6(ip) => Err(to_runtime_error(format!("{ip} is an IPv6 address, only IPv4 addresses can be converted to integers")))
+            }
+        }))
+        .insert(tremor_const_fn! (net|from_integer(_context, _input) {
+            _input
+                .as_u64()
+                .and_then(|input| u32::try_from(input).ok())
+                .map(|input| Value::from(Ipv4Addr::from(input).to_string()))
+                .ok_or_else(|| to_runtime_error(format!("Invalid IPv4 address {}", _input.encode())))
+        }))
+        .insert(tremor_const_fn! (net|to_bytes(_context, _address) {
+            ip(_address).map(|ip| Value::Bytes(octets(ip).into())).map_err(to_runtime_error)
+        }))
+        .insert(tremor_const_fn! (net|from_bytes(_context, _input) {
+            let ip = match _input {
+                Value::Bytes(bytes) => {
+                    let bytes: &[u8] = bytes;
+                    <[u8; 4]>::try_from(bytes).map(IpAddr::from).ok()
+                        .or_else(|| <[u8; 16]>::try_from(bytes).map(IpAddr::from).ok())
+                }
+                _ => None
+            };
+            ip.map(|ip| Value::from(unmap(ip).to_string()))
+                .ok_or_else(|| to_runtime_error(format!("Invalid IP address {}", _input.encode())))
+        }))
+        .insert(tremor_const_fn! (net|in_cidr(_context, _address, _cidrs) {
+            ip(_address).and_then(|ip| in_cidr(ip, _cidrs)).map(Value::from).map_err(to_runtime_error)
+        }))
+        .insert(tremor_const_fn! (net|cidr_set(_context, _cidrs: Array) {
+            cidr_set(_cidrs).map_err(to_runtime_error)
+        }))
+        .insert(tremor_const_fn! (net|in_cidr_set(_context, _address, _set) {
+            let ip = ip(_address).map_err(to_runtime_error)?;
+            in_cidr_set(ip, _set)
+                .map(Value::from)
+                .ok_or_else(|| to_runtime_error("Invalid CIDR set, use `net::cidr_set` to create one"))
+        }))
+        .insert(tremor_const_fn! (net|network(_context, _cidr) {
+            Network::from_value(_cidr)
+                .map(|network| Value::from(from_bits(network.start, network.width).to_string()))
+                .map_err(to_runtime_error)
+        }))
+        .insert(tremor_const_fn! (net|broadcast(_context, _cidr) {
+            Network::from_value(_cidr)
+                .map(|network| Value::from(from_bits(network.end, network.width).to_string()))
+                .map_err(to_runtime_error)
+        }))
+        .insert(tremor_const_fn! (net|netmask(_context, _cidr) {
+            Network::from_value(_cidr)
+                .map(|network| Value::from(from_bits(network.netmask(), network.width).to_string()))
+                .map_err(to_runtime_error)
+        }));
+}
+
+#[cfg(test)]
+mod test {
+    use crate::registry::fun;
+    use crate::Value;
+    use tremor_value::literal;
+
+    #[test]
+    fn parse_and_normalize() {
+        let f = fun("net", "normalize");
+        assert_val!(f(&[&Value::from("2001:0DB8:0000::0001")]), "2001:db8::1");
+        assert_val!(f(&[&Value::from("::ffff:10.0.0.1")]), "10.0.0.1");
+        assert!(f(&[&Value::from("10.0.0.256")]).is_err());
+        assert!(f(&[&Value::from(42)]).is_err());
+        assert_val!(fun("net", "version")(&[&Value::from("::1")]), 6);
+        assert_val!(fun("net", "is_valid")(&[&Value::from("snot")]), false);
+        assert_val!(fun("net", "is_valid")(&[&Value::from("127.0.0.1")]), true);
+    }
+
+    #[test]
+    fn classify() {
+        let private = fun("net", "is_private");
+        assert_val!(private(&[&Value::from("172.16.0.1")]), true);
+        assert_val!(private(&[&Value::from("172.32.0.1")]), false);
+        assert_val!(private(&[&Value::from("fd00::1")]), true);
+        assert_val!(private(&[&Value::from("2001:db8::1")]), false);
+        assert_val!(fun("net", "is_loopback")(&[&Value::from("::1")]), true);
+        assert_val!(
+            fun("net", "is_multicast")(&[&Value::from("224.0.0.1")]),
+            true
+        );
+        assert_val!(
+            fun("net", "is_link_local")(&[&Value::from("fe80::1")]),
+            true
+        );
+        assert_val!(
+            fun("net", "is_link_local")(&[&Value::from("169.254.1.1")]),
+            true
+        );
+        assert_val!(
+            fun("net", "is_unspecified")(&[&Value::from("0.0.0.0")]),
+            true
+        );
+    }
+
+    #[test]
+    fn integers_and_bytes() {
+        let address = Value::from("192.168.0.1");
+        assert_val!(fun("net", "to_integer")(&[&address]), 3_232_235_521_u32);
+        assert!(fun("net", "to_integer")(&[&Value::from("::1")]).is_err());
+        assert_val!(
+            fun("net", "from_integer")(&[&Value::from(3_232_235_521_u32)]),
+            "192.168.0.1"
+        );
+        assert!(fun("net", "from_integer")(&[&Value::from(-1)]).is_err());
+        let bytes = fun("net", "to_bytes")(&[&address]).expect("invalid address");
+        assert_eq!(Value::Bytes(vec![192, 168, 0, 1].into()), bytes);
+        assert_val!(fun("net", "from_bytes")(&[&bytes]), "192.168.0.1");
+        let mut v6 = vec![0_u8; 16];
+        v6[15] = 1;
+        assert_val!(fun("net", "from_bytes")(&[&Value::Bytes(v6.into())]), "::1");
+        assert!(fun("net", "from_bytes")(&[&Value::Bytes(vec![1, 2, 3].into())]).is_err());
+    }
+
+    #[test]
+    fn cidrs() {
+        let f = fun("net", "in_cidr");
+        let address = Value::from("10.1.2.3");
+        assert_val!(f(&[&address, &Value::from("10.0.0.0/8")]), true);
+        assert_val!(f(&[&address, &Value::from("10.1.2.3")]), true);
+        assert_val!(
+            f(&[&address, &literal!(["192.168.0.0/16", "10.1.2.0/24"])]),
+            true
+        );
+        assert_val!(f(&[&address, &literal!(["::/0"])]), false);
+        assert!(f(&[&address, &Value::from("10.0.0.0/33")]).is_err());
+
+        let cidr = Value::from("10.1.2.3/20");
+        assert_val!(fun("net", "network")(&[&cidr]), "10.1.0.0");
+        assert_val!(fun("net", "broadcast")(&[&cidr]), "10.1.15.255");
+        assert_val!(fun("net", "netmask")(&[&cidr]), "255.255.240.0");
+        let cidr = Value::from("2001:db8::1/32");
+        assert_val!(fun("net", "network")(&[&cidr]), "2001:db8::");
+        assert_val!(
+            fun("net", "broadcast")(&[&cidr]),
+            "2001:db8:ffff:ffff:ffff:ffff:ffff:ffff"
+        );
+        assert_val!(
+            fun("net", "netmask")(&[&Value::from("0.0.0.0/0")]),
+            "0.0.0.0"
+        );
+    }
+
+    #[test]
+    fn cidr_sets() {
+        let set = fun("net", "cidr_set")(&[&literal!([
+            "10.0.0.0/9",
+            "10.128.0.0/9",
+            "192.168.0.0/24",
+            "192.168.0.128/25",
+            "2001:db8::/32"
+        ])])
+        .expect("invalid cidrs");
+        assert_eq!(
+            literal!({
+                "v4": [
+                    [Value::Bytes(vec![10, 0, 0, 0].into()), Value::Bytes(vec![10, 255, 255, 255].into())],
+                    [Value::Bytes(vec![192, 168, 0, 0].into()), Value::Bytes(vec![192, 168, 0, 255].into())]
+                ],
+                "v6": [[
+                    Value::Bytes(vec![0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0].into()),
+                    Value::Bytes(vec![0x20, 0x01, 0x0d, 0xb8, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255].into())
+                ]]
+            }),
+            set
+        );
+        let f = fun("net", "in_cidr_set");
+        for (address, expected) in [
+            ("9.255.255.255", false),
+            ("10.0.0.0", true),
+            ("10.200.0.1", true),
+            ("11.0.0.0", false),
+            ("192.168.0.255", true),
+            ("192.168.1.0", false),
+            ("2001:db8::1", true),
+            ("2001:db9::1", false),
+        ] {
+            assert_val!(f(&[&Value::from(address), &set]), expected);
+        }
+        assert!(f(&[&Value::from("10.0.0.1"), &Value::from("snot")]).is_err());
+        assert!(fun("net", "cidr_set")(&[&literal!(["snot"])]).is_err());
+    }
+}
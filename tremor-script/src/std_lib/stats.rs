@@ -15,15 +15,20 @@
 
 use crate::prelude::*;
 use crate::registry::{
-    mfa, Aggr as AggrRegistry, FResult, FunctionError, TremorAggrFn, TremorAggrFnWrapper,
+    invalid_snapshot, mfa, Aggr as AggrRegistry, FResult, FunctionError, TremorAggrFn,
+    TremorAggrFnWrapper,
 };
 use crate::Value;
-use hdrhistogram::Histogram;
+use hdrhistogram::{
+    serialization::{Deserializer, Serializer, V2Serializer},
+    Histogram,
+};
 use sketches_ddsketch::{Config as DDSketchConfig, DDSketch};
 use std::cmp::max;
 use std::f64;
 use std::ops::RangeInclusive;
 use std::u64;
+use tremor_value::{structurize, to_value};
 
 /// Round up.
 ///
@@ -43,6 +48,35 @@ pub fn ceil(value: f64, scale: i8) -> f64 {
     (value * multiplier).ceil() / multiplier
 }
 
+fn restore_optional_f64(snapshot: &Value, f: &str) -> FResult<Option<f64>> {
+    if snapshot.is_null() {
+        Ok(None)
+    } else {
+        snapshot
+            .as_f64()
+            .map(Some)
+            .ok_or_else(|| invalid_snapshot("stats", f, 1))
+    }
+}
+
+fn snapshot_percentiles(percentiles: &[(String, f64)]) -> Value<'static> {
+    percentiles
+        .iter()
+        .map(|(name, p)| literal!([name.clone(), *p]))
+        .collect()
+}
+
+fn restore_percentiles(snapshot: &Value) -> Option<Vec<(String, f64)>> {
+    snapshot
+        .get_array("percentiles")?
+        .iter()
+        .map(|p| {
+            let p = p.as_array()?;
+            Some((p.first()?.as_str()?.to_string(), p.get(1)?.as_f64()?))
+        })
+        .collect()
+}
+
 #[derive(Clone, Debug, Default)]
 struct Count(i64);
 impl TremorAggrFn for Count {
@@ -65,6 +99,16 @@ impl TremorAggrFn for Count {
         Ok(())
     }
 
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(Value::from(self.0))
+    }
+    fn restore(&mut self, snapshot: &Value) -> FResult<()> {
+        self.0 = snapshot
+            .as_i64()
+            .ok_or_else(|| invalid_snapshot("stats", "count", 0))?;
+        Ok(())
+    }
+
     fn boxed_clone(&self) -> Box<dyn TremorAggrFn> {
         Box::new(self.clone())
     }
@@ -105,6 +149,16 @@ impl TremorAggrFn for Sum {
         Ok(())
     }
 
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(Value::from(self.0))
+    }
+    fn restore(&mut self, snapshot: &Value) -> FResult<()> {
+        self.0 = snapshot
+            .as_f64()
+            .ok_or_else(|| invalid_snapshot("stats", "sum", 1))?;
+        Ok(())
+    }
+
     fn boxed_clone(&self) -> Box<dyn TremorAggrFn> {
         Box::new(self.clone())
     }
@@ -151,6 +205,22 @@ impl TremorAggrFn for Mean {
         Ok(())
     }
 
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(literal!([self.0, self.1]))
+    }
+    fn restore(&mut self, snapshot: &Value) -> FResult<()> {
+        if let Some((Some(n), Some(sum))) = snapshot
+            .as_array()
+            .and_then(|a| Some((a.first()?.as_i64(), a.get(1)?.as_f64())))
+        {
+            self.0 = n;
+            self.1 = sum;
+            Ok(())
+        } else {
+            Err(invalid_snapshot("stats", "mean", 1))
+        }
+    }
+
     fn boxed_clone(&self) -> Box<dyn TremorAggrFn> {
         Box::new(self.clone())
     }
@@ -195,6 +265,14 @@ impl TremorAggrFn for Min {
         Ok(())
     }
 
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(self.0.map_or_else(Value::null, Value::from))
+    }
+    fn restore(&mut self, snapshot: &Value) -> FResult<()> {
+        self.0 = restore_optional_f64(snapshot, "min")?;
+        Ok(())
+    }
+
     fn boxed_clone(&self) -> Box<dyn TremorAggrFn> {
         Box::new(self.clone())
     }
@@ -238,6 +316,14 @@ impl TremorAggrFn for Max {
         Ok(())
     }
 
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(self.0.map_or_else(Value::null, Value::from))
+    }
+    fn restore(&mut self, snapshot: &Value) -> FResult<()> {
+        self.0 = restore_optional_f64(snapshot, "max")?;
+        Ok(())
+    }
+
     fn boxed_clone(&self) -> Box<dyn TremorAggrFn> {
         Box::new(self.clone())
     }
@@ -299,6 +385,31 @@ impl TremorAggrFn for Var {
         Ok(())
     }
 
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(literal!({
+            "n": self.n,
+            "k": self.k,
+            "ex": self.ex,
+            "ex2": self.ex2,
+        }))
+    }
+    fn restore(&mut self, snapshot: &Value) -> FResult<()> {
+        if let (Some(n), Some(k), Some(ex), Some(ex2)) = (
+            snapshot.get_u64("n"),
+            snapshot.get_f64("k"),
+            snapshot.get_f64("ex"),
+            snapshot.get_f64("ex2"),
+        ) {
+            self.n = n;
+            self.k = k;
+            self.ex = ex;
+            self.ex2 = ex2;
+            Ok(())
+        } else {
+            Err(invalid_snapshot("stats", "var", 1))
+        }
+    }
+
     fn boxed_clone(&self) -> Box<dyn TremorAggrFn> {
         Box::new(self.clone())
     }
@@ -332,6 +443,13 @@ impl TremorAggrFn for Stdev {
         Ok(())
     }
 
+    fn snapshot(&self) -> Option<Value<'static>> {
+        self.0.snapshot()
+    }
+    fn restore(&mut self, snapshot: &Value) -> FResult<()> {
+        self.0.restore(snapshot)
+    }
+
     fn boxed_clone(&self) -> Box<dyn TremorAggrFn> {
         Box::new(self.clone())
     }
@@ -520,6 +638,35 @@ impl TremorAggrFn for Dds {
         self.cache.clear();
     }
 
+    fn snapshot(&self) -> Option<Value<'static>> {
+        let sketch = match &self.sketch {
+            Some(sketch) => to_value(sketch).ok()?,
+            None => Value::null(),
+        };
+        Some(literal!({
+            "sketch": sketch,
+            "cache": self.cache.clone(),
+            "percentiles": snapshot_percentiles(&self.percentiles),
+            "percentiles_set": self.percentiles_set,
+        }))
+    }
+    fn restore(&mut self, snapshot: &Value) -> FResult<()> {
+        let err = || invalid_snapshot("stats", "dds", 2);
+        self.sketch = match snapshot.get("sketch") {
+            Some(sketch) if !sketch.is_null() => {
+                Some(structurize(sketch.clone_static()).map_err(|_| err())?)
+            }
+            _ => None,
+        };
+        self.cache = snapshot
+            .get_array("cache")
+            .and_then(|cache| cache.iter().map(ValueAsScalar::as_f64).collect())
+            .ok_or_else(err)?;
+        self.percentiles = restore_percentiles(snapshot).ok_or_else(err)?;
+        self.percentiles_set = snapshot.get_bool("percentiles_set").ok_or_else(err)?;
+        Ok(())
+    }
+
     fn boxed_clone(&self) -> Box<dyn TremorAggrFn> {
         Box::new(self.clone())
     }
@@ -711,6 +858,45 @@ impl TremorAggrFn for Hdr {
         self.cache.clear();
     }
 
+    fn snapshot(&self) -> Option<Value<'static>> {
+        let histo = match &self.histo {
+            Some(histo) => {
+                let mut data = Vec::new();
+                V2Serializer::new().serialize(histo, &mut data).ok()?;
+                Value::Bytes(data.into())
+            }
+            None => Value::null(),
+        };
+        Some(literal!({
+            "histo": histo,
+            "cache": self.cache.clone(),
+            "percentiles": snapshot_percentiles(&self.percentiles),
+            "percentiles_set": self.percentiles_set,
+            "high_bound": self.high_bound,
+        }))
+    }
+    fn restore(&mut self, snapshot: &Value) -> FResult<()> {
+        let err = || invalid_snapshot("stats", "hdr", 2);
+        self.histo = match snapshot.get_bytes("histo") {
+            Some(mut data) => {
+                let mut histo: Histogram<u64> = Deserializer::new()
+                    .deserialize(&mut data)
+                    .map_err(|_| err())?;
+                histo.auto(true);
+                Some(histo)
+            }
+            None => None,
+        };
+        self.cache = snapshot
+            .get_array("cache")
+            .and_then(|cache| cache.iter().map(ValueAsScalar::as_u64).collect())
+            .ok_or_else(err)?;
+        self.percentiles = restore_percentiles(snapshot).ok_or_else(err)?;
+        self.percentiles_set = snapshot.get_bool("percentiles_set").ok_or_else(err)?;
+        self.high_bound = snapshot.get_u64("high_bound").ok_or_else(err)?;
+        Ok(())
+    }
+
     fn boxed_clone(&self) -> Box<dyn TremorAggrFn> {
         Box::new(self.clone())
     }
@@ -792,6 +978,43 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn snapshot_and_restore() -> Result<()> {
+        fn roundtrip<T: TremorAggrFn + Default>(a: &mut T) -> Result<()> {
+            let mut b = T::default();
+            b.restore(&a.snapshot().expect("no snapshot"))?;
+            assert_eq!(a.emit()?, b.emit()?);
+            Ok(())
+        }
+        let values = [Value::from(1), Value::from(2.5), Value::from(42)];
+        let mut mean = Mean::default();
+        let mut min = Min::default();
+        let mut stdev = Stdev::default();
+        let mut dds = Dds::default();
+        let mut hdr = Hdr::default();
+        for v in &values {
+            mean.accumulate(&[v])?;
+            min.accumulate(&[v])?;
+            stdev.accumulate(&[v])?;
+            dds.accumulate(&[v])?;
+            hdr.accumulate(&[v])?;
+        }
+        roundtrip(&mut mean)?;
+        roundtrip(&mut min)?;
+        roundtrip(&mut Max::default())?;
+        roundtrip(&mut stdev)?;
+        roundtrip(&mut dds)?;
+        roundtrip(&mut hdr)?;
+        // once they switched to a sketch / histogram
+        dds.switch_to_sketch(Dds::new_sketch());
+        roundtrip(&mut dds)?;
+        hdr.switch_to_histo(Hdr::new_histo(hdr.high_bound())?)?;
+        roundtrip(&mut hdr)?;
+
+        assert!(Sum::default().restore(&Value::from("snot")).is_err());
+        Ok(())
+    }
+
     #[test]
     fn min() -> Result<()> {
         let mut a = Min::default();
@@ -0,0 +1,112 @@
+// Copyright 2022, The Tremor Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::prelude::*;
+use crate::registry::Registry;
+use crate::{tremor_const_fn, tremor_fn};
+use rand::Rng;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Builds a version 7 uuid from a unix timestamp in milliseconds and random bytes
+fn v7(unix_ms: u64, random: [u8; 10]) -> Uuid {
+    let mut bytes = [0_u8; 16];
+    bytes[..6].copy_from_slice(&unix_ms.to_be_bytes()[2..]);
+    bytes[6..].copy_from_slice(&random);
+    bytes[6] = (bytes[6] & 0x0F) | 0x70;
+    bytes[8] = (bytes[8] & 0x3F) | 0x80;
+    Uuid::from_bytes(bytes)
+}
+
+/// Parses uuids given as strings or as 16 bytes
+fn parse(input: &Value) -> Option<Uuid> {
+    match input {
+        Value::String(input) => Uuid::parse_str(input).ok(),
+        Value::Bytes(input) => Uuid::from_slice(input).ok(),
+        _ => None,
+    }
+}
+
+pub fn load(registry: &mut Registry) {
+    registry
+        .insert(tremor_fn! (uuid|v4(_context) {
+            Ok(Value::from(Uuid::new_v4().to_string()))
+        }))
+        .insert(tremor_fn! (uuid|v7(_context) {
+            let unix_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_err(to_runtime_error)?.as_millis();
+            let unix_ms = u64::try_from(unix_ms).map_err(to_runtime_error)?;
+            Ok(Value::from(v7(unix_ms, rand::thread_rng().gen()).to_string()))
+        }))
+        .insert(tremor_const_fn! (uuid|parse(_context, _input) {
+            parse(_input)
+                .map(|uuid| Value::Bytes(uuid.as_bytes().to_vec().into()))
+                .ok_or_else(|| to_runtime_error(format!("Invalid uuid {}", _input.encode())))
+        }))
+        .insert(tremor_const_fn! (uuid|format(_context, _input) {
+            parse(_input)
+                .map(|uuid| Value::from(uuid.to_string()))
+                .ok_or_else(|| to_runtime_error(format!("Invalid uuid {}", _input.encode())))
+        }))
+        .insert(tremor_const_fn! (uuid|is_valid(_context, _input) {
+            Ok(Value::from(parse(_input).is_some()))
+        }))
+        .insert(tremor_const_fn! (uuid|version(_context, _input) {
+            parse(_input)
+                .map(|uuid| Value::from(uuid.get_version_num()))
+                .ok_or_else(|| to_runtime_error(format!("Invalid uuid {}", _input.encode())))
+        }));
+}
+
+#[cfg(test)]
+mod test {
+    use crate::registry::fun;
+    use crate::Value;
+
+    #[test]
+    fn v4() {
+        let uuid = fun("uuid", "v4")(&[]).expect("no uuid");
+        assert_val!(fun("uuid", "version")(&[&uuid]), 4);
+        assert_ne!(Ok(uuid), fun("uuid", "v4")(&[]));
+    }
+
+    #[test]
+    fn v7() {
+        let uuid = super::v7(0x0184_4c1b_a5f0, [0xFF; 10]);
+        assert_eq!("01844c1b-a5f0-7fff-bfff-ffffffffffff", uuid.to_string());
+        let first = fun("uuid", "v7")(&[]).expect("no uuid");
+        assert_val!(fun("uuid", "version")(&[&first]), 7);
+        // the timestamp comes first, so later uuids sort after earlier ones
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let second = fun("uuid", "v7")(&[]).expect("no uuid");
+        assert!(first < second);
+    }
+
+    #[test]
+    fn parse_and_format() {
+        let uuid = Value::from("67E55044-10B1-426F-9247-BB680E5FE0C8");
+        let bytes = fun("uuid", "parse")(&[&uuid]).expect("invalid uuid");
+        assert_eq!(Some(16), bytes.as_bytes().map(<[u8]>::len));
+        assert_val!(
+            fun("uuid", "format")(&[&bytes]),
+            "67e55044-10b1-426f-9247-bb680e5fe0c8"
+        );
+        assert_val!(
+            fun("uuid", "format")(&[&Value::from("67e5504410b1426f9247bb680e5fe0c8")]),
+            "67e55044-10b1-426f-9247-bb680e5fe0c8"
+        );
+        assert_val!(fun("uuid", "is_valid")(&[&uuid]), true);
+        assert_val!(fun("uuid", "is_valid")(&[&Value::from("snot")]), false);
+        assert!(fun("uuid", "parse")(&[&Value::from("snot")]).is_err());
+    }
+}
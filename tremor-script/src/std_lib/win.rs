@@ -12,7 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::registry::{Aggr as AggrRegistry, FResult, TremorAggrFn, TremorAggrFnWrapper};
+use crate::registry::{
+    invalid_snapshot, Aggr as AggrRegistry, FResult, TremorAggrFn, TremorAggrFnWrapper,
+};
 
 use crate::{prelude::*, tremor_fn};
 
@@ -49,6 +51,18 @@ impl TremorAggrFn for First {
         Ok(())
     }
 
+    fn snapshot(&self) -> Option<Value<'static>> {
+        // wrapped in an array to tell an accumulated `null` from no value
+        Some(Value::from(self.0.iter().cloned().collect::<Vec<_>>()))
+    }
+    fn restore(&mut self, snapshot: &Value) -> FResult<()> {
+        let value = snapshot
+            .as_array()
+            .ok_or_else(|| invalid_snapshot("win", "first", 1))?;
+        self.0 = value.first().map(Value::clone_static);
+        Ok(())
+    }
+
     fn boxed_clone(&self) -> Box<dyn TremorAggrFn> {
         Box::new(self.clone())
     }
@@ -86,6 +100,18 @@ impl TremorAggrFn for Last {
         Ok(())
     }
 
+    fn snapshot(&self) -> Option<Value<'static>> {
+        // wrapped in an array to tell an accumulated `null` from no value
+        Some(Value::from(self.0.iter().cloned().collect::<Vec<_>>()))
+    }
+    fn restore(&mut self, snapshot: &Value) -> FResult<()> {
+        let value = snapshot
+            .as_array()
+            .ok_or_else(|| invalid_snapshot("win", "last", 1))?;
+        self.0 = value.first().map(Value::clone_static);
+        Ok(())
+    }
+
     fn boxed_clone(&self) -> Box<dyn TremorAggrFn> {
         Box::new(self.clone())
     }
@@ -124,6 +150,19 @@ impl TremorAggrFn for CollectFlattened {
         Ok(())
     }
 
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(Value::from(self.0.clone()))
+    }
+    fn restore(&mut self, snapshot: &Value) -> FResult<()> {
+        self.0 = snapshot
+            .as_array()
+            .ok_or_else(|| invalid_snapshot("win", "collect_flattened", 1))?
+            .iter()
+            .map(Value::clone_static)
+            .collect();
+        Ok(())
+    }
+
     fn boxed_clone(&self) -> Box<dyn TremorAggrFn> {
         Box::new(self.clone())
     }
@@ -167,6 +206,19 @@ impl TremorAggrFn for CollectNested {
         Ok(())
     }
 
+    fn snapshot(&self) -> Option<Value<'static>> {
+        Some(Value::from(self.0.clone()))
+    }
+    fn restore(&mut self, snapshot: &Value) -> FResult<()> {
+        self.0 = snapshot
+            .as_array()
+            .ok_or_else(|| invalid_snapshot("win", "collect_nested", 1))?
+            .iter()
+            .map(Value::clone_static)
+            .collect();
+        Ok(())
+    }
+
     fn boxed_clone(&self) -> Box<dyn TremorAggrFn> {
         Box::new(self.clone())
     }
@@ -237,6 +289,26 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn snapshot_and_restore() -> Result<()> {
+        let mut a = Last::default();
+        let mut b = Last::default();
+        b.restore(&a.snapshot().expect("no snapshot"))?;
+        assert!(b.0.is_none());
+        a.accumulate(&[&Value::null()])?;
+        b.restore(&a.snapshot().expect("no snapshot"))?;
+        assert_eq!(b.0, Some(Value::null()));
+
+        let mut a = CollectNested::default();
+        a.accumulate(&[&Value::from(1)])?;
+        a.accumulate(&[&Value::Bytes(b"snot".to_vec().into())])?;
+        let mut b = CollectNested::default();
+        b.restore(&a.snapshot().expect("no snapshot"))?;
+        assert_eq!(a.emit()?, b.emit()?);
+        assert!(b.restore(&Value::null()).is_err());
+        Ok(())
+    }
+
     #[test]
     fn collect() -> Result<()> {
         let mut a = CollectFlattened::default();